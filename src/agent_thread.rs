@@ -1,72 +1,312 @@
 //! Agent thread — bridges the blocking AgentLoop with the UI event loop via channels.
 
-use std::sync::mpsc;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 
-use crate::session::Session;
-use crate::commands::{self, CommandResult};
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::{self, ContextEntry, Control, ModelCost, ShellKiller, Steering, TurnRecord};
+use crate::bench;
+use crate::session::{self, Session};
+use crate::session_store;
+use crate::shell;
+use crate::ui::{ansi, icons};
+use crate::commands::{self, Capability, CommandResult, CompactStrategy, EditAction, MemoryAction, WorkdirAction};
 
 /// Events sent from the agent thread to the UI.
 #[derive(Debug, Clone)]
 pub enum AgentEvent {
     Narration(String),
     ToolCallStarted { name: String, args: String },
-    ToolCallCompleted { name: String, success: bool, duration_ms: u64 },
+    /// `output` is the tool's (possibly truncated) output; `error` says why it failed.
+    ToolCallCompleted { name: String, success: bool, duration_ms: u64, output: String, error: Option<String> },
+    /// A `!` command started; `killer` stops it.
+    ShellStarted { command: String, killer: ShellKiller },
+    /// Complete lines the running `!` command printed.
+    ShellOutput(Vec<String>),
+    ShellFinished { code: u32, killed: bool },
+    /// Unified diff of an existing file rewritten by write_file.
+    FileDiff { path: String, diff: String },
     LlmCall { model: String, prompt_tokens: usize, completion_tokens: usize, duration_ms: u64 },
+    /// The provider rate-limited the turn; it is retried after the wait.
+    RateLimited { provider: String, retry_in_secs: u64 },
+    /// Background task `id` started running.
+    BackgroundStarted(usize),
+    /// Background task `id` finished with its answer, or failed.
+    BackgroundFinished { id: usize, outcome: Result<String, String> },
+    /// Seconds left before the turn times out, sent each second once it is into
+    /// the last quarter of the manifest's `turn_timeout_secs`.
+    TimeoutCountdown(u64),
     StageStarted { stage_id: String, stage_kind: String },
     StageCompleted { stage_id: String, duration_ms: u64, skipped: bool },
+    StageFailed { stage_id: String, error: String },
+    Artifact { stage_id: String, name: String, content: String },
+    /// A chunk of the answer as it streams in; `Response` carries the final text.
+    StreamDelta(String),
+    /// A failed attempt at the turn was rolled back to run again; the text it
+    /// streamed goes.
+    AttemptDiscarded,
     Response(String),
-    TokenUpdate { total: usize, turns: usize, cost: f64 },
+    TokenUpdate { total: usize, turns: usize, cost: f64, per_model: Vec<ModelCost> },
+    ModelChanged(String),
+    /// Snapshot for `/state`, as JSON.
+    State(String),
+    /// The conversation for `/save` (or `/checkpoint`); the UI adds the chat
+    /// and writes the file.
+    SessionSnapshot { name: String, state: serde_json::Value, checkpoint: bool },
+    /// `/load` or `/restore` brought a conversation back; the UI restores this
+    /// chat (the file's chat half) to go with it.
+    ConversationRestored(serde_json::Value),
+    /// A turn is starting on a workflow with these stages (empty when unknown).
+    Pipeline(Vec<String>),
+    /// What the conversation is made of, for `/context`.
+    Context(Vec<ContextEntry>),
+    /// Every turn run so far, for `/stats`.
+    Stats(Vec<TurnRecord>),
+    /// `/autonomy` rebuilt the policy at this level.
+    AutonomyChanged(String),
+    /// `/workflow` loaded another workflow with these stages.
+    WorkflowChanged { name: String, stages: Vec<String> },
+    /// Capabilities safe mode still withholds (sent at startup and on every change).
+    SafeMode(Vec<Capability>),
+    /// Pin a persistent warning for condition `id` under the status panel.
+    PinNotice { id: String, text: String },
+    /// The condition behind notice `id` no longer holds.
+    ClearNotice(String),
+    /// The turn prompted by this input was taken back out of the conversation.
+    TurnUndone(String),
+    /// `/edit` rolled back the turn prompted by `previous`; `prompt` runs in its place.
+    TurnRevised { previous: String, prompt: String },
+    /// Guidance typed during a pause that the model never got, the turn having
+    /// failed before another tool call.
+    GuidanceUndelivered(String),
+    /// Models installed in the local Ollama, for `/model` completion.
+    OllamaModels(Vec<String>),
+    /// `/handoff` replaced the session with another agent's.
+    AgentChanged { agent: String, model: String, workflow: String, autonomy: String, token_budget: Option<usize> },
     Error(String),
     SystemMessage(String),
     Done,
     Quit,
 }
 
-/// Spawn the agent thread. Returns a sender for user input.
+/// Identifies one of the sessions the TUI runs at once. Their events share a
+/// channel and carry it, so the UI can route each to its session's tab.
+pub type SessionId = usize;
+
+/// Sends a session's events to the shared channel, tagged with its id.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    id: SessionId,
+    tx: mpsc::Sender<(SessionId, AgentEvent)>,
+}
+
+impl EventSender {
+    pub fn new(id: SessionId, tx: mpsc::Sender<(SessionId, AgentEvent)>) -> Self {
+        Self { id, tx }
+    }
+
+    /// A sender for the same session whose events go nowhere.
+    pub fn discarding(&self) -> Self {
+        let (tx, _) = mpsc::channel();
+        Self { id: self.id, tx }
+    }
+
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    pub fn send(&self, event: AgentEvent) -> Result<(), mpsc::SendError<AgentEvent>> {
+        self.tx.send((self.id, event)).map_err(|mpsc::SendError((_, event))| mpsc::SendError(event))
+    }
+}
+
+/// What the UI asks of an agent thread.
+pub enum UiCommand {
+    /// A prompt for the agent.
+    UserInput(String),
+    /// A slash or `!` command, parsed by the UI.
+    Command(CommandResult),
+    /// Usage of a background task, sent by the tab's background queue so
+    /// `/cost`, `/stats` and the budget count it.
+    BackgroundUsage(session::SessionStats),
+    /// End the thread. The control thread sends it on [`Control::Shutdown`],
+    /// so an idle agent loop wakes up to it.
+    Shutdown,
+}
+
+impl UiCommand {
+    /// The command for a line submitted in the input bar, `custom` holding the
+    /// user-defined commands.
+    pub fn parse(text: &str, custom: &commands::CustomCommands) -> Self {
+        match commands::process_command_with(text, custom) {
+            CommandResult::NotACommand => UiCommand::UserInput(text.to_string()),
+            result => UiCommand::Command(result),
+        }
+    }
+}
+
+/// A running agent thread: the id its events carry, where its commands go and
+/// where controls that skip the command queue go. Dropping the handle ends the
+/// thread once its current turn is over.
+pub struct AgentHandle {
+    pub id: SessionId,
+    pub command_tx: mpsc::Sender<UiCommand>,
+    pub control_tx: mpsc::Sender<Control>,
+}
+
+/// Apply controls as they arrive, while the agent thread may be deep in a turn:
+/// the cancel flag stops the turn at the kernel's next check (or ends its wait
+/// for a rate limit), and a shutdown reaches the agent loop through `wake` once
+/// the turn is over. Nothing queues behind a turn to skip: the UI only sends
+/// agent-bound input while the agent is idle.
+fn apply_controls(control_rx: mpsc::Receiver<Control>, cancel: Arc<AtomicBool>, steering: Steering, wake: mpsc::Sender<UiCommand>) {
+    for control in control_rx {
+        cancel.store(true, Ordering::Relaxed);
+        // A paused turn has to run on to notice
+        steering.resume(None);
+        if control == Control::Shutdown {
+            let _ = wake.send(UiCommand::Shutdown);
+        }
+    }
+}
+
+/// Spawn the agent thread for `session`, whose events go out through `event_tx`.
 pub fn spawn(
     session: Session,
-    event_tx: mpsc::Sender<AgentEvent>,
-) -> mpsc::Sender<String> {
-    let (input_tx, input_rx) = mpsc::channel::<String>();
+    event_tx: EventSender,
+) -> AgentHandle {
+    let id = event_tx.id();
+    let (command_tx, command_rx) = mpsc::channel::<UiCommand>();
+    let (control_tx, control_rx) = mpsc::channel::<Control>();
+
+    // Handoff keeps the cancel flag and steering, so these stay the session's
+    let (cancel, steering, wake) = (session.cancel_flag(), session.steering(), command_tx.clone());
+    std::thread::spawn(move || apply_controls(control_rx, cancel, steering, wake));
+
+    // `ollama list` can take a moment, so it doesn't hold up the first turn
+    let models_tx = event_tx.clone();
+    std::thread::spawn(move || {
+        let _ = models_tx.send(AgentEvent::OllamaModels(session::ollama_models()));
+    });
 
     std::thread::Builder::new()
-        .name("agent".into())
+        .name(format!("agent-{id}"))
         .spawn(move || {
-            agent_loop(session, input_rx, event_tx);
+            agent_loop(session, command_rx, event_tx);
         })
         .expect("Failed to spawn agent thread");
 
-    input_tx
+    AgentHandle { id, command_tx, control_tx }
 }
 
 fn agent_loop(
     mut session: Session,
-    input_rx: mpsc::Receiver<String>,
-    event_tx: mpsc::Sender<AgentEvent>,
+    command_rx: mpsc::Receiver<UiCommand>,
+    event_tx: EventSender,
 ) {
-    while let Ok(input) = input_rx.recv() {
-        let input = input.trim().to_string();
-        if input.is_empty() {
+    while let Ok(command) = command_rx.recv() {
+        // Commands that end in a turn set its input
+        let (mut input, result) = match command {
+            UiCommand::UserInput(text) => (text.trim().to_string(), CommandResult::NotACommand),
+            UiCommand::Command(result) => (String::new(), result),
+            UiCommand::BackgroundUsage(usage) => {
+                session.stats.absorb(usage);
+                let _ = event_tx.send(token_update(&session));
+                continue;
+            }
+            UiCommand::Shutdown => break,
+        };
+        if input.is_empty() && matches!(result, CommandResult::NotACommand) {
             let _ = event_tx.send(AgentEvent::Done);
             continue;
         }
 
-        // Process slash commands
-        match commands::process_command(&input) {
+        let mut retry_stage = None;
+        // `/retry --model` runs one turn on another model, then switches back
+        let mut restore_model = None;
+        match result {
             CommandResult::NotACommand => {}
+            CommandResult::RetryStage(stage_id) => retry_stage = Some(stage_id),
+            CommandResult::Retry { model } => {
+                if session.last_input().is_none() {
+                    let _ = event_tx.send(AgentEvent::SystemMessage("Nothing to retry yet".into()));
+                    let _ = event_tx.send(AgentEvent::Done);
+                    continue;
+                }
+                let last = match session.rollback_turn() {
+                    Ok(last) => last,
+                    Err(e) => {
+                        let _ = event_tx.send(AgentEvent::Error(format!("Retry failed: {e:#}")));
+                        let _ = event_tx.send(AgentEvent::Done);
+                        continue;
+                    }
+                };
+                if let Some(model) = model {
+                    let previous = format!("{}:{}", session.provider, session.model_name);
+                    match session.switch_model(&model, false) {
+                        Ok(()) => restore_model = Some(previous),
+                        Err(e) => {
+                            let _ = event_tx.send(AgentEvent::Error(format!(
+                                "Model switch failed: {e:#}; retrying on {previous}"
+                            )));
+                        }
+                    }
+                }
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "{} Retrying ({}): {}",
+                    icons::current().retry,
+                    session.model_name,
+                    app::shorten(&last, 60)
+                )));
+                input = last;
+            }
+            CommandResult::Handoff(manifest) => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "{} Summarizing the conversation for {manifest}…",
+                    icons::current().handoff
+                )));
+                match session.handoff(&manifest) {
+                    Ok((next, context)) => {
+                        let _ = session.shutdown();
+                        session = next;
+                        let _ = event_tx.send(AgentEvent::AgentChanged {
+                            agent: session.agent_name.clone(),
+                            model: session.model_name.clone(),
+                            workflow: session.workflow_name.clone(),
+                            autonomy: session.autonomy.clone(),
+                            token_budget: session.token_budget,
+                        });
+                        let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                            "{} Handed off to {} ({})",
+                            icons::current().handoff, session.agent_name, session.model_name
+                        )));
+                        // The summary becomes the new agent's first turn
+                        input = context;
+                    }
+                    Err(e) => {
+                        let _ = event_tx.send(AgentEvent::Error(format!("Handoff failed: {e:#}")));
+                        let _ = event_tx.send(AgentEvent::Done);
+                        continue;
+                    }
+                }
+            }
             CommandResult::Quit => {
                 let _ = event_tx.send(AgentEvent::Quit);
                 break;
             }
             CommandResult::Continue => {
-                // Check if it was /help
-                if input.trim().starts_with("/help") || input.trim() == "/?" {
-                    let help = "\
-Commands: /quit /clear /model <m> /compact /cost /help\n\
-Shell: !<command>\n\
-Keys: Ctrl+C quit | Ctrl+L clear | PgUp/PgDn scroll | Up/Down history";
-                    let _ = event_tx.send(AgentEvent::SystemMessage(help.to_string()));
-                }
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Message(lines) => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(lines.join("\n")));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Usage(syntax) => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!("Usage: {syntax}")));
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
@@ -75,29 +315,261 @@ Keys: Ctrl+C quit | Ctrl+L clear | PgUp/PgDn scroll | Up/Down history";
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
-            CommandResult::SwitchModel(model) => {
-                let _ = event_tx.send(AgentEvent::SystemMessage(
-                    format!("⚠ Model switching not yet implemented. Restart with --model {model}")
-                ));
+            CommandResult::SwitchModel { model, check } => {
+                let switched = session.switch_model(&model, check);
+                if check {
+                    let _ = event_tx.send(token_update(&session));
+                }
+                let _ = event_tx.send(match switched {
+                    Ok(()) => AgentEvent::SystemMessage(format!(
+                        "Switched to {}:{}; the conversation carries over",
+                        session.provider, session.model_name
+                    )),
+                    Err(e) => AgentEvent::Error(format!("Model switch failed: {e:#}")),
+                });
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
-            CommandResult::Compact => {
-                session.compact_with_callback(|msg| {
-                    let _ = event_tx.send(AgentEvent::SystemMessage(msg));
+            CommandResult::SwitchProvider { provider: None, .. } => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "Provider: {} ({}). Switch with /provider anthropic|ollama|claude-cli [model]",
+                    session.provider, session.model_name
+                )));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::SwitchProvider { provider: Some(provider), model } => {
+                let _ = event_tx.send(match session.switch_provider(&provider, model.as_deref()) {
+                    Ok(()) => AgentEvent::SystemMessage(format!(
+                        "Switched to {}:{}; the conversation carries over",
+                        session.provider, session.model_name
+                    )),
+                    Err(e) => AgentEvent::Error(format!("Provider switch failed: {e:#}")),
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Edit(EditAction::Resubmit(prompt)) => match session.rollback_turn() {
+                Ok(previous) => {
+                    let _ = event_tx.send(AgentEvent::TurnRevised { previous, prompt: prompt.clone() });
+                    input = prompt;
+                }
+                Err(e) => {
+                    let _ = event_tx.send(AgentEvent::Error(format!("Edit failed: {e:#}")));
+                    let _ = event_tx.send(AgentEvent::Done);
+                    continue;
+                }
+            },
+            CommandResult::Undo => {
+                match session.rollback_turn() {
+                    Ok(last) => {
+                        let _ = event_tx.send(AgentEvent::TurnUndone(last));
+                    }
+                    Err(e) => {
+                        let _ = event_tx.send(AgentEvent::Error(format!("Undo failed: {e:#}")));
+                    }
+                }
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Compact(strategy) => {
+                let _ = event_tx.send(match session.compact(strategy) {
+                    Ok(summary) => AgentEvent::SystemMessage(summary),
+                    Err(e) => AgentEvent::Error(format!("{} Can't compact: {e:#}", icons::current().warning)),
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Save(name) => {
+                let _ = event_tx.send(snapshot(&session, name, false));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Checkpoint(label) => {
+                let _ = event_tx.send(snapshot(&session, label, true));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Load(Some(name)) => {
+                let restored = session_store::read(session_store::dir().as_deref(), &name).and_then(|saved| {
+                    anyhow::ensure!(saved["chat"]["messages"].is_array(), "'{name}' has no chat to restore");
+                    session.restore_conversation(&saved["agent"])?;
+                    Ok(saved)
+                });
+                match restored {
+                    Ok(mut saved) => {
+                        // The chat follows the conversation, so a failed load leaves both as they were
+                        let _ = event_tx.send(AgentEvent::ConversationRestored(saved["chat"].take()));
+                        if let Err(e) = session.restore_llm(&saved["agent"]) {
+                            let _ = event_tx.send(AgentEvent::Error(format!("{e:#}")));
+                        }
+                        let _ = event_tx.send(token_update(&session));
+                        let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                            "{} Restored session '{name}'; the conversation continues on {}",
+                            icons::current().loaded, session.model_name
+                        )));
+                    }
+                    Err(e) => {
+                        let _ = event_tx.send(AgentEvent::Error(format!("Load failed: {e:#}")));
+                    }
+                }
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Restore(Some(label)) => {
+                let restored = session_store::read(session.checkpoint_dir().as_deref(), &label).and_then(|saved| {
+                    anyhow::ensure!(saved["chat"]["messages"].is_array(), "'{label}' has no chat to restore");
+                    session.restore_conversation(&saved["agent"])?;
+                    Ok(saved)
+                });
+                match restored {
+                    Ok(mut saved) => {
+                        let _ = event_tx.send(AgentEvent::ConversationRestored(saved["chat"].take()));
+                        if let Err(e) = session.restore_llm(&saved["agent"]) {
+                            let _ = event_tx.send(AgentEvent::Error(format!("{e:#}")));
+                        }
+                        let _ = event_tx.send(token_update(&session));
+                        let _ = event_tx.send(AgentEvent::SystemMessage(format!("{} Rolled back to checkpoint '{label}'", icons::current().rewind)));
+                    }
+                    Err(e) => {
+                        let _ = event_tx.send(AgentEvent::Error(format!("Restore failed: {e:#}")));
+                    }
+                }
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::State => {
+                let snapshot = serde_json::to_string(&session.state_snapshot()).unwrap_or_default();
+                let _ = event_tx.send(AgentEvent::State(snapshot));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Autonomy(None) => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "Autonomy: {} (levels: {})",
+                    session.autonomy,
+                    Session::AUTONOMY_LEVELS.join(", "),
+                )));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Autonomy(Some(level)) => {
+                let _ = event_tx.send(match session.set_autonomy(&level) {
+                    Ok(()) => AgentEvent::SystemMessage(format!("Autonomy set to {}", session.autonomy)),
+                    Err(e) => AgentEvent::Error(format!("Can't change autonomy: {e:#}")),
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Workflow(None) => {
+                let mut msg = format!("Workflow: {}", session.workflow_name);
+                if session.routed() {
+                    msg.push_str(" (a router picks the workflow for each turn)");
+                }
+                if session.pipeline().is_empty() {
+                    msg.push_str("\n  No stages (the default agentic loop)");
+                }
+                for (i, stage) in session.pipeline().iter().enumerate() {
+                    msg.push_str(&format!("\n  {}. {stage}", i + 1));
+                }
+                let _ = event_tx.send(AgentEvent::SystemMessage(msg));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Workflow(Some(path)) => {
+                let _ = event_tx.send(match session.switch_workflow(&path) {
+                    Ok(()) => AgentEvent::SystemMessage(format!(
+                        "Switched to workflow {} ({} stages); the conversation carries over",
+                        session.workflow_name,
+                        session.pipeline().len(),
+                    )),
+                    Err(e) => AgentEvent::Error(format!("Can't load workflow: {e:#}")),
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Context => {
+                let _ = event_tx.send(AgentEvent::Context(session.context_entries()));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Stats => {
+                let _ = event_tx.send(AgentEvent::Stats(session.stats.turns.clone()));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Memory(MemoryAction::List) => {
+                let _ = event_tx.send(match session.memory_entries() {
+                    None => AgentEvent::Error("No history module to list memories from".into()),
+                    Some(entries) if entries.is_empty() => AgentEvent::SystemMessage("Memory is empty.".into()),
+                    Some(entries) => {
+                        let pin = icons::current().pin;
+                        let mut msg = format!("Memory ({} messages, {pin} kept through compaction; /memory pin <n>):", entries.len());
+                        for (i, entry) in entries.iter().enumerate() {
+                            let preview = entry.text.split_whitespace().collect::<Vec<_>>().join(" ");
+                            msg.push_str(&format!(
+                                "\n  {:>3}. {} {:<9} ~{:>6}  {}",
+                                i + 1,
+                                if entry.pinned { pin.to_string() } else { " ".repeat(pin.width()) },
+                                entry.role,
+                                app::format_tokens(entry.tokens),
+                                app::shorten(&preview, 60),
+                            ));
+                        }
+                        AgentEvent::SystemMessage(msg)
+                    }
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Memory(MemoryAction::Clear) => {
+                let _ = event_tx.send(match session.clear_memory() {
+                    Ok(()) => AgentEvent::SystemMessage(format!(
+                        "{} Memory cleared; the agent starts the conversation over",
+                        icons::current().cleared
+                    )),
+                    Err(e) => AgentEvent::Error(format!("Can't clear memory: {e:#}")),
+                });
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Memory(MemoryAction::Pin(n)) => {
+                let _ = event_tx.send(match session.pin_memory(n) {
+                    Ok(()) => AgentEvent::SystemMessage(format!("{} Pinned message {n}; compaction keeps it", icons::current().pin)),
+                    Err(e) => AgentEvent::Error(format!("Can't pin: {e:#}")),
                 });
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
+            CommandResult::Tools => {
+                let tools = session.tools();
+                let access: Vec<String> = tools.iter().map(|t| session.tool_access(&t.name)).collect();
+                let name_width = tools.iter().map(|t| t.name.len()).max().unwrap_or(0);
+                let module_width = tools.iter().map(|t| t.module.len()).max().unwrap_or(0);
+                let access_width = access.iter().map(|a| a.len()).max().unwrap_or(0);
+                let mut msg = format!("Registered tools ({}, autonomy {}):", tools.len(), session.autonomy);
+                for (tool, access) in tools.iter().zip(&access) {
+                    msg.push_str(&format!(
+                        "\n  {:<name_width$}  {:<module_width$}  {access:<access_width$}  {}",
+                        tool.name, tool.module, tool.description,
+                    ));
+                }
+                if session.is_locked(Capability::Tools) {
+                    msg.push_str("\n\nTools are disabled in safe mode (/unsafe tools to allow).");
+                }
+                let _ = event_tx.send(AgentEvent::SystemMessage(msg.trim_end().to_string()));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
             CommandResult::Cost => {
                 let stats = &session.stats;
                 let total_prompt = stats.total_prompt_tokens;
                 let total_completion = stats.total_completion_tokens;
                 let total = stats.total_tokens();
                 let cost = stats.estimated_cost();
-                let context_budget = 200_000usize;
+                let context_budget = app::context_window(&session.model_name);
                 let context_pct = (total_prompt as f64 / context_budget as f64 * 100.0).min(100.0);
-                let msg = format!(
+                let mut msg = format!(
                     "Session cost breakdown:\n  Turns: {}\n  Input tokens: ~{}\n  Output tokens: ~{}\n  Estimated cost: ~${:.2}\n\n  Context: {:.0}% full ({}k / {}k)",
                     stats.total_turns,
                     total_prompt,
@@ -107,59 +579,339 @@ Keys: Ctrl+C quit | Ctrl+L clear | PgUp/PgDn scroll | Up/Down history";
                     total_prompt / 1000,
                     context_budget / 1000,
                 );
+                if stats.per_model.len() > 1 {
+                    msg.push_str("\n\n  By model:");
+                    for (model, usage) in &stats.per_model {
+                        msg.push_str(&format!(
+                            "\n    {model}: ~{} in / ~{} out, ~${:.2}",
+                            usage.prompt_tokens, usage.completion_tokens, usage.cost,
+                        ));
+                    }
+                }
                 let _ = event_tx.send(AgentEvent::SystemMessage(msg));
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
-            CommandResult::ShellCommand(cmd) => {
-                let output = std::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(&cmd)
-                    .output();
-                match output {
-                    Ok(out) => {
-                        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
-                        let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-                        let combined = if stderr.is_empty() { stdout } else { format!("{stdout}{stderr}") };
-                        let _ = event_tx.send(AgentEvent::SystemMessage(combined));
-                    }
-                    Err(e) => {
-                        let _ = event_tx.send(AgentEvent::Error(format!("Shell error: {e}")));
+            CommandResult::Artifacts(_)
+            | CommandResult::Export { .. }
+            | CommandResult::Goto(_)
+            | CommandResult::Image(_)
+            | CommandResult::Pager
+            | CommandResult::Sidebar
+            | CommandResult::Zen
+            | CommandResult::Layout(_)
+            | CommandResult::Tab(_)
+            | CommandResult::Background(_)
+            | CommandResult::CancelBackground(_)
+            | CommandResult::Theme(_)
+            | CommandResult::Dismiss(_)
+            | CommandResult::Filter(_)
+            | CommandResult::Trace(_)
+            | CommandResult::Keys
+            | CommandResult::Copy { .. }
+            | CommandResult::Files(_)
+            | CommandResult::Diff(_)
+            | CommandResult::Alias(_)
+            | CommandResult::Set { .. }
+            | CommandResult::Edit(_)
+            | CommandResult::Load(None)
+            | CommandResult::Restore(None) => {
+                // These operate on UI state; the UI thread handles them itself
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Bench { runs, prompt } => {
+                let label = format!("{}:{}", session.provider, session.model_name);
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "{} Benchmarking {label} with {runs} calls…",
+                    icons::current().timer
+                )));
+                let mut results = Vec::new();
+                let mut failures = 0;
+                for _ in 0..runs {
+                    match session.bench_call(&prompt) {
+                        Ok(run) => results.push(run),
+                        Err(e) => {
+                            failures += 1;
+                            let _ = event_tx.send(AgentEvent::Error(format!("Bench call failed: {e:#}")));
+                        }
                     }
                 }
+                let pricing = session.stats.pricing;
+                let report = bench::report(&label, &results, failures, pricing.input_per_mtok, pricing.output_per_mtok);
+                let _ = event_tx.send(AgentEvent::SystemMessage(report));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Unsafe { capability, confirmed } => {
+                let message = match capability {
+                    None => {
+                        let locked = session.locked_capabilities();
+                        if locked.is_empty() {
+                            "Safe mode is off; nothing is withheld.".to_string()
+                        } else {
+                            let names: Vec<&str> = locked.iter().map(|c| c.name()).collect();
+                            format!("Safe mode withholds: {}. Enable one with /unsafe <capability>.", names.join(", "))
+                        }
+                    }
+                    Some(capability) if !session.is_locked(capability) => {
+                        format!("{} already enabled.", capability.name())
+                    }
+                    Some(capability) if !confirmed => format!(
+                        "{} Enabling {} means {}. Type /unsafe {} confirm to proceed.",
+                        icons::current().warning,
+                        capability.name(),
+                        capability.risk(),
+                        capability.name()
+                    ),
+                    Some(capability) => {
+                        session.unlock(capability);
+                        let _ = event_tx.send(AgentEvent::SafeMode(session.locked_capabilities()));
+                        format!("{} {} enabled", icons::current().unlocked, capability.name())
+                    }
+                };
+                let _ = event_tx.send(AgentEvent::SystemMessage(message));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Workdir(WorkdirAction::Show) => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "Working directory: {}\nStarted in: {}",
+                    session.workdir().display(),
+                    session.original_cwd.display()
+                )));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Workdir(WorkdirAction::Reset) => {
+                let path = session.reset_workdir();
+                let _ = event_tx.send(AgentEvent::SystemMessage(
+                    format!("{} Working directory restored to {}", icons::current().undo, path.display())
+                ));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::ShellCommand(_) | CommandResult::ShellRepeat(_) if session.is_locked(Capability::Shell) => {
+                let _ = event_tx.send(AgentEvent::Error(
+                    "Shell access is disabled in safe mode (/unsafe shell to allow)".into(),
+                ));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::ShellRepeat(keyword) => {
+                let Some(cmd) = commands::recall_shell(&session.shell_history, keyword.as_deref()).cloned() else {
+                    let msg = match keyword {
+                        Some(keyword) => format!("No earlier shell command contains '{keyword}'"),
+                        None => "No shell command to repeat yet".to_string(),
+                    };
+                    let _ = event_tx.send(AgentEvent::SystemMessage(msg));
+                    let _ = event_tx.send(AgentEvent::Done);
+                    continue;
+                };
+                session.shell_history.push(cmd.clone());
+                shell_command(&cmd, &session.workdir(), &event_tx);
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::ShellCommand(cmd) => {
+                session.shell_history.push(cmd.clone());
+                shell_command(&cmd, &session.workdir(), &event_tx);
                 let _ = event_tx.send(AgentEvent::Done);
                 continue;
             }
+            CommandResult::Custom { run: Some(_), .. } if session.is_locked(Capability::Shell) => {
+                let _ = event_tx.send(AgentEvent::Error(
+                    "Shell access is disabled in safe mode (/unsafe shell to allow)".into(),
+                ));
+                let _ = event_tx.send(AgentEvent::Done);
+                continue;
+            }
+            CommandResult::Custom { run, prompt } => {
+                let output = match run {
+                    Some(cmd) => match run_snippet(&cmd, &session.workdir(), &event_tx) {
+                        Some(output) => output,
+                        None => {
+                            let _ = event_tx.send(AgentEvent::Done);
+                            continue;
+                        }
+                    },
+                    None => String::new(),
+                };
+                input = prompt.replace("{output}", output.trim_end());
+            }
         }
 
-        // Run agent turn
-        match session.run_turn_with_events(&input, &event_tx) {
-            Ok(_) => {
-                // Send updated stats
-                let stats = &session.stats;
-                let _ = event_tx.send(AgentEvent::TokenUpdate {
-                    total: stats.total_tokens(),
-                    turns: stats.total_turns,
-                    cost: stats.estimated_cost(),
-                });
+        // Run agent turn (or resume the last one from a failed stage), then any
+        // guidance that came after its last tool call as a turn of its own
+        loop {
+            let outcome = match &retry_stage {
+                Some(stage_id) => session.retry_stage(stage_id),
+                None => session.run_turn_with_events(&input, &event_tx),
+            };
+            let succeeded = outcome.is_ok();
+            match outcome {
+                Ok(_) => {
+                    // Send updated stats
+                    let _ = event_tx.send(token_update(&session));
 
-                // Auto-compact at 80% context usage
-                let context_budget: usize = 200_000;
-                let usage = session.stats.total_prompt_tokens;
-                if usage > context_budget * 80 / 100 && session.stats.total_turns >= 3 {
-                    let pct = (usage as f64 / context_budget as f64 * 100.0) as u32;
-                    session.compact_with_callback(|_| {});
-                    let _ = event_tx.send(AgentEvent::SystemMessage(
-                        format!("⚡ Auto-compacted: context was {}% full", pct)
-                    ));
+                    // Auto-compact at 80% context usage
+                    let context_budget = app::context_window(&session.model_name);
+                    let usage = session.stats.total_prompt_tokens;
+                    if usage > context_budget * 80 / 100 && session.stats.total_turns >= 3 {
+                        let pct = (usage as f64 / context_budget as f64 * 100.0) as u32;
+                        let _ = event_tx.send(match session.compact(CompactStrategy::Summarize) {
+                            Ok(_) => AgentEvent::SystemMessage(format!(
+                                "{} Auto-compacted: context was {}% full",
+                                icons::current().compact,
+                                pct
+                            )),
+                            Err(e) => AgentEvent::Error(format!("Auto-compact failed with the context {pct}% full: {e:#}")),
+                        });
+                    }
+
+                    // Keep a warning pinned while the context is filling up
+                    let usage = session.stats.total_prompt_tokens;
+                    let notice = if usage > context_budget * 60 / 100 {
+                        let threshold = if usage > context_budget * 75 / 100 { 75 } else { 60 };
+                        AgentEvent::PinNotice {
+                            id: "context".into(),
+                            text: format!("Context over {threshold}% full; /compact frees space"),
+                        }
+                    } else {
+                        AgentEvent::ClearNotice("context".into())
+                    };
+                    let _ = event_tx.send(notice);
+                }
+                Err(e) if e.is::<session::TurnCanceled>() => {
+                    let _ = event_tx.send(AgentEvent::SystemMessage(format!("{e}")));
+                }
+                Err(e) => {
+                    let _ = event_tx.send(AgentEvent::Error(format!("{e:#}")));
                 }
             }
-            Err(e) => {
-                let _ = event_tx.send(AgentEvent::Error(format!("{e}")));
+            match session.steering().take_guidance() {
+                Some(guidance) if succeeded => {
+                    retry_stage = None;
+                    input = guidance;
+                }
+                Some(guidance) => {
+                    let _ = event_tx.send(AgentEvent::GuidanceUndelivered(guidance));
+                    break;
+                }
+                None => break,
             }
         }
+        if let Some(previous) = restore_model {
+            let _ = event_tx.send(match session.switch_model(&previous, false) {
+                Ok(()) => AgentEvent::SystemMessage(format!("Back on {previous}")),
+                Err(e) => AgentEvent::Error(format!("Switching back to {previous} failed: {e:#}")),
+            });
+        }
         let _ = event_tx.send(AgentEvent::Done);
     }
 
     let _ = session.shutdown();
 }
+
+/// Run a `!` command in `dir`, streaming its output into the chat.
+fn shell_command(cmd: &str, dir: &Path, event_tx: &EventSender) {
+    let started = |killer| {
+        let _ = event_tx.send(AgentEvent::ShellStarted { command: cmd.to_string(), killer });
+    };
+    let output = |lines| {
+        let _ = event_tx.send(AgentEvent::ShellOutput(lines));
+    };
+    match shell::run(cmd, dir, started, output) {
+        Ok(exit) => {
+            let _ = event_tx.send(AgentEvent::ShellFinished { code: exit.code, killed: exit.killed });
+        }
+        Err(e) => {
+            let _ = event_tx.send(AgentEvent::Error(format!("Shell error: {e:#}")));
+        }
+    }
+}
+
+/// Run a custom command's snippet as `!` commands run, so its output shows as
+/// it is printed and Ctrl+C or Esc stops it. Returns what it printed, without
+/// colors, for the prompt; None if it was stopped or couldn't start.
+fn run_snippet(cmd: &str, dir: &Path, event_tx: &EventSender) -> Option<String> {
+    let mut printed = Vec::new();
+    let started = |killer| {
+        let _ = event_tx.send(AgentEvent::ShellStarted { command: cmd.to_string(), killer });
+    };
+    let output = |lines: Vec<String>| {
+        printed.extend(lines.iter().map(|line| ansi::strip(line)));
+        let _ = event_tx.send(AgentEvent::ShellOutput(lines));
+    };
+    match shell::run(cmd, dir, started, output) {
+        Ok(exit) => {
+            let _ = event_tx.send(AgentEvent::ShellFinished { code: exit.code, killed: exit.killed });
+            (!exit.killed).then(|| printed.join("\n"))
+        }
+        Err(e) => {
+            let _ = event_tx.send(AgentEvent::Error(format!("Shell error: {e:#}")));
+            None
+        }
+    }
+}
+
+/// The session's token usage, for the status panel.
+fn token_update(session: &Session) -> AgentEvent {
+    let stats = &session.stats;
+    AgentEvent::TokenUpdate {
+        total: stats.total_tokens(),
+        turns: stats.total_turns,
+        cost: stats.estimated_cost(),
+        per_model: stats
+            .per_model
+            .iter()
+            .map(|(model, usage)| ModelCost {
+                model: model.clone(),
+                tokens: usage.prompt_tokens + usage.completion_tokens,
+                cost: usage.cost,
+            })
+            .collect(),
+    }
+}
+
+/// The conversation half of a `/save` or `/checkpoint`, named by timestamp without a name.
+fn snapshot(session: &Session, name: Option<String>, checkpoint: bool) -> AgentEvent {
+    match session.conversation_snapshot() {
+        Ok(state) => AgentEvent::SessionSnapshot {
+            name: name.unwrap_or_else(|| session_store::default_name(if checkpoint { "checkpoint" } else { "session" })),
+            state,
+            checkpoint,
+        },
+        Err(e) => AgentEvent::Error(format!("Save failed: {e:#}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_apply_controls() {
+        let (control_tx, control_rx) = mpsc::channel();
+        let (command_tx, command_rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let steering = Steering::default();
+        steering.pause();
+        let applier = {
+            let (cancel, steering) = (cancel.clone(), steering.clone());
+            std::thread::spawn(move || apply_controls(control_rx, cancel, steering, command_tx))
+        };
+
+        control_tx.send(Control::Cancel).unwrap();
+        control_tx.send(Control::Shutdown).unwrap();
+        // Only the shutdown has to reach the agent loop
+        assert!(matches!(command_rx.recv_timeout(Duration::from_secs(5)), Ok(UiCommand::Shutdown)));
+        assert!(cancel.load(Ordering::Relaxed));
+        assert!(!steering.is_paused());
+
+        drop(control_tx);
+        applier.join().unwrap();
+        assert!(command_rx.recv().is_err());
+    }
+}
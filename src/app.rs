@@ -1,19 +1,264 @@
 //! Application state.
 
-use std::time::Instant;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{self, AliasAction, Capability, CustomCommands, FilterAction, TabAction, TraceAction};
+use crate::config::{BudgetConfig, LayoutMode, StreamScroll};
+use crate::graphics::{self, ImageProtocol, ImageSlot};
+use crate::links::{self, LinkSlot};
+use crate::session_store;
+use crate::theme::{Theme, PRESETS};
+use crate::ui::ansi;
+use crate::ui::compare::CompareState;
+use crate::ui::icons;
+use crate::ui::json_tree::JsonTreeState;
+use crate::ui::layout::Splits;
+use crate::ui::line_cache::LineCache;
+use crate::ui::pager::PagerState;
+use crate::ui::palette::PaletteState;
+use crate::ui::popup::PopupState;
+use crate::ui::trace_view::TraceViewState;
 
 /// A single chat message for display.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChatMessage {
     User(String),
     Assistant(String),
     Narration(String),
-    ToolCall { name: String, args_short: String },
-    ToolResult { name: String, success: bool, duration_ms: u64 },
+    ToolCall { name: String, args_short: String, args: String },
+    /// `output` is the tool's (possibly truncated) output, empty if unknown;
+    /// `error` says why it failed.
+    ToolResult {
+        name: String,
+        success: bool,
+        duration_ms: u64,
+        output: String,
+        #[serde(default)]
+        error: Option<String>,
+    },
+    /// Unified diff of a file the agent rewrote with write_file.
+    Diff { path: String, diff: String },
+    /// A `!` command and its output so far, escapes and all; `exit` is `None`
+    /// while it runs.
+    Shell { command: String, output: String, exit: Option<u32>, killed: bool },
     Error(String),
     System(String),
 }
 
+impl ChatMessage {
+    /// Plain text of the message, used for search and export.
+    pub fn text(&self) -> String {
+        match self {
+            ChatMessage::User(text)
+            | ChatMessage::Assistant(text)
+            | ChatMessage::Narration(text)
+            | ChatMessage::Error(text)
+            | ChatMessage::System(text) => text.clone(),
+            ChatMessage::ToolCall { name, args, .. } => format!("{name} {args}"),
+            ChatMessage::ToolResult { name, output, error, .. } => {
+                let mut text = name.clone();
+                for part in [Some(output), error.as_ref()].into_iter().flatten().filter(|part| !part.is_empty()) {
+                    text.push(' ');
+                    text.push_str(part);
+                }
+                text
+            }
+            ChatMessage::Diff { diff, .. } => diff.clone(),
+            ChatMessage::Shell { command, output, .. } => format!("$ {command}\n{}", ansi::strip(output)),
+        }
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            ChatMessage::User(_) => MessageKind::User,
+            ChatMessage::Assistant(_) => MessageKind::Assistant,
+            ChatMessage::Narration(_) => MessageKind::Narration,
+            ChatMessage::ToolCall { .. } | ChatMessage::ToolResult { .. } => MessageKind::Tool,
+            ChatMessage::Diff { .. } => MessageKind::Diff,
+            ChatMessage::Error(_) => MessageKind::Error,
+            ChatMessage::System(_) | ChatMessage::Shell { .. } => MessageKind::System,
+        }
+    }
+}
+
+/// How a `!` command stands: `running`, `killed` or `exit <code>`.
+pub fn shell_status(exit: Option<u32>, killed: bool) -> String {
+    match exit {
+        None => "running".to_string(),
+        Some(_) if killed => "killed".to_string(),
+        Some(code) => format!("exit {code}"),
+    }
+}
+
+/// Stops a running `!` command. The agent thread waits on the command, so it
+/// hands one of these to the UI for Ctrl+C.
+#[derive(Clone)]
+pub struct ShellKiller(Arc<Mutex<Box<dyn FnMut() + Send>>>);
+
+impl ShellKiller {
+    pub fn new(kill: impl FnMut() + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(kill))))
+    }
+
+    pub fn kill(&self) {
+        if let Ok(mut kill) = self.0.lock() {
+            kill();
+        }
+    }
+}
+
+impl std::fmt::Debug for ShellKiller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ShellKiller")
+    }
+}
+
+/// Controls for an agent thread. They travel on a channel of their own and act
+/// at once, where a command on the input channel would wait for the running
+/// turn to end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Stop the running turn.
+    Cancel,
+    /// Stop the running turn and end the thread.
+    Shutdown,
+}
+
+/// Pause-and-steer state shared by the UI and the session. While paused, the
+/// agent waits before its next tool call or stage; guidance typed meanwhile
+/// reaches the model with the next tool result.
+#[derive(Clone, Default)]
+pub struct Steering(Arc<(Mutex<SteeringState>, Condvar)>);
+
+#[derive(Default)]
+struct SteeringState {
+    paused: bool,
+    guidance: Vec<String>,
+}
+
+impl Steering {
+    pub fn pause(&self) {
+        self.update(|state| state.paused = true);
+    }
+
+    /// Carry on, with `guidance` for the model if there is any.
+    pub fn resume(&self, guidance: Option<String>) {
+        self.update(|state| {
+            state.paused = false;
+            state.guidance.extend(guidance);
+        });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0 .0.lock().is_ok_and(|state| state.paused)
+    }
+
+    /// Block the calling (agent) thread while paused.
+    pub fn wait_while_paused(&self) {
+        let (state, resumed) = &*self.0;
+        if let Ok(state) = state.lock() {
+            drop(resumed.wait_while(state, |state| state.paused));
+        }
+    }
+
+    /// Guidance the model hasn't seen yet, oldest first, as one message.
+    pub fn take_guidance(&self) -> Option<String> {
+        let guidance = std::mem::take(&mut self.0 .0.lock().ok()?.guidance);
+        (!guidance.is_empty()).then(|| guidance.join("\n\n"))
+    }
+
+    fn update(&self, change: impl FnOnce(&mut SteeringState)) {
+        let (state, resumed) = &*self.0;
+        if let Ok(mut state) = state.lock() {
+            change(&mut state);
+        }
+        resumed.notify_all();
+    }
+}
+
+/// Message categories that `/filter` can hide (tool calls and results count as one).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MessageKind {
+    User,
+    Assistant,
+    Narration,
+    Tool,
+    Diff,
+    Error,
+    System,
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 7] = [
+        MessageKind::User,
+        MessageKind::Assistant,
+        MessageKind::Narration,
+        MessageKind::Tool,
+        MessageKind::Diff,
+        MessageKind::Error,
+        MessageKind::System,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageKind::User => "user",
+            MessageKind::Assistant => "assistant",
+            MessageKind::Narration => "narration",
+            MessageKind::Tool => "tools",
+            MessageKind::Diff => "diffs",
+            MessageKind::Error => "errors",
+            MessageKind::System => "system",
+        }
+    }
+}
+
+impl FromStr for MessageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "user" | "users" | "you" => Ok(MessageKind::User),
+            "assistant" | "answers" => Ok(MessageKind::Assistant),
+            "narration" => Ok(MessageKind::Narration),
+            "tool" | "tools" => Ok(MessageKind::Tool),
+            "diff" | "diffs" => Ok(MessageKind::Diff),
+            "error" | "errors" => Ok(MessageKind::Error),
+            "system" => Ok(MessageKind::System),
+            other => Err(format!("unknown message kind '{other}'")),
+        }
+    }
+}
+
+/// Per-message metadata kept alongside `App.messages` (same index).
+#[derive(Debug, Clone)]
+pub struct MessageMeta {
+    /// Stable id, unique for the session (never reused after /clear); used for
+    /// `#msg-<id>` anchors in exports and `/goto <id>`.
+    pub id: usize,
+    pub timestamp: DateTime<Local>,
+}
+
+/// In-chat search state.
+#[derive(Debug, Clone, Default)]
+pub struct SearchState {
+    pub query: String,
+    /// Still typing the query; keys edit it instead of navigating.
+    pub editing: bool,
+    /// Indices of messages containing the query (case-insensitive).
+    pub matches: Vec<usize>,
+    /// Position in `matches` of the active hit.
+    pub current: usize,
+    /// Scroll the chat pane to the active hit on the next frame.
+    pub jump_pending: bool,
+}
+
 /// Tool status for the sidebar.
 #[derive(Debug, Clone)]
 pub struct ToolStatus {
@@ -21,6 +266,21 @@ pub struct ToolStatus {
     pub success: bool,
 }
 
+/// Running totals for one tool, from its `ToolCallCompleted` events.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ToolStats {
+    pub calls: usize,
+    pub failures: usize,
+    pub total_ms: u64,
+}
+
+impl ToolStats {
+    /// Share of calls that failed, in percent.
+    pub fn failure_percent(&self) -> usize {
+        self.failures * 100 / self.calls.max(1)
+    }
+}
+
 /// LLM call log entry for the sidebar.
 #[derive(Debug, Clone)]
 pub struct LlmCallEntry {
@@ -28,6 +288,33 @@ pub struct LlmCallEntry {
     pub prompt_tokens: usize,
     pub completion_tokens: usize,
     pub duration_ms: u64,
+    pub timestamp: DateTime<Local>,
+}
+
+/// A persistent warning pinned under the status panel until dismissed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    /// Condition this notice reports (e.g. "context"); pinning it again updates the text.
+    pub id: String,
+    pub text: String,
+}
+
+/// A named artifact produced by a workflow stage (plan, summary, intermediate JSON).
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub stage_id: String,
+    pub name: String,
+    pub content: String,
+}
+
+impl Artifact {
+    /// Image file this artifact stands for: its name, or content that is just a path.
+    pub fn image_path(&self) -> Option<PathBuf> {
+        [self.name.as_str(), self.content.trim()]
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|path| graphics::is_image(path))
+    }
 }
 
 /// A trace log entry for the workflow trace panel.
@@ -35,10 +322,85 @@ pub struct LlmCallEntry {
 pub enum TraceEntry {
     StageStart { id: String, kind: String },
     StageEnd { id: String, duration_ms: u64, skipped: bool },
+    StageFailed { id: String, error: String },
     LlmCall { model: String, ctx_tokens: usize, out_tokens: usize, duration_ms: u64 },
     ToolCall { name: String, args: String },
-    ToolResult { name: String, success: bool, duration_ms: u64 },
+    ToolResult { name: String, success: bool, duration_ms: u64, error: Option<String> },
     Narration(String),
+    Artifact { stage_id: String, name: String },
+}
+
+impl TraceEntry {
+    pub fn kind(&self) -> TraceKind {
+        match self {
+            TraceEntry::StageStart { .. } | TraceEntry::StageEnd { .. } | TraceEntry::StageFailed { .. } => {
+                TraceKind::Stages
+            }
+            TraceEntry::LlmCall { .. } => TraceKind::Llm,
+            TraceEntry::ToolCall { .. } | TraceEntry::ToolResult { .. } => TraceKind::Tools,
+            TraceEntry::Narration(_) => TraceKind::Narration,
+            TraceEntry::Artifact { .. } => TraceKind::Artifacts,
+        }
+    }
+
+    /// Whether a `/trace filter` to `kinds` lets it through; none lets everything through.
+    pub fn passes(&self, kinds: &BTreeSet<TraceKind>) -> bool {
+        kinds.is_empty() || kinds.contains(&self.kind())
+    }
+}
+
+/// Trace entry categories that `/trace filter` can limit the panel to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TraceKind {
+    Stages,
+    Llm,
+    Tools,
+    Narration,
+    Artifacts,
+}
+
+impl TraceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceKind::Stages => "stages",
+            TraceKind::Llm => "llm",
+            TraceKind::Tools => "tools",
+            TraceKind::Narration => "narration",
+            TraceKind::Artifacts => "artifacts",
+        }
+    }
+}
+
+impl FromStr for TraceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stage" | "stages" => Ok(TraceKind::Stages),
+            "llm" | "model" => Ok(TraceKind::Llm),
+            "tool" | "tools" => Ok(TraceKind::Tools),
+            "narration" => Ok(TraceKind::Narration),
+            "artifact" | "artifacts" => Ok(TraceKind::Artifacts),
+            other => Err(format!("unknown trace kind '{other}'")),
+        }
+    }
+}
+
+/// Progress of one workflow stage in the sidebar pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageState {
+    Pending,
+    Running,
+    Done,
+    Skipped,
+    Failed,
+}
+
+/// A stage of the loaded workflow, in pipeline order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineStage {
+    pub id: String,
+    pub state: StageState,
 }
 
 /// Status info for the sidebar.
@@ -47,18 +409,148 @@ pub struct StatusInfo {
     pub model: String,
     pub agent_name: String,
     pub workflow: String,
+    /// Autonomy level of the agent's policy, e.g. `supervised`.
+    pub autonomy: String,
     pub total_tokens: usize,
     pub total_turns: usize,
     pub cost: f64,
+    /// Tokens and cost by model; the sidebar lists them once more than one was used.
+    pub per_model: Vec<ModelCost>,
+}
+
+/// One message or summary in the conversation sent to the model, for `/context`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextEntry {
+    /// `system`, `user`, `assistant`, `tool` or `summary`, as the kernel labels it.
+    pub role: String,
+    pub text: String,
+    /// Estimated, as the kernel doesn't count tokens per message.
+    pub tokens: usize,
+    /// Pinned with `/memory pin`, so compaction keeps it.
+    pub pinned: bool,
+}
+
+/// How often the agent read and wrote a file this session, for `/files`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileAccess {
+    pub reads: usize,
+    pub writes: usize,
+    pub last: DateTime<Local>,
+}
+
+/// A prompt sent to the background queue with `&` or `/bg`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundTask {
+    /// 1-based, in the order the tasks were submitted.
+    pub id: usize,
+    pub prompt: String,
+    pub state: TaskState,
+    /// `/bg cancel` asked for it to stop.
+    pub canceled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskState {
+    Queued,
+    Running(Instant),
+    Done { duration_ms: u64 },
+    Failed { duration_ms: u64 },
+    Canceled,
+}
+
+/// One turn the session ran, kept in its stats for `/stats`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// Unix time the turn finished (or failed).
+    pub finished: i64,
+    pub duration_ms: u64,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub cost: f64,
+    /// Name and outcome of each tool call the turn made.
+    pub tools: Vec<(String, bool)>,
+    pub failed: bool,
+}
+
+/// `/stats`: latency, tokens, tool use and cost over the turns run so far.
+pub fn stats_report(turns: &[TurnRecord]) -> String {
+    if turns.is_empty() {
+        return "No turns yet.".into();
+    }
+    let count = turns.len();
+    let failed = turns.iter().filter(|turn| turn.failed).count();
+    let total_ms: u64 = turns.iter().map(|turn| turn.duration_ms).sum();
+    let slowest = turns.iter().map(|turn| turn.duration_ms).max().unwrap_or(0);
+    let prompt: usize = turns.iter().map(|turn| turn.prompt_tokens).sum();
+    let completion: usize = turns.iter().map(|turn| turn.completion_tokens).sum();
+    let cost: f64 = turns.iter().map(|turn| turn.cost).sum();
+    let mut out = format!(
+        "{count} turns{} · avg {} · slowest {}\nTokens: {} in, {} out · {} per turn\nCost: ${cost:.2} · ${:.3} per turn",
+        if failed > 0 { format!(" ({failed} failed)") } else { String::new() },
+        format_duration(total_ms / count as u64),
+        format_duration(slowest),
+        format_tokens(prompt),
+        format_tokens(completion),
+        format_tokens((prompt + completion) / count),
+        cost / count as f64,
+    );
+
+    let mut tools: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for (name, success) in turns.iter().flat_map(|turn| &turn.tools) {
+        let (calls, failures) = tools.entry(name.as_str()).or_default();
+        *calls += 1;
+        *failures += usize::from(!success);
+    }
+    if !tools.is_empty() {
+        let mut tools: Vec<(&str, (usize, usize))> = tools.into_iter().collect();
+        tools.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+        let total: usize = tools.iter().map(|(_, (calls, _))| calls).sum();
+        let busiest = tools[0].1 .0;
+        let width = tools.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        out.push_str(&format!("\n\nTool calls ({total})"));
+        for (name, (calls, failures)) in tools {
+            let bar = "█".repeat((calls * 20).div_ceil(busiest));
+            let failures = if failures > 0 { format!("  {failures} failed") } else { String::new() };
+            out.push_str(&format!("\n  {name:<width$} {calls:>4} {bar}{failures}"));
+        }
+    }
+
+    out.push_str("\n\nCost over time\n     #  time      took    tokens   cost     total");
+    let mut running = 0.0;
+    for (i, turn) in turns.iter().enumerate() {
+        running += turn.cost;
+        let time = DateTime::from_timestamp(turn.finished, 0)
+            .map(|t| t.with_timezone(&Local).format("%H:%M:%S").to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "\n  {:>4}  {time:<8}  {:>6}  {:>7}  ${:<7.3} ${running:.3}{}",
+            i + 1,
+            format_duration(turn.duration_ms),
+            format_tokens(turn.prompt_tokens + turn.completion_tokens),
+            turn.cost,
+            if turn.failed { "  failed" } else { "" },
+        ));
+    }
+    out
+}
+
+/// Tokens and estimated cost of one model this session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelCost {
+    pub model: String,
+    pub tokens: usize,
+    pub cost: f64,
 }
 
 impl StatusInfo {
+    /// Every token the session has spent. Unlike `total_tokens`, which
+    /// `/compact` starts over, this only grows.
+    pub fn spent_tokens(&self) -> usize {
+        self.per_model.iter().map(|model| model.tokens).sum()
+    }
+
     pub fn tokens_display(&self) -> String {
-        if self.total_tokens >= 1000 {
-            format!("{:.1}k", self.total_tokens as f64 / 1000.0)
-        } else {
-            format!("{}", self.total_tokens)
-        }
+        format_tokens(self.total_tokens)
     }
 
     pub fn cost_display(&self) -> String {
@@ -66,40 +558,330 @@ impl StatusInfo {
     }
 }
 
-/// Which panel has focus for scrolling.
+/// How much of the manifest's token budget the session has used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLevel {
+    Ok,
+    Warn,
+    Alert,
+    Exceeded,
+}
+
+/// What the agent is doing, for the status line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    Idle,
+    Working,
+    /// Working, but waiting out a provider's rate limit.
+    RateLimited,
+    /// Esc was pressed; the turn is winding down.
+    Canceling,
+}
+
+/// Which panel has focus. Keys go to the input bar unless a panel is focused.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PanelFocus {
+    Input,
     Chat,
+    /// The sidebar's active tab.
+    Sidebar,
+}
+
+/// Tabs of the sidebar; each keeps its own scroll state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidebarTab {
+    Status,
     Trace,
+    Llm,
+    Files,
+}
+
+impl SidebarTab {
+    pub const ALL: [SidebarTab; 4] = [SidebarTab::Status, SidebarTab::Trace, SidebarTab::Llm, SidebarTab::Files];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            SidebarTab::Status => "Status",
+            SidebarTab::Trace => "Trace",
+            SidebarTab::Llm => "LLM",
+            SidebarTab::Files => "Files",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&tab| tab == self).unwrap_or(0)
+    }
+}
+
+/// A view drawn over the panels (full-screen, or a centered popup); it takes
+/// all keys while open.
+#[derive(Debug, Clone)]
+pub enum Overlay {
+    Pager(PagerState),
+    JsonTree(JsonTreeState),
+    Popup(PopupState),
+    Palette(PaletteState),
+    Trace(TraceViewState),
+    Compare(CompareState),
+}
+
+/// Anthropic models `/model` completes; the aliases track the newest of each family.
+pub const ANTHROPIC_MODELS: &[&str] = &["sonnet", "opus", "haiku"];
+
+/// Context window of `model` in tokens, from its family name; 200k when unknown.
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    let windows: &[(&str, usize)] = &[
+        ("claude", 200_000),
+        ("gpt-4.1", 1_000_000),
+        ("gpt-4o", 128_000),
+        ("gemini", 1_000_000),
+        ("llama3", 128_000),
+        ("qwen", 32_768),
+        ("mistral", 32_768),
+        ("phi", 16_384),
+    ];
+    windows
+        .iter()
+        .find(|(family, _)| model.contains(family))
+        .map(|&(_, window)| window)
+        .unwrap_or(200_000)
+}
+
+/// Token count as "950" or "12.3k".
+pub fn format_tokens(tokens: usize) -> String {
+    if tokens >= 1000 {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    } else {
+        format!("{tokens}")
+    }
+}
+
+/// Duration as "850ms" or "1.2s".
+pub fn format_duration(ms: u64) -> String {
+    if ms >= 1000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{ms}ms")
+    }
+}
+
+/// Wall-clock span as "45s", "3m 12s" or "1h 02m".
+pub fn format_elapsed(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Shorten text to at most `max` characters, appending "..." when cut.
+pub fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max.saturating_sub(3)).collect();
+    format!("{kept}...")
+}
+
+/// Extract the contents of fenced (```) code blocks from markdown text.
+pub fn code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(body) => blocks.push(body.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(body) = current.as_mut() {
+            body.push(line);
+        }
+    }
+    // An unterminated fence still counts (e.g. a truncated response)
+    if let Some(body) = current {
+        blocks.push(body.join("\n"));
+    }
+    blocks
 }
 
 /// Main application state.
 pub struct App {
     pub messages: Vec<ChatMessage>,
+    pub message_meta: Vec<MessageMeta>,
+    next_message_id: usize,
+    /// Wrapped chat rows, reused across frames.
+    pub line_cache: LineCache,
+    /// Message index the chat pane should scroll to on the next frame.
+    pub jump_to_message: Option<usize>,
     pub input: String,
     pub cursor_pos: usize,
+    /// First visible chat row, in rendered (post-wrap) lines; `usize::MAX` follows the tail.
     pub scroll_offset: usize,
+    /// How the chat scrolls while an answer streams in.
+    pub stream_scroll: StreamScroll,
+    /// Index of the assistant message currently being streamed.
+    pub streaming: Option<usize>,
+    /// Kills the `!` command that is running, if one is.
+    pub shell: Option<ShellKiller>,
+    /// Controls for the agent thread, once it is running.
+    pub control: Option<mpsc::Sender<Control>>,
+    /// Shared with the session, to pause the running turn and steer it.
+    pub steering: Steering,
+    /// Rendered chat height and viewport, updated by the chat pane every frame.
+    pub chat_lines: usize,
+    pub chat_view_height: usize,
+    /// How image previews are drawn; `None` shows a text placeholder.
+    pub image_protocol: ImageProtocol,
+    /// Image file previewed under a message, by message index.
+    pub images: HashMap<usize, PathBuf>,
+    /// Where this frame's previews go, filled in by the chat pane.
+    pub image_slots: Vec<ImageSlot>,
+    /// Message kinds hidden from the chat pane by `/filter`.
+    pub hidden_kinds: BTreeSet<MessageKind>,
+    /// Links on screen this frame, filled in by the chat pane.
+    pub link_slots: Vec<LinkSlot>,
+    /// Message and link last opened with `o`, so repeated presses cycle.
+    link_cursor: Option<(usize, usize)>,
     pub status: StatusInfo,
     pub recent_files: Vec<String>,
+    /// Every file a tool changed this session, for the exit summary.
+    pub changed_files: BTreeSet<String>,
+    /// Every file the agent read or wrote this session, by path.
+    pub file_access: BTreeMap<String, FileAccess>,
+    /// `(path, diff)` for every write this session, oldest first. Kept apart from
+    /// the chat so `/diff` still works after `/clear`.
+    pub diffs: Vec<(String, String)>,
+    /// Paths transcripts were exported to this session.
+    pub exports: Vec<String>,
+    /// Paths `/save` and `/checkpoint` wrote this session, for the exit summary.
+    pub saves: Vec<String>,
+    /// Command aliases from `tui.toml` and `/alias`, by name without the slash.
+    pub aliases: BTreeMap<String, String>,
+    /// User-defined commands from `tui.toml`.
+    pub custom_commands: CustomCommands,
+    /// Where `/checkpoint` writes and `/restore` looks.
+    pub checkpoint_dir: Option<PathBuf>,
+    /// Models installed in the local Ollama, once the agent thread has asked it.
+    pub ollama_models: Vec<String>,
+    /// Completions Tab is cycling through, and which one is in the input.
+    completion: Option<(Vec<String>, usize)>,
+    /// When the session started.
+    pub started: Instant,
+    /// Token budget from the manifest, shown as a bar in the status panel.
+    pub token_budget: Option<usize>,
+    pub budget_thresholds: BudgetConfig,
     pub recent_tools: Vec<ToolStatus>,
+    /// Per-tool call counts, failures and time for the sidebar's tool panel.
+    pub tool_stats: HashMap<String, ToolStats>,
     pub llm_calls: Vec<LlmCallEntry>,
     pub trace_log: Vec<TraceEntry>,
+    /// Stages of the current workflow, updated from the stage events in the trace.
+    pub pipeline: Vec<PipelineStage>,
+    /// When each trace entry was recorded (same index as `trace_log`).
+    pub trace_times: Vec<DateTime<Local>>,
+    /// Whether new trace entries are recorded (`/trace on|off`).
+    pub trace_enabled: bool,
+    /// Kinds the trace panel, viewer and exports show; empty shows all (`/trace filter`).
+    pub trace_kinds: BTreeSet<TraceKind>,
+    pub artifacts: Vec<Artifact>,
+    pub notices: Vec<Notice>,
+    /// Capabilities `--safe-mode` still withholds.
+    pub locked: BTreeSet<Capability>,
+    /// Text of dismissed notices by id, so re-pinning the same warning stays quiet.
+    dismissed_notices: HashMap<String, String>,
+    /// Trace entry (a stage start) selected for actions like retry.
+    pub selected_trace: Option<usize>,
     pub trace_scroll: Option<usize>,  // None = auto-scroll (follow), Some(n) = pinned at offset n
+    /// First LLM call shown in the log panel; None follows the newest.
+    pub llm_log_scroll: Option<usize>,
+    /// Sidebar tab on show (and taking keys while the sidebar has focus).
+    pub sidebar_tab: SidebarTab,
+    /// First visible row of the Status tab.
+    pub status_scroll: usize,
+    /// First visible row of the Files tab.
+    pub files_scroll: usize,
     pub focus: PanelFocus,
+    /// Whether the right column (status, trace, LLM log) is shown.
+    pub show_sidebar: bool,
+    /// Zen mode: full-width chat over a one-line input, without the sidebar.
+    pub zen: bool,
+    /// Whether the sidebar was shown before zen mode hid it.
+    sidebar_before_zen: bool,
+    /// Pane ratios, adjusted with Alt+Arrow keys.
+    pub splits: Splits,
+    /// Whether the sidebar sits right of the chat or under it.
+    pub layout_mode: LayoutMode,
+    pub selected_message: Option<usize>,
+    /// Expanded tool call and thought blocks.
+    pub expanded_tools: HashSet<usize>,
+    pub search: Option<SearchState>,
+    /// Up/Down move over every message instead of just tool blocks.
+    pub selection_mode: bool,
+    /// Short-lived feedback shown in the chat border (e.g. "Copied").
+    pub toast: Option<(String, Instant)>,
+    pub overlay: Option<Overlay>,
+    /// Set by `/pager` or `p`; the main loop suspends the TUI and opens `$PAGER`.
+    pub external_pager: bool,
+    /// Set by `/edit --editor`; the main loop opens the last prompt in `$EDITOR`.
+    pub external_editor: bool,
+    /// The input bar holds the last prompt for `/edit`; submitting it replaces that exchange.
+    pub editing_prompt: bool,
+    /// Theme to restore if the `/theme` picker, which previews as it moves, is closed without a pick.
+    pub theme_before_preview: Option<Theme>,
+    /// A `/tab` command for the main loop, which owns the tabs.
+    pub tab_request: Option<TabAction>,
+    /// Background tasks of this session, oldest first, for the sidebar.
+    pub background_tasks: Vec<BackgroundTask>,
+    /// Tasks submitted but not yet handed to the background queue, which the
+    /// main loop owns.
+    pub background_requests: Vec<(usize, String)>,
+    /// Tasks the background queue already has that `/bg cancel` stops, for
+    /// the main loop.
+    pub background_cancels: Vec<usize>,
     pub agent_busy: bool,
     pub should_quit: bool,
     pub input_history: Vec<String>,
     pub history_index: Option<usize>,
     pub thinking_since: Option<Instant>,
+    /// Stage started but not yet completed, and when it started.
+    pub running_stage: Option<(String, Instant)>,
+    /// Tool call started but not yet completed, and when it started.
+    pub running_tool: Option<(String, Instant)>,
+    /// Provider that rate-limited the turn, and when the retry fires.
+    pub rate_limited: Option<(String, Instant)>,
+    /// The running turn was asked to stop and hasn't finished yet.
+    pub canceling: bool,
+    /// Seconds before the running turn times out, once it is close.
+    pub timeout_left: Option<u64>,
+    /// Rate-limit responses seen this session.
+    pub rate_limit_hits: usize,
 }
 
 impl App {
     pub fn new(agent_name: &str, model: &str, workflow: &str) -> Self {
         Self {
             messages: Vec::new(),
+            message_meta: Vec::new(),
+            next_message_id: 1,
+            line_cache: LineCache::default(),
+            jump_to_message: None,
             input: String::new(),
             cursor_pos: 0,
             scroll_offset: 0,
+            stream_scroll: StreamScroll::default(),
+            streaming: None,
+            shell: None,
+            control: None,
+            steering: Steering::default(),
+            chat_lines: 0,
+            chat_view_height: 0,
+            image_protocol: ImageProtocol::None,
+            images: HashMap::new(),
+            image_slots: Vec::new(),
+            hidden_kinds: BTreeSet::new(),
+            link_slots: Vec::new(),
+            link_cursor: None,
             status: StatusInfo {
                 model: model.to_string(),
                 agent_name: agent_name.to_string(),
@@ -107,16 +889,68 @@ impl App {
                 ..Default::default()
             },
             recent_files: Vec::new(),
+            changed_files: BTreeSet::new(),
+            file_access: BTreeMap::new(),
+            diffs: Vec::new(),
+            exports: Vec::new(),
+            saves: Vec::new(),
+            aliases: BTreeMap::new(),
+            custom_commands: CustomCommands::new(),
+            checkpoint_dir: None,
+            ollama_models: Vec::new(),
+            completion: None,
+            started: Instant::now(),
+            token_budget: None,
+            budget_thresholds: BudgetConfig::default(),
             recent_tools: Vec::new(),
+            tool_stats: HashMap::new(),
             llm_calls: Vec::new(),
             trace_log: Vec::new(),
+            pipeline: Vec::new(),
+            trace_times: Vec::new(),
+            trace_enabled: true,
+            trace_kinds: BTreeSet::new(),
+            artifacts: Vec::new(),
+            notices: Vec::new(),
+            locked: BTreeSet::new(),
+            dismissed_notices: HashMap::new(),
+            selected_trace: None,
             trace_scroll: None,
-            focus: PanelFocus::Chat,
+            llm_log_scroll: None,
+            sidebar_tab: SidebarTab::Status,
+            status_scroll: 0,
+            files_scroll: 0,
+            focus: PanelFocus::Input,
+            show_sidebar: true,
+            zen: false,
+            sidebar_before_zen: true,
+            splits: Splits::default(),
+            layout_mode: LayoutMode::default(),
+            selected_message: None,
+            expanded_tools: HashSet::new(),
+            search: None,
+            selection_mode: false,
+            toast: None,
+            overlay: None,
+            external_pager: false,
+            external_editor: false,
+            editing_prompt: false,
+            theme_before_preview: None,
+            tab_request: None,
+            background_tasks: Vec::new(),
+            background_requests: Vec::new(),
+            background_cancels: Vec::new(),
             agent_busy: false,
             should_quit: false,
             input_history: Vec::new(),
             history_index: None,
             thinking_since: None,
+            running_stage: None,
+            running_tool: None,
+            rate_limited: None,
+            canceling: false,
+            timeout_left: None,
+            rate_limit_hits: 0,
         }
     }
 
@@ -217,99 +1051,1436 @@ impl App {
 
     pub fn add_message(&mut self, msg: ChatMessage) {
         self.messages.push(msg);
+        self.message_meta.push(MessageMeta { id: self.next_message_id, timestamp: Local::now() });
+        self.next_message_id += 1;
+        self.detect_image(self.messages.len() - 1);
+        if let Some(search) = self.search.as_mut() {
+            // Keep the match list current without yanking the view away from the active hit
+            let index = self.messages.len() - 1;
+            let needle = search.query.to_ascii_lowercase();
+            let visible = !self.hidden_kinds.contains(&self.messages[index].kind());
+            if visible && !needle.is_empty() && self.messages[index].text().to_ascii_lowercase().contains(&needle) {
+                search.matches.push(index);
+            }
+            return;
+        }
         // Auto-scroll to bottom
         self.scroll_offset = usize::MAX;
     }
 
-    pub fn add_recent_file(&mut self, path: String) {
-        // Remove if already present, then push to front
-        self.recent_files.retain(|f| f != &path);
-        self.recent_files.insert(0, path);
-        if self.recent_files.len() > 10 {
-            self.recent_files.truncate(10);
+    pub fn push_trace(&mut self, entry: TraceEntry) {
+        match &entry {
+            TraceEntry::StageStart { id, .. } => self.set_stage_state(id, StageState::Running),
+            TraceEntry::StageEnd { id, skipped, .. } => {
+                self.set_stage_state(id, if *skipped { StageState::Skipped } else { StageState::Done })
+            }
+            TraceEntry::StageFailed { id, .. } => self.set_stage_state(id, StageState::Failed),
+            _ => {}
+        }
+        match &entry {
+            TraceEntry::StageStart { id, .. } => self.running_stage = Some((id.clone(), Instant::now())),
+            TraceEntry::StageEnd { id, .. } | TraceEntry::StageFailed { id, .. }
+                if self.running_stage.as_ref().is_some_and(|(running, _)| running == id) =>
+            {
+                self.running_stage = None
+            }
+            TraceEntry::ToolCall { name, .. } => self.running_tool = Some((name.clone(), Instant::now())),
+            TraceEntry::ToolResult { .. } => self.running_tool = None,
+            _ => {}
+        }
+        // Pipeline and running state still follow while the trace isn't recorded
+        if !self.trace_enabled {
+            return;
         }
+        self.trace_log.push(entry);
+        self.trace_times.push(Local::now());
     }
 
-    pub fn add_recent_tool(&mut self, name: String, success: bool) {
-        self.recent_tools.insert(0, ToolStatus { name, success });
-        if self.recent_tools.len() > 8 {
-            self.recent_tools.truncate(8);
+    /// Start a fresh pipeline for a turn of the workflow with these stages.
+    pub fn set_pipeline(&mut self, stages: Vec<String>) {
+        self.pipeline = stages.into_iter().map(|id| PipelineStage { id, state: StageState::Pending }).collect();
+    }
+
+    /// Stages missing from the workflow definition (or with none loaded) are
+    /// appended as they report in.
+    fn set_stage_state(&mut self, id: &str, state: StageState) {
+        match self.pipeline.iter_mut().find(|stage| stage.id == id) {
+            Some(stage) => stage.state = state,
+            None => self.pipeline.push(PipelineStage { id: id.to_string(), state }),
         }
     }
 
-    pub fn clear_messages(&mut self) {
-        self.messages.clear();
-        self.scroll_offset = 0;
+    fn is_stage_start(&self, index: usize) -> bool {
+        matches!(self.trace_log.get(index), Some(TraceEntry::StageStart { .. }))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Show or hide the sidebar, moving focus off it when hidden. Returns whether it's shown.
+    /// In zen mode this leaves zen with the sidebar showing.
+    pub fn toggle_sidebar(&mut self) -> bool {
+        if self.zen {
+            self.zen = false;
+            self.show_sidebar = true;
+            return true;
+        }
+        self.show_sidebar = !self.show_sidebar;
+        if !self.show_sidebar && self.focus == PanelFocus::Sidebar {
+            self.focus = PanelFocus::Chat;
+        }
+        self.show_sidebar
+    }
 
-    #[test]
-    fn test_app_new() {
-        let app = App::new("test-agent", "sonnet", "default");
-        assert!(app.messages.is_empty());
-        assert!(app.input.is_empty());
-        assert_eq!(app.cursor_pos, 0);
-        assert_eq!(app.scroll_offset, 0);
-        assert_eq!(app.status.agent_name, "test-agent");
-        assert_eq!(app.status.model, "sonnet");
-        assert_eq!(app.status.workflow, "default");
-        assert_eq!(app.status.total_tokens, 0);
-        assert_eq!(app.status.cost, 0.0);
-        assert_eq!(app.focus, PanelFocus::Chat);
-        assert!(!app.agent_busy);
-        assert!(!app.should_quit);
-        assert!(app.input_history.is_empty());
-        assert!(app.history_index.is_none());
+    /// Enter or leave zen mode. Leaving restores the sidebar as it was. Returns
+    /// whether zen mode is on.
+    pub fn toggle_zen(&mut self) -> bool {
+        self.zen = !self.zen;
+        if self.zen {
+            self.sidebar_before_zen = self.show_sidebar;
+            self.show_sidebar = false;
+            if self.focus == PanelFocus::Sidebar {
+                self.focus = PanelFocus::Chat;
+            }
+        } else {
+            self.show_sidebar = self.sidebar_before_zen;
+        }
+        self.zen
     }
 
-    #[test]
-    fn test_add_message() {
-        let mut app = App::new("a", "m", "w");
-        app.add_message(ChatMessage::User("hello".into()));
-        assert_eq!(app.messages.len(), 1);
-        assert_eq!(app.scroll_offset, usize::MAX);
-        app.add_message(ChatMessage::Assistant("hi".into()));
-        assert_eq!(app.messages.len(), 2);
+    /// Whether the sidebar has focus with `tab` showing.
+    pub fn tab_focused(&self, tab: SidebarTab) -> bool {
+        self.focus == PanelFocus::Sidebar && self.sidebar_tab == tab
     }
 
-    #[test]
-    fn test_add_recent_tool() {
-        let mut app = App::new("a", "m", "w");
-        for i in 0..10 {
-            app.add_recent_tool(format!("tool_{i}"), true);
+    /// Show `tab` and give the sidebar focus.
+    pub fn focus_tab(&mut self, tab: SidebarTab) {
+        self.sidebar_tab = tab;
+        self.focus = PanelFocus::Sidebar;
+    }
+
+    /// Tab/Shift+Tab: Input → Chat → each sidebar tab in turn → Input.
+    pub fn cycle_focus(&mut self, forward: bool) {
+        let tab = self.sidebar_tab.index();
+        let last = SidebarTab::ALL.len() - 1;
+        match (self.focus, forward) {
+            (PanelFocus::Input, true) => self.focus = PanelFocus::Chat,
+            (PanelFocus::Chat, true) if self.show_sidebar => self.focus_tab(SidebarTab::ALL[0]),
+            (PanelFocus::Sidebar, true) if tab < last => self.sidebar_tab = SidebarTab::ALL[tab + 1],
+            (PanelFocus::Chat | PanelFocus::Sidebar, true) => self.focus = PanelFocus::Input,
+            (PanelFocus::Input, false) if self.show_sidebar => self.focus_tab(SidebarTab::ALL[last]),
+            (PanelFocus::Sidebar, false) if tab > 0 => self.sidebar_tab = SidebarTab::ALL[tab - 1],
+            (PanelFocus::Input | PanelFocus::Sidebar, false) => self.focus = PanelFocus::Chat,
+            (PanelFocus::Chat, false) => self.focus = PanelFocus::Input,
         }
-        assert_eq!(app.recent_tools.len(), 8); // max capacity
-        assert_eq!(app.recent_tools[0].name, "tool_9"); // most recent first
     }
 
-    #[test]
-    fn test_add_recent_file() {
-        let mut app = App::new("a", "m", "w");
-        app.add_recent_file("a.rs".into());
-        app.add_recent_file("b.rs".into());
-        app.add_recent_file("a.rs".into()); // dedup
-        assert_eq!(app.recent_files.len(), 2);
-        assert_eq!(app.recent_files[0], "a.rs"); // moved to front
+    /// Scroll the Files tab by `delta` rows.
+    pub fn scroll_files(&mut self, delta: isize) {
+        self.files_scroll = self.files_scroll.saturating_add_signed(delta);
+    }
 
-        for i in 0..15 {
-            app.add_recent_file(format!("file_{i}.rs"));
-        }
-        assert_eq!(app.recent_files.len(), 10); // max capacity
+    /// Scroll the Status tab by `delta` rows.
+    pub fn scroll_status(&mut self, delta: isize) {
+        self.status_scroll = self.status_scroll.saturating_add_signed(delta);
     }
 
-    #[test]
-    fn test_input_editing() {
-        let mut app = App::new("a", "m", "w");
-        app.insert_char('h');
-        app.insert_char('i');
-        assert_eq!(app.input, "hi");
-        assert_eq!(app.cursor_pos, 2);
+    /// Prompt tokens of the latest LLM call (the context actually sent) and the
+    /// model's context window.
+    pub fn context_usage(&self) -> (usize, usize) {
+        let used = self.llm_calls.last().map_or(0, |call| call.prompt_tokens);
+        (used, context_window(&self.status.model))
+    }
 
-        app.move_cursor_left();
+    /// `/context`: what the conversation is made of, entry by entry, against
+    /// the model's context window.
+    pub fn context_report(&self, entries: &[ContextEntry]) -> String {
+        let (used, window) = self.context_usage();
+        let estimated: usize = entries.iter().map(|entry| entry.tokens).sum();
+        let percent = |tokens: usize| tokens as f64 * 100.0 / window.max(1) as f64;
+        let mut out = format!(
+            "{} entries, ~{} tokens ({:.1}% of the {} window)",
+            entries.len(),
+            format_tokens(estimated),
+            percent(estimated),
+            format_tokens(window),
+        );
+        if used > 0 {
+            out.push_str(&format!("\nLast call sent {} tokens ({:.1}%)", format_tokens(used), percent(used)));
+        }
+        out.push('\n');
+        for (i, entry) in entries.iter().enumerate() {
+            let preview = entry.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            out.push_str(&format!(
+                "\n{:>3}. {:<9} {:>6} {:>5.1}% {} {}",
+                i + 1,
+                entry.role,
+                format_tokens(entry.tokens),
+                percent(entry.tokens),
+                if entry.pinned { icons::current().pin } else { " " },
+                shorten(&preview, 56),
+            ));
+        }
+        out
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        if !self.agent_busy {
+            RunMode::Idle
+        } else if self.canceling {
+            RunMode::Canceling
+        } else if self.rate_limited.is_some() {
+            RunMode::RateLimited
+        } else {
+            RunMode::Working
+        }
+    }
+
+    /// Terminal window title, so a finished run stands out among terminal tabs.
+    pub fn terminal_title(&self) -> String {
+        let state = match self.run_mode() {
+            RunMode::Idle => "idle",
+            RunMode::Canceling => "canceling",
+            _ => "working",
+        };
+        format!("neocognos: {} — {state} ({})", self.status.agent_name, self.status.model)
+    }
+
+    /// Tokens used against the manifest budget, and how close that is to the limit.
+    pub fn budget_usage(&self) -> Option<(usize, usize, BudgetLevel)> {
+        let budget = self.token_budget?;
+        let used = self.status.spent_tokens();
+        let percent = used * 100 / budget.max(1);
+        let level = if used > budget {
+            BudgetLevel::Exceeded
+        } else if percent >= self.budget_thresholds.alert as usize {
+            BudgetLevel::Alert
+        } else if percent >= self.budget_thresholds.warn as usize {
+            BudgetLevel::Warn
+        } else {
+            BudgetLevel::Ok
+        };
+        Some((used, budget, level))
+    }
+
+    /// Scroll the LLM call log by `delta` calls; scrolling past the newest resumes following.
+    pub fn scroll_llm_log(&mut self, delta: isize) {
+        let total = self.llm_calls.len();
+        let position = self.llm_log_scroll.unwrap_or(total).saturating_add_signed(delta);
+        self.llm_log_scroll = (position < total).then_some(position);
+    }
+
+    /// Cumulative prompt tokens, completion tokens and latency over every LLM call.
+    pub fn llm_totals(&self) -> (usize, usize, u64) {
+        self.llm_calls.iter().fold((0, 0, 0), |(ctx, out, ms), call| {
+            (ctx + call.prompt_tokens, out + call.completion_tokens, ms + call.duration_ms)
+        })
+    }
+
+    /// Note that `provider` rate-limited the turn and it is retried in `secs`.
+    pub fn set_rate_limited(&mut self, provider: &str, secs: u64) {
+        self.rate_limited = Some((provider.to_string(), Instant::now() + Duration::from_secs(secs)));
+        self.rate_limit_hits += 1;
+    }
+
+    /// Seconds until the rate-limited retry fires (0 once it is under way), if
+    /// the turn is waiting on one.
+    pub fn rate_limit_wait(&self) -> Option<u64> {
+        self.rate_limited
+            .as_ref()
+            .map(|(_, until)| until.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as u64)
+    }
+
+    /// Durations of the last `n` LLM calls in milliseconds, oldest first.
+    pub fn recent_latencies(&self, n: usize) -> Vec<u64> {
+        let skip = self.llm_calls.len().saturating_sub(n);
+        self.llm_calls.iter().skip(skip).map(|call| call.duration_ms).collect()
+    }
+
+    /// Select the previous stage in the trace (the last one if none is selected).
+    pub fn select_prev_stage(&mut self) {
+        let end = self.selected_trace.unwrap_or(self.trace_log.len());
+        if let Some(i) = (0..end).rev().find(|&i| self.is_stage_start(i)) {
+            self.selected_trace = Some(i);
+        }
+    }
+
+    pub fn select_next_stage(&mut self) {
+        let Some(current) = self.selected_trace else {
+            self.select_prev_stage();
+            return;
+        };
+        if let Some(i) = (current + 1..self.trace_log.len()).find(|&i| self.is_stage_start(i)) {
+            self.selected_trace = Some(i);
+        }
+    }
+
+    /// Id of the selected stage if that run of it failed.
+    pub fn selected_failed_stage(&self) -> Option<String> {
+        let start = self.selected_trace?;
+        let TraceEntry::StageStart { id, .. } = self.trace_log.get(start)? else {
+            return None;
+        };
+        // The stage's outcome is the first end/failure for the same id after its start
+        self.trace_log[start + 1..]
+            .iter()
+            .find_map(|entry| match entry {
+                TraceEntry::StageFailed { id: failed, .. } if failed == id => Some(true),
+                TraceEntry::StageEnd { id: ended, .. } if ended == id => Some(false),
+                _ => None,
+            })
+            .filter(|&failed| failed)
+            .map(|_| id.clone())
+    }
+
+    /// Largest useful chat scroll offset: the content height minus the viewport.
+    pub fn max_chat_scroll(&self) -> usize {
+        self.chat_lines.saturating_sub(self.chat_view_height)
+    }
+
+    /// Current first visible chat row, resolving tail-follow and clamping to the content.
+    pub fn chat_scroll_position(&self) -> usize {
+        self.scroll_offset.min(self.max_chat_scroll())
+    }
+
+    /// Scroll the chat by `delta` rendered lines; reaching the bottom resumes following.
+    pub fn scroll_chat_by(&mut self, delta: isize) {
+        let position = self.chat_scroll_position().saturating_add_signed(delta);
+        self.scroll_offset = if position >= self.max_chat_scroll() { usize::MAX } else { position };
+    }
+
+    /// Lines moved by PageUp/PageDown: a screenful, keeping two lines of context.
+    pub fn chat_page(&self) -> usize {
+        self.chat_view_height.saturating_sub(2).max(1)
+    }
+
+    /// Lines moved by Ctrl+U/Ctrl+D in the chat pane.
+    pub fn chat_half_page(&self) -> usize {
+        (self.chat_view_height / 2).max(1)
+    }
+
+    /// Jump to the first chat line (`g`/Home).
+    pub fn scroll_chat_to_top(&mut self) {
+        self.scroll_offset = 0;
+    }
+
+    /// Jump to the last chat line and resume following (`G`/End).
+    pub fn scroll_chat_to_bottom(&mut self) {
+        self.scroll_offset = usize::MAX;
+    }
+
+    /// Append streamed answer text, starting a new assistant message if needed.
+    pub fn append_stream(&mut self, delta: &str) {
+        let previous_offset = self.chat_scroll_position();
+        let Some(index) = self.streaming else {
+            self.add_message(ChatMessage::Assistant(delta.to_string()));
+            let index = self.messages.len() - 1;
+            self.streaming = Some(index);
+            match self.stream_scroll {
+                StreamScroll::Follow => {}
+                StreamScroll::Top => {
+                    self.jump_to_message = Some(index);
+                }
+                StreamScroll::None => self.scroll_offset = previous_offset,
+            }
+            return;
+        };
+        if let Some(ChatMessage::Assistant(text)) = self.messages.get_mut(index) {
+            text.push_str(delta);
+        }
+        self.line_cache.invalidate_from(index);
+        if self.stream_scroll == StreamScroll::Follow && self.search.is_none() {
+            self.scroll_offset = usize::MAX;
+        }
+    }
+
+    /// A `!` command started; its output streams into a message of its own.
+    pub fn start_shell(&mut self, command: String, killer: ShellKiller) {
+        self.add_message(ChatMessage::Shell { command, output: String::new(), exit: None, killed: false });
+        self.shell = Some(killer);
+    }
+
+    /// The message of the `!` command still running.
+    fn running_shell(&self) -> Option<usize> {
+        self.messages.iter().rposition(|msg| matches!(msg, ChatMessage::Shell { exit: None, .. }))
+    }
+
+    /// Append lines of output to the running `!` command.
+    pub fn append_shell(&mut self, lines: &[String]) {
+        let Some(index) = self.running_shell() else { return };
+        if let Some(ChatMessage::Shell { output, .. }) = self.messages.get_mut(index) {
+            for line in lines {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(line);
+            }
+        }
+        self.line_cache.invalidate_from(index);
+        if self.search.is_none() {
+            self.scroll_offset = usize::MAX;
+        }
+    }
+
+    /// The running `!` command exited with `code`.
+    pub fn finish_shell(&mut self, code: u32, was_killed: bool) {
+        self.shell = None;
+        let Some(index) = self.running_shell() else { return };
+        if let Some(ChatMessage::Shell { exit, killed, .. }) = self.messages.get_mut(index) {
+            *exit = Some(code);
+            *killed = was_killed;
+        }
+        self.line_cache.invalidate_from(index);
+    }
+
+    /// Ctrl+C while a `!` command runs: kill it. Returns false if none is running.
+    pub fn kill_shell(&mut self) -> bool {
+        let Some(killer) = &self.shell else { return false };
+        killer.kill();
+        self.show_toast("Killing the shell command");
+        true
+    }
+
+    /// Queue `prompt` as a background task; the main loop hands it on.
+    pub fn queue_background(&mut self, prompt: String) -> usize {
+        let id = self.background_tasks.len() + 1;
+        self.background_requests.push((id, prompt.clone()));
+        self.add_message(ChatMessage::System(format!(
+            "{} Background task #{id} queued: {}",
+            icons::current().waiting,
+            shorten(&prompt, 60)
+        )));
+        self.background_tasks.push(BackgroundTask { id, prompt, state: TaskState::Queued, canceled: false });
+        id
+    }
+
+    /// `/bg cancel <id>`: drop a task the queue doesn't have yet, or ask the
+    /// main loop to stop it.
+    pub fn cancel_background(&mut self, id: usize) {
+        let Some(task) = self.background_tasks.iter_mut().find(|task| task.id == id) else {
+            self.add_message(ChatMessage::System(format!("No background task #{id}")));
+            return;
+        };
+        if !matches!(task.state, TaskState::Queued | TaskState::Running(_)) || task.canceled {
+            self.add_message(ChatMessage::System(format!("Background task #{id} isn't queued or running")));
+            return;
+        }
+        task.canceled = true;
+        let before = self.background_requests.len();
+        self.background_requests.retain(|(request, _)| *request != id);
+        if self.background_requests.len() < before {
+            self.finish_background(id, Err(String::new()));
+        } else {
+            self.background_cancels.push(id);
+            self.show_toast(format!("Canceling background task #{id}"));
+        }
+    }
+
+    /// Background task `id` reached the front of its queue.
+    pub fn start_background(&mut self, id: usize) {
+        if let Some(task) = self.background_tasks.iter_mut().find(|task| task.id == id) {
+            task.state = TaskState::Running(Instant::now());
+        }
+    }
+
+    /// Background task `id` finished with an answer or an error; the chat gets
+    /// the outcome.
+    pub fn finish_background(&mut self, id: usize, outcome: Result<String, String>) {
+        let Some(task) = self.background_tasks.iter_mut().find(|task| task.id == id) else { return };
+        let duration_ms = match task.state {
+            TaskState::Running(since) => since.elapsed().as_millis() as u64,
+            _ => 0,
+        };
+        let prompt = shorten(&task.prompt, 60);
+        let icons = icons::current();
+        let text = match outcome {
+            Ok(answer) => {
+                task.state = TaskState::Done { duration_ms };
+                format!(
+                    "{} Background task #{id} done in {}: {prompt}\n\n{answer}",
+                    icons.ok,
+                    format_duration(duration_ms)
+                )
+            }
+            Err(_) if task.canceled => {
+                task.state = TaskState::Canceled;
+                format!("{} Background task #{id} canceled: {prompt}", icons.skipped)
+            }
+            Err(error) => {
+                task.state = TaskState::Failed { duration_ms };
+                format!("{} Background task #{id} failed: {prompt}\n{error}", icons.failed)
+            }
+        };
+        self.add_message(ChatMessage::System(text));
+        self.show_toast(format!("Background task #{id} finished"));
+    }
+
+    /// Esc while the agent works: kill the running `!` command, or cancel the turn.
+    pub fn cancel_turn(&mut self) {
+        if !self.agent_busy || self.kill_shell() {
+            return;
+        }
+        if let Some(control) = &self.control {
+            let _ = control.send(Control::Cancel);
+        }
+        self.canceling = true;
+        // A turn waiting out a rate limit ends instead of retrying
+        self.rate_limited = None;
+        // A paused turn has to run on to notice
+        self.steering.resume(None);
+        self.steering.take_guidance();
+        self.show_toast("Canceling the turn…");
+    }
+
+    /// Ctrl+G: pause the running turn before its next tool call or stage, or
+    /// resume it without guidance.
+    pub fn toggle_pause(&mut self) {
+        if self.steering.is_paused() {
+            self.steering.resume(None);
+            self.show_toast("Resumed");
+        } else if self.agent_busy {
+            self.steering.pause();
+            self.focus = PanelFocus::Input;
+            self.show_toast("Pausing before the next tool call or stage; type guidance and press Enter");
+        } else {
+            self.show_toast("Nothing is running");
+        }
+    }
+
+    /// Enter while paused: queue `text` for the model and resume.
+    pub fn steer(&mut self, text: String) {
+        self.add_message(ChatMessage::User(text.clone()));
+        self.steering.resume(Some(text));
+        self.add_message(ChatMessage::System(format!("{} Guidance queued for the agent; resuming", icons::current().resume)));
+    }
+
+    /// Guidance the failed turn never passed on: back into the input bar to send
+    /// again, or shown in full if something is being typed there already.
+    pub fn return_guidance(&mut self, guidance: String) {
+        let warning = icons::current().warning;
+        if self.input.is_empty() {
+            self.input = guidance;
+            self.cursor_pos = self.input.len();
+            self.add_message(ChatMessage::System(format!(
+                "{warning} The turn failed before your guidance reached the agent; it's back in the input bar"
+            )));
+        } else {
+            self.add_message(ChatMessage::System(format!(
+                "{warning} The turn failed before your guidance reached the agent: {guidance}"
+            )));
+        }
+    }
+
+    /// Settle the streamed message with the final answer text, keeping what
+    /// streamed when that is empty. The next delta starts a message of its own.
+    /// Returns false if nothing was streaming, in which case the caller adds the
+    /// answer itself.
+    pub fn finish_stream(&mut self, text: &str) -> bool {
+        let Some(index) = self.streaming.take() else {
+            return false;
+        };
+        if let Some(ChatMessage::Assistant(streamed)) = self.messages.get_mut(index) {
+            if streamed != text && !text.is_empty() {
+                *streamed = text.to_string();
+                self.line_cache.invalidate_from(index);
+            }
+        }
+        self.detect_image(index);
+        self.refresh_search();
+        true
+    }
+
+    /// Add a message previewing the image at `path`, e.g. for `/image` or an image artifact.
+    pub fn show_image(&mut self, path: &Path) -> Result<(), String> {
+        if !graphics::is_image(path) {
+            return Err(format!("{} is not a supported image (png, jpg, gif)", path.display()));
+        }
+        let (width, height) =
+            image::image_dimensions(path).map_err(|e| format!("Can't read {}: {e}", path.display()))?;
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        self.add_message(ChatMessage::System(format!("{} {name} — {width}×{height}", icons::current().image)));
+        self.images.insert(self.messages.len() - 1, path.to_path_buf());
+        Ok(())
+    }
+
+    /// Remember the image a prompt, answer or tool call refers to, for a preview.
+    fn detect_image(&mut self, index: usize) {
+        let path = match &self.messages[index] {
+            // Images named in a prompt are usually attachments for a vision model
+            ChatMessage::User(_) if self.locked.contains(&Capability::Attachments) => None,
+            ChatMessage::User(text) | ChatMessage::Assistant(text) => graphics::find_image(text),
+            ChatMessage::ToolCall { args, .. } => graphics::find_image(args),
+            _ => None,
+        };
+        if let Some(path) = path {
+            self.images.insert(index, path);
+            self.line_cache.invalidate_from(index);
+        }
+    }
+
+    /// Note that the agent read (or wrote) `path`.
+    pub fn record_file_access(&mut self, path: String, write: bool) {
+        let access = self
+            .file_access
+            .entry(path.clone())
+            .or_insert(FileAccess { reads: 0, writes: 0, last: Local::now() });
+        access.last = Local::now();
+        if write {
+            access.writes += 1;
+        } else {
+            access.reads += 1;
+        }
+        self.add_recent_file(path);
+    }
+
+    /// Show the diff of a change a tool made in the chat, keep it for `/diff`
+    /// and count the file as changed.
+    pub fn record_diff(&mut self, path: String, diff: String) {
+        self.changed_files.insert(path.clone());
+        self.diffs.push((path.clone(), diff.clone()));
+        self.add_message(ChatMessage::Diff { path, diff });
+    }
+
+    /// The most recent write's diff, or the most recent one to `path` (matched
+    /// whole or as a trailing path, so `main.rs` finds `src/main.rs`).
+    pub fn last_diff(&self, path: Option<&str>) -> Option<&(String, String)> {
+        let path = path.map(|p| p.trim_start_matches("./"));
+        let matches = |written: &str| match path {
+            None => true,
+            Some(path) => written == path || written.ends_with(&format!("/{path}")),
+        };
+        self.diffs.iter().rev().find(|(written, _)| matches(written))
+    }
+
+    /// `/diff`: read the last write's diff full screen, colored.
+    pub fn open_diff(&mut self, path: Option<&str>) -> bool {
+        let Some((written, diff)) = self.last_diff(path) else {
+            return false;
+        };
+        let pager = PagerState::diff(format!("Diff · {written}"), diff.clone());
+        self.overlay = Some(Overlay::Pager(pager));
+        true
+    }
+
+    /// `/files`: every file touched this session, most recent first.
+    pub fn files_report(&self) -> String {
+        if self.file_access.is_empty() {
+            return "No files touched yet.".into();
+        }
+        let mut files: Vec<(&String, &FileAccess)> = self.file_access.iter().collect();
+        files.sort_by_key(|(_, access)| std::cmp::Reverse(access.last));
+        let mut out = format!("Files touched this session ({}):\n  reads writes  last      path", files.len());
+        for (path, access) in files {
+            out.push_str(&format!(
+                "\n  {:>5} {:>6}  {}  {path}",
+                access.reads,
+                access.writes,
+                access.last.format("%H:%M:%S"),
+            ));
+        }
+        out
+    }
+
+    pub fn add_recent_file(&mut self, path: String) {
+        // Remove if already present, then push to front
+        self.recent_files.retain(|f| f != &path);
+        self.recent_files.insert(0, path);
+        if self.recent_files.len() > 10 {
+            self.recent_files.truncate(10);
+        }
+    }
+
+    /// Summary printed to the terminal on exit (after leaving the alternate screen).
+    pub fn shutdown_summary(&self) -> String {
+        const LISTED_FILES: usize = 5;
+        let mut out = format!(
+            "Session: {} · {} turns · {} tokens · {}",
+            format_elapsed(self.started.elapsed().as_secs()),
+            self.status.total_turns,
+            self.status.tokens_display(),
+            self.status.cost_display(),
+        );
+        if !self.changed_files.is_empty() {
+            out.push_str(&format!("\nFiles changed ({}):", self.changed_files.len()));
+            for path in self.changed_files.iter().take(LISTED_FILES) {
+                out.push_str(&format!("\n  {path}"));
+            }
+            if self.changed_files.len() > LISTED_FILES {
+                out.push_str(&format!("\n  … and {} more", self.changed_files.len() - LISTED_FILES));
+            }
+        }
+        for path in &self.exports {
+            out.push_str(&format!("\nTranscript: {path}"));
+        }
+        for path in &self.saves {
+            out.push_str(&format!("\nSaved: {path}"));
+        }
+        out
+    }
+
+    pub fn add_recent_tool(&mut self, name: String, success: bool) {
+        self.recent_tools.insert(0, ToolStatus { name, success });
+        if self.recent_tools.len() > 8 {
+            self.recent_tools.truncate(8);
+        }
+    }
+
+    /// Count a finished tool call in the tool statistics.
+    pub fn record_tool_call(&mut self, name: &str, success: bool, duration_ms: u64) {
+        let stats = self.tool_stats.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.failures += usize::from(!success);
+        stats.total_ms += duration_ms;
+    }
+
+    /// Tool statistics, busiest tool first.
+    pub fn tool_stats_by_calls(&self) -> Vec<(&str, ToolStats)> {
+        let mut stats: Vec<(&str, ToolStats)> =
+            self.tool_stats.iter().map(|(name, stats)| (name.as_str(), *stats)).collect();
+        stats.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then(a.0.cmp(b.0)));
+        stats
+    }
+
+    /// Store an artifact; a stage re-emitting the same name replaces the old content.
+    pub fn add_artifact(&mut self, artifact: Artifact) {
+        if let Some(existing) = self
+            .artifacts
+            .iter_mut()
+            .find(|a| a.stage_id == artifact.stage_id && a.name == artifact.name)
+        {
+            *existing = artifact;
+        } else {
+            self.artifacts.push(artifact);
+        }
+    }
+
+    /// Look up an artifact by 1-based index or by name (latest match wins).
+    pub fn find_artifact(&self, target: &str) -> Option<&Artifact> {
+        if let Ok(n) = target.parse::<usize>() {
+            return n.checked_sub(1).and_then(|i| self.artifacts.get(i));
+        }
+        self.artifacts.iter().rev().find(|a| a.name == target)
+    }
+
+    /// Pair each tool call with the result that completed it (call index → result index).
+    /// Results are matched to the oldest pending call of the same tool.
+    pub fn tool_pairs(&self) -> HashMap<usize, usize> {
+        let mut pending: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut pairs = HashMap::new();
+        for (i, msg) in self.messages.iter().enumerate() {
+            match msg {
+                ChatMessage::ToolCall { name, .. } => {
+                    pending.entry(name.as_str()).or_default().push(i);
+                }
+                ChatMessage::ToolResult { name, .. } => {
+                    if let Some(calls) = pending.get_mut(name.as_str()) {
+                        if !calls.is_empty() {
+                            pairs.insert(calls.remove(0), i);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        pairs
+    }
+
+    /// Move the chat selection to the previous collapsible block (tool call or thought).
+    pub fn select_prev_tool(&mut self) {
+        let end = self.selected_message.unwrap_or(self.messages.len());
+        if let Some(i) = (0..end).rev().find(|&i| self.is_block(i) && self.is_visible(i)) {
+            self.selected_message = Some(i);
+        }
+    }
+
+    /// Move the chat selection to the next collapsible block.
+    pub fn select_next_tool(&mut self) {
+        let Some(current) = self.selected_message else {
+            return;
+        };
+        if let Some(i) = (current + 1..self.messages.len()).find(|&i| self.is_block(i) && self.is_visible(i)) {
+            self.selected_message = Some(i);
+        }
+    }
+
+    /// Expand or collapse the selected tool call or thought block.
+    pub fn toggle_selected_tool(&mut self) {
+        if let Some(i) = self.selected_message.filter(|&i| self.is_block(i)) {
+            if !self.expanded_tools.remove(&i) {
+                self.expanded_tools.insert(i);
+            }
+        }
+    }
+
+    /// Move the selection to the previous message (tool results belong to their call block).
+    pub fn select_prev_message(&mut self) {
+        let end = self.selected_message.unwrap_or(self.messages.len());
+        let paired: HashSet<usize> = self.tool_pairs().into_values().collect();
+        if let Some(i) = (0..end).rev().find(|&i| !paired.contains(&i) && self.is_visible(i)) {
+            self.selected_message = Some(i);
+        }
+    }
+
+    /// Move the selection to the next message.
+    pub fn select_next_message(&mut self) {
+        let Some(current) = self.selected_message else {
+            return;
+        };
+        let paired: HashSet<usize> = self.tool_pairs().into_values().collect();
+        if let Some(i) = (current + 1..self.messages.len()).find(|&i| !paired.contains(&i) && self.is_visible(i)) {
+            self.selected_message = Some(i);
+        }
+    }
+
+    /// Enter or leave message selection mode. Entering selects the newest message.
+    pub fn toggle_selection_mode(&mut self) {
+        self.selection_mode = !self.selection_mode;
+        if self.selection_mode {
+            if self.selected_message.is_none() {
+                self.select_prev_message();
+            }
+        } else {
+            self.selected_message = None;
+        }
+    }
+
+    /// Whether message `index` passes the `/filter` settings.
+    pub fn is_visible(&self, index: usize) -> bool {
+        self.messages.get(index).is_some_and(|m| !self.hidden_kinds.contains(&m.kind()))
+    }
+
+    /// Apply `/set key value`, or describe the current value when `value` is
+    /// None. Returns the confirmation, or why the setting was rejected.
+    pub fn set_option(&mut self, key: &str, value: Option<&str>) -> Result<String, String> {
+        let current = match key {
+            "layout.chat" => self.splits.chat(),
+            "layout.status" => self.splits.status,
+            _ => return Err(format!("Unknown setting '{key}' (try layout.chat or layout.status)")),
+        };
+        let Some(value) = value else {
+            return Ok(format!("{key} = {current}"));
+        };
+        let percent: u16 = value.trim_end_matches('%').parse().map_err(|_| format!("{key} takes a percentage, got '{value}'"))?;
+        match key {
+            "layout.chat" => self.splits.set_chat(percent),
+            _ => self.splits.set_status(percent),
+        }
+        let applied = if key == "layout.chat" { self.splits.chat() } else { self.splits.status };
+        Ok(format!("{key} = {applied}"))
+    }
+
+    /// Apply a `/filter` change and describe the result.
+    pub fn apply_filter(&mut self, action: FilterAction) -> String {
+        match action {
+            FilterAction::Status => {}
+            FilterAction::Hide(kinds) => self.hidden_kinds.extend(kinds),
+            FilterAction::Show(kinds) => {
+                for kind in kinds {
+                    self.hidden_kinds.remove(&kind);
+                }
+            }
+            FilterAction::Only(kinds) => {
+                self.hidden_kinds = MessageKind::ALL.into_iter().filter(|k| !kinds.contains(k)).collect();
+            }
+            FilterAction::Reset => self.hidden_kinds.clear(),
+        }
+        if self.selected_message.is_some_and(|i| !self.is_visible(i)) {
+            self.selected_message = None;
+        }
+        self.refresh_search();
+        let names = |kinds: Vec<MessageKind>| kinds.into_iter().map(MessageKind::name).collect::<Vec<_>>().join(", ");
+        if self.hidden_kinds.is_empty() {
+            return "Showing all messages".into();
+        }
+        let shown: Vec<MessageKind> = MessageKind::ALL.into_iter().filter(|k| !self.hidden_kinds.contains(k)).collect();
+        format!(
+            "Showing {} · hidden: {} (/filter reset to show all)",
+            if shown.is_empty() { "nothing".to_string() } else { names(shown) },
+            names(self.hidden_kinds.iter().copied().collect()),
+        )
+    }
+
+    /// Whether the trace panel shows `entry` under the `/trace filter`.
+    pub fn shows_trace(&self, entry: &TraceEntry) -> bool {
+        entry.passes(&self.trace_kinds)
+    }
+
+    /// Apply a `/trace` command; returns a one-line summary of the trace settings.
+    pub fn apply_trace(&mut self, action: TraceAction) -> String {
+        match action {
+            TraceAction::Status => {}
+            TraceAction::Enable(enabled) => self.trace_enabled = enabled,
+            TraceAction::Filter(kinds) => {
+                self.trace_kinds = kinds.into_iter().collect();
+                self.trace_scroll = None;
+            }
+        }
+        let recording = if self.trace_enabled { "on" } else { "off (/trace on resumes)" };
+        let shown = if self.trace_kinds.is_empty() {
+            "all entries".to_string()
+        } else {
+            self.trace_kinds.iter().map(|kind| kind.name()).collect::<Vec<_>>().join(", ")
+        };
+        format!("Trace {recording} · showing {shown}")
+    }
+
+    /// `/alias`: change the aliases and report them.
+    pub fn apply_alias(&mut self, action: AliasAction) -> String {
+        match action {
+            AliasAction::List => {}
+            AliasAction::Set { name, .. } if commands::is_builtin(&name) => {
+                return format!("/{name} is a built-in command and can't be an alias");
+            }
+            AliasAction::Set { name, .. } if self.custom_commands.contains_key(&name) => {
+                return format!("/{name} is a command from tui.toml and can't be an alias");
+            }
+            AliasAction::Set { name, .. } if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')) => {
+                return format!("Invalid alias name '{name}' (use letters, digits, - and _)");
+            }
+            AliasAction::Set { name, text } => {
+                let summary = format!("Alias /{name} → {text}");
+                self.aliases.insert(name, text);
+                return summary;
+            }
+            AliasAction::Remove(name) => {
+                return match self.aliases.remove(&name) {
+                    Some(_) => format!("Removed alias /{name}"),
+                    None => format!("No alias /{name}"),
+                };
+            }
+        }
+        if self.aliases.is_empty() {
+            return "No aliases (/alias <name> <text> defines one)".into();
+        }
+        let width = self.aliases.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+        let mut out = String::from("Aliases:");
+        for (name, text) in &self.aliases {
+            out.push_str(&format!("\n  /{name:<width$}  {text}"));
+        }
+        out
+    }
+
+    /// The input with a leading alias expanded, if it starts with one.
+    pub fn expand_alias(&self, input: &str) -> Option<String> {
+        commands::expand_alias(input, &self.aliases)
+    }
+
+    /// What the argument of `command` can be, for Tab completion.
+    fn argument_completions(&self, command: &str) -> Vec<String> {
+        match command {
+            "/model" => ANTHROPIC_MODELS
+                .iter()
+                .map(|model| model.to_string())
+                .chain(self.ollama_models.iter().cloned())
+                .collect(),
+            "/theme" => PRESETS.iter().map(|(name, _)| name.to_string()).collect(),
+            "/load" => session_store::list(session_store::dir().as_deref()),
+            "/restore" => session_store::list(self.checkpoint_dir.as_deref()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Tab in the input bar: complete the command name or its argument. One
+    /// completion is filled in; several fill in what they share and are listed,
+    /// and further Tabs cycle through them.
+    pub fn complete_input(&mut self) {
+        if let Some((completions, i)) = &mut self.completion {
+            if completions.get(*i) == Some(&self.input) {
+                *i = (*i + 1) % completions.len();
+                self.input = completions[*i].clone();
+                self.cursor_pos = self.input.len();
+                return;
+            }
+        }
+        self.completion = None;
+        let mut names: Vec<String> = commands::COMMANDS.iter().map(|name| name.to_string()).collect();
+        names.extend(self.custom_commands.keys().chain(self.aliases.keys()).map(|name| format!("/{name}")));
+        let completions = commands::completions(&self.input, &names, |command| self.argument_completions(command));
+        match completions.len() {
+            0 => return,
+            1 => self.input = format!("{} ", completions[0]),
+            _ => {
+                let prefix = commands::common_prefix(&completions);
+                let listed: Vec<&str> = completions.iter().map(|c| c.rsplit(' ').next().unwrap_or(c)).collect();
+                self.show_toast(listed.join("  "));
+                if prefix.len() > self.input.len() {
+                    self.input = prefix;
+                } else {
+                    self.input = completions[0].clone();
+                    self.completion = Some((completions, 0));
+                }
+            }
+        }
+        self.cursor_pos = self.input.len();
+    }
+
+    pub fn selected(&self) -> Option<&ChatMessage> {
+        self.selected_message.and_then(|i| self.messages.get(i))
+    }
+
+    /// Pin (or update) the notice for condition `id`, unless this exact text was dismissed.
+    pub fn pin_notice(&mut self, id: &str, text: String) {
+        if self.dismissed_notices.get(id) == Some(&text) {
+            return;
+        }
+        self.dismissed_notices.remove(id);
+        match self.notices.iter_mut().find(|n| n.id == id) {
+            Some(notice) => notice.text = text,
+            None => self.notices.push(Notice { id: id.to_string(), text }),
+        }
+    }
+
+    /// Record what safe mode withholds and keep a notice pinned while it withholds anything.
+    pub fn set_locked(&mut self, locked: Vec<Capability>) {
+        self.locked = locked.into_iter().collect();
+        if self.locked.is_empty() {
+            self.clear_notice("safe-mode");
+        } else {
+            let names: Vec<&str> = self.locked.iter().map(|c| c.name()).collect();
+            self.pin_notice("safe-mode", format!("Safe mode: {} disabled (/unsafe)", names.join(", ")));
+        }
+    }
+
+    /// Drop the notice for `id` because its condition went away.
+    pub fn clear_notice(&mut self, id: &str) {
+        self.notices.retain(|n| n.id != id);
+        self.dismissed_notices.remove(id);
+    }
+
+    /// Dismiss the 1-based notice `n`, or every notice. Returns how many were dismissed.
+    pub fn dismiss_notices(&mut self, n: Option<usize>) -> usize {
+        let dismissed = match n {
+            None => std::mem::take(&mut self.notices),
+            Some(n) if (1..=self.notices.len()).contains(&n) => vec![self.notices.remove(n - 1)],
+            Some(_) => Vec::new(),
+        };
+        for notice in &dismissed {
+            self.dismissed_notices.insert(notice.id.clone(), notice.text.clone());
+        }
+        dismissed.len()
+    }
+
+    /// Show a transient notice in the chat border.
+    pub fn show_toast(&mut self, text: impl Into<String>) {
+        self.toast = Some((text.into(), Instant::now()));
+    }
+
+    /// Stable id of the message at `index`.
+    pub fn message_id(&self, index: usize) -> Option<usize> {
+        self.message_meta.get(index).map(|m| m.id)
+    }
+
+    /// Select and scroll to the message with stable id `id`. Tool results resolve
+    /// to their call block. Returns false if no such message is on screen.
+    pub fn goto_message(&mut self, id: usize) -> bool {
+        let Some(mut index) = self.message_meta.iter().position(|m| m.id == id) else {
+            return false;
+        };
+        if let Some((&call, _)) = self.tool_pairs().iter().find(|(_, &result)| result == index) {
+            index = call;
+        }
+        self.selected_message = Some(index);
+        self.jump_to_message = Some(index);
+        self.focus = PanelFocus::Chat;
+        true
+    }
+
+    /// Scroll to the start of the most recent assistant answer. Returns false if there is none.
+    pub fn jump_to_last_answer(&mut self) -> bool {
+        let Some(index) = self.messages.iter().rposition(|m| matches!(m, ChatMessage::Assistant(_))) else {
+            return false;
+        };
+        self.jump_to_message = Some(index);
+        true
+    }
+
+    /// Next link to open from the selected message (or the latest one with links),
+    /// cycling on repeated calls. Returns (url, position, count).
+    pub fn next_link(&mut self) -> Option<(String, usize, usize)> {
+        let index = match self.selected_message {
+            Some(index) => index,
+            None => self.messages.iter().rposition(|m| !links::find_urls(&m.text()).is_empty())?,
+        };
+        let text = self.messages.get(index)?.text();
+        let urls = links::urls(&text);
+        if urls.is_empty() {
+            return None;
+        }
+        let n = match self.link_cursor {
+            Some((i, n)) if i == index => (n + 1) % urls.len(),
+            _ => 0,
+        };
+        self.link_cursor = Some((index, n));
+        Some((urls[n].to_string(), n + 1, urls.len()))
+    }
+
+    /// Open the selected message in the pager. Collapsible blocks expand in place instead.
+    pub fn open_pager(&mut self) -> bool {
+        match self.selected_message.filter(|&i| !self.is_block(i)) {
+            Some(index) => self.open_overlay(index, false),
+            None => false,
+        }
+    }
+
+    /// Show the selected message's exact text (markdown, whitespace) in the raw view.
+    pub fn open_raw_view(&mut self) -> bool {
+        match self.selected_message {
+            Some(index) => self.open_overlay(index, true),
+            None => false,
+        }
+    }
+
+    /// Browse the selected tool call's arguments as a collapsible JSON tree.
+    pub fn open_json_tree(&mut self) -> bool {
+        let Some(index) = self.selected_message else { return false };
+        let Some(ChatMessage::ToolCall { name, args, .. }) = self.messages.get(index) else {
+            return false;
+        };
+        self.overlay = Some(Overlay::JsonTree(JsonTreeState::new(format!("{} {name} args", icons::current().tool), args)));
+        true
+    }
+
+    /// Expand the trace to full screen, narrowed to the stage selected in the Trace tab if any.
+    pub fn open_trace_view(&mut self) {
+        let stage = self.selected_trace.and_then(|i| match self.trace_log.get(i) {
+            Some(TraceEntry::StageStart { id, .. }) => Some(id.clone()),
+            _ => None,
+        });
+        self.overlay = Some(Overlay::Trace(TraceViewState::new(stage)));
+    }
+
+    /// Browse an agent state snapshot (from `/state`) as a collapsible JSON tree.
+    pub fn open_state(&mut self, json: &str) {
+        self.overlay = Some(Overlay::JsonTree(JsonTreeState::new(format!("{} Agent state", icons::current().model), json)));
+    }
+
+    fn open_overlay(&mut self, index: usize, raw: bool) -> bool {
+        let title = match self.messages.get(index) {
+            Some(ChatMessage::Assistant(_)) => "Assistant",
+            Some(ChatMessage::User(_)) => "You",
+            Some(ChatMessage::Narration(_)) => "Narration",
+            Some(ChatMessage::Error(_)) => "Error",
+            Some(ChatMessage::System(_)) => "System",
+            Some(ChatMessage::ToolCall { .. }) => "Tool call",
+            Some(ChatMessage::Diff { .. }) => "Diff",
+            Some(ChatMessage::Shell { .. }) => "Shell",
+            _ => return false,
+        };
+        let text = match &self.messages[index] {
+            ChatMessage::ToolCall { args, .. } => args.clone(),
+            msg => msg.text(),
+        };
+        let title = format!("{title} · message {}", index + 1);
+        let pager = match &self.messages[index] {
+            _ if raw => PagerState::raw(title, text),
+            ChatMessage::Diff { .. } => PagerState::diff(title, text),
+            _ => PagerState::new(title, text),
+        };
+        self.overlay = Some(Overlay::Pager(pager));
+        true
+    }
+
+    /// Tool calls and thoughts render as blocks that expand and collapse.
+    fn is_block(&self, index: usize) -> bool {
+        matches!(self.messages.get(index), Some(ChatMessage::ToolCall { .. } | ChatMessage::Narration(_)))
+    }
+
+    /// Hide or show every thought block. Returns true if they are now hidden.
+    pub fn toggle_thoughts(&mut self) -> bool {
+        let hide = !self.hidden_kinds.contains(&MessageKind::Narration);
+        let kinds = vec![MessageKind::Narration];
+        self.apply_filter(if hide { FilterAction::Hide(kinds) } else { FilterAction::Show(kinds) });
+        hide
+    }
+
+    /// Begin typing a new search query.
+    pub fn start_search(&mut self) {
+        self.search = Some(SearchState { editing: true, ..Default::default() });
+    }
+
+    pub fn end_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        if let Some(search) = self.search.as_mut() {
+            search.query.push(c);
+        }
+        self.refresh_search();
+    }
+
+    pub fn search_pop(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.query.pop();
+        }
+        self.refresh_search();
+    }
+
+    /// Recompute matches for the current query; the newest hit becomes active.
+    pub fn refresh_search(&mut self) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let needle = search.query.to_ascii_lowercase();
+        search.matches = if needle.is_empty() {
+            Vec::new()
+        } else {
+            self.messages
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| !self.hidden_kinds.contains(&m.kind()))
+                .filter(|(_, m)| m.text().to_ascii_lowercase().contains(&needle))
+                .map(|(i, _)| i)
+                .collect()
+        };
+        search.current = search.matches.len().saturating_sub(1);
+        search.jump_pending = !search.matches.is_empty();
+    }
+
+    /// Jump to the next hit, wrapping around.
+    pub fn search_next(&mut self) {
+        if let Some(search) = self.search.as_mut().filter(|s| !s.matches.is_empty()) {
+            search.current = (search.current + 1) % search.matches.len();
+            search.jump_pending = true;
+        }
+    }
+
+    /// Jump to the previous hit, wrapping around.
+    pub fn search_prev(&mut self) {
+        if let Some(search) = self.search.as_mut().filter(|s| !s.matches.is_empty()) {
+            search.current = search.current.checked_sub(1).unwrap_or(search.matches.len() - 1);
+            search.jump_pending = true;
+        }
+    }
+
+    /// Message index of the active search hit.
+    pub fn active_match(&self) -> Option<usize> {
+        self.search.as_ref().and_then(|s| s.matches.get(s.current).copied())
+    }
+
+    pub fn clear_messages(&mut self) {
+        self.messages.clear();
+        self.message_meta.clear();
+        self.line_cache.invalidate();
+        self.streaming = None;
+        self.scroll_offset = 0;
+        self.selected_message = None;
+        self.selection_mode = false;
+        self.expanded_tools.clear();
+        self.images.clear();
+        self.link_cursor = None;
+        self.refresh_search();
+    }
+
+    /// The last prompt sent to the agent, leaving out slash and shell commands.
+    pub fn last_prompt(&self) -> Option<&str> {
+        self.messages.iter().rev().find_map(|m| match m {
+            ChatMessage::User(text) if !text.starts_with('/') && !text.starts_with('!') => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The agent's latest answer, for `/copy`.
+    pub fn last_answer(&self) -> Option<&str> {
+        self.messages.iter().rev().find_map(|m| match m {
+            ChatMessage::Assistant(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// What the agent said after message `id`: its answers, or else the last
+    /// error, as `/broadcast` compares them.
+    pub fn answer_since(&self, id: usize) -> Result<String, String> {
+        let mut answers = Vec::new();
+        let mut error = None;
+        for (message, _) in self.messages.iter().zip(&self.message_meta).filter(|(_, meta)| meta.id > id) {
+            match message {
+                ChatMessage::Assistant(text) => answers.push(text.as_str()),
+                ChatMessage::Error(text) => error = Some(text.clone()),
+                _ => {}
+            }
+        }
+        if answers.is_empty() {
+            Err(error.unwrap_or_else(|| "No answer".to_string()))
+        } else {
+            Ok(answers.join("\n\n"))
+        }
+    }
+
+    /// Load `prompt` into the input bar for `/edit`.
+    pub fn edit_prompt(&mut self, prompt: &str) {
+        self.input = prompt.to_string();
+        self.cursor_pos = self.input.len();
+        self.history_index = None;
+        self.editing_prompt = true;
+        self.focus = PanelFocus::Input;
+    }
+
+    pub fn cancel_edit(&mut self) {
+        self.editing_prompt = false;
+        self.input.clear();
+        self.cursor_pos = 0;
+    }
+
+    /// Take the exchange prompted by `input` back out of the chat: its last
+    /// showing and everything after it. False if the prompt isn't shown.
+    pub fn remove_exchange(&mut self, input: &str) -> bool {
+        let Some(index) = self.messages.iter().rposition(|m| matches!(m, ChatMessage::User(text) if text == input)) else {
+            return false;
+        };
+        self.truncate_messages(index);
+        true
+    }
+
+    /// Drop the answer streamed so far, for a turn whose attempt runs again.
+    pub fn discard_stream(&mut self) {
+        if let Some(index) = self.streaming {
+            self.truncate_messages(index);
+        }
+    }
+
+    /// Drop the messages from `index` on.
+    fn truncate_messages(&mut self, index: usize) {
+        self.messages.truncate(index);
+        self.message_meta.truncate(index);
+        self.line_cache.invalidate_from(index);
+        self.streaming = None;
+        self.scroll_offset = usize::MAX;
+        self.selected_message = self.selected_message.filter(|&i| i < index);
+        self.expanded_tools.retain(|&i| i < index);
+        self.images.retain(|&i, _| i < index);
+        self.link_cursor = None;
+        self.refresh_search();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_app_new() {
+        let app = App::new("test-agent", "sonnet", "default");
+        assert!(app.messages.is_empty());
+        assert!(app.input.is_empty());
+        assert_eq!(app.cursor_pos, 0);
+        assert_eq!(app.scroll_offset, 0);
+        assert_eq!(app.status.agent_name, "test-agent");
+        assert_eq!(app.status.model, "sonnet");
+        assert_eq!(app.status.workflow, "default");
+        assert_eq!(app.status.total_tokens, 0);
+        assert_eq!(app.status.cost, 0.0);
+        assert_eq!(app.focus, PanelFocus::Input);
+        assert!(!app.agent_busy);
+        assert!(!app.should_quit);
+        assert!(app.input_history.is_empty());
+        assert!(app.history_index.is_none());
+    }
+
+    #[test]
+    fn test_add_message() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hello".into()));
+        assert_eq!(app.messages.len(), 1);
+        assert_eq!(app.message_meta.len(), 1);
+        assert_eq!(app.scroll_offset, usize::MAX);
+        app.add_message(ChatMessage::Assistant("hi".into()));
+        assert_eq!(app.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_tool_stats() {
+        let mut app = App::new("a", "m", "w");
+        app.record_tool_call("exec", false, 900);
+        app.record_tool_call("read_file", true, 5);
+        app.record_tool_call("exec", true, 100);
+        app.record_tool_call("exec", false, 1000);
+        let stats = app.tool_stats_by_calls();
+        assert_eq!(stats[0], ("exec", ToolStats { calls: 3, failures: 2, total_ms: 2000 }));
+        assert_eq!(stats[0].1.failure_percent(), 66);
+        assert_eq!(stats[1], ("read_file", ToolStats { calls: 1, failures: 0, total_ms: 5 }));
+    }
+
+    #[test]
+    fn test_add_recent_tool() {
+        let mut app = App::new("a", "m", "w");
+        for i in 0..10 {
+            app.add_recent_tool(format!("tool_{i}"), true);
+        }
+        assert_eq!(app.recent_tools.len(), 8); // max capacity
+        assert_eq!(app.recent_tools[0].name, "tool_9"); // most recent first
+    }
+
+    #[test]
+    fn test_file_access() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.files_report(), "No files touched yet.");
+        for i in 0..12 {
+            app.record_file_access(format!("src/f{i}.rs"), false);
+        }
+        app.record_file_access("src/f0.rs".into(), true);
+        app.record_file_access("src/f0.rs".into(), false);
+        assert_eq!(app.recent_files.len(), 10);
+        assert_eq!(app.file_access.len(), 12);
+        assert_eq!(app.file_access["src/f0.rs"].reads, 2);
+        assert_eq!(app.file_access["src/f0.rs"].writes, 1);
+        // Only a diff shows the write changed anything
+        assert!(app.changed_files.is_empty());
+        app.record_diff("src/f0.rs".into(), "-a\n+b\n".into());
+        assert!(app.changed_files.contains("src/f0.rs"));
+
+        let report = app.files_report();
+        assert!(report.starts_with("Files touched this session (12):"));
+        let first = report.lines().nth(2).unwrap();
+        assert!(first.starts_with("      2      1  ") && first.ends_with("  src/f0.rs"), "{first}");
+    }
+
+    #[test]
+    fn test_add_recent_file() {
+        let mut app = App::new("a", "m", "w");
+        app.add_recent_file("a.rs".into());
+        app.add_recent_file("b.rs".into());
+        app.add_recent_file("a.rs".into()); // dedup
+        assert_eq!(app.recent_files.len(), 2);
+        assert_eq!(app.recent_files[0], "a.rs"); // moved to front
+
+        for i in 0..15 {
+            app.add_recent_file(format!("file_{i}.rs"));
+        }
+        assert_eq!(app.recent_files.len(), 10); // max capacity
+    }
+
+    #[test]
+    fn test_shutdown_summary() {
+        let mut app = App::new("a", "m", "w");
+        app.status.total_turns = 3;
+        app.status.total_tokens = 12_300;
+        let summary = app.shutdown_summary();
+        assert!(summary.starts_with("Session: 0s · 3 turns · 12.3k tokens · ~$0.0000"));
+        assert_eq!(summary.lines().count(), 1);
+
+        for i in 0..7 {
+            app.changed_files.insert(format!("src/f{i}.rs"));
+        }
+        app.exports.push("neocognos-transcript.md".into());
+        app.saves.push("sessions/refactor.json".into());
+        let summary = app.shutdown_summary();
+        assert!(summary.contains("Files changed (7):\n  src/f0.rs"));
+        assert!(summary.contains("… and 2 more"));
+        assert!(summary.ends_with("Transcript: neocognos-transcript.md\nSaved: sessions/refactor.json"));
+
+        assert_eq!(format_elapsed(192), "3m 12s");
+        assert_eq!(format_elapsed(3720), "1h 02m");
+    }
+
+    #[test]
+    fn test_input_editing() {
+        let mut app = App::new("a", "m", "w");
+        app.insert_char('h');
+        app.insert_char('i');
+        assert_eq!(app.input, "hi");
+        assert_eq!(app.cursor_pos, 2);
+
+        app.move_cursor_left();
         assert_eq!(app.cursor_pos, 1);
         app.insert_char('!');
         assert_eq!(app.input, "h!i");
@@ -327,61 +2498,348 @@ mod tests {
     }
 
     #[test]
-    fn test_history_navigation() {
+    fn test_history_navigation() {
+        let mut app = App::new("a", "m", "w");
+        app.input = "first".into();
+        app.submit_input();
+        app.input = "second".into();
+        app.submit_input();
+
+        app.history_up();
+        assert_eq!(app.input, "second");
+        app.history_up();
+        assert_eq!(app.input, "first");
+        app.history_up(); // at beginning, stays
+        assert_eq!(app.input, "first");
+
+        app.history_down();
+        assert_eq!(app.input, "second");
+        app.history_down(); // past end, clears
+        assert!(app.input.is_empty());
+    }
+
+    #[test]
+    fn test_panel_focus_toggle() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.focus, PanelFocus::Input);
+        app.focus = PanelFocus::Chat;
+        assert_eq!(app.focus, PanelFocus::Chat);
+        app.focus = PanelFocus::Sidebar;
+        assert_eq!(app.focus, PanelFocus::Sidebar);
+        app.focus = PanelFocus::Chat;
+        assert_eq!(app.focus, PanelFocus::Chat);
+    }
+
+    #[test]
+    fn test_cycle_focus_through_tabs() {
+        let mut app = App::new("a", "m", "w");
+        let mut seen = Vec::new();
+        for _ in 0..7 {
+            app.cycle_focus(true);
+            seen.push((app.focus, app.sidebar_tab));
+        }
+        use SidebarTab::*;
+        assert_eq!(
+            seen,
+            vec![
+                (PanelFocus::Chat, Status),
+                (PanelFocus::Sidebar, Status),
+                (PanelFocus::Sidebar, Trace),
+                (PanelFocus::Sidebar, Llm),
+                (PanelFocus::Sidebar, Files),
+                (PanelFocus::Input, Files),
+                (PanelFocus::Chat, Files),
+            ]
+        );
+        app.cycle_focus(false);
+        assert_eq!(app.focus, PanelFocus::Input);
+        app.cycle_focus(false);
+        assert!(app.tab_focused(Files));
+        app.cycle_focus(false);
+        assert!(app.tab_focused(Llm));
+        app.focus_tab(Status);
+        app.cycle_focus(false);
+        assert_eq!(app.focus, PanelFocus::Chat);
+
+        // A hidden sidebar drops out of the cycle
+        app.toggle_sidebar();
+        app.cycle_focus(true);
+        assert_eq!(app.focus, PanelFocus::Input);
+        app.cycle_focus(false);
+        assert_eq!(app.focus, PanelFocus::Chat);
+    }
+
+    #[test]
+    fn test_status_display() {
+        let mut info = StatusInfo::default();
+        info.total_tokens = 500;
+        assert_eq!(info.tokens_display(), "500");
+
+        info.total_tokens = 1000;
+        assert_eq!(info.tokens_display(), "1.0k");
+
+        info.total_tokens = 15432;
+        assert_eq!(info.tokens_display(), "15.4k");
+
+        info.cost = 0.0123;
+        assert_eq!(info.cost_display(), "~$0.0123");
+    }
+
+    #[test]
+    fn test_clear_messages() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::System("ok".into()));
+        assert_eq!(app.messages.len(), 2);
+        app.clear_messages();
+        assert!(app.messages.is_empty());
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_remove_exchange() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::Assistant("hello".into()));
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::Assistant("hello again".into()));
+        app.add_message(ChatMessage::User("/undo".into()));
+        app.expanded_tools.insert(3);
+        assert!(app.remove_exchange("hi"));
+        assert_eq!(app.messages.len(), 2);
+        assert_eq!(app.message_meta.len(), 2);
+        assert!(app.expanded_tools.is_empty());
+        assert!(!app.remove_exchange("never asked"));
+        assert_eq!(app.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_edit_prompt() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("first".into()));
+        app.add_message(ChatMessage::User("second".into()));
+        app.add_message(ChatMessage::User("!ls".into()));
+        app.add_message(ChatMessage::User("/edit".into()));
+        assert_eq!(app.last_prompt(), Some("second"));
+        app.edit_prompt("second");
+        assert_eq!(app.input, "second");
+        assert_eq!(app.cursor_pos, 6);
+        assert!(app.editing_prompt);
+        app.cancel_edit();
+        assert!(!app.editing_prompt);
+        assert!(app.input.is_empty());
+    }
+
+    #[test]
+    fn test_last_answer() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.last_answer(), None);
+        app.add_message(ChatMessage::Assistant("first".into()));
+        app.add_message(ChatMessage::User("more".into()));
+        app.add_message(ChatMessage::Assistant("second".into()));
+        app.add_message(ChatMessage::System("done".into()));
+        app.add_message(ChatMessage::User("/copy".into()));
+        assert_eq!(app.last_answer(), Some("second"));
+    }
+
+    #[test]
+    fn test_answer_since() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::Assistant("earlier".into()));
+        app.add_message(ChatMessage::User("why".into()));
+        let id = app.message_id(app.messages.len() - 1).unwrap();
+        assert_eq!(app.answer_since(id), Err("No answer".into()));
+        app.add_message(ChatMessage::Error("rate limited".into()));
+        assert_eq!(app.answer_since(id), Err("rate limited".into()));
+        app.add_message(ChatMessage::Assistant("because".into()));
+        app.add_message(ChatMessage::Assistant("of scattering".into()));
+        assert_eq!(app.answer_since(id), Ok("because\n\nof scattering".into()));
+    }
+
+    #[test]
+    fn test_stats_report() {
+        assert_eq!(stats_report(&[]), "No turns yet.");
+        let turn = |duration_ms, prompt_tokens, cost, tools: &[(&str, bool)], failed| TurnRecord {
+            finished: 0,
+            duration_ms,
+            prompt_tokens,
+            completion_tokens: 0,
+            cost,
+            tools: tools.iter().map(|(name, ok)| (name.to_string(), *ok)).collect(),
+            failed,
+        };
+        let turns = vec![
+            turn(2000, 3000, 0.01, &[("exec", true), ("read_file", true), ("exec", false)], false),
+            turn(4000, 1000, 0.03, &[("exec", true)], true),
+        ];
+        let report = stats_report(&turns);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "2 turns (1 failed) · avg 3.0s · slowest 4.0s");
+        assert_eq!(lines[1], "Tokens: 4.0k in, 0 out · 2.0k per turn");
+        assert_eq!(lines[2], "Cost: $0.04 · $0.020 per turn");
+        assert_eq!(lines[4], "Tool calls (4)");
+        assert_eq!(lines[5], format!("  exec         3 {}  1 failed", "█".repeat(20)));
+        assert_eq!(lines[6], format!("  read_file    1 {}", "█".repeat(7)));
+        assert!(lines[11].contains("$0.030   $0.040  failed"), "{}", lines[11]);
+    }
+
+    #[test]
+    fn test_cancel_turn() {
         let mut app = App::new("a", "m", "w");
-        app.input = "first".into();
-        app.submit_input();
-        app.input = "second".into();
-        app.submit_input();
+        let (control, controls) = mpsc::channel();
+        app.control = Some(control);
+        app.cancel_turn();
+        assert!(controls.try_recv().is_err());
+        app.agent_busy = true;
+        app.cancel_turn();
+        assert_eq!(controls.try_recv(), Ok(Control::Cancel));
+        assert_eq!(app.run_mode(), RunMode::Canceling);
 
-        app.history_up();
-        assert_eq!(app.input, "second");
-        app.history_up();
-        assert_eq!(app.input, "first");
-        app.history_up(); // at beginning, stays
-        assert_eq!(app.input, "first");
+        // A running `!` command is killed instead
+        let killed = Arc::new(AtomicBool::new(false));
+        let flag = killed.clone();
+        app.start_shell("sleep 9".into(), ShellKiller::new(move || flag.store(true, Ordering::Relaxed)));
+        app.cancel_turn();
+        assert!(killed.load(Ordering::Relaxed));
+        assert!(controls.try_recv().is_err());
+    }
 
-        app.history_down();
-        assert_eq!(app.input, "second");
-        app.history_down(); // past end, clears
-        assert!(app.input.is_empty());
+    #[test]
+    fn test_background_tasks() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.queue_background("research lifetimes".into()), 1);
+        assert_eq!(app.queue_background("summarize the crate".into()), 2);
+        assert_eq!(std::mem::take(&mut app.background_requests).len(), 2);
+        app.start_background(1);
+        assert!(matches!(app.background_tasks[0].state, TaskState::Running(_)));
+        app.finish_background(1, Ok("Lifetimes are regions.".into()));
+        assert!(matches!(app.background_tasks[0].state, TaskState::Done { .. }));
+        assert!(app.messages.last().unwrap().text().contains("#1 done"));
+        assert!(app.messages.last().unwrap().text().ends_with("Lifetimes are regions."));
+        app.start_background(2);
+        app.finish_background(2, Err("provider down".into()));
+        assert!(matches!(app.background_tasks[1].state, TaskState::Failed { .. }));
+        assert!(app.messages.last().unwrap().text().contains("#2 failed"));
+        // Unknown ids change nothing
+        let count = app.messages.len();
+        app.finish_background(9, Ok(String::new()));
+        assert_eq!(app.messages.len(), count);
     }
 
     #[test]
-    fn test_panel_focus_toggle() {
+    fn test_cancel_background() {
         let mut app = App::new("a", "m", "w");
-        assert_eq!(app.focus, PanelFocus::Chat);
-        app.focus = PanelFocus::Trace;
-        assert_eq!(app.focus, PanelFocus::Trace);
-        app.focus = PanelFocus::Chat;
-        assert_eq!(app.focus, PanelFocus::Chat);
+        app.queue_background("research lifetimes".into());
+        app.queue_background("summarize the crate".into());
+        app.queue_background("list the todos".into());
+        // Still waiting for the main loop: dropped on the spot
+        app.cancel_background(3);
+        assert_eq!(app.background_requests.len(), 2);
+        assert_eq!(app.background_tasks[2].state, TaskState::Canceled);
+        assert!(app.messages.last().unwrap().text().contains("#3 canceled"));
+        // Handed on: the main loop stops it
+        app.background_requests.clear();
+        app.start_background(1);
+        app.cancel_background(1);
+        assert_eq!(app.background_cancels, vec![1]);
+        app.finish_background(1, Err("Turn canceled".into()));
+        assert_eq!(app.background_tasks[0].state, TaskState::Canceled);
+        // Finished, already canceled or unknown tasks aren't canceled again
+        app.cancel_background(1);
+        app.cancel_background(9);
+        assert_eq!(app.background_cancels, vec![1]);
+        assert!(app.messages.last().unwrap().text().contains("No background task #9"));
+        // A task that finishes despite the cancel keeps its answer
+        app.start_background(2);
+        app.cancel_background(2);
+        app.finish_background(2, Ok("It's a TUI.".into()));
+        assert!(matches!(app.background_tasks[1].state, TaskState::Done { .. }));
     }
 
     #[test]
-    fn test_status_display() {
-        let mut info = StatusInfo::default();
-        info.total_tokens = 500;
-        assert_eq!(info.tokens_display(), "500");
+    fn test_steering() {
+        let steering = Steering::default();
+        assert_eq!(steering.take_guidance(), None);
+        steering.pause();
+        let agent = steering.clone();
+        let waiter = std::thread::spawn(move || {
+            agent.wait_while_paused();
+            agent.take_guidance()
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        steering.resume(Some("use the other API".into()));
+        assert_eq!(waiter.join().unwrap().as_deref(), Some("use the other API"));
+        assert!(!steering.is_paused());
 
-        info.total_tokens = 1000;
-        assert_eq!(info.tokens_display(), "1.0k");
+        let mut app = App::new("a", "m", "w");
+        app.toggle_pause();
+        assert!(!app.steering.is_paused());
+        app.agent_busy = true;
+        app.toggle_pause();
+        assert!(app.steering.is_paused());
+        app.steer("stop editing tests".into());
+        assert!(!app.steering.is_paused());
+        assert_eq!(app.steering.take_guidance().as_deref(), Some("stop editing tests"));
 
-        info.total_tokens = 15432;
-        assert_eq!(info.tokens_display(), "15.4k");
+        app.return_guidance("stop editing tests".into());
+        assert_eq!(app.input, "stop editing tests");
+        assert_eq!(app.cursor_pos, app.input.len());
+        app.return_guidance("and the docs".into());
+        assert_eq!(app.input, "stop editing tests");
+        assert!(app.messages.last().unwrap().text().ends_with(": and the docs"));
+    }
 
-        info.cost = 0.0123;
-        assert_eq!(info.cost_display(), "~$0.0123");
+    #[test]
+    fn test_complete_input() {
+        let mut app = App::new("a", "m", "w");
+        app.input = "/mod".into();
+        app.complete_input();
+        assert_eq!(app.input, "/model ");
+        app.ollama_models = vec!["llama3.2:3b".into(), "llama3.1:8b".into()];
+        app.input = "/model ll".into();
+        app.complete_input();
+        assert_eq!(app.input, "/model llama3.");
+        // Nothing more in common, so Tab cycles through the candidates
+        app.complete_input();
+        assert_eq!(app.input, "/model llama3.2:3b");
+        app.complete_input();
+        assert_eq!(app.input, "/model llama3.1:8b");
+        assert_eq!(app.cursor_pos, app.input.len());
+        app.input = "/theme nope".into();
+        app.complete_input();
+        assert_eq!(app.input, "/theme nope");
     }
 
     #[test]
-    fn test_clear_messages() {
+    fn test_apply_alias() {
         let mut app = App::new("a", "m", "w");
-        app.add_message(ChatMessage::User("hi".into()));
-        app.add_message(ChatMessage::System("ok".into()));
-        assert_eq!(app.messages.len(), 2);
+        assert_eq!(app.apply_alias(AliasAction::List), "No aliases (/alias <name> <text> defines one)");
+        let set = |name: &str, text: &str| AliasAction::Set { name: name.into(), text: text.into() };
+        app.apply_alias(set("review", "Please review:"));
+        app.apply_alias(set("t", "/trace off"));
+        assert!(app.apply_alias(set("clear", "x")).contains("built-in"));
+        assert!(app.apply_alias(set("a/b", "x")).starts_with("Invalid"));
+        assert_eq!(app.apply_alias(AliasAction::List), "Aliases:\n  /review  Please review:\n  /t       /trace off");
+        assert_eq!(app.expand_alias("/review this").as_deref(), Some("Please review: this"));
+        assert_eq!(app.apply_alias(AliasAction::Remove("t".into())), "Removed alias /t");
+        assert_eq!(app.apply_alias(AliasAction::Remove("t".into())), "No alias /t");
+    }
+
+    #[test]
+    fn test_last_diff() {
+        let mut app = App::new("a", "m", "w");
+        assert!(app.last_diff(None).is_none());
+        assert!(!app.open_diff(None));
+        app.record_diff("src/main.rs".into(), "-a\n+b\n".into());
+        app.record_diff("src/app.rs".into(), "-c\n+d\n".into());
         app.clear_messages();
-        assert!(app.messages.is_empty());
-        assert_eq!(app.scroll_offset, 0);
+        assert_eq!(app.last_diff(None).unwrap().0, "src/app.rs");
+        assert_eq!(app.last_diff(Some("main.rs")).unwrap().1, "-a\n+b\n");
+        assert_eq!(app.last_diff(Some("./src/main.rs")).unwrap().0, "src/main.rs");
+        assert!(app.last_diff(Some("ain.rs")).is_none());
+        assert!(app.open_diff(Some("src/app.rs")));
+        assert!(matches!(&app.overlay, Some(Overlay::Pager(p)) if p.diff && p.title == "Diff · src/app.rs"));
     }
 
     #[test]
@@ -398,8 +2856,8 @@ mod tests {
             ChatMessage::User("u".into()),
             ChatMessage::Assistant("a".into()),
             ChatMessage::Narration("n".into()),
-            ChatMessage::ToolCall { name: "t".into(), args_short: "{}".into() },
-            ChatMessage::ToolResult { name: "t".into(), success: true, duration_ms: 100 },
+            ChatMessage::ToolCall { name: "t".into(), args_short: "{}".into(), args: "{}".into() },
+            ChatMessage::ToolResult { name: "t".into(), success: true, duration_ms: 100, output: String::new(), error: None },
             ChatMessage::Error("e".into()),
             ChatMessage::System("s".into()),
         ];
@@ -412,8 +2870,736 @@ mod tests {
             TraceEntry::StageEnd { id: "s1".into(), duration_ms: 50, skipped: false },
             TraceEntry::LlmCall { model: "m".into(), ctx_tokens: 100, out_tokens: 50, duration_ms: 200 },
             TraceEntry::ToolCall { name: "t".into(), args: "{}".into() },
-            TraceEntry::ToolResult { name: "t".into(), success: true, duration_ms: 10 },
+            TraceEntry::ToolResult { name: "t".into(), success: true, duration_ms: 10, error: None },
             TraceEntry::Narration("n".into()),
+            TraceEntry::Artifact { stage_id: "s1".into(), name: "plan".into() },
+        ];
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("short", 10), "short");
+        assert_eq!(shorten("abcdefghijkl", 10), "abcdefg...");
+        assert_eq!(shorten("ééééééééééé", 5), "éé...");
+    }
+
+    fn tool_call(name: &str) -> ChatMessage {
+        ChatMessage::ToolCall { name: name.into(), args_short: "{}".into(), args: "{}".into() }
+    }
+
+    fn tool_result(name: &str) -> ChatMessage {
+        ChatMessage::ToolResult { name: name.into(), success: true, duration_ms: 1, output: String::new(), error: None }
+    }
+
+    #[test]
+    fn test_tool_pairs() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(tool_call("read_file"));   // 0
+        app.add_message(tool_call("exec"));        // 1
+        app.add_message(tool_result("read_file")); // 2
+        app.add_message(tool_call("read_file"));   // 3
+        app.add_message(tool_result("exec"));      // 4
+        app.add_message(tool_result("read_file")); // 5
+        app.add_message(tool_call("exec"));        // 6 still running
+        let pairs = app.tool_pairs();
+        assert_eq!(pairs.get(&0), Some(&2));
+        assert_eq!(pairs.get(&1), Some(&4));
+        assert_eq!(pairs.get(&3), Some(&5));
+        assert_eq!(pairs.get(&6), None);
+    }
+
+    #[test]
+    fn test_tool_selection_and_toggle() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("go".into()));
+        app.add_message(tool_call("read_file"));
+        app.add_message(tool_result("read_file"));
+        app.add_message(tool_call("exec"));
+
+        app.select_next_tool(); // nothing selected yet
+        assert_eq!(app.selected_message, None);
+        app.select_prev_tool(); // starts from the bottom
+        assert_eq!(app.selected_message, Some(3));
+        app.select_prev_tool();
+        assert_eq!(app.selected_message, Some(1));
+        app.select_prev_tool(); // no earlier tool call, stays
+        assert_eq!(app.selected_message, Some(1));
+
+        app.toggle_selected_tool();
+        assert!(app.expanded_tools.contains(&1));
+        app.toggle_selected_tool();
+        assert!(app.expanded_tools.is_empty());
+
+        app.select_next_tool();
+        assert_eq!(app.selected_message, Some(3));
+        app.toggle_selected_tool();
+        app.clear_messages();
+        assert!(app.expanded_tools.is_empty());
+        assert_eq!(app.selected_message, None);
+    }
+
+    #[test]
+    fn test_thought_blocks() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("go".into()));
+        app.add_message(ChatMessage::Narration("First I'll look around.\nThen decide.".into()));
+        app.add_message(tool_call("exec"));
+        app.add_message(ChatMessage::Assistant("done".into()));
+
+        app.select_prev_tool();
+        app.select_prev_tool();
+        assert_eq!(app.selected_message, Some(1));
+        assert!(!app.open_pager());
+        app.toggle_selected_tool();
+        assert!(app.expanded_tools.contains(&1));
+
+        assert!(app.toggle_thoughts());
+        assert!(!app.is_visible(1));
+        assert_eq!(app.selected_message, None);
+        assert!(!app.toggle_thoughts());
+        assert!(app.is_visible(1));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("where is the Config file?".into()));
+        app.add_message(ChatMessage::Assistant("nothing here".into()));
+        app.add_message(ChatMessage::Assistant("the config lives in ~/.config".into()));
+
+        app.start_search();
+        for c in "CONFIG".chars() {
+            app.search_push(c);
+        }
+        let search = app.search.as_ref().unwrap();
+        assert_eq!(search.matches, vec![0, 2]);
+        assert!(search.jump_pending);
+        assert_eq!(app.active_match(), Some(2)); // newest hit first
+
+        app.search_next(); // wraps to the oldest
+        assert_eq!(app.active_match(), Some(0));
+        app.search_prev();
+        assert_eq!(app.active_match(), Some(2));
+
+        // New messages extend the match list
+        app.add_message(ChatMessage::System("config reloaded".into()));
+        assert_eq!(app.search.as_ref().unwrap().matches, vec![0, 2, 3]);
+
+        app.search_pop();
+        app.search_pop();
+        app.search_pop();
+        app.search_pop();
+        app.search_pop();
+        app.search_pop();
+        assert!(app.search.as_ref().unwrap().matches.is_empty());
+        assert_eq!(app.active_match(), None);
+
+        app.end_search();
+        assert!(app.search.is_none());
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let text = "Try this:\n```rust\nfn main() {}\n```\nand\n  ```\nls -la\npwd\n  ```\n```\nunterminated";
+        assert_eq!(code_blocks(text), vec!["fn main() {}", "ls -la\npwd", "unterminated"]);
+        assert!(code_blocks("no code here").is_empty());
+    }
+
+    #[test]
+    fn test_message_selection() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("q".into()));  // 0
+        app.add_message(tool_call("exec"));               // 1
+        app.add_message(tool_result("exec"));             // 2 (part of block 1)
+        app.add_message(ChatMessage::Assistant("a".into())); // 3
+
+        app.toggle_selection_mode();
+        assert!(app.selection_mode);
+        assert_eq!(app.selected_message, Some(3));
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(1)); // skips the paired result
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(0));
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(0));
+        app.select_next_message();
+        app.select_next_message();
+        assert_eq!(app.selected_message, Some(3));
+        assert!(matches!(app.selected(), Some(ChatMessage::Assistant(_))));
+
+        app.toggle_selection_mode();
+        assert!(!app.selection_mode);
+        assert_eq!(app.selected_message, None);
+    }
+
+    #[test]
+    fn test_trace_settings() {
+        let mut app = App::new("a", "m", "w");
+        app.set_pipeline(vec!["plan".into()]);
+        app.push_trace(TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() });
+        assert_eq!(app.apply_trace(TraceAction::Enable(false)), "Trace off (/trace on resumes) · showing all entries");
+        app.push_trace(TraceEntry::Narration("hidden".into()));
+        app.push_trace(TraceEntry::StageEnd { id: "plan".into(), duration_ms: 5, skipped: false });
+        assert_eq!(app.trace_log.len(), 1);
+        assert_eq!(app.trace_times.len(), 1);
+        assert_eq!(app.pipeline[0].state, StageState::Done);
+
+        app.apply_trace(TraceAction::Enable(true));
+        app.push_trace(TraceEntry::ToolCall { name: "exec".into(), args: "{}".into() });
+        let summary = app.apply_trace(TraceAction::Filter(vec![TraceKind::Tools, TraceKind::Llm]));
+        assert_eq!(summary, "Trace on · showing llm, tools");
+        assert!(!app.shows_trace(&app.trace_log[0]));
+        assert!(app.shows_trace(&app.trace_log[1]));
+        app.apply_trace(TraceAction::Filter(Vec::new()));
+        assert!(app.shows_trace(&app.trace_log[0]));
+    }
+
+    #[test]
+    fn test_filter_hides_kinds() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::Narration("thinking about it".into()));
+        app.add_message(ChatMessage::ToolCall { name: "exec".into(), args_short: "{}".into(), args: "{}".into() });
+        app.add_message(ChatMessage::ToolResult {
+            name: "exec".into(),
+            success: true,
+            duration_ms: 1,
+            output: String::new(),
+            error: None,
+        });
+        app.add_message(ChatMessage::Assistant("it".into()));
+
+        let summary = app.apply_filter(FilterAction::Hide(vec![MessageKind::Narration, MessageKind::Tool]));
+        assert!(summary.contains("hidden: narration, tools"));
+        assert!(!app.is_visible(1) && !app.is_visible(3));
+        assert!(app.is_visible(0) && app.is_visible(4));
+        // Hidden messages are skipped by selection and search, but kept
+        app.select_prev_tool();
+        assert_eq!(app.selected_message, None);
+        app.toggle_selection_mode();
+        assert_eq!(app.selected_message, Some(4));
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(0));
+        app.start_search();
+        app.search_push('i');
+        assert_eq!(app.search.as_ref().unwrap().matches, vec![0, 4]);
+        assert_eq!(app.messages.len(), 5);
+
+        app.apply_filter(FilterAction::Only(vec![MessageKind::Error]));
+        assert_eq!(app.selected_message, None);
+        assert_eq!(app.hidden_kinds.len(), MessageKind::ALL.len() - 1);
+        assert_eq!(app.apply_filter(FilterAction::Reset), "Showing all messages");
+        assert!(app.search.as_ref().unwrap().matches.contains(&1));
+    }
+
+    #[test]
+    fn test_jump_to_last_answer() {
+        let mut app = App::new("a", "m", "w");
+        assert!(!app.jump_to_last_answer());
+        app.add_message(ChatMessage::Assistant("first".into()));
+        app.add_message(ChatMessage::User("more".into()));
+        app.add_message(ChatMessage::Assistant("second".into()));
+        app.add_message(ChatMessage::System("done".into()));
+        assert!(app.jump_to_last_answer());
+        assert_eq!(app.jump_to_message, Some(2));
+    }
+
+    #[test]
+    fn test_next_link_cycles() {
+        let mut app = App::new("a", "m", "w");
+        assert!(app.next_link().is_none());
+        app.add_message(ChatMessage::Assistant("See https://a.io and https://b.io.".into()));
+        app.add_message(ChatMessage::System("no links".into()));
+        assert_eq!(app.next_link(), Some(("https://a.io".to_string(), 1, 2)));
+        assert_eq!(app.next_link(), Some(("https://b.io".to_string(), 2, 2)));
+        assert_eq!(app.next_link(), Some(("https://a.io".to_string(), 1, 2)));
+
+        app.selected_message = Some(1);
+        assert!(app.next_link().is_none());
+    }
+
+    #[test]
+    fn test_image_references() {
+        let plot = std::env::temp_dir().join(format!("neocognos-app-{}.png", std::process::id()));
+        std::fs::write(&plot, b"").unwrap();
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User(format!("describe {}", plot.display())));
+        app.add_message(ChatMessage::Assistant(format!("Saved the chart to {}.", plot.display())));
+        app.add_message(ChatMessage::ToolCall {
+            name: "screenshot".into(),
+            args_short: String::new(),
+            args: format!("{{\"path\":\"{}\"}}", plot.display()),
+        });
+        app.add_message(ChatMessage::System(format!("wrote {}", plot.display())));
+        // Prompts, answers and tool calls get previews
+        assert_eq!(app.images.keys().copied().collect::<HashSet<_>>(), HashSet::from([0, 1, 2]));
+        assert_eq!(app.images[&1], plot);
+
+        app.clear_messages();
+        assert!(app.images.is_empty());
+        std::fs::remove_file(&plot).unwrap();
+    }
+
+    #[test]
+    fn test_show_image() {
+        let dir = std::env::temp_dir().join(format!("neocognos-show-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shot = dir.join("shot.png");
+        image::RgbImage::new(4, 3).save(&shot).unwrap();
+        let mut app = App::new("a", "m", "w");
+
+        app.show_image(&shot).unwrap();
+        assert_eq!(app.messages[0].text(), "🖼 shot.png — 4×3");
+        assert_eq!(app.images[&0], shot);
+        assert!(app.show_image(&dir.join("missing.png")).is_err());
+
+        let artifact = Artifact { stage_id: "render".into(), name: "chart".into(), content: format!("{}\n", shot.display()) };
+        assert_eq!(artifact.image_path(), Some(shot));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_streaming_scroll_modes() {
+        let stream = |mode: StreamScroll| {
+            let mut app = App::new("a", "m", "w");
+            app.stream_scroll = mode;
+            app.chat_lines = 100;
+            app.chat_view_height = 20;
+            app.scroll_offset = 10;
+            app.append_stream("Hel");
+            app.append_stream("lo");
+            app
+        };
+
+        let app = stream(StreamScroll::Follow);
+        assert_eq!(app.scroll_offset, usize::MAX);
+        assert!(matches!(&app.messages[0], ChatMessage::Assistant(t) if t == "Hello"));
+        assert_eq!(app.streaming, Some(0));
+
+        let app = stream(StreamScroll::Top);
+        assert_eq!(app.jump_to_message, Some(0));
+
+        let app = stream(StreamScroll::None);
+        assert_eq!(app.scroll_offset, 10);
+        assert_eq!(app.jump_to_message, None);
+
+        let mut app = stream(StreamScroll::Follow);
+        assert!(app.finish_stream("Hello!"));
+        assert!(matches!(&app.messages[0], ChatMessage::Assistant(t) if t == "Hello!"));
+        assert_eq!(app.streaming, None);
+        assert!(!app.finish_stream("again"));
+
+        // An empty final text settles the stream without wiping it
+        let mut app = stream(StreamScroll::Follow);
+        assert!(app.finish_stream(""));
+        app.append_stream("Next");
+        assert!(matches!(&app.messages[0], ChatMessage::Assistant(t) if t == "Hello"));
+        assert!(matches!(&app.messages[1], ChatMessage::Assistant(t) if t == "Next"));
+    }
+
+    #[test]
+    fn test_discard_stream() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.discard_stream();
+        assert_eq!(app.messages.len(), 1);
+
+        app.append_stream("Partial ans");
+        app.discard_stream();
+        assert_eq!(app.messages.len(), 1);
+        assert_eq!(app.streaming, None);
+        app.append_stream("Full answer");
+        assert!(matches!(&app.messages[1], ChatMessage::Assistant(t) if t == "Full answer"));
+    }
+
+    #[test]
+    fn test_chat_scroll_math() {
+        let mut app = App::new("a", "m", "w");
+        app.chat_lines = 100;
+        app.chat_view_height = 20;
+        app.scroll_offset = usize::MAX;
+        assert_eq!(app.chat_scroll_position(), 80);
+
+        app.scroll_chat_by(-(app.chat_page() as isize));
+        assert_eq!(app.scroll_offset, 62);
+        app.scroll_chat_by(-1000);
+        assert_eq!(app.scroll_offset, 0);
+        app.scroll_chat_by(30);
+        assert_eq!(app.scroll_offset, 30);
+        // Reaching the bottom goes back to following the tail
+        app.scroll_chat_by(50);
+        assert_eq!(app.scroll_offset, usize::MAX);
+
+        // A stale offset past the content is clamped
+        app.scroll_offset = 500;
+        assert_eq!(app.chat_scroll_position(), 80);
+    }
+
+    #[test]
+    fn test_context_report() {
+        let app = App::new("a", "claude-sonnet-4-20250514", "w");
+        let entries = vec![
+            ContextEntry { role: "system".into(), text: "You are\na coder".into(), tokens: 2_000, pinned: false },
+            ContextEntry { role: "user".into(), text: "x".repeat(100), tokens: 18_000, pinned: true },
         ];
+        let report = app.context_report(&entries);
+        assert!(report.starts_with("2 entries, ~20.0k tokens (10.0% of the 200.0k window)"));
+        assert!(report.contains("  1. system      2.0k   1.0%   You are a coder"));
+        assert!(report.contains("  2. user       18.0k   9.0% 📌 xxx"));
+        assert!(report.lines().all(|line| line.chars().count() < 90));
+    }
+
+    #[test]
+    fn test_context_usage() {
+        let mut app = App::new("a", "claude-sonnet-4-20250514", "w");
+        assert_eq!(app.context_usage(), (0, 200_000));
+        for prompt_tokens in [30_000, 52_000] {
+            app.llm_calls.push(LlmCallEntry {
+                model: "m".into(),
+                prompt_tokens,
+                completion_tokens: 10,
+                duration_ms: 1,
+                timestamp: Local::now(),
+            });
+        }
+        assert_eq!(app.context_usage(), (52_000, 200_000));
+        assert_eq!(context_window("llama3.2:3b"), 128_000);
+        assert_eq!(context_window("something-new"), 200_000);
+    }
+
+    #[test]
+    fn test_budget_usage() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.budget_usage(), None);
+        app.token_budget = Some(10_000);
+        let level = |app: &mut App, used| {
+            app.status.total_tokens = used;
+            app.status.per_model = vec![ModelCost { model: "m".into(), tokens: used, cost: 0.0 }];
+            app.budget_usage().unwrap().2
+        };
+        assert_eq!(level(&mut app, 7_000), BudgetLevel::Ok);
+        assert_eq!(level(&mut app, 7_500), BudgetLevel::Warn);
+        assert_eq!(level(&mut app, 9_000), BudgetLevel::Alert);
+        assert_eq!(level(&mut app, 10_000), BudgetLevel::Alert);
+        assert_eq!(level(&mut app, 10_001), BudgetLevel::Exceeded);
+        app.budget_thresholds = BudgetConfig { warn: 50, alert: 60 };
+        assert_eq!(level(&mut app, 5_500), BudgetLevel::Warn);
+
+        // Compacting starts the context count over, but not the spend
+        level(&mut app, 9_000);
+        app.status.per_model.push(ModelCost { model: "other".into(), tokens: 2_000, cost: 0.0 });
+        app.status.total_tokens = 0;
+        assert_eq!(app.budget_usage(), Some((11_000, 10_000, BudgetLevel::Exceeded)));
+    }
+
+    #[test]
+    fn test_toggle_sidebar() {
+        let mut app = App::new("a", "m", "w");
+        app.focus_tab(SidebarTab::Trace);
+        assert!(!app.toggle_sidebar());
+        assert_eq!(app.focus, PanelFocus::Chat);
+        assert!(app.toggle_sidebar());
+        assert_eq!(app.focus, PanelFocus::Chat);
+    }
+
+    #[test]
+    fn test_zen_restores_layout() {
+        let mut app = App::new("a", "m", "w");
+        app.focus_tab(SidebarTab::Llm);
+        assert!(app.toggle_zen());
+        assert!(!app.show_sidebar);
+        assert_eq!(app.focus, PanelFocus::Chat);
+        assert!(!app.toggle_zen());
+        assert!(app.show_sidebar);
+
+        // A sidebar hidden before zen stays hidden after it
+        app.toggle_sidebar();
+        app.toggle_zen();
+        app.toggle_zen();
+        assert!(!app.show_sidebar);
+
+        // Ctrl+B in zen brings the sidebar back and leaves zen
+        app.toggle_zen();
+        assert!(app.toggle_sidebar());
+        assert!(!app.zen);
+    }
+
+    #[test]
+    fn test_set_layout_option() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.set_option("layout.chat", None), Ok("layout.chat = 75".into()));
+        assert_eq!(app.set_option("layout.chat", Some("80")), Ok("layout.chat = 80".into()));
+        assert_eq!(app.splits.sidebar, 20);
+        // Clamped to what keeps the sidebar usable
+        assert_eq!(app.set_option("layout.chat", Some("95%")), Ok("layout.chat = 85".into()));
+        assert_eq!(app.set_option("layout.status", Some("45")), Ok("layout.status = 45".into()));
+        assert!(app.set_option("layout.status", Some("wide")).is_err());
+        assert!(app.set_option("theme", Some("dark")).is_err());
+    }
+
+    #[test]
+    fn test_llm_log_scroll_and_totals() {
+        let mut app = App::new("a", "m", "w");
+        for i in 0..10 {
+            app.llm_calls.push(LlmCallEntry {
+                model: "m".into(),
+                prompt_tokens: 1000,
+                completion_tokens: 100 + i,
+                duration_ms: 500,
+                timestamp: Local::now(),
+            });
+        }
+        assert_eq!(app.llm_totals(), (10_000, 1045, 5000));
+        app.llm_calls[9].duration_ms = 2500;
+        assert_eq!(app.recent_latencies(3), vec![500, 500, 2500]);
+        assert_eq!(app.recent_latencies(50).len(), 10);
+
+        app.scroll_llm_log(-4);
+        assert_eq!(app.llm_log_scroll, Some(6));
+        app.scroll_llm_log(-20);
+        assert_eq!(app.llm_log_scroll, Some(0));
+        app.scroll_llm_log(12);
+        assert_eq!(app.llm_log_scroll, None);
+        assert_eq!(format_tokens(12_345), "12.3k");
+        assert_eq!(format_duration(850), "850ms");
+        assert_eq!(format_duration(1300), "1.3s");
+    }
+
+    #[test]
+    fn test_safe_mode_withholds_attachments() {
+        let dir = std::env::temp_dir().join(format!("neocognos-safe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shot = dir.join("shot.png");
+        std::fs::write(&shot, b"").unwrap();
+
+        let mut app = App::new("a", "m", "w");
+        app.set_locked(Capability::ALL.to_vec());
+        assert_eq!(app.notices[0].text, "Safe mode: tools, shell, attachments disabled (/unsafe)");
+        app.add_message(ChatMessage::User(format!("what is in {}?", shot.display())));
+        assert!(app.images.is_empty());
+
+        app.set_locked(vec![Capability::Tools]);
+        app.add_message(ChatMessage::User(format!("and {}?", shot.display())));
+        assert_eq!(app.images.get(&1), Some(&shot));
+        app.set_locked(Vec::new());
+        assert!(app.notices.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_notices() {
+        let mut app = App::new("a", "m", "w");
+        app.pin_notice("context", "Context 70% full".into());
+        app.pin_notice("provider", "Provider degraded".into());
+        app.pin_notice("context", "Context 75% full".into());
+        assert_eq!(app.notices.len(), 2);
+        assert_eq!(app.notices[0].text, "Context 75% full");
+
+        assert_eq!(app.dismiss_notices(Some(1)), 1);
+        assert_eq!(app.dismiss_notices(Some(5)), 0);
+        // The same warning stays dismissed; a changed one comes back
+        app.pin_notice("context", "Context 75% full".into());
+        assert_eq!(app.notices.len(), 1);
+        app.pin_notice("context", "Context 90% full".into());
+        assert_eq!(app.notices.len(), 2);
+
+        app.clear_notice("provider");
+        assert_eq!(app.notices, vec![Notice { id: "context".into(), text: "Context 90% full".into() }]);
+        assert_eq!(app.dismiss_notices(None), 1);
+        assert!(app.notices.is_empty());
+    }
+
+    #[test]
+    fn test_chat_jumps_and_half_page() {
+        let mut app = App::new("a", "m", "w");
+        app.chat_lines = 100;
+        app.chat_view_height = 20;
+        app.scroll_chat_to_top();
+        assert_eq!(app.chat_scroll_position(), 0);
+        app.scroll_chat_by(app.chat_half_page() as isize);
+        assert_eq!(app.scroll_offset, 10);
+        app.scroll_chat_to_bottom();
+        assert_eq!(app.scroll_offset, usize::MAX);
+        app.scroll_chat_by(-(app.chat_half_page() as isize));
+        assert_eq!(app.scroll_offset, 70);
+    }
+
+    #[test]
+    fn test_stage_selection_and_failure() {
+        let mut app = App::new("a", "m", "w");
+        app.push_trace(TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() });
+        app.push_trace(TraceEntry::StageEnd { id: "plan".into(), duration_ms: 5, skipped: false });
+        app.push_trace(TraceEntry::StageStart { id: "review".into(), kind: "llm".into() });
+        app.push_trace(TraceEntry::Narration("checking".into()));
+        app.push_trace(TraceEntry::StageFailed { id: "review".into(), error: "timeout".into() });
+
+        app.select_next_stage();
+        assert_eq!(app.selected_trace, Some(2));
+        assert_eq!(app.selected_failed_stage().as_deref(), Some("review"));
+        app.select_prev_stage();
+        assert_eq!(app.selected_trace, Some(0));
+        assert_eq!(app.selected_failed_stage(), None);
+        app.select_prev_stage();
+        assert_eq!(app.selected_trace, Some(0));
+        app.select_next_stage();
+        assert_eq!(app.selected_trace, Some(2));
+
+        // The full-screen trace opens narrowed to the selected stage
+        app.open_trace_view();
+        assert!(matches!(&app.overlay, Some(Overlay::Trace(view)) if view.stage.as_deref() == Some("review")));
+    }
+
+    #[test]
+    fn test_pipeline_follows_stage_events() {
+        let mut app = App::new("a", "m", "w");
+        app.set_pipeline(vec!["plan".into(), "lint".into(), "act".into(), "review".into()]);
+        app.push_trace(TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() });
+        app.push_trace(TraceEntry::StageEnd { id: "plan".into(), duration_ms: 5, skipped: false });
+        app.push_trace(TraceEntry::StageEnd { id: "lint".into(), duration_ms: 0, skipped: true });
+        app.push_trace(TraceEntry::StageStart { id: "act".into(), kind: "tool".into() });
+        app.push_trace(TraceEntry::StageStart { id: "extra".into(), kind: "llm".into() });
+        let states: Vec<(&str, StageState)> = app.pipeline.iter().map(|s| (s.id.as_str(), s.state)).collect();
+        assert_eq!(
+            states,
+            vec![
+                ("plan", StageState::Done),
+                ("lint", StageState::Skipped),
+                ("act", StageState::Running),
+                ("review", StageState::Pending),
+                ("extra", StageState::Running),
+            ]
+        );
+        app.push_trace(TraceEntry::StageFailed { id: "act".into(), error: "boom".into() });
+        assert_eq!(app.pipeline[2].state, StageState::Failed);
+
+        // A new turn starts the pipeline over
+        app.set_pipeline(vec!["plan".into()]);
+        assert_eq!(app.pipeline, vec![PipelineStage { id: "plan".into(), state: StageState::Pending }]);
+    }
+
+    #[test]
+    fn test_running_stage_and_tool() {
+        let mut app = App::new("a", "m", "w");
+        app.push_trace(TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() });
+        app.push_trace(TraceEntry::ToolCall { name: "exec".into(), args: "{}".into() });
+        assert_eq!(app.running_stage.as_ref().map(|(id, _)| id.as_str()), Some("plan"));
+        assert_eq!(app.running_tool.as_ref().map(|(name, _)| name.as_str()), Some("exec"));
+
+        app.push_trace(TraceEntry::ToolResult { name: "exec".into(), success: true, duration_ms: 3, error: None });
+        assert!(app.running_tool.is_none());
+        // Only the stage that is running can end it
+        app.push_trace(TraceEntry::StageEnd { id: "other".into(), duration_ms: 1, skipped: true });
+        assert!(app.running_stage.is_some());
+        app.push_trace(TraceEntry::StageFailed { id: "plan".into(), error: "x".into() });
+        assert!(app.running_stage.is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_countdown() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.rate_limit_wait(), None);
+        app.set_rate_limited("anthropic", 12);
+        assert!(matches!(app.rate_limit_wait(), Some(11..=12)));
+        app.set_rate_limited("anthropic", 0);
+        assert_eq!(app.rate_limit_wait(), Some(0));
+        assert_eq!(app.rate_limit_hits, 2);
+    }
+
+    #[test]
+    fn test_run_mode() {
+        let mut app = App::new("a", "m", "w");
+        assert_eq!(app.run_mode(), RunMode::Idle);
+        app.agent_busy = true;
+        assert_eq!(app.run_mode(), RunMode::Working);
+        app.set_rate_limited("anthropic", 5);
+        assert_eq!(app.run_mode(), RunMode::RateLimited);
+        app.canceling = true;
+        assert_eq!(app.run_mode(), RunMode::Canceling);
+        app.canceling = false;
+        app.cancel_turn();
+        assert_eq!(app.rate_limited, None);
+        assert_eq!(app.run_mode(), RunMode::Canceling);
+        assert!(app.terminal_title().contains("canceling"));
+    }
+
+    #[test]
+    fn test_terminal_title() {
+        let mut app = App::new("coder", "sonnet", "w");
+        assert_eq!(app.terminal_title(), "neocognos: coder — idle (sonnet)");
+        app.agent_busy = true;
+        assert_eq!(app.terminal_title(), "neocognos: coder — working (sonnet)");
+    }
+
+    #[test]
+    fn test_stable_ids_and_goto() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("q".into()));
+        app.add_message(tool_call("exec"));
+        app.add_message(tool_result("exec"));
+        assert_eq!(app.message_id(0), Some(1));
+        assert_eq!(app.message_id(2), Some(3));
+
+        assert!(app.goto_message(3)); // the result resolves to its call block
+        assert_eq!(app.selected_message, Some(1));
+        assert_eq!(app.jump_to_message, Some(1));
+        assert_eq!(app.focus, PanelFocus::Chat);
+        assert!(!app.goto_message(42));
+
+        // Ids keep counting after a clear so old references stay unambiguous
+        app.clear_messages();
+        app.add_message(ChatMessage::User("again".into()));
+        assert_eq!(app.message_id(0), Some(4));
+        assert!(!app.goto_message(1));
+    }
+
+    #[test]
+    fn test_open_pager() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(tool_call("exec"));                         // 0
+        app.add_message(ChatMessage::Assistant("long\nanswer".into())); // 1
+
+        assert!(!app.open_pager());
+        app.selected_message = Some(0);
+        assert!(!app.open_pager());
+        app.selected_message = Some(1);
+        assert!(app.open_pager());
+        match &app.overlay {
+            Some(Overlay::Pager(pager)) => {
+                assert_eq!(pager.title, "Assistant · message 2");
+                assert_eq!(pager.text, "long\nanswer");
+            }
+            _ => panic!("pager not opened"),
+        }
+
+        // The raw view also covers tool calls, showing their exact args
+        app.overlay = None;
+        app.selected_message = Some(0);
+        assert!(app.open_raw_view());
+        match &app.overlay {
+            Some(Overlay::Pager(pager)) => {
+                assert!(pager.raw);
+                assert_eq!(pager.title, "Tool call · message 1");
+            }
+            _ => panic!("raw view not opened"),
+        }
+
+        assert!(app.open_json_tree());
+        assert!(matches!(app.overlay, Some(Overlay::JsonTree(_))));
+        app.selected_message = Some(1);
+        assert!(!app.open_json_tree());
+    }
+
+    #[test]
+    fn test_artifacts() {
+        let mut app = App::new("a", "m", "w");
+        app.add_artifact(Artifact { stage_id: "plan".into(), name: "steps".into(), content: "v1".into() });
+        app.add_artifact(Artifact { stage_id: "review".into(), name: "summary".into(), content: "s".into() });
+        app.add_artifact(Artifact { stage_id: "plan".into(), name: "steps".into(), content: "v2".into() });
+        assert_eq!(app.artifacts.len(), 2); // same stage+name replaced
+        assert_eq!(app.find_artifact("1").unwrap().content, "v2");
+        assert_eq!(app.find_artifact("summary").unwrap().stage_id, "review");
+        assert!(app.find_artifact("0").is_none());
+        assert!(app.find_artifact("3").is_none());
+        assert!(app.find_artifact("missing").is_none());
     }
 }
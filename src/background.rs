@@ -0,0 +1,130 @@
+//! Light or dark terminal background, so the default palette stays readable on
+//! white terminals.
+//!
+//! The terminal is asked for its background color with OSC 11. Not every
+//! terminal answers that, so a DA1 query follows it: practically every terminal
+//! answers DA1, which ends the reply even when OSC 11 went unanswered and keeps
+//! the reader from swallowing the first keystrokes. Reads wait on `poll` with a
+//! deadline, so nothing is left blocked on the terminal once detection gives up.
+//! `COLORFGBG` is the fallback.
+
+use std::time::Duration;
+
+/// How long to wait for the terminal's replies.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+/// Background of the running terminal, if it can be told. Must be called in raw
+/// mode, before anything else reads terminal input.
+pub fn detect() -> Option<Background> {
+    query_color()
+        .map(|rgb| if is_light(rgb) { Background::Light } else { Background::Dark })
+        .or_else(|| from_colorfgbg(&std::env::var("COLORFGBG").ok()?))
+}
+
+/// Ask for the background color with OSC 11.
+#[cfg(unix)]
+fn query_color() -> Option<(u8, u8, u8)> {
+    use std::io::{IsTerminal, Read, Write};
+    use std::time::Instant;
+
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    tty.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+    tty.flush().ok()?;
+
+    // One byte at a time, so nothing past the DA1 reply (typed keys) is consumed
+    let deadline = Instant::now() + REPLY_TIMEOUT;
+    let mut reply = Vec::new();
+    let mut byte = [0u8; 1];
+    while !da1_answered(&reply) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || !readable(&tty, left) || !matches!(tty.read(&mut byte), Ok(1)) {
+            return None;
+        }
+        reply.push(byte[0]);
+    }
+    parse_osc11(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn query_color() -> Option<(u8, u8, u8)> {
+    None
+}
+
+/// Wait up to `timeout` for `tty` to have input.
+#[cfg(unix)]
+fn readable(tty: &std::fs::File, timeout: Duration) -> bool {
+    use std::os::fd::AsRawFd;
+    let mut fd = libc::pollfd { fd: tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    let millis = timeout.as_millis().clamp(1, i32::MAX as u128) as libc::c_int;
+    // SAFETY: `fd` is a single valid pollfd that outlives the call
+    unsafe { libc::poll(&mut fd, 1, millis) > 0 }
+}
+
+/// Whether `reply` ends with the answer to DA1 (`ESC [ ? … c`).
+fn da1_answered(reply: &[u8]) -> bool {
+    reply.ends_with(b"c") && reply.windows(3).any(|w| w == b"\x1b[?")
+}
+
+/// Color from an OSC 11 reply like `ESC ] 11 ; rgb:ffff/ffff/ffff ESC \`.
+/// Components have one to four hex digits each.
+fn parse_osc11(reply: &str) -> Option<(u8, u8, u8)> {
+    let start = reply.find("]11;rgb:")? + "]11;rgb:".len();
+    let body: String = reply[start..].chars().take_while(|c| c.is_ascii_hexdigit() || *c == '/').collect();
+    let mut channels = body.split('/').map(|hex| {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = 16u32.checked_pow(hex.len() as u32)?.checked_sub(1).filter(|&max| max > 0)?;
+        Some((value * 255 / max) as u8)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+fn is_light((r, g, b): (u8, u8, u8)) -> bool {
+    0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64 > 127.5
+}
+
+/// `COLORFGBG` is `fg;bg` (or `fg;default;bg`) in ANSI color numbers, set by
+/// rxvt, Konsole and some others; white or light gray backgrounds are light.
+fn from_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if bg == 7 || bg == 15 { Background::Light } else { Background::Dark })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc11() {
+        assert_eq!(parse_osc11("\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?62;c"), Some((255, 255, 255)));
+        assert_eq!(parse_osc11("\x1b]11;rgb:1e/1e/2e\x07"), Some((0x1e, 0x1e, 0x2e)));
+        assert_eq!(parse_osc11("\x1b]11;rgb:0/8/f\x07"), Some((0, 136, 255)));
+        assert_eq!(parse_osc11("\x1b[?62;22c"), None);
+        assert_eq!(parse_osc11("\x1b]11;rgb:ffff/ffff\x07"), None);
+    }
+
+    #[test]
+    fn test_light_or_dark() {
+        assert!(is_light((255, 255, 255)));
+        assert!(is_light((253, 246, 227)));
+        assert!(!is_light((0, 43, 54)));
+        assert_eq!(from_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(from_colorfgbg("15;default;0"), Some(Background::Dark));
+        assert_eq!(from_colorfgbg("nonsense"), None);
+    }
+
+    #[test]
+    fn test_da1_ends_reply() {
+        assert!(!da1_answered(b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\"));
+        assert!(da1_answered(b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?62;22c"));
+        assert!(da1_answered(b"\x1b[?1;2c"));
+    }
+}
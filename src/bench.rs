@@ -0,0 +1,96 @@
+//! `/bench`: latency, throughput and cost of the configured provider over repeated calls.
+
+use crate::app::format_duration;
+use crate::ui::icons;
+
+/// Prompt used when `/bench` is given none.
+pub const DEFAULT_PROMPT: &str = "Reply with one short sentence describing a sunny day.";
+
+/// Calls made when `/bench` doesn't say how many.
+pub const DEFAULT_RUNS: usize = 5;
+
+/// Most calls a single `/bench` may make.
+pub const MAX_RUNS: usize = 50;
+
+/// One timed call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchRun {
+    pub duration_ms: u64,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+/// Nearest-rank percentile `p` (0–100) of ascending `sorted` values.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+/// Summary table for `runs` against `label` (provider:model), with prompt and
+/// completion tokens priced at `input_per_mtok` and `output_per_mtok`.
+pub fn report(label: &str, runs: &[BenchRun], failures: usize, input_per_mtok: f64, output_per_mtok: f64) -> String {
+    let mut out = format!("{} Benchmark {label}: {} ok, {failures} failed", icons::current().timer, runs.len());
+    if runs.is_empty() {
+        return out;
+    }
+    let mut latencies: Vec<u64> = runs.iter().map(|r| r.duration_ms).collect();
+    latencies.sort_unstable();
+    let total_ms: u64 = latencies.iter().sum();
+    let prompt_tokens: usize = runs.iter().map(|r| r.prompt_tokens).sum();
+    let completion_tokens: usize = runs.iter().map(|r| r.completion_tokens).sum();
+    let total_tokens = prompt_tokens + completion_tokens;
+    let mean_ms = total_ms / runs.len() as u64;
+    let tokens_per_sec = if total_ms > 0 { total_tokens as f64 * 1000.0 / total_ms as f64 } else { 0.0 };
+    let cost = prompt_tokens as f64 * input_per_mtok / 1_000_000.0 + completion_tokens as f64 * output_per_mtok / 1_000_000.0;
+    let cost_per_call = cost / runs.len() as f64;
+
+    out.push_str(&format!(
+        "\n  latency  min {}  p50 {}  p90 {}  max {}  mean {}",
+        format_duration(latencies[0]),
+        format_duration(percentile(&latencies, 50)),
+        format_duration(percentile(&latencies, 90)),
+        format_duration(latencies[latencies.len() - 1]),
+        format_duration(mean_ms),
+    ));
+    out.push_str(&format!(
+        "\n  tokens   {} per call ({} in, {} out), {tokens_per_sec:.0} tok/s",
+        total_tokens / runs.len(),
+        prompt_tokens / runs.len(),
+        completion_tokens / runs.len(),
+    ));
+    out.push_str(&format!("\n  cost     ~${cost_per_call:.4} per call"));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let values = [100, 200, 300, 400, 500, 600, 700, 800, 900, 1000];
+        assert_eq!(percentile(&values, 50), 500);
+        assert_eq!(percentile(&values, 90), 900);
+        assert_eq!(percentile(&[42], 90), 42);
+    }
+
+    #[test]
+    fn test_report() {
+        let runs = [
+            BenchRun { duration_ms: 1000, prompt_tokens: 150, completion_tokens: 50 },
+            BenchRun { duration_ms: 500, prompt_tokens: 75, completion_tokens: 25 },
+            BenchRun { duration_ms: 1500, prompt_tokens: 225, completion_tokens: 75 },
+        ];
+        let report = report("anthropic:sonnet", &runs, 1, 3.0, 15.0);
+        assert!(report.starts_with("⏱ Benchmark anthropic:sonnet: 3 ok, 1 failed"));
+        assert!(report.contains("min 500ms  p50 1.0s  p90 1.5s  max 1.5s  mean 1.0s"));
+        assert!(report.contains("200 per call (150 in, 50 out), 200 tok/s"));
+        // 150 prompt tokens at $3/M and 50 completion tokens at $15/M
+        assert!(report.contains("~$0.0012 per call"));
+    }
+
+    #[test]
+    fn test_report_all_failed() {
+        assert_eq!(report("x:y", &[], 2, 3.0, 15.0), "⏱ Benchmark x:y: 0 ok, 2 failed");
+    }
+}
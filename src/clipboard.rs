@@ -0,0 +1,44 @@
+//! System clipboard access: native copy tools when available, OSC 52 otherwise.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use base64::Engine;
+
+/// Copy text to the system clipboard. Returns the mechanism that was used.
+pub fn copy(text: &str) -> Result<&'static str> {
+    let candidates: &[(&str, &[&str], bool)] = &[
+        ("pbcopy", &[], cfg!(target_os = "macos")),
+        ("wl-copy", &[], std::env::var_os("WAYLAND_DISPLAY").is_some()),
+        ("xclip", &["-selection", "clipboard"], std::env::var_os("DISPLAY").is_some()),
+        ("xsel", &["--clipboard", "--input"], std::env::var_os("DISPLAY").is_some()),
+    ];
+    for (program, args, applicable) in candidates {
+        if *applicable && pipe_to(program, args, text).is_ok() {
+            return Ok(program);
+        }
+    }
+
+    // Fall back to OSC 52, which most modern terminals (and SSH sessions) honor
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{encoded}\x07")?;
+    stdout.flush()?;
+    Ok("OSC 52")
+}
+
+fn pipe_to(program: &str, args: &[&str], text: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "{program} exited with {status}");
+    Ok(())
+}
@@ -1,19 +1,476 @@
 //! Slash command handling.
 
+use std::collections::BTreeMap;
+
+use crate::app::{MessageKind, TraceKind};
+use crate::bench;
+use crate::config::{CustomCommand, LayoutMode};
+
+/// Reference for `/help` and the F1 popup.
+pub const HELP: &str = "\
+Commands: /quit /clear /model [--check] <m> /provider [name [model]] /compact [summarize|truncate|keep-last <n>] /cost /stats /artifacts /export md|json|html [path] /export <dir> /copy [code] /files [path] /diff [path] /alias [list|<name> <text>|remove <name>] /goto <id> /image <path> /pager /sidebar /zen /layout [vertical|horizontal] /theme [name] /tab [new [manifest|model]|close|<n>] /broadcast [<n>,<n>...] <prompt> /state /context /memory [list|clear|pin <n>] /tools /workflow [path] /autonomy [manual|supervised|semi|full] /save [name] /load [name] /checkpoint [label] /restore [label] /dismiss [n] /filter [hide|show|only <kinds>|reset] /trace [on|off|filter <kinds>] /set <key> [value] /retry [--model <m>] /undo /edit [--editor|<prompt>] /retry-stage <id> /handoff <manifest> /bench [n] [prompt] /bg <prompt> /bg cancel <n> /unsafe [<capability> [confirm]] /workdir [reset] /keys /help\n\
+Shell: !<command>  !! (repeat the last)  !?<keyword> (repeat the last containing keyword)\n\
+Background: <prompt> &  or  /bg <prompt>  (runs on its own queue; the input stays free)\n\
+Keys: /keys or F1";
+
 /// Result of processing a slash command.
 pub enum CommandResult {
     NotACommand,
+    /// Nothing to do.
     Continue,
+    /// Text to show in the chat, a line per entry.
+    Message(Vec<String>),
+    /// A command given the wrong arguments, with its syntax as `/help` lists it.
+    Usage(String),
     Quit,
-    SwitchModel(String),
+    /// Move the conversation to another model; with `check`, only once a short
+    /// call to it succeeds.
+    SwitchModel { model: String, check: bool },
+    /// Move the conversation to another provider, on `model` or its default;
+    /// shows the current one without a name.
+    SwitchProvider { provider: Option<String>, model: Option<String> },
     Clear,
     ShellCommand(String),
-    Compact,
+    /// `!!` re-runs the last shell command, `!?keyword` the last one containing `keyword`.
+    ShellRepeat(Option<String>),
+    Compact(CompactStrategy),
     Cost,
+    /// Summarize the session's turns: latency, tokens, tool calls, failures and cost.
+    Stats,
+    Artifacts(ArtifactsAction),
+    Workdir(WorkdirAction),
+    Export { format: ExportFormat, path: Option<String> },
+    /// Jump to the message with this stable id.
+    Goto(usize),
+    /// Roll back the last turn and run its input again, on `model` if given.
+    Retry { model: Option<String> },
+    /// Drop the last exchange from the conversation and the chat.
+    Undo,
+    /// Revise the last prompt and run it again in place of its exchange.
+    Edit(EditAction),
+    /// Re-run the last turn starting from this workflow stage.
+    RetryStage(String),
+    /// Preview an image file in the chat.
+    Image(String),
+    /// Open the transcript in the external `$PAGER`.
+    Pager,
+    /// List what safe mode withholds, or re-enable a capability once `confirmed`.
+    Unsafe { capability: Option<Capability>, confirmed: bool },
+    /// Show or hide the sidebar.
+    Sidebar,
+    /// Toggle zen mode: full-width chat with minimal chrome.
+    Zen,
+    /// Put the sidebar right of the chat or under it; offers a picker without a mode.
+    Layout(Option<LayoutMode>),
+    /// Switch to a built-in theme; offers a previewing picker without a name.
+    Theme(Option<String>),
+    /// Open, close, switch or list session tabs.
+    Tab(TabAction),
+    /// Browse the agent's identity, module configuration and remembered turns.
+    State,
+    /// Show or change the autonomy level of the agent's policy.
+    Autonomy(Option<String>),
+    /// Show the active workflow and its stages, or load the workflow YAML at a path.
+    Workflow(Option<String>),
+    /// Inspect, clear or pin the history module's messages.
+    Memory(MemoryAction),
+    /// Break down what the conversation in the context window is made of.
+    Context,
+    /// List the tools registered on the agent and whether they may run.
+    Tools,
+    /// Save the chat and conversation under a name (a timestamp by default).
+    Save(Option<String>),
+    /// Restore a saved session, or list them without a name.
+    Load(Option<String>),
+    /// Snapshot the chat and conversation to the checkpoint directory.
+    Checkpoint(Option<String>),
+    /// Roll back to a checkpoint, or pick one without a label.
+    Restore(Option<String>),
+    /// Time `runs` calls of `prompt` against the current provider.
+    Bench { runs: usize, prompt: String },
+    /// Run a prompt on the background queue (`/bg <prompt>` or `<prompt> &`).
+    Background(String),
+    /// Stop the 1-based background task, running or queued.
+    CancelBackground(usize),
+    /// Dismiss the 1-based pinned notice, or all of them.
+    Dismiss(Option<usize>),
+    /// Summarize the conversation and continue it with the agent in this manifest.
+    Handoff(String),
+    /// Change which kinds of chat messages are shown.
+    Filter(FilterAction),
+    /// Copy the last response, or only its code blocks, to the clipboard.
+    Copy { code_only: bool },
+    /// List every file the agent read or wrote, or write the list to a path.
+    Files(Option<String>),
+    /// Read the diff of the last write, or the last write to a path, full screen.
+    Diff(Option<String>),
+    /// List, define or remove command aliases.
+    Alias(AliasAction),
+    /// A `[commands]` entry from `tui.toml`: run the snippet, if any, then send
+    /// the prompt with `{output}` replaced by what it printed.
+    Custom { run: Option<String>, prompt: String },
+    /// Show every key binding, grouped by where it applies.
+    Keys,
+    /// Toggle trace recording or limit the trace panel, viewer and exports to
+    /// some kinds of entry.
+    Trace(TraceAction),
+    /// Change a UI setting such as `layout.chat` for this session, or show it
+    /// when there is no value.
+    Set { key: String, value: Option<String> },
+}
+
+impl CommandResult {
+    /// Whether the UI handles the command on its own, so it works while the
+    /// agent is busy.
+    pub fn is_ui_only(&self) -> bool {
+        matches!(
+            self,
+            CommandResult::Artifacts(_)
+                | CommandResult::Export { .. }
+                | CommandResult::Goto(_)
+                | CommandResult::Image(_)
+                | CommandResult::Pager
+                | CommandResult::Sidebar
+                | CommandResult::Zen
+                | CommandResult::Layout(_)
+                | CommandResult::Tab(_)
+                | CommandResult::Background(_)
+                | CommandResult::CancelBackground(_)
+                | CommandResult::Theme(_)
+                | CommandResult::Dismiss(_)
+                | CommandResult::Filter(_)
+                | CommandResult::Trace(_)
+                | CommandResult::Keys
+                | CommandResult::Copy { .. }
+                | CommandResult::Files(_)
+                | CommandResult::Diff(_)
+                | CommandResult::Alias(_)
+                | CommandResult::Set { .. }
+                | CommandResult::Edit(EditAction::Load | EditAction::Editor)
+                | CommandResult::Load(None)
+                | CommandResult::Restore(None)
+        )
+    }
+}
+
+/// Transcript formats supported by `/export`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+    /// A directory with the transcript, trace, LLM calls and stats.
+    Bundle,
+}
+
+/// Forms of `/edit`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditAction {
+    /// Put the last prompt back in the input bar.
+    Load,
+    /// Revise the last prompt in `$EDITOR`.
+    Editor,
+    /// Roll back the last exchange and run this prompt instead.
+    Resubmit(String),
+}
+
+/// How `/compact` shrinks the history. Pinned messages survive all of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompactStrategy {
+    /// Replace older messages with a summary written by the model.
+    Summarize,
+    /// Drop the oldest exchanges until the history fits in half the context window.
+    Truncate,
+    /// Keep only the last n exchanges.
+    KeepLast(usize),
+}
+
+impl CompactStrategy {
+    pub fn name(self) -> String {
+        match self {
+            CompactStrategy::Summarize => "summarize".into(),
+            CompactStrategy::Truncate => "truncate".into(),
+            CompactStrategy::KeepLast(n) => format!("keep-last {n}"),
+        }
+    }
+}
+
+/// Sub-commands of `/memory`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryAction {
+    /// Number and preview the messages the history module holds.
+    List,
+    /// Forget the conversation.
+    Clear,
+    /// Keep message n (1-based) through compaction.
+    Pin(usize),
+}
+
+/// Sub-commands of `/workdir`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkdirAction {
+    /// Show the current and original working directories.
+    Show,
+    /// Restore the working directory the TUI was started from.
+    Reset,
+}
+
+/// Sub-commands of `/tab`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TabAction {
+    /// List the open tabs.
+    List,
+    /// Open a session in a new tab, optionally for another manifest and/or model.
+    New { manifest: Option<String>, model: Option<String> },
+    /// Close the current tab.
+    Close,
+    /// Switch to the 1-based tab.
+    Select(usize),
+    /// Send a prompt to the 1-based tabs, or to every tab when none are given,
+    /// and compare their answers.
+    Broadcast { tabs: Vec<usize>, prompt: String },
+}
+
+/// Sub-commands of `/filter`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterAction {
+    /// Report what is currently hidden.
+    Status,
+    Hide(Vec<MessageKind>),
+    Show(Vec<MessageKind>),
+    /// Hide everything except these kinds.
+    Only(Vec<MessageKind>),
+    /// Show every message again.
+    Reset,
+}
+
+/// Sub-commands of `/trace`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceAction {
+    /// Report whether the trace records and what the panel shows.
+    Status,
+    /// Start or stop recording trace entries.
+    Enable(bool),
+    /// Limit the trace panel to these kinds; empty shows everything.
+    Filter(Vec<TraceKind>),
+}
+
+/// Sub-commands of `/alias`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AliasAction {
+    /// Show every alias and what it expands to.
+    List,
+    /// Make `/name` expand to `text`.
+    Set { name: String, text: String },
+    Remove(String),
+}
+
+/// Sub-commands of `/artifacts`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtifactsAction {
+    /// List every collected artifact.
+    List,
+    /// Preview an artifact by 1-based index or name.
+    Show(String),
+    /// Write an artifact's content to a file.
+    Export { target: String, path: String },
+}
+
+/// Something `--safe-mode` withholds until it is re-enabled with `/unsafe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Tools,
+    Shell,
+    Attachments,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [Capability::Tools, Capability::Shell, Capability::Attachments];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Tools => "tools",
+            Capability::Shell => "shell",
+            Capability::Attachments => "attachments",
+        }
+    }
+
+    /// What enabling it allows, for the confirmation prompt.
+    pub fn risk(self) -> &'static str {
+        match self {
+            Capability::Tools => "the agent can run commands and read or write files on this machine",
+            Capability::Shell => "`!` commands run in your shell",
+            Capability::Attachments => "files named in prompts are read from disk and previewed",
+        }
+    }
+}
+
+impl std::str::FromStr for Capability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Capability::ALL
+            .into_iter()
+            .find(|c| c.name() == s || c.name().trim_end_matches('s') == s)
+            .ok_or_else(|| format!("unknown capability '{s}'"))
+    }
+}
+
+/// Every built-in command, including the short forms. Aliases can't take these names.
+pub const COMMANDS: &[&str] = &[
+    "/quit", "/exit", "/q", "/clear", "/model", "/provider", "/help", "/?", "/compact", "/cost", "/stats",
+    "/artifacts", "/export", "/retry", "/undo", "/save", "/load", "/checkpoint", "/restore", "/edit",
+    "/retry-stage", "/goto", "/filter", "/trace", "/keys", "/files", "/diff", "/copy", "/alias", "/tab",
+    "/theme", "/set", "/pager", "/sidebar", "/zen", "/layout", "/state", "/tools", "/context", "/memory",
+    "/autonomy", "/workflow", "/bench", "/unsafe", "/dismiss", "/handoff", "/image", "/workdir",
+    "/bg", "/broadcast",
+];
+
+/// Whether `/name` is a built-in command.
+pub fn is_builtin(name: &str) -> bool {
+    COMMANDS.iter().any(|command| command[1..] == *name)
+}
+
+/// User-defined commands (the `[commands]` section of `tui.toml`), by name
+/// without the slash.
+pub type CustomCommands = BTreeMap<String, CustomCommand>;
+
+/// `/help`: the reference, with any user-defined commands and their descriptions.
+pub fn help(custom: &CustomCommands) -> String {
+    if custom.is_empty() {
+        return HELP.to_string();
+    }
+    let commands: Vec<String> = custom
+        .iter()
+        .map(|(name, command)| match &command.description {
+            Some(description) => format!("/{name} ({description})"),
+            None => format!("/{name}"),
+        })
+        .collect();
+    format!("{HELP}\nCustom: {}", commands.join(" "))
+}
+
+/// `template` with `{args}` filled in, or `args` appended when it has no `{args}`.
+fn with_args(template: &str, args: &str) -> String {
+    if template.contains("{args}") {
+        template.replace("{args}", args)
+    } else if args.is_empty() {
+        template.to_string()
+    } else {
+        format!("{template} {args}")
+    }
+}
+
+/// `word` as one shell word: as is when nothing in it is special to the
+/// shell, otherwise single-quoted.
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// A user-defined command invoked with `args`. Arguments without an `{args}`
+/// slot go to the snippet when there is one, otherwise to the prompt. The
+/// snippet gets each word of them quoted, so the shell expands none of it.
+fn custom_command(custom: &CustomCommands, name: &str, args: &str) -> Option<CommandResult> {
+    let command = custom.get(name)?;
+    let words = args.split_whitespace().map(shell_quote).collect::<Vec<_>>().join(" ");
+    let run = command.run.as_deref().map(|run| with_args(run, &words));
+    let prompt = match (&command.prompt, &run) {
+        (Some(prompt), Some(_)) => prompt.replace("{args}", args),
+        (Some(prompt), None) => with_args(prompt, args),
+        (None, Some(run)) => format!("Output of `{run}`:\n```\n{{output}}\n```"),
+        (None, None) => return None,
+    };
+    Some(CommandResult::Custom { run, prompt })
+}
+
+/// Expand `/name rest` when `name` is an alias: its text, then the rest of the line.
+pub fn expand_alias(input: &str, aliases: &BTreeMap<String, String>) -> Option<String> {
+    let trimmed = input.trim_start();
+    let (name, rest) = trimmed.strip_prefix('/')?.split_once(char::is_whitespace).unwrap_or((&trimmed[1..], ""));
+    let text = aliases.get(name)?;
+    let rest = rest.trim();
+    Some(if rest.is_empty() { text.clone() } else { format!("{text} {rest}") })
+}
+
+/// Syntax of `command` as the `/help` reference lists it, e.g. `/memory [list|clear|pin <n>]`;
+/// forms listed separately are joined with "or".
+pub fn syntax(command: &str) -> String {
+    let listed = HELP.lines().next().unwrap_or_default().trim_start_matches("Commands: ");
+    let forms: Vec<String> = listed
+        .split(" /")
+        .map(|form| format!("/{}", form.trim_start_matches('/')))
+        .filter(|form| form.split(' ').next() == Some(command))
+        .collect();
+    if forms.is_empty() {
+        command.to_string()
+    } else {
+        forms.join(" or ")
+    }
+}
+
+/// Tab completions for a partly typed command line, each a whole line: command
+/// names from `names` while the first word is typed, then `arguments(command)`
+/// narrowed by the word after it.
+pub fn completions(input: &str, names: &[String], arguments: impl Fn(&str) -> Vec<String>) -> Vec<String> {
+    if !input.starts_with('/') {
+        return Vec::new();
+    }
+    let Some((command, partial)) = input.split_once(' ') else {
+        let mut matches: Vec<String> = names.iter().filter(|name| name.starts_with(input)).cloned().collect();
+        matches.sort();
+        matches.dedup();
+        return matches;
+    };
+    // Only one argument is completed
+    if partial.contains(' ') {
+        return Vec::new();
+    }
+    arguments(command)
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(partial))
+        .map(|candidate| format!("{command} {candidate}"))
+        .collect()
+}
+
+/// The longest prefix every completion shares.
+pub fn common_prefix(completions: &[String]) -> String {
+    let Some(first) = completions.first() else { return String::new() };
+    let mut prefix = first.as_str();
+    for other in &completions[1..] {
+        let end = prefix
+            .char_indices()
+            .zip(other.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(other.len()), |((i, _), _)| i);
+        prefix = &prefix[..end];
+    }
+    prefix.to_string()
+}
+
+/// The shell command `!!` (no keyword) or `!?keyword` re-runs: the latest in
+/// `history`, or the latest containing `keyword`.
+pub fn recall_shell<'a>(history: &'a [String], keyword: Option<&str>) -> Option<&'a String> {
+    history.iter().rev().find(|command| keyword.is_none_or(|keyword| command.contains(keyword)))
+}
+
+fn usage(command: &str) -> CommandResult {
+    CommandResult::Usage(syntax(command))
 }
 
-/// Process a potential slash command or shell command.
+/// Process a potential slash command or shell command, knowing only the
+/// built-in commands.
 pub fn process_command(input: &str) -> CommandResult {
+    process_command_with(input, &CustomCommands::new())
+}
+
+/// Process a potential slash command or shell command, `custom` holding the
+/// user-defined commands.
+pub fn process_command_with(input: &str, custom: &CustomCommands) -> CommandResult {
     let trimmed = input.trim();
 
     if trimmed.starts_with('!') {
@@ -21,11 +478,24 @@ pub fn process_command(input: &str) -> CommandResult {
         if command.is_empty() {
             return CommandResult::Continue;
         }
+        if command == "!" {
+            return CommandResult::ShellRepeat(None);
+        }
+        if let Some(keyword) = command.strip_prefix('?') {
+            return match keyword.trim() {
+                "" => CommandResult::Usage("!?<keyword>".into()),
+                keyword => CommandResult::ShellRepeat(Some(keyword.to_string())),
+            };
+        }
         return CommandResult::ShellCommand(command.to_string());
     }
 
     if !trimmed.starts_with('/') {
-        return CommandResult::NotACommand;
+        // A trailing ` &` sends the prompt to the background, as in a shell
+        return match trimmed.strip_suffix(" &").map(str::trim) {
+            Some(prompt) if !prompt.is_empty() => CommandResult::Background(prompt.to_string()),
+            _ => CommandResult::NotACommand,
+        };
     }
 
     let parts: Vec<&str> = trimmed.splitn(2, ' ').collect();
@@ -36,16 +506,335 @@ pub fn process_command(input: &str) -> CommandResult {
         "/quit" | "/exit" | "/q" => CommandResult::Quit,
         "/clear" => CommandResult::Clear,
         "/model" => {
+            let check = arg.split_whitespace().any(|word| word == "--check");
+            match arg.split_whitespace().filter(|word| *word != "--check").collect::<Vec<_>>()[..] {
+                [model] => CommandResult::SwitchModel { model: model.to_string(), check },
+                _ => usage(cmd),
+            }
+        }
+        "/provider" => {
+            let mut parts = arg.split_whitespace();
+            CommandResult::SwitchProvider {
+                provider: parts.next().map(str::to_string),
+                model: parts.next().map(str::to_string),
+            }
+        }
+        "/help" | "/?" => CommandResult::Message(help(custom).lines().map(str::to_string).collect()),
+        "/compact" => match arg.split_whitespace().collect::<Vec<_>>()[..] {
+            [] | ["summarize"] => CommandResult::Compact(CompactStrategy::Summarize),
+            ["truncate"] => CommandResult::Compact(CompactStrategy::Truncate),
+            ["keep-last", n] => match n.parse() {
+                Ok(n) if n > 0 => CommandResult::Compact(CompactStrategy::KeepLast(n)),
+                _ => usage(cmd),
+            },
+            _ => usage(cmd),
+        },
+        "/cost" => CommandResult::Cost,
+        "/stats" => CommandResult::Stats,
+        "/artifacts" => parse_artifacts(arg),
+        "/export" => parse_export(arg),
+        "/retry" => match arg.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => CommandResult::Retry { model: None },
+            ["--model", model] => CommandResult::Retry { model: Some(model.to_string()) },
+            _ => usage(cmd),
+        },
+        "/undo" => CommandResult::Undo,
+        "/save" => CommandResult::Save((!arg.is_empty()).then(|| arg.to_string())),
+        "/load" => CommandResult::Load((!arg.is_empty()).then(|| arg.to_string())),
+        "/checkpoint" => CommandResult::Checkpoint((!arg.is_empty()).then(|| arg.to_string())),
+        "/restore" => CommandResult::Restore((!arg.is_empty()).then(|| arg.to_string())),
+        "/edit" => CommandResult::Edit(match arg {
+            "" => EditAction::Load,
+            "--editor" | "-e" => EditAction::Editor,
+            prompt => EditAction::Resubmit(prompt.to_string()),
+        }),
+        "/retry-stage" => {
             if arg.is_empty() {
-                CommandResult::Continue
+                usage(cmd)
             } else {
-                CommandResult::SwitchModel(arg.to_string())
+                CommandResult::RetryStage(arg.to_string())
             }
         }
-        "/help" | "/?" => CommandResult::Continue,
-        "/compact" => CommandResult::Compact,
-        "/cost" => CommandResult::Cost,
-        _ => CommandResult::Continue,
+        "/goto" => match arg.trim_start_matches('#').trim_start_matches("msg-").parse() {
+            Ok(id) => CommandResult::Goto(id),
+            Err(_) => usage(cmd),
+        },
+        "/filter" => parse_filter(arg),
+        "/trace" => parse_trace(arg),
+        "/keys" => CommandResult::Keys,
+        "/files" => CommandResult::Files((!arg.is_empty()).then(|| arg.to_string())),
+        "/diff" => CommandResult::Diff((!arg.is_empty()).then(|| arg.to_string())),
+        "/alias" => parse_alias(arg),
+        "/copy" => match arg {
+            "" => CommandResult::Copy { code_only: false },
+            "code" => CommandResult::Copy { code_only: true },
+            _ => usage(cmd),
+        },
+        "/tab" => parse_tab(arg),
+        "/broadcast" => parse_broadcast(arg),
+        "/theme" => CommandResult::Theme((!arg.is_empty()).then(|| arg.to_string())),
+        "/set" => {
+            let mut parts = arg.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(key), value) => CommandResult::Set { key: key.to_string(), value: value.map(str::to_string) },
+                (None, _) => usage(cmd),
+            }
+        }
+        "/pager" => CommandResult::Pager,
+        "/sidebar" => CommandResult::Sidebar,
+        "/zen" => CommandResult::Zen,
+        "/layout" => match arg {
+            "" => CommandResult::Layout(None),
+            mode => mode.parse().map(|mode| CommandResult::Layout(Some(mode))).unwrap_or_else(|_| usage(cmd)),
+        },
+        "/state" => CommandResult::State,
+        "/tools" => CommandResult::Tools,
+        "/context" => CommandResult::Context,
+        "/memory" => match arg.split_whitespace().collect::<Vec<_>>()[..] {
+            [] | ["list"] => CommandResult::Memory(MemoryAction::List),
+            ["clear"] => CommandResult::Memory(MemoryAction::Clear),
+            ["pin", n] => n.parse().map(|n| CommandResult::Memory(MemoryAction::Pin(n))).unwrap_or_else(|_| usage(cmd)),
+            _ => usage(cmd),
+        },
+        "/autonomy" => CommandResult::Autonomy((!arg.is_empty()).then(|| arg.to_lowercase())),
+        "/workflow" => CommandResult::Workflow((!arg.is_empty()).then(|| arg.to_string())),
+        "/bench" => parse_bench(arg),
+        "/unsafe" => parse_unsafe(arg),
+        "/dismiss" => match arg {
+            "" | "all" => CommandResult::Dismiss(None),
+            n => n.parse().map(|n| CommandResult::Dismiss(Some(n))).unwrap_or_else(|_| usage(cmd)),
+        },
+        "/bg" => match arg.split_once(' ') {
+            Some(("cancel", n)) => n.trim().parse().map(CommandResult::CancelBackground).unwrap_or_else(|_| usage(cmd)),
+            _ if arg.is_empty() || arg == "cancel" => usage(cmd),
+            _ => CommandResult::Background(arg.to_string()),
+        },
+        "/handoff" => {
+            if arg.is_empty() {
+                usage(cmd)
+            } else {
+                CommandResult::Handoff(arg.to_string())
+            }
+        }
+        "/image" => {
+            if arg.is_empty() {
+                usage(cmd)
+            } else {
+                CommandResult::Image(arg.to_string())
+            }
+        }
+        "/workdir" => match arg {
+            "" => CommandResult::Workdir(WorkdirAction::Show),
+            "reset" => CommandResult::Workdir(WorkdirAction::Reset),
+            _ => usage(cmd),
+        },
+        _ => custom_command(custom, &cmd[1..], arg).unwrap_or_else(|| CommandResult::Message(vec![unknown(cmd, custom)])),
+    }
+}
+
+/// The message for an unknown `cmd`, suggesting close commands when there are any.
+fn unknown(cmd: &str, custom: &CustomCommands) -> String {
+    let suggestions = suggestions(cmd, custom);
+    match suggestions.split_last() {
+        None => format!("Unknown command {cmd} (/help lists them)"),
+        Some((only, [])) => format!("Unknown command {cmd}. Did you mean {only}?"),
+        Some((last, rest)) => format!("Unknown command {cmd}. Did you mean {} or {last}?", rest.join(", ")),
+    }
+}
+
+/// Up to three commands (built-in or custom) within a few typos of `cmd`,
+/// closest first. Longer names tolerate more typos.
+fn suggestions(cmd: &str, custom: &CustomCommands) -> Vec<String> {
+    let typed = cmd.trim_start_matches('/');
+    let allowed = (typed.chars().count() / 3).clamp(1, 3);
+    let mut close: Vec<(usize, String)> = COMMANDS
+        .iter()
+        .map(|name| name[1..].to_string())
+        .chain(custom.keys().cloned())
+        // `/q` and `/?` are within a typo of every short word
+        .filter(|name| name.chars().count() > 1)
+        .map(|name| (edit_distance(typed, &name), name))
+        .filter(|(distance, _)| *distance <= allowed)
+        .collect();
+    close.sort();
+    close.into_iter().take(3).map(|(_, name)| format!("/{name}")).collect()
+}
+
+/// Edits (insert, delete, substitute, swap neighbours) to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // rows[i][j]: distance between the first i chars of a and the first j of b
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+fn parse_bench(arg: &str) -> CommandResult {
+    let (first, rest) = arg.split_once(' ').unwrap_or((arg, ""));
+    let (runs, prompt) = match first.parse::<usize>() {
+        Ok(runs) => (runs, rest.trim()),
+        Err(_) => (bench::DEFAULT_RUNS, arg),
+    };
+    if runs == 0 || runs > bench::MAX_RUNS {
+        return usage("/bench");
+    }
+    let prompt = if prompt.is_empty() { bench::DEFAULT_PROMPT } else { prompt };
+    CommandResult::Bench { runs, prompt: prompt.to_string() }
+}
+
+fn parse_unsafe(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    let capability = match parts.next().map(str::parse) {
+        None => None,
+        Some(Ok(capability)) => Some(capability),
+        Some(Err(_)) => return usage("/unsafe"),
+    };
+    let confirmed = match (capability, parts.next()) {
+        (_, None) => false,
+        (Some(_), Some("confirm")) => true,
+        _ => return usage("/unsafe"),
+    };
+    CommandResult::Unsafe { capability, confirmed }
+}
+
+fn parse_tab(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    let action = match parts.next() {
+        None => TabAction::List,
+        Some("close") => TabAction::Close,
+        Some("new") => {
+            let (mut manifest, mut model) = (None, None);
+            while let Some(part) = parts.next() {
+                match part {
+                    "--manifest" => manifest = parts.next().map(str::to_string),
+                    "--model" => model = parts.next().map(str::to_string),
+                    // A bare argument is a manifest file or else a model
+                    path if path.ends_with(".yaml") || path.ends_with(".yml") => manifest = Some(path.to_string()),
+                    name => model = Some(name.to_string()),
+                }
+            }
+            TabAction::New { manifest, model }
+        }
+        Some(n) => match n.parse() {
+            Ok(n) if n > 0 => TabAction::Select(n),
+            _ => return usage("/tab"),
+        },
+    };
+    CommandResult::Tab(action)
+}
+
+/// `/broadcast [<n>,<n>...] <prompt>`: a leading list of tab numbers picks the tabs.
+fn parse_broadcast(arg: &str) -> CommandResult {
+    let (tabs, prompt) = match arg.split_once(' ') {
+        Some((first, rest)) => match first.split(',').map(str::parse).collect::<Result<Vec<usize>, _>>() {
+            Ok(tabs) => (tabs, rest.trim()),
+            Err(_) => (Vec::new(), arg),
+        },
+        None => (Vec::new(), arg),
+    };
+    if prompt.is_empty() || tabs.contains(&0) {
+        return usage("/broadcast");
+    }
+    let mut tabs = tabs;
+    tabs.sort_unstable();
+    tabs.dedup();
+    CommandResult::Tab(TabAction::Broadcast { tabs, prompt: prompt.to_string() })
+}
+
+fn parse_filter(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    let verb = parts.next();
+    let kinds: Result<Vec<MessageKind>, _> = parts.map(str::parse).collect();
+    let action = match (verb, kinds) {
+        (None, _) => FilterAction::Status,
+        (Some("reset" | "all" | "off"), Ok(kinds)) if kinds.is_empty() => FilterAction::Reset,
+        (Some("hide"), Ok(kinds)) if !kinds.is_empty() => FilterAction::Hide(kinds),
+        (Some("show"), Ok(kinds)) if !kinds.is_empty() => FilterAction::Show(kinds),
+        (Some("only"), Ok(kinds)) if !kinds.is_empty() => FilterAction::Only(kinds),
+        _ => return usage("/filter"),
+    };
+    CommandResult::Filter(action)
+}
+
+fn parse_trace(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    let action = match parts.next() {
+        None => TraceAction::Status,
+        Some("on") => TraceAction::Enable(true),
+        Some("off") => TraceAction::Enable(false),
+        Some("filter") => {
+            let words: Vec<&str> = parts.collect();
+            match words[..] {
+                [] | ["all" | "reset"] => TraceAction::Filter(Vec::new()),
+                _ => match words.iter().map(|word| word.parse()).collect() {
+                    Ok(kinds) => TraceAction::Filter(kinds),
+                    Err(_) => return usage("/trace"),
+                },
+            }
+        }
+        Some(_) => return usage("/trace"),
+    };
+    CommandResult::Trace(action)
+}
+
+fn parse_alias(arg: &str) -> CommandResult {
+    let (first, rest) = arg.split_once(' ').unwrap_or((arg, ""));
+    let rest = rest.trim();
+    let action = match (first, rest) {
+        ("" | "list", "") => AliasAction::List,
+        ("remove", name) if !name.is_empty() => AliasAction::Remove(name.trim_start_matches('/').to_string()),
+        (_, "") => return usage("/alias"),
+        (name, text) => {
+            let text = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text);
+            AliasAction::Set { name: name.trim_start_matches('/').to_string(), text: text.to_string() }
+        }
+    };
+    CommandResult::Alias(action)
+}
+
+fn parse_export(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    let format = match parts.next() {
+        Some("md" | "markdown") => ExportFormat::Markdown,
+        Some("json") => ExportFormat::Json,
+        Some("html") => ExportFormat::Html,
+        // Anything else names the directory for a bundle
+        Some(dir) if parts.next().is_none() => {
+            return CommandResult::Export { format: ExportFormat::Bundle, path: Some(dir.to_string()) }
+        }
+        _ => return usage("/export"),
+    };
+    CommandResult::Export { format, path: parts.next().map(str::to_string) }
+}
+
+fn parse_artifacts(arg: &str) -> CommandResult {
+    let mut parts = arg.split_whitespace();
+    match parts.next() {
+        None => CommandResult::Artifacts(ArtifactsAction::List),
+        Some("export") => match (parts.next(), parts.next()) {
+            (Some(target), Some(path)) => CommandResult::Artifacts(ArtifactsAction::Export {
+                target: target.to_string(),
+                path: path.to_string(),
+            }),
+            _ => usage("/artifacts"),
+        },
+        Some(target) => CommandResult::Artifacts(ArtifactsAction::Show(target.to_string())),
     }
 }
 
@@ -62,8 +851,26 @@ mod tests {
 
     #[test]
     fn test_help_command() {
-        assert!(matches!(process_command("/help"), CommandResult::Continue));
-        assert!(matches!(process_command("/?"), CommandResult::Continue));
+        assert!(matches!(process_command("/help"), CommandResult::Message(_)));
+        match process_command("/?") {
+            CommandResult::Message(lines) => assert_eq!(lines.join("\n"), help(&CustomCommands::new())),
+            _ => panic!("expected Message"),
+        }
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(syntax("/memory"), "/memory [list|clear|pin <n>]");
+        assert_eq!(syntax("/export"), "/export md|json|html [path] or /export <dir>");
+        assert_eq!(syntax("/retry"), "/retry [--model <m>]");
+        match process_command("/memory forget") {
+            CommandResult::Usage(syntax) => assert_eq!(syntax, "/memory [list|clear|pin <n>]"),
+            _ => panic!("expected Usage"),
+        }
+        // Every command but the short forms is in the reference
+        for command in COMMANDS.iter().filter(|c| !matches!(**c, "/exit" | "/q" | "/?")) {
+            assert!(HELP.split([' ', '\n']).any(|word| word == *command), "{command} missing from HELP");
+        }
     }
 
     #[test]
@@ -73,22 +880,48 @@ mod tests {
 
     #[test]
     fn test_compact_command() {
-        assert!(matches!(process_command("/compact"), CommandResult::Compact));
+        assert!(matches!(process_command("/compact"), CommandResult::Compact(CompactStrategy::Summarize)));
+        assert!(matches!(process_command("/compact truncate"), CommandResult::Compact(CompactStrategy::Truncate)));
+        assert!(matches!(process_command("/compact keep-last 3"), CommandResult::Compact(CompactStrategy::KeepLast(3))));
+        assert!(matches!(process_command("/compact keep-last 0"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/compact everything"), CommandResult::Usage(_)));
     }
 
     #[test]
     fn test_cost_command() {
         assert!(matches!(process_command("/cost"), CommandResult::Cost));
+        assert!(matches!(process_command("/stats"), CommandResult::Stats));
     }
 
     #[test]
     fn test_model_command() {
         match process_command("/model sonnet") {
-            CommandResult::SwitchModel(m) => assert_eq!(m, "sonnet"),
+            CommandResult::SwitchModel { model, check } => assert_eq!((model.as_str(), check), ("sonnet", false)),
             _ => panic!("expected SwitchModel"),
         }
-        // No arg returns Continue
-        assert!(matches!(process_command("/model"), CommandResult::Continue));
+        for input in ["/model --check ollama:qwen2.5", "/model ollama:qwen2.5 --check"] {
+            match process_command(input) {
+                CommandResult::SwitchModel { model, check } => assert_eq!((model.as_str(), check), ("ollama:qwen2.5", true)),
+                _ => panic!("expected SwitchModel"),
+            }
+        }
+        assert!(matches!(process_command("/model --check"), CommandResult::Usage(_)));
+        // No argument returns the usage
+        assert!(matches!(process_command("/model"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_provider_command() {
+        let provider = |input: &str| match process_command(input) {
+            CommandResult::SwitchProvider { provider, model } => Some((provider, model)),
+            _ => None,
+        };
+        assert_eq!(provider("/provider"), Some((None, None)));
+        assert_eq!(provider("/provider anthropic"), Some((Some("anthropic".into()), None)));
+        assert_eq!(
+            provider("/provider ollama llama3.2:3b"),
+            Some((Some("ollama".into()), Some("llama3.2:3b".into())))
+        );
     }
 
     #[test]
@@ -97,6 +930,17 @@ mod tests {
             CommandResult::ShellCommand(c) => assert_eq!(c, "ls -la"),
             _ => panic!("expected ShellCommand"),
         }
+        assert!(matches!(process_command("!!"), CommandResult::ShellRepeat(None)));
+        match process_command("!?cargo t") {
+            CommandResult::ShellRepeat(Some(keyword)) => assert_eq!(keyword, "cargo t"),
+            _ => panic!("expected ShellRepeat"),
+        }
+        assert!(matches!(process_command("!?"), CommandResult::Usage(_)));
+        let history = vec!["cargo test".to_string(), "cargo build".to_string(), "ls".to_string()];
+        assert_eq!(recall_shell(&history, None).map(String::as_str), Some("ls"));
+        assert_eq!(recall_shell(&history, Some("cargo")).map(String::as_str), Some("cargo build"));
+        assert_eq!(recall_shell(&history, Some("make")), None);
+        assert_eq!(recall_shell(&[], None), None);
         // Empty shell command
         assert!(matches!(process_command("!"), CommandResult::Continue));
     }
@@ -106,8 +950,501 @@ mod tests {
         assert!(matches!(process_command("hello"), CommandResult::NotACommand));
     }
 
+    #[test]
+    fn test_artifacts_command() {
+        assert!(matches!(
+            process_command("/artifacts"),
+            CommandResult::Artifacts(ArtifactsAction::List)
+        ));
+        match process_command("/artifacts plan") {
+            CommandResult::Artifacts(ArtifactsAction::Show(t)) => assert_eq!(t, "plan"),
+            _ => panic!("expected Show"),
+        }
+        match process_command("/artifacts export 2 out/plan.json") {
+            CommandResult::Artifacts(ArtifactsAction::Export { target, path }) => {
+                assert_eq!(target, "2");
+                assert_eq!(path, "out/plan.json");
+            }
+            _ => panic!("expected Export"),
+        }
+        // Export without a destination is ignored
+        assert!(matches!(process_command("/artifacts export 2"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_ui_only() {
+        for text in ["/theme", "/keys", "/sidebar", "/zen", "/bg look around", "/load", "/edit"] {
+            assert!(process_command(text).is_ui_only(), "{text}");
+        }
+        for text in ["hello", "/model haiku", "/load work", "/edit new prompt", "/compact", "/quit"] {
+            assert!(!process_command(text).is_ui_only(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_export_command() {
+        match process_command("/export md notes/session.md") {
+            CommandResult::Export { format, path } => {
+                assert_eq!(format, ExportFormat::Markdown);
+                assert_eq!(path.as_deref(), Some("notes/session.md"));
+            }
+            _ => panic!("expected Export"),
+        }
+        assert!(matches!(
+            process_command("/export markdown"),
+            CommandResult::Export { format: ExportFormat::Markdown, path: None }
+        ));
+        assert!(matches!(
+            process_command("/export json out.json"),
+            CommandResult::Export { format: ExportFormat::Json, path: Some(_) }
+        ));
+        assert!(matches!(
+            process_command("/export html"),
+            CommandResult::Export { format: ExportFormat::Html, path: None }
+        ));
+        assert!(matches!(process_command("/export"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/export reports bug-42"), CommandResult::Usage(_)));
+        // Anything but a format names the bundle directory
+        match process_command("/export reports/bug-42") {
+            CommandResult::Export { format, path } => {
+                assert_eq!(format, ExportFormat::Bundle);
+                assert_eq!(path.as_deref(), Some("reports/bug-42"));
+            }
+            _ => panic!("expected Export"),
+        }
+    }
+
+    #[test]
+    fn test_workdir_command() {
+        assert!(matches!(process_command("/workdir"), CommandResult::Workdir(WorkdirAction::Show)));
+        assert!(matches!(
+            process_command("/workdir reset"),
+            CommandResult::Workdir(WorkdirAction::Reset)
+        ));
+        assert!(matches!(process_command("/workdir elsewhere"), CommandResult::Usage(_)));
+    }
+
     #[test]
     fn test_unknown_slash() {
-        assert!(matches!(process_command("/unknown"), CommandResult::Continue));
+        match process_command("/unknown") {
+            CommandResult::Message(lines) => assert_eq!(lines, vec!["Unknown command /unknown (/help lists them)"]),
+            _ => panic!("expected Message"),
+        }
+        match process_command("/mdoel gpt") {
+            CommandResult::Message(lines) => assert_eq!(lines, vec!["Unknown command /mdoel. Did you mean /model?"]),
+            _ => panic!("expected Message"),
+        }
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(edit_distance("model", "model"), 0);
+        assert_eq!(edit_distance("mdoel", "model"), 1);
+        assert_eq!(edit_distance("chekpoint", "checkpoint"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        let none = CustomCommands::new();
+        assert_eq!(suggestions("/clera", &none), vec!["/clear"]);
+        assert_eq!(suggestions("/sav", &none), vec!["/save"]);
+        assert_eq!(unknown("/stat", &none), "Unknown command /stat. Did you mean /state or /stats?");
+        assert_eq!(suggestions("/st", &none), vec!["/set"]);
+        assert!(suggestions("/x", &none).is_empty());
+        assert!(suggestions("/frobnicate", &none).is_empty());
+        let custom = CustomCommands::from([("frobnicate".to_string(), CustomCommand::default())]);
+        assert_eq!(suggestions("/frobnicat", &custom), vec!["/frobnicate"]);
+    }
+
+    #[test]
+    fn test_goto() {
+        assert!(matches!(process_command("/goto 42"), CommandResult::Goto(42)));
+        assert!(matches!(process_command("/goto #msg-7"), CommandResult::Goto(7)));
+        assert!(matches!(process_command("/goto"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/goto last"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_retry_stage() {
+        match process_command("/retry-stage review") {
+            CommandResult::RetryStage(id) => assert_eq!(id, "review"),
+            _ => panic!("expected RetryStage"),
+        }
+        assert!(matches!(process_command("/retry-stage"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_save_load() {
+        assert!(matches!(process_command("/save"), CommandResult::Save(None)));
+        assert!(matches!(process_command("/save bugfix"), CommandResult::Save(Some(name)) if name == "bugfix"));
+        assert!(matches!(process_command("/load"), CommandResult::Load(None)));
+        assert!(matches!(process_command("/load bugfix"), CommandResult::Load(Some(name)) if name == "bugfix"));
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        assert!(matches!(process_command("/checkpoint"), CommandResult::Checkpoint(None)));
+        assert!(matches!(process_command("/checkpoint before-refactor"), CommandResult::Checkpoint(Some(label)) if label == "before-refactor"));
+        assert!(matches!(process_command("/restore"), CommandResult::Restore(None)));
+        assert!(matches!(process_command("/restore before-refactor"), CommandResult::Restore(Some(label)) if label == "before-refactor"));
+    }
+
+    #[test]
+    fn test_retry() {
+        assert!(matches!(process_command("/retry"), CommandResult::Retry { model: None }));
+        match process_command("/retry --model haiku") {
+            CommandResult::Retry { model } => assert_eq!(model.as_deref(), Some("haiku")),
+            _ => panic!("expected Retry"),
+        }
+        assert!(matches!(process_command("/retry haiku"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/undo"), CommandResult::Undo));
+        let edit = |input: &str| match process_command(input) {
+            CommandResult::Edit(action) => Some(action),
+            _ => None,
+        };
+        assert_eq!(edit("/edit"), Some(EditAction::Load));
+        assert_eq!(edit("/edit -e"), Some(EditAction::Editor));
+        assert_eq!(edit("/edit say it shorter"), Some(EditAction::Resubmit("say it shorter".into())));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = |input: &str| match process_command(input) {
+            CommandResult::Filter(action) => Some(action),
+            _ => None,
+        };
+        assert_eq!(filter("/filter"), Some(FilterAction::Status));
+        assert_eq!(filter("/filter reset"), Some(FilterAction::Reset));
+        assert_eq!(
+            filter("/filter hide narration tools"),
+            Some(FilterAction::Hide(vec![MessageKind::Narration, MessageKind::Tool]))
+        );
+        assert_eq!(filter("/filter only errors"), Some(FilterAction::Only(vec![MessageKind::Error])));
+        assert_eq!(filter("/filter show user"), Some(FilterAction::Show(vec![MessageKind::User])));
+        assert_eq!(filter("/filter hide"), None);
+        assert_eq!(filter("/filter hide gossip"), None);
+        assert_eq!(filter("/filter reset tools"), None);
+    }
+
+    #[test]
+    fn test_copy() {
+        assert!(matches!(process_command("/copy"), CommandResult::Copy { code_only: false }));
+        assert!(matches!(process_command("/copy code"), CommandResult::Copy { code_only: true }));
+        assert!(matches!(process_command("/copy everything"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_files() {
+        assert!(matches!(process_command("/files"), CommandResult::Files(None)));
+        assert!(matches!(process_command("/files audit.txt"), CommandResult::Files(Some(path)) if path == "audit.txt"));
+        assert!(matches!(process_command("/diff"), CommandResult::Diff(None)));
+        assert!(matches!(process_command("/diff src/main.rs"), CommandResult::Diff(Some(path)) if path == "src/main.rs"));
+    }
+
+    #[test]
+    fn test_keys() {
+        assert!(matches!(process_command("/keys"), CommandResult::Keys));
+    }
+
+    #[test]
+    fn test_trace_command() {
+        let trace = |input: &str| match process_command(input) {
+            CommandResult::Trace(action) => Some(action),
+            _ => None,
+        };
+        assert_eq!(trace("/trace"), Some(TraceAction::Status));
+        assert_eq!(trace("/trace off"), Some(TraceAction::Enable(false)));
+        assert_eq!(trace("/trace on"), Some(TraceAction::Enable(true)));
+        assert_eq!(trace("/trace filter tools llm"), Some(TraceAction::Filter(vec![TraceKind::Tools, TraceKind::Llm])));
+        assert_eq!(trace("/trace filter stages"), Some(TraceAction::Filter(vec![TraceKind::Stages])));
+        assert_eq!(trace("/trace filter all"), Some(TraceAction::Filter(Vec::new())));
+        assert_eq!(trace("/trace filter"), Some(TraceAction::Filter(Vec::new())));
+        assert_eq!(trace("/trace filter bogus"), None);
+        assert_eq!(trace("/trace sometimes"), None);
+    }
+
+    #[test]
+    fn test_handoff() {
+        match process_command("/handoff agents/coder.yaml") {
+            CommandResult::Handoff(manifest) => assert_eq!(manifest, "agents/coder.yaml"),
+            _ => panic!("expected Handoff"),
+        }
+        assert!(matches!(process_command("/handoff"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_background() {
+        match process_command("research the borrow checker &") {
+            CommandResult::Background(prompt) => assert_eq!(prompt, "research the borrow checker"),
+            _ => panic!("expected Background"),
+        }
+        match process_command("/bg summarize the crate") {
+            CommandResult::Background(prompt) => assert_eq!(prompt, "summarize the crate"),
+            _ => panic!("expected Background"),
+        }
+        assert!(matches!(process_command("/bg"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/bg cancel 2"), CommandResult::CancelBackground(2)));
+        match process_command("/bg cancel") {
+            CommandResult::Usage(syntax) => assert_eq!(syntax, "/bg <prompt> or /bg cancel <n>"),
+            _ => panic!("expected Usage"),
+        }
+        assert!(matches!(process_command("/bg cancel it"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("&"), CommandResult::NotACommand));
+        assert!(matches!(process_command("R&D budget"), CommandResult::NotACommand));
+        // `!` commands keep their `&` for the shell
+        assert!(matches!(process_command("!sleep 5 &"), CommandResult::ShellCommand(_)));
+    }
+
+    #[test]
+    fn test_unsafe() {
+        assert!(matches!(process_command("/unsafe"), CommandResult::Unsafe { capability: None, confirmed: false }));
+        assert!(matches!(
+            process_command("/unsafe tools"),
+            CommandResult::Unsafe { capability: Some(Capability::Tools), confirmed: false }
+        ));
+        assert!(matches!(
+            process_command("/unsafe shell confirm"),
+            CommandResult::Unsafe { capability: Some(Capability::Shell), confirmed: true }
+        ));
+        assert!(matches!(
+            process_command("/unsafe attachment confirm"),
+            CommandResult::Unsafe { capability: Some(Capability::Attachments), confirmed: true }
+        ));
+        assert!(matches!(process_command("/unsafe network"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/unsafe tools please"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_dismiss() {
+        assert!(matches!(process_command("/dismiss"), CommandResult::Dismiss(None)));
+        assert!(matches!(process_command("/dismiss all"), CommandResult::Dismiss(None)));
+        assert!(matches!(process_command("/dismiss 2"), CommandResult::Dismiss(Some(2))));
+        assert!(matches!(process_command("/dismiss budget"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_bench() {
+        let bench = |input: &str| match process_command(input) {
+            CommandResult::Bench { runs, prompt } => Some((runs, prompt)),
+            _ => None,
+        };
+        assert_eq!(bench("/bench"), Some((bench::DEFAULT_RUNS, bench::DEFAULT_PROMPT.to_string())));
+        assert_eq!(bench("/bench 10"), Some((10, bench::DEFAULT_PROMPT.to_string())));
+        assert_eq!(bench("/bench 3 say hi"), Some((3, "say hi".to_string())));
+        assert_eq!(bench("/bench say hi"), Some((bench::DEFAULT_RUNS, "say hi".to_string())));
+        assert_eq!(bench("/bench 0"), None);
+        assert_eq!(bench("/bench 500"), None);
+    }
+
+    #[test]
+    fn test_sidebar() {
+        assert!(matches!(process_command("/sidebar"), CommandResult::Sidebar));
+    }
+
+    #[test]
+    fn test_set() {
+        match process_command("/set layout.chat 80") {
+            CommandResult::Set { key, value } => {
+                assert_eq!(key, "layout.chat");
+                assert_eq!(value.as_deref(), Some("80"));
+            }
+            _ => panic!("expected Set"),
+        }
+        assert!(matches!(process_command("/set layout.status"), CommandResult::Set { value: None, .. }));
+        assert!(matches!(process_command("/set"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_tab() {
+        let tab = |input: &str| match process_command(input) {
+            CommandResult::Tab(action) => Some(action),
+            _ => None,
+        };
+        assert_eq!(tab("/tab"), Some(TabAction::List));
+        assert_eq!(tab("/tab close"), Some(TabAction::Close));
+        assert_eq!(tab("/tab 2"), Some(TabAction::Select(2)));
+        assert_eq!(tab("/tab 0"), None);
+        assert_eq!(tab("/tab new"), Some(TabAction::New { manifest: None, model: None }));
+        assert_eq!(
+            tab("/tab new agents/coder.yaml"),
+            Some(TabAction::New { manifest: Some("agents/coder.yaml".into()), model: None })
+        );
+        assert_eq!(
+            tab("/tab new --manifest a.yml --model ollama:llama3.2:3b"),
+            Some(TabAction::New { manifest: Some("a.yml".into()), model: Some("ollama:llama3.2:3b".into()) })
+        );
+        assert_eq!(tab("/tab new sonnet"), Some(TabAction::New { manifest: None, model: Some("sonnet".into()) }));
+
+        let broadcast = |tabs: Vec<usize>, prompt: &str| Some(TabAction::Broadcast { tabs, prompt: prompt.into() });
+        assert_eq!(tab("/broadcast why is the sky blue"), broadcast(vec![], "why is the sky blue"));
+        assert_eq!(tab("/broadcast 3,1,3 why"), broadcast(vec![1, 3], "why"));
+        assert_eq!(tab("/broadcast 42"), broadcast(vec![], "42"));
+        assert_eq!(tab("/broadcast"), None);
+        assert_eq!(tab("/broadcast 0,2 why"), None);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert!(matches!(process_command("/layout"), CommandResult::Layout(None)));
+        assert!(matches!(
+            process_command("/layout horizontal"),
+            CommandResult::Layout(Some(LayoutMode::Horizontal))
+        ));
+        assert!(matches!(process_command("/layout sideways"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_theme() {
+        assert!(matches!(process_command("/theme"), CommandResult::Theme(None)));
+        assert!(matches!(process_command("/theme gruvbox"), CommandResult::Theme(Some(name)) if name == "gruvbox"));
+    }
+
+    #[test]
+    fn test_zen() {
+        assert!(matches!(process_command("/zen"), CommandResult::Zen));
+    }
+
+    #[test]
+    fn test_state() {
+        assert!(matches!(process_command("/state"), CommandResult::State));
+    }
+
+    #[test]
+    fn test_tools() {
+        assert!(matches!(process_command("/tools"), CommandResult::Tools));
+        assert!(matches!(process_command("/context"), CommandResult::Context));
+    }
+
+    #[test]
+    fn test_memory() {
+        assert!(matches!(process_command("/memory"), CommandResult::Memory(MemoryAction::List)));
+        assert!(matches!(process_command("/memory list"), CommandResult::Memory(MemoryAction::List)));
+        assert!(matches!(process_command("/memory clear"), CommandResult::Memory(MemoryAction::Clear)));
+        assert!(matches!(process_command("/memory pin 3"), CommandResult::Memory(MemoryAction::Pin(3))));
+        assert!(matches!(process_command("/memory pin three"), CommandResult::Usage(_)));
+        assert!(matches!(process_command("/memory forget"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_autonomy() {
+        assert!(matches!(process_command("/autonomy"), CommandResult::Autonomy(None)));
+        match process_command("/autonomy Full") {
+            CommandResult::Autonomy(Some(level)) => assert_eq!(level, "full"),
+            _ => panic!("expected Autonomy"),
+        }
+    }
+
+    #[test]
+    fn test_workflow() {
+        assert!(matches!(process_command("/workflow"), CommandResult::Workflow(None)));
+        match process_command("/workflow workflows/review.yaml") {
+            CommandResult::Workflow(Some(path)) => assert_eq!(path, "workflows/review.yaml"),
+            _ => panic!("expected Workflow"),
+        }
+    }
+
+    #[test]
+    fn test_alias() {
+        assert!(matches!(process_command("/alias"), CommandResult::Alias(AliasAction::List)));
+        assert!(matches!(process_command("/alias list"), CommandResult::Alias(AliasAction::List)));
+        match process_command("/alias review \"Please review the following diff:\"") {
+            CommandResult::Alias(AliasAction::Set { name, text }) => {
+                assert_eq!(name, "review");
+                assert_eq!(text, "Please review the following diff:");
+            }
+            _ => panic!("expected Alias"),
+        }
+        assert!(matches!(process_command("/alias remove /review"), CommandResult::Alias(AliasAction::Remove(n)) if n == "review"));
+        assert!(matches!(process_command("/alias review"), CommandResult::Usage(_)));
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases = BTreeMap::from([("review".to_string(), "Please review:".to_string())]);
+        assert_eq!(expand_alias("/review", &aliases).as_deref(), Some("Please review:"));
+        assert_eq!(expand_alias("/review  the diff ", &aliases).as_deref(), Some("Please review: the diff"));
+        assert_eq!(expand_alias("/reviewer", &aliases), None);
+        assert_eq!(expand_alias("review", &aliases), None);
+        assert!(is_builtin("clear") && is_builtin("?"));
+        assert!(!is_builtin("review"));
+        for command in COMMANDS {
+            assert!(!matches!(process_command(command), CommandResult::NotACommand));
+        }
+    }
+
+    #[test]
+    fn test_completions() {
+        let names: Vec<String> = ["/save", "/sidebar", "/set", "/review"].iter().map(|s| s.to_string()).collect();
+        let arguments = |command: &str| match command {
+            "/theme" => vec!["dark".to_string(), "dracula".to_string(), "light".to_string()],
+            _ => Vec::new(),
+        };
+        assert_eq!(completions("/s", &names, arguments), vec!["/save", "/set", "/sidebar"]);
+        assert_eq!(completions("/theme d", &names, arguments), vec!["/theme dark", "/theme dracula"]);
+        assert_eq!(completions("/theme ", &names, arguments).len(), 3);
+        assert!(completions("/theme dark x", &names, arguments).is_empty());
+        assert!(completions("s", &names, arguments).is_empty());
+        assert_eq!(common_prefix(&completions("/theme d", &names, arguments)), "/theme d");
+        assert_eq!(common_prefix(&["/sidebar".into(), "/side".into()]), "/side");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_custom_commands() {
+        let command = |run: Option<&str>, prompt: Option<&str>| CustomCommand {
+            run: run.map(str::to_string),
+            prompt: prompt.map(str::to_string),
+            description: None,
+        };
+        let custom = CustomCommands::from([
+            ("check-tests".to_string(), command(Some("cargo test"), None)),
+            ("check-fix".to_string(), command(Some("cargo test {args}"), Some("Fix {args}:\n{output}"))),
+            ("check-explain".to_string(), command(None, Some("Explain"))),
+        ]);
+        let process_command = |input: &str| process_command_with(input, &custom);
+        assert!(help(&custom).ends_with("\nCustom: /check-explain /check-fix /check-tests"));
+        assert_eq!(help(&CustomCommands::new()), HELP);
+        match process_command("/check-tests --lib") {
+            CommandResult::Custom { run, prompt } => {
+                assert_eq!(run.as_deref(), Some("cargo test --lib"));
+                assert_eq!(prompt, "Output of `cargo test --lib`:\n```\n{output}\n```");
+            }
+            _ => panic!("expected Custom"),
+        }
+        match process_command("/check-fix app") {
+            CommandResult::Custom { run, prompt } => {
+                assert_eq!(run.as_deref(), Some("cargo test app"));
+                assert_eq!(prompt, "Fix app:\n{output}");
+            }
+            _ => panic!("expected Custom"),
+        }
+        match process_command("/check-explain main.rs") {
+            CommandResult::Custom { run, prompt } => {
+                assert_eq!(run, None);
+                assert_eq!(prompt, "Explain main.rs");
+            }
+            _ => panic!("expected Custom"),
+        }
+        // Arguments reach the snippet as words, never as shell syntax
+        match process_command("/check-fix $(rm -rf ~); it's") {
+            CommandResult::Custom { run, prompt } => {
+                assert_eq!(run.as_deref(), Some(r"cargo test '$(rm' -rf '~);' 'it'\''s'"));
+                assert_eq!(prompt, "Fix $(rm -rf ~); it's:\n{output}");
+            }
+            _ => panic!("expected Custom"),
+        }
+        assert!(matches!(process_command("/check-other"), CommandResult::Message(_)));
+        assert!(matches!(super::process_command("/check-tests"), CommandResult::Message(_)));
+    }
+
+    #[test]
+    fn test_pager() {
+        assert!(matches!(process_command("/pager"), CommandResult::Pager));
+    }
+
+    #[test]
+    fn test_image() {
+        match process_command("/image shots/login page.png") {
+            CommandResult::Image(path) => assert_eq!(path, "shots/login page.png"),
+            _ => panic!("expected Image"),
+        }
+        assert!(matches!(process_command("/image"), CommandResult::Usage(_)));
     }
 }
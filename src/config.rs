@@ -0,0 +1,268 @@
+//! User settings for the TUI, read from `~/.config/neocognos/tui.toml`.
+//!
+//! Every field has a default, so a missing file or a partial one is fine:
+//!
+//! ```toml
+//! [chat]
+//! stream_scroll = "top"   # follow | top | none
+//! images = "sixel"        # kitty | iterm2 | sixel | none (detected when unset)
+//! ascii_icons = true      # plain ASCII instead of emoji, for fonts without them
+//!
+//! [budget]
+//! warn = 75               # % of the manifest token_budget before the bar turns yellow
+//! alert = 90              # … and red
+//!
+//! [layout]
+//! mode = "horizontal"     # vertical (sidebar on the right) | horizontal (under the chat)
+//! chat = 70               # % of the width (height when horizontal) for the chat
+//! status = 40             # % of the Status tab for the status box over tool stats
+//!
+//! [aliases]
+//! review = "Please review the following diff:"   # `/review <text>` sends both
+//!
+//! [commands.test]
+//! run = "cargo test {args}"                      # shell snippet, run by `/test`
+//! prompt = "Fix the failing tests:\n{output}"     # what the agent gets (default: the output)
+//! description = "run the tests and fix failures"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::commands;
+use crate::graphics::ImageProtocol;
+use crate::theme::Theme;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    pub chat: ChatConfig,
+    pub budget: BudgetConfig,
+    pub layout: LayoutConfig,
+    /// Command aliases: `/name` expands to the text before it is sent.
+    pub aliases: BTreeMap<String, String>,
+    /// User-defined slash commands, by name without the slash.
+    pub commands: BTreeMap<String, CustomCommand>,
+}
+
+/// A `[commands.<name>]` entry: a shell snippet whose output goes to the agent,
+/// a prompt template, or both. `{args}` is what followed the command and
+/// `{output}` the snippet's output.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomCommand {
+    pub run: Option<String>,
+    pub prompt: Option<String>,
+    /// Shown beside the command in `/help`.
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// How the chat pane scrolls while an answer is streaming in.
+    pub stream_scroll: StreamScroll,
+    /// Graphics protocol for inline image previews; detected from the terminal when unset.
+    pub images: Option<ImageProtocol>,
+    /// Draw ASCII in place of emoji and symbols, in the sidebar and trace as well.
+    pub ascii_icons: bool,
+}
+
+/// Where the token-budget bar changes color, in percent of the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BudgetConfig {
+    pub warn: u8,
+    pub alert: u8,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self { warn: 75, alert: 90 }
+    }
+}
+
+/// Starting pane proportions, in percent; Alt+Arrow and `/set layout.*` adjust
+/// them for the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub mode: LayoutMode,
+    pub chat: u16,
+    pub status: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self { mode: LayoutMode::Vertical, chat: 75, status: 35 }
+    }
+}
+
+/// Where the sidebar goes relative to the chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    /// Sidebar as a column right of the chat.
+    #[default]
+    Vertical,
+    /// Sidebar as a strip under the chat, for wide but short terminals.
+    Horizontal,
+}
+
+impl FromStr for LayoutMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "vertical" => Ok(Self::Vertical),
+            "horizontal" => Ok(Self::Horizontal),
+            other => anyhow::bail!("unknown layout '{other}' (expected vertical or horizontal)"),
+        }
+    }
+}
+
+/// Chat scrolling while an assistant answer streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamScroll {
+    /// Keep the newest text in view.
+    #[default]
+    Follow,
+    /// Show the start of the answer and stay there until it's done.
+    Top,
+    /// Leave the view where it is.
+    None,
+}
+
+impl FromStr for StreamScroll {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "follow" => Ok(Self::Follow),
+            "top" => Ok(Self::Top),
+            "none" => Ok(Self::None),
+            other => anyhow::bail!("unknown stream scroll mode '{other}' (expected follow, top or none)"),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/neocognos`, falling back to `~/.config/neocognos`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("neocognos"))
+}
+
+pub fn parse(text: &str) -> Result<TuiConfig> {
+    let config: TuiConfig = toml::from_str(text)?;
+    let names = config.aliases.keys().chain(config.commands.keys());
+    if let Some(name) = names.into_iter().find(|name| commands::is_builtin(name)) {
+        anyhow::bail!("'{name}' would hide the built-in /{name} command");
+    }
+    if let Some(name) = config.commands.keys().find(|name| config.aliases.contains_key(*name)) {
+        anyhow::bail!("'{name}' is both an alias and a command");
+    }
+    if let Some((name, _)) = config.commands.iter().find(|(_, c)| c.run.is_none() && c.prompt.is_none()) {
+        anyhow::bail!("command '{name}' needs a `run` snippet or a `prompt`");
+    }
+    let BudgetConfig { warn, alert } = config.budget;
+    if alert > 100 || warn > alert {
+        anyhow::bail!("budget thresholds need warn <= alert <= 100 (got warn = {warn}, alert = {alert})");
+    }
+    Ok(config)
+}
+
+/// Load `tui.toml` from the config directory. A missing file yields the defaults.
+pub fn load() -> Result<TuiConfig> {
+    let Some(path) = config_dir().map(|dir| dir.join("tui.toml")) else {
+        return Ok(TuiConfig::default());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => parse(&text).with_context(|| format!("Invalid config {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(TuiConfig::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Load colors from `theme.toml` in the config directory, if there is one.
+pub fn load_theme() -> Result<Option<Theme>> {
+    let Some(path) = config_dir().map(|dir| dir.join("theme.toml")) else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Theme::parse(&text).map(Some).with_context(|| format!("Invalid theme {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(parse("").unwrap().chat.stream_scroll, StreamScroll::Follow);
+        let config = parse("[chat]\nstream_scroll = \"top\"\n").unwrap();
+        assert_eq!(config.chat.stream_scroll, StreamScroll::Top);
+        assert!(parse("[chat]\nstream_scroll = \"sideways\"\n").is_err());
+        assert!(parse("[chat]\nstream_scrol = \"top\"\n").is_err());
+        assert_eq!(parse("").unwrap().chat.images, None);
+        let config = parse("[chat]\nimages = \"iterm2\"\n").unwrap();
+        assert_eq!(config.chat.images, Some(ImageProtocol::Iterm2));
+        assert!(!config.chat.ascii_icons);
+        assert!(parse("[chat]\nascii_icons = true\n").unwrap().chat.ascii_icons);
+    }
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse("").unwrap().budget, BudgetConfig { warn: 75, alert: 90 });
+        let config = parse("[budget]\nwarn = 50\n").unwrap();
+        assert_eq!(config.budget, BudgetConfig { warn: 50, alert: 90 });
+        assert!(parse("[budget]\nwarn = 500\n").is_err());
+        assert!(parse("[budget]\nwarn = 95\n").is_err());
+        assert!(parse("[budget]\nwarn = 80\nalert = 120\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        assert_eq!(parse("").unwrap().layout, LayoutConfig::default());
+        let config = parse("[layout]\nchat = 60\n").unwrap();
+        assert_eq!(config.layout, LayoutConfig { chat: 60, ..LayoutConfig::default() });
+        let config = parse("[layout]\nmode = \"horizontal\"\n").unwrap();
+        assert_eq!(config.layout.mode, LayoutMode::Horizontal);
+        assert!(parse("[layout]\nsidebar = 40\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        assert!(parse("").unwrap().aliases.is_empty());
+        let config = parse("[aliases]\nreview = \"Please review:\"\n").unwrap();
+        assert_eq!(config.aliases["review"], "Please review:");
+        assert!(parse("[aliases]\nclear = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let config = parse("[commands.test]\nrun = \"cargo test\"\ndescription = \"run the tests\"\n").unwrap();
+        assert_eq!(config.commands["test"].run.as_deref(), Some("cargo test"));
+        assert_eq!(config.commands["test"].prompt, None);
+        assert!(parse("[commands.test]\ndescription = \"nothing to do\"\n").is_err());
+        assert!(parse("[commands.help]\nprompt = \"x\"\n").is_err());
+        assert!(parse("[commands.t]\nprompt = \"x\"\n[aliases]\nt = \"y\"\n").is_err());
+        assert!(parse("[commands.t]\nprompt = \"x\"\nshell = \"y\"\n").is_err());
+    }
+
+    #[test]
+    fn test_stream_scroll_from_str() {
+        assert_eq!("none".parse::<StreamScroll>().unwrap(), StreamScroll::None);
+        assert!("tail".parse::<StreamScroll>().is_err());
+    }
+}
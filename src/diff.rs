@@ -0,0 +1,71 @@
+//! Unified diffs of files rewritten by the agent.
+
+use similar::TextDiff;
+
+/// Unified diff from `before` to `after` with 3 lines of context, or `None` if unchanged.
+pub fn unified(path: &str, before: &str, after: &str) -> Option<String> {
+    if before == after {
+        return None;
+    }
+    let diff = TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string();
+    Some(diff)
+}
+
+/// Number of leading file-header lines (`---`/`+++`) before the first hunk.
+pub fn header_len(diff: &str) -> usize {
+    diff.lines().position(|line| line.starts_with("@@")).unwrap_or(0)
+}
+
+/// The hunks of a unified diff, without its leading file header. Lines inside
+/// the hunks that start with `++` or `--` are content, not headers.
+pub fn hunks(diff: &str) -> impl Iterator<Item = &str> {
+    diff.lines().skip(header_len(diff))
+}
+
+/// Count added and removed lines in a unified diff (file headers excluded).
+pub fn stats(diff: &str) -> (usize, usize) {
+    hunks(diff).fold((0, 0), |(added, removed), line| {
+        if line.starts_with('+') {
+            (added + 1, removed)
+        } else if line.starts_with('-') {
+            (added, removed + 1)
+        } else {
+            (added, removed)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let before = "one\ntwo\nthree\n";
+        let after = "one\n2\nthree\nfour\n";
+        let diff = unified("src/n.txt", before, after).unwrap();
+        assert!(diff.starts_with("--- a/src/n.txt\n+++ b/src/n.txt\n@@"));
+        assert!(diff.contains("\n-two\n+2\n"));
+        assert!(diff.contains("\n+four\n"));
+        assert_eq!(stats(&diff), (2, 1));
+    }
+
+    #[test]
+    fn test_lines_that_look_like_headers() {
+        let before = "a\n-- comment\nb\n";
+        let after = "a\n++counter;\nb\n";
+        let diff = unified("x.c", before, after).unwrap();
+        assert_eq!(header_len(&diff), 2);
+        assert_eq!(hunks(&diff).filter(|l| !l.starts_with("@@")).count(), 4);
+        assert_eq!(stats(&diff), (1, 1));
+    }
+
+    #[test]
+    fn test_unchanged() {
+        assert!(unified("a", "same\n", "same\n").is_none());
+    }
+}
@@ -0,0 +1,99 @@
+//! Explicit dotenv loading for `--env-file`.
+
+use anyhow::Result;
+
+use crate::ui::icons;
+
+/// Parse dotenv-style `KEY=VALUE` lines. Blank lines, `#` comments and an
+/// optional `export ` prefix are accepted; surrounding quotes are stripped.
+pub fn parse(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+            let value = value.trim().trim_matches('"').trim_matches('\'');
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Load a dotenv file into the process environment (inherited by providers and tools).
+/// Returns the loaded pairs.
+pub fn load(path: &str) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read env file {path}: {e}"))?;
+    let pairs = parse(&content);
+    for (key, value) in &pairs {
+        std::env::set_var(key, value);
+    }
+    Ok(pairs)
+}
+
+/// Mask a secret for display, keeping just enough to recognise it: at most
+/// four characters of the value are shown.
+pub fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len().min(8));
+    }
+    let head: String = chars[..2].iter().collect();
+    let tail: String = chars[chars.len() - 2..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+/// One-line summary of loaded keys with masked values.
+pub fn summary(path: &str, pairs: &[(String, String)]) -> String {
+    let keys: Vec<String> = pairs
+        .iter()
+        .map(|(k, v)| format!("{k}={}", mask(v)))
+        .collect();
+    let noun = if pairs.len() == 1 { "key" } else { "keys" };
+    format!("{} Loaded {} {noun} from {path}: {}", icons::current().key, pairs.len(), keys.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let pairs = parse(
+            "# comment\n\nANTHROPIC_API_KEY=\"sk-ant-123\"\nexport OLLAMA_HOST='http://x:1'\n=nokey\nnovalue\nEMPTY=\n",
+        );
+        assert_eq!(
+            pairs,
+            vec![
+                ("ANTHROPIC_API_KEY".to_string(), "sk-ant-123".to_string()),
+                ("OLLAMA_HOST".to_string(), "http://x:1".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(mask(""), "");
+        assert_eq!(mask("short"), "*****");
+        assert_eq!(mask("sk-ant-api03-abcdef"), "sk…ef");
+    }
+
+    #[test]
+    fn test_summary() {
+        let pairs = vec![("A".to_string(), "sk-ant-api03-abcdef".to_string())];
+        assert_eq!(summary(".env", &pairs), "🔑 Loaded 1 key from .env: A=sk…ef");
+        let pairs = vec![
+            ("A".to_string(), "sk-ant-api03-abcdef".to_string()),
+            ("B".to_string(), "x".to_string()),
+        ];
+        assert_eq!(summary(".env", &pairs), "🔑 Loaded 2 keys from .env: A=sk…ef, B=*");
+    }
+}
@@ -0,0 +1,557 @@
+//! Transcript export — serializes the chat into shareable documents.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde_json::{json, Value};
+
+use crate::app::{self, App, ChatMessage, TraceEntry};
+use crate::ui::{ansi, trace_view};
+
+/// Default file name for an export, timestamped so repeated exports don't collide.
+pub fn default_path(extension: &str) -> String {
+    format!("neocognos-transcript-{}.{extension}", Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Render the chat as a readable Markdown document.
+pub fn to_markdown(app: &App) -> String {
+    let mut out = format!(
+        "# Neocognos session — {} ({})\n\n_Workflow: {} · exported {}_\n",
+        app.status.agent_name,
+        app.status.model,
+        app.status.workflow,
+        Local::now().format("%Y-%m-%d %H:%M"),
+    );
+
+    for (i, msg) in app.messages.iter().enumerate() {
+        let time = app
+            .message_meta
+            .get(i)
+            .map(|m| m.timestamp.format("%H:%M:%S").to_string())
+            .unwrap_or_default();
+        out.push('\n');
+        // An explicit anchor keeps `#msg-<id>` links working in any Markdown renderer
+        let id = app.message_id(i).unwrap_or(i + 1);
+        out.push_str(&format!("<a id=\"msg-{id}\"></a>\n\n"));
+        match msg {
+            ChatMessage::User(text) => {
+                out.push_str(&format!("## User · {time} · [#msg-{id}](#msg-{id})\n\n{text}\n"));
+            }
+            ChatMessage::Assistant(text) => {
+                out.push_str(&format!("## Assistant · {time} · [#msg-{id}](#msg-{id})\n\n{text}\n"));
+            }
+            ChatMessage::Narration(text) => {
+                out.push_str(&quote(&format!("💬 {text}")));
+            }
+            ChatMessage::ToolCall { name, args, .. } => {
+                let pretty = serde_json::from_str::<serde_json::Value>(args)
+                    .ok()
+                    .and_then(|v| serde_json::to_string_pretty(&v).ok())
+                    .unwrap_or_else(|| args.clone());
+                out.push_str(&format!("**Tool call: `{name}`** · {time}\n\n```json\n{pretty}\n```\n"));
+            }
+            ChatMessage::ToolResult { name, success, duration_ms, output, error } => {
+                let status = if *success { "✓ completed" } else { "✗ failed" };
+                out.push_str(&quote(&format!("`{name}` {status} in {duration_ms}ms")));
+                if let Some(error) = error {
+                    out.push_str(&quote(&format!("**Error:** {error}")));
+                }
+                if !output.is_empty() {
+                    out.push_str(&format!("\n```\n{}\n```\n", output.trim_end()));
+                }
+            }
+            ChatMessage::Diff { path, diff } => {
+                out.push_str(&format!("**Changed `{path}`** · {time}\n\n```diff\n{}\n```\n", diff.trim_end()));
+            }
+            ChatMessage::Shell { command, output, exit, killed } => {
+                let status = app::shell_status(*exit, *killed);
+                out.push_str(&format!("**`$ {command}`** ({status}) · {time}\n\n```\n{}\n```\n", ansi::strip(output).trim_end()));
+            }
+            ChatMessage::Error(text) => {
+                out.push_str(&quote(&format!("**Error** · {time}: {text}")));
+            }
+            ChatMessage::System(text) => {
+                out.push_str(&quote(text));
+            }
+        }
+    }
+    out
+}
+
+/// Render the chat as a standalone HTML page. Every message is a `<section
+/// id="msg-<id>">` with a permalink, so `transcript.html#msg-42` points at it.
+pub fn to_html(app: &App) -> String {
+    let title = format!("Neocognos session — {} ({})", app.status.agent_name, app.status.model);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{}</h1>\n<p class=\"meta\">Workflow: {} · exported {}</p>\n",
+        escape_html(&title),
+        escape_html(&title),
+        escape_html(&app.status.workflow),
+        Local::now().format("%Y-%m-%d %H:%M"),
+    );
+
+    for (i, msg) in app.messages.iter().enumerate() {
+        let id = app.message_id(i).unwrap_or(i + 1);
+        let time = app
+            .message_meta
+            .get(i)
+            .map(|m| m.timestamp.format("%H:%M:%S").to_string())
+            .unwrap_or_default();
+        let (class, label, body) = match msg {
+            ChatMessage::User(text) => ("user", "User".to_string(), text.clone()),
+            ChatMessage::Assistant(text) => ("assistant", "Assistant".to_string(), text.clone()),
+            ChatMessage::Narration(text) => ("narration", "Narration".to_string(), text.clone()),
+            ChatMessage::ToolCall { name, args, .. } => {
+                let pretty = serde_json::from_str::<Value>(args)
+                    .ok()
+                    .and_then(|v| serde_json::to_string_pretty(&v).ok())
+                    .unwrap_or_else(|| args.clone());
+                ("tool", format!("Tool call: {name}"), pretty)
+            }
+            ChatMessage::ToolResult { name, success, duration_ms, output, error } => {
+                let status = if *success { "✓ completed" } else { "✗ failed" };
+                let mut body = format!("{status} in {duration_ms}ms");
+                if let Some(error) = error {
+                    body.push_str(&format!(": {error}"));
+                }
+                if !output.is_empty() {
+                    body.push_str(&format!("\n\n{}", output.trim_end()));
+                }
+                ("tool", format!("Tool result: {name}"), body)
+            }
+            ChatMessage::Diff { path, diff } => ("tool", format!("Changed {path}"), diff.clone()),
+            ChatMessage::Shell { command, output, exit, killed } => {
+                ("tool", format!("$ {command} ({})", app::shell_status(*exit, *killed)), ansi::strip(output))
+            }
+            ChatMessage::Error(text) => ("error", "Error".to_string(), text.clone()),
+            ChatMessage::System(text) => ("system", "System".to_string(), text.clone()),
+        };
+        out.push_str(&format!(
+            "<section id=\"msg-{id}\" class=\"{class}\">\n<header><a href=\"#msg-{id}\">#{id}</a> {} · {time}</header>\n<pre>{}</pre>\n</section>\n",
+            escape_html(&label),
+            escape_html(&body),
+        ));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; max-width: 60rem; margin: 2rem auto; }
+section { border-left: 3px solid #ccc; padding: 0 1rem; margin: 1rem 0; }
+section:target { background: #fff8d0; }
+section.user { border-color: #3a7bd5; }
+section.assistant { border-color: #2e9e5b; }
+section.error { border-color: #d0443a; }
+header { color: #666; font-size: 0.85rem; }
+header a { color: inherit; text-decoration: none; }
+pre { white-space: pre-wrap; font-family: inherit; }
+section.tool pre { font-family: monospace; }
+.meta { color: #666; }
+";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Serialize the session as a JSON document for downstream tooling (evals, dashboards).
+///
+/// Every chat message, trace entry and LLM call is included with its RFC 3339
+/// timestamp; `version` is bumped on breaking schema changes.
+pub fn to_json(app: &App) -> Value {
+    let messages: Vec<Value> = app
+        .messages
+        .iter()
+        .enumerate()
+        .map(|(i, msg)| {
+            let mut value = message_json(msg);
+            value["id"] = json!(app.message_id(i));
+            value["timestamp"] = json!(app.message_meta.get(i).map(|m| m.timestamp.to_rfc3339()));
+            value
+        })
+        .collect();
+    json!({
+        "version": 1,
+        "exported_at": Local::now().to_rfc3339(),
+        "agent": app.status.agent_name,
+        "model": app.status.model,
+        "workflow": app.status.workflow,
+        "totals": {
+            "tokens": app.status.total_tokens,
+            "turns": app.status.total_turns,
+            "cost_usd": app.status.cost,
+        },
+        "messages": messages,
+        "trace": trace_to_json(app),
+        "llm_calls": llm_calls_to_json(app),
+    })
+}
+
+/// Every LLM call with its RFC 3339 timestamp.
+pub fn llm_calls_to_json(app: &App) -> Value {
+    app.llm_calls
+        .iter()
+        .map(|call| {
+            json!({
+                "timestamp": call.timestamp.to_rfc3339(),
+                "model": call.model,
+                "prompt_tokens": call.prompt_tokens,
+                "completion_tokens": call.completion_tokens,
+                "duration_ms": call.duration_ms,
+            })
+        })
+        .collect()
+}
+
+/// Usage totals, cost by model and per-tool statistics.
+pub fn stats_to_json(app: &App) -> Value {
+    let per_model: Vec<Value> = app
+        .status
+        .per_model
+        .iter()
+        .map(|entry| json!({ "model": entry.model, "tokens": entry.tokens, "cost_usd": entry.cost }))
+        .collect();
+    let tools: Vec<Value> = app
+        .tool_stats_by_calls()
+        .into_iter()
+        .map(|(name, stats)| {
+            json!({ "name": name, "calls": stats.calls, "failures": stats.failures, "total_ms": stats.total_ms })
+        })
+        .collect();
+    json!({
+        "agent": app.status.agent_name,
+        "model": app.status.model,
+        "workflow": app.status.workflow,
+        "duration_secs": app.started.elapsed().as_secs(),
+        "tokens": app.status.total_tokens,
+        "turns": app.status.total_turns,
+        "cost_usd": app.status.cost,
+        "per_model": per_model,
+        "tools": tools,
+        "changed_files": app.changed_files,
+    })
+}
+
+/// The LLM call log as aligned plain text.
+pub fn llm_calls_to_text(app: &App) -> String {
+    let mut out = String::new();
+    for call in &app.llm_calls {
+        out.push_str(&format!(
+            "{}  {:<30} {:>7} → {:<6} {}\n",
+            call.timestamp.format("%H:%M:%S"),
+            call.model,
+            call.prompt_tokens,
+            call.completion_tokens,
+            app::format_duration(call.duration_ms),
+        ));
+    }
+    out
+}
+
+/// Usage and tool statistics as plain text.
+pub fn stats_to_text(app: &App) -> String {
+    let status = &app.status;
+    let mut out = format!(
+        "Agent: {} ({})\nWorkflow: {}\nDuration: {}\nTurns: {}\nTokens: {}\nEstimated cost: {}\n",
+        status.agent_name,
+        status.model,
+        status.workflow,
+        app::format_elapsed(app.started.elapsed().as_secs()),
+        status.total_turns,
+        status.tokens_display(),
+        status.cost_display(),
+    );
+    if !status.per_model.is_empty() {
+        out.push_str("\nBy model:\n");
+        for entry in &status.per_model {
+            out.push_str(&format!("  {}: {} tokens, ~${:.4}\n", entry.model, entry.tokens, entry.cost));
+        }
+    }
+    let tools = app.tool_stats_by_calls();
+    if !tools.is_empty() {
+        out.push_str("\nTools:\n");
+        for (name, stats) in tools {
+            out.push_str(&format!(
+                "  {name}: {} calls, {} failed ({}%), {}\n",
+                stats.calls,
+                stats.failures,
+                stats.failure_percent(),
+                app::format_duration(stats.total_ms),
+            ));
+        }
+    }
+    if !app.changed_files.is_empty() {
+        out.push_str("\nChanged files:\n");
+        for path in &app.changed_files {
+            out.push_str(&format!("  {path}\n"));
+        }
+    }
+    out
+}
+
+/// Files of a `/export <dir>` bundle: each part of the session readable and as JSON.
+pub const BUNDLE_FILES: &[&str] = &[
+    "transcript.md",
+    "transcript.json",
+    "trace.txt",
+    "trace.json",
+    "llm_calls.txt",
+    "llm_calls.json",
+    "stats.txt",
+    "stats.json",
+];
+
+/// Write a bundle for sharing a reproducible report into `dir`, creating it if needed.
+pub fn write_bundle(app: &App, dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Can't create {}", dir.display()))?;
+    let pretty = |value: Value| serde_json::to_string_pretty(&value).unwrap_or_default();
+    let trace: Vec<String> = trace_view::rows(&app.trace_log, &app.trace_times, None, &app.trace_kinds, 100)
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    let contents = [
+        to_markdown(app),
+        pretty(to_json(app)),
+        trace.join("\n") + "\n",
+        pretty(trace_to_json(app)),
+        llm_calls_to_text(app),
+        pretty(llm_calls_to_json(app)),
+        stats_to_text(app),
+        pretty(stats_to_json(app)),
+    ];
+    for (name, content) in BUNDLE_FILES.iter().zip(contents) {
+        let path = dir.join(name);
+        std::fs::write(&path, content).with_context(|| format!("Can't write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The workflow trace alone, as far as the `/trace filter` lets it through,
+/// each entry with its RFC 3339 timestamp.
+pub fn trace_to_json(app: &App) -> Value {
+    app.trace_log
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| app.shows_trace(entry))
+        .map(|(i, entry)| {
+            let mut value = trace_json(entry);
+            value["timestamp"] = json!(app.trace_times.get(i).map(|t| t.to_rfc3339()));
+            value
+        })
+        .collect()
+}
+
+fn message_json(msg: &ChatMessage) -> Value {
+    match msg {
+        ChatMessage::User(text) => json!({ "type": "user", "text": text }),
+        ChatMessage::Assistant(text) => json!({ "type": "assistant", "text": text }),
+        ChatMessage::Narration(text) => json!({ "type": "narration", "text": text }),
+        ChatMessage::Error(text) => json!({ "type": "error", "text": text }),
+        ChatMessage::System(text) => json!({ "type": "system", "text": text }),
+        ChatMessage::Diff { path, diff } => json!({ "type": "diff", "path": path, "diff": diff }),
+        ChatMessage::Shell { command, output, exit, killed } => {
+            json!({ "type": "shell", "command": command, "output": ansi::strip(output), "exit": exit, "killed": killed })
+        }
+        ChatMessage::ToolCall { name, args, .. } => {
+            // Keep structured args structured; fall back to the raw string
+            let args = serde_json::from_str::<Value>(args).unwrap_or_else(|_| json!(args));
+            json!({ "type": "tool_call", "name": name, "args": args })
+        }
+        ChatMessage::ToolResult { name, success, duration_ms, output, error } => json!({
+            "type": "tool_result",
+            "name": name,
+            "success": success,
+            "duration_ms": duration_ms,
+            "output": output,
+            "error": error,
+        }),
+    }
+}
+
+fn trace_json(entry: &TraceEntry) -> Value {
+    match entry {
+        TraceEntry::StageStart { id, kind } => json!({ "type": "stage_start", "id": id, "kind": kind }),
+        TraceEntry::StageEnd { id, duration_ms, skipped } => {
+            json!({ "type": "stage_end", "id": id, "duration_ms": duration_ms, "skipped": skipped })
+        }
+        TraceEntry::StageFailed { id, error } => json!({ "type": "stage_failed", "id": id, "error": error }),
+        TraceEntry::LlmCall { model, ctx_tokens, out_tokens, duration_ms } => json!({
+            "type": "llm_call",
+            "model": model,
+            "ctx_tokens": ctx_tokens,
+            "out_tokens": out_tokens,
+            "duration_ms": duration_ms,
+        }),
+        TraceEntry::ToolCall { name, args } => json!({ "type": "tool_call", "name": name, "args": args }),
+        TraceEntry::ToolResult { name, success, duration_ms, error } => json!({
+            "type": "tool_result",
+            "name": name,
+            "success": success,
+            "duration_ms": duration_ms,
+            "error": error,
+        }),
+        TraceEntry::Narration(text) => json!({ "type": "narration", "text": text }),
+        TraceEntry::Artifact { stage_id, name } => json!({ "type": "artifact", "stage_id": stage_id, "name": name }),
+    }
+}
+
+/// Prefix every line with a Markdown blockquote marker.
+fn quote(text: &str) -> String {
+    let mut out = String::new();
+    for line in text.lines() {
+        out.push_str("> ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{LlmCallEntry, TraceKind};
+    use crate::commands::TraceAction;
+
+    #[test]
+    fn test_markdown_export() {
+        let mut app = App::new("coder", "sonnet", "default");
+        app.add_message(ChatMessage::User("list files".into()));
+        app.add_message(ChatMessage::ToolCall {
+            name: "exec".into(),
+            args_short: "{}".into(),
+            args: r#"{"command":"ls"}"#.into(),
+        });
+        app.add_message(ChatMessage::ToolResult {
+            name: "exec".into(),
+            success: true,
+            duration_ms: 12,
+            output: "Cargo.toml\nsrc".into(),
+            error: None,
+        });
+        app.add_message(ChatMessage::Assistant("Here they are.".into()));
+        app.add_message(ChatMessage::System("line one\nline two".into()));
+        app.add_message(ChatMessage::ToolResult {
+            name: "read_file".into(),
+            success: false,
+            duration_ms: 2,
+            output: String::new(),
+            error: Some("No such file: notes.md".into()),
+        });
+
+        let md = to_markdown(&app);
+        assert!(md.starts_with("# Neocognos session — coder (sonnet)"));
+        assert!(md.contains("## User · "));
+        assert!(md.contains("<a id=\"msg-1\"></a>"));
+        assert!(md.contains("[#msg-4](#msg-4)"));
+        assert!(md.contains("\n\nlist files\n"));
+        assert!(md.contains("**Tool call: `exec`**"));
+        assert!(md.contains("```json\n{\n  \"command\": \"ls\"\n}\n```"));
+        assert!(md.contains("> `exec` ✓ completed in 12ms"));
+        assert!(md.contains("```\nCargo.toml\nsrc\n```"));
+        assert!(md.contains("> `read_file` ✗ failed in 2ms\n> **Error:** No such file: notes.md"));
+        assert!(md.contains("## Assistant · "));
+        assert!(md.contains("> line one\n> line two\n"));
+    }
+
+    #[test]
+    fn test_diff_export() {
+        let mut app = App::new("coder", "sonnet", "default");
+        let diff = "--- a/x.txt\n+++ b/x.txt\n@@ -1 +1 @@\n-old\n+new\n".to_string();
+        app.add_message(ChatMessage::Diff { path: "x.txt".into(), diff });
+
+        let md = to_markdown(&app);
+        assert!(md.contains("**Changed `x.txt`**"));
+        assert!(md.contains("```diff\n--- a/x.txt\n+++ b/x.txt\n@@ -1 +1 @@\n-old\n+new\n```"));
+        let json = to_json(&app);
+        assert_eq!(json["messages"][0]["type"], "diff");
+        assert_eq!(json["messages"][0]["path"], "x.txt");
+    }
+
+    #[test]
+    fn test_html_export() {
+        let mut app = App::new("coder", "sonnet", "default");
+        app.add_message(ChatMessage::User("is 1 < 2 & \"x\"?".into()));
+        app.add_message(ChatMessage::Assistant("yes".into()));
+
+        let html = to_html(&app);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<section id=\"msg-1\" class=\"user\">"));
+        assert!(html.contains("<a href=\"#msg-2\">#2</a> Assistant"));
+        assert!(html.contains("is 1 &lt; 2 &amp; &quot;x&quot;?"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn test_json_export() {
+        let mut app = App::new("coder", "sonnet", "default");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::ToolCall {
+            name: "exec".into(),
+            args_short: "{}".into(),
+            args: r#"{"command":"ls"}"#.into(),
+        });
+        app.push_trace(TraceEntry::StageStart { id: "s1".into(), kind: "plan".into() });
+        app.llm_calls.push(LlmCallEntry {
+            model: "sonnet".into(),
+            prompt_tokens: 120,
+            completion_tokens: 30,
+            duration_ms: 900,
+            timestamp: Local::now(),
+        });
+
+        let doc = to_json(&app);
+        assert_eq!(doc["version"], 1);
+        assert_eq!(doc["agent"], "coder");
+        assert_eq!(doc["messages"][0]["type"], "user");
+        assert_eq!(doc["messages"][1]["id"], 2);
+        assert_eq!(doc["messages"][0]["text"], "hi");
+        assert!(doc["messages"][0]["timestamp"].is_string());
+        assert_eq!(doc["messages"][1]["args"]["command"], "ls");
+        assert_eq!(doc["trace"][0]["type"], "stage_start");
+        assert!(doc["trace"][0]["timestamp"].is_string());
+        assert_eq!(doc["llm_calls"][0]["prompt_tokens"], 120);
+        assert_eq!(doc["llm_calls"][0]["completion_tokens"], 30);
+    }
+
+    #[test]
+    fn test_bundle() {
+        let mut app = App::new("coder", "sonnet", "default");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.push_trace(TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() });
+        app.llm_calls.push(LlmCallEntry {
+            model: "sonnet".into(),
+            prompt_tokens: 120,
+            completion_tokens: 30,
+            duration_ms: 900,
+            timestamp: Local::now(),
+        });
+        app.status.total_tokens = 150;
+
+        assert!(llm_calls_to_text(&app).contains("sonnet"));
+        assert!(stats_to_text(&app).contains("Tokens: 150"));
+        assert_eq!(stats_to_json(&app)["tokens"], 150);
+
+        let dir = std::env::temp_dir().join(format!("neocognos-bundle-{}", std::process::id()));
+        write_bundle(&app, &dir).unwrap();
+        for name in BUNDLE_FILES {
+            assert!(dir.join(name).is_file(), "missing {name}");
+        }
+        let trace = std::fs::read_to_string(dir.join("trace.txt")).unwrap();
+        assert!(trace.contains("plan (llm)"));
+        // Only what the `/trace filter` shows is exported
+        app.apply_trace(TraceAction::Filter(vec![TraceKind::Llm]));
+        write_bundle(&app, &dir).unwrap();
+        assert!(!std::fs::read_to_string(dir.join("trace.txt")).unwrap().contains("plan (llm)"));
+        assert_eq!(trace_to_json(&app), json!([]));
+        let calls: Value = serde_json::from_str(&std::fs::read_to_string(dir.join("llm_calls.json")).unwrap()).unwrap();
+        assert_eq!(calls[0]["completion_tokens"], 30);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_path() {
+        let path = default_path("md");
+        assert!(path.starts_with("neocognos-transcript-"));
+        assert!(path.ends_with(".md"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Inline image thumbnails through terminal graphics protocols.
+//!
+//! ratatui only knows about text cells, so the chat pane reserves blank rows for
+//! each preview and reports where they landed ([`ImageSlot`]); after the frame is
+//! drawn, [`Renderer`] paints the images into those rows with kitty, iTerm2 or
+//! sixel escape sequences. Terminals without graphics get a text placeholder.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::Deserialize;
+
+/// Image file extensions worth previewing.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif"];
+
+/// Terminal graphics protocol used for previews.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// No graphics support: show a text placeholder instead.
+    #[default]
+    None,
+}
+
+impl ImageProtocol {
+    /// Guess the protocol from the environment of the running terminal.
+    pub fn detect() -> Self {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        // Multiplexers swallow or mangle graphics escapes
+        if var("TMUX").is_some() || var("STY").is_some() {
+            return Self::None;
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || program == "ghostty" {
+            Self::Kitty
+        } else if program == "iTerm.app" || program == "WezTerm" {
+            Self::Iterm2
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Self::Sixel
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Where the chat pane reserved room for an image this frame (terminal cells).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImageSlot {
+    pub path: PathBuf,
+    pub x: u16,
+    pub y: u16,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Whether `path` is an existing file with an image extension we can decode.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        && path.is_file()
+}
+
+/// First existing image file mentioned in `text` (bare, quoted or in backticks).
+pub fn find_image(text: &str) -> Option<PathBuf> {
+    text.split(|c: char| c.is_whitespace() || "\"'`()[]<>,".contains(c))
+        .map(|token| PathBuf::from(token.trim_end_matches([':', '.', ';', '!', '?'])))
+        .find(|path| is_image(path))
+}
+
+/// Paints image slots after each frame, re-encoding only when a slot changes.
+pub struct Renderer {
+    protocol: ImageProtocol,
+    /// Terminal cell size in pixels, for sizing sixel output.
+    cell: (u32, u32),
+    encoded: HashMap<ImageSlot, Vec<u8>>,
+    shown: Vec<ImageSlot>,
+}
+
+impl Renderer {
+    pub fn new(protocol: ImageProtocol) -> Self {
+        let cell = crossterm::terminal::window_size()
+            .ok()
+            .filter(|size| size.width > 0 && size.columns > 0 && size.rows > 0)
+            .map(|size| ((size.width / size.columns) as u32, (size.height / size.rows) as u32))
+            .unwrap_or((10, 20));
+        Self { protocol, cell, encoded: HashMap::new(), shown: Vec::new() }
+    }
+
+    /// Forget what is on screen, e.g. after the terminal was handed to another
+    /// program, so the next sync paints every slot again.
+    pub fn forget(&mut self) {
+        self.shown.clear();
+    }
+
+    /// Erase whatever images are on screen, whoever painted them, as when
+    /// another tab's replace them. The caller clears the terminal too; the next
+    /// sync paints every slot again.
+    pub fn erase(&mut self, out: &mut impl Write) -> Result<()> {
+        if self.protocol == ImageProtocol::Kitty {
+            write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+        }
+        self.shown.clear();
+        Ok(())
+    }
+
+    /// Bring the screen in line with `slots`. Returns true if previously shown
+    /// images need erasing first, in which case the caller should clear the
+    /// terminal and call this again after the next full redraw.
+    pub fn sync(&mut self, out: &mut impl Write, slots: &[ImageSlot]) -> Result<bool> {
+        if self.protocol == ImageProtocol::None || self.shown == slots {
+            return Ok(false);
+        }
+        if !self.shown.is_empty() {
+            if self.protocol == ImageProtocol::Kitty {
+                // Kitty keeps placements across text redraws; delete them all
+                write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+            }
+            self.shown.clear();
+            return Ok(true);
+        }
+        write!(out, "\x1b7")?;
+        for slot in slots {
+            if !self.encoded.contains_key(slot) {
+                // An unreadable image simply stays a blank placeholder
+                let Ok(bytes) = encode(self.protocol, slot, self.cell) else { continue };
+                self.encoded.insert(slot.clone(), bytes);
+            }
+            write!(out, "\x1b[{};{}H", slot.y + 1, slot.x + 1)?;
+            out.write_all(&self.encoded[slot])?;
+        }
+        write!(out, "\x1b8")?;
+        out.flush()?;
+        self.shown = slots.to_vec();
+        Ok(false)
+    }
+}
+
+/// Escape sequence drawing the image at `slot.path` into a `slot.cols` × `slot.rows` box.
+fn encode(protocol: ImageProtocol, slot: &ImageSlot, cell: (u32, u32)) -> Result<Vec<u8>> {
+    let image = image::open(&slot.path).with_context(|| format!("Failed to read {}", slot.path.display()))?;
+    let (width, height) = fit(image.dimensions(), (slot.cols as u32 * cell.0, slot.rows as u32 * cell.1));
+    let cols = width.div_ceil(cell.0).max(1);
+    let rows = height.div_ceil(cell.1).max(1);
+    let engine = base64::engine::general_purpose::STANDARD;
+    match protocol {
+        ImageProtocol::Kitty => {
+            let mut png = Vec::new();
+            image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            let payload = engine.encode(png);
+            let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+            let mut out = Vec::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                if i == 0 {
+                    write!(out, "\x1b_Ga=T,f=100,q=2,C=1,c={cols},r={rows},m={more};")?;
+                } else {
+                    write!(out, "\x1b_Gm={more};")?;
+                }
+                out.extend_from_slice(chunk);
+                out.extend_from_slice(b"\x1b\\");
+            }
+            Ok(out)
+        }
+        ImageProtocol::Iterm2 => {
+            let bytes = std::fs::read(&slot.path)?;
+            Ok(format!(
+                "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+                bytes.len(),
+                engine.encode(&bytes),
+            )
+            .into_bytes())
+        }
+        ImageProtocol::Sixel => Ok(sixel(&image.thumbnail(width, height))),
+        ImageProtocol::None => Ok(Vec::new()),
+    }
+}
+
+/// Scale `size` down to fit `bounds`, keeping the aspect ratio. Never scales up.
+fn fit(size: (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    let (w, h) = (size.0.max(1) as f64, size.1.max(1) as f64);
+    let scale = (bounds.0 as f64 / w).min(bounds.1 as f64 / h).min(1.0);
+    (((w * scale) as u32).max(1), ((h * scale) as u32).max(1))
+}
+
+/// Sixel encoding with a fixed 6×6×6 color cube; transparent pixels are skipped.
+fn sixel(image: &DynamicImage) -> Vec<u8> {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let index = |x: u32, y: u32| -> Option<usize> {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        (a >= 128).then(|| (r as usize * 6 / 256) * 36 + (g as usize * 6 / 256) * 6 + b as usize * 6 / 256)
+    };
+
+    let mut out = format!("\x1bPq\"1;1;{width};{height}").into_bytes();
+    for color in 0..216 {
+        let level = |c: usize| c * 100 / 5;
+        let _ = write!(out, "#{color};2;{};{};{}", level(color / 36), level(color / 6 % 6), level(color % 6));
+    }
+    for band in (0..height).step_by(6) {
+        // One pass per color present in this band, each starting at column 0
+        let mut colors: Vec<usize> = Vec::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                if let Some(c) = index(x, y).filter(|c| !colors.contains(c)) {
+                    colors.push(c);
+                }
+            }
+        }
+        for (n, &color) in colors.iter().enumerate() {
+            if n > 0 {
+                out.push(b'$');
+            }
+            let _ = write!(out, "#{color}");
+            let bits = |x: u32| {
+                (0..6u32)
+                    .filter(|dy| band + dy < height && index(x, band + dy) == Some(color))
+                    .fold(0u8, |acc, dy| acc | 1 << dy)
+            };
+            let mut x = 0;
+            while x < width {
+                let value = bits(x);
+                let mut run = 1;
+                while x + run < width && bits(x + run) == value {
+                    run += 1;
+                }
+                let symbol = (63 + value) as char;
+                if run > 3 {
+                    let _ = write!(out, "!{run}{symbol}");
+                } else {
+                    for _ in 0..run {
+                        out.push(symbol as u8);
+                    }
+                }
+                x += run;
+            }
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_protocol() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(ImageProtocol::detect_from(env(&[("TERM", "xterm-kitty")])), ImageProtocol::Kitty);
+        assert_eq!(ImageProtocol::detect_from(env(&[("TERM_PROGRAM", "iTerm.app")])), ImageProtocol::Iterm2);
+        assert_eq!(ImageProtocol::detect_from(env(&[("TERM", "foot")])), ImageProtocol::Sixel);
+        assert_eq!(ImageProtocol::detect_from(env(&[("TERM", "xterm-256color")])), ImageProtocol::None);
+        assert_eq!(
+            ImageProtocol::detect_from(env(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")])),
+            ImageProtocol::None
+        );
+    }
+
+    #[test]
+    fn test_find_image() {
+        let dir = std::env::temp_dir().join(format!("neocognos-graphics-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plot = dir.join("plot.PNG");
+        std::fs::write(&plot, b"").unwrap();
+
+        let text = format!("Saved the chart to `{}`.", plot.display());
+        assert_eq!(find_image(&text), Some(plot.clone()));
+        assert_eq!(find_image(&format!("{{\"path\":\"{}\"}}", plot.display())), Some(plot));
+        assert_eq!(find_image("see missing.png"), None);
+        assert_eq!(find_image("no images here"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fit_keeps_aspect() {
+        assert_eq!(fit((800, 400), (400, 400)), (400, 200));
+        assert_eq!(fit((100, 50), (400, 400)), (100, 50));
+    }
+
+    #[test]
+    fn test_sixel_encoding() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(8, 7, image::Rgba([255, 0, 0, 255])));
+        let out = String::from_utf8(sixel(&image)).unwrap();
+        assert!(out.starts_with("\x1bPq\"1;1;8;7#0;2;0;0;0"));
+        // Pure red is color 5*36 = 180; first band is six full rows, second just one
+        assert!(out.contains("#180!8~-#180!8@-"));
+        assert!(out.ends_with("\x1b\\"));
+    }
+}
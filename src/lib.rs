@@ -1,5 +1,14 @@
 //! Library re-exports for testing.
 
 pub mod app;
+pub mod background;
+pub mod bench;
 pub mod commands;
+pub mod config;
+pub mod diff;
+pub mod export;
+pub mod graphics;
+pub mod links;
+pub mod session_store;
+pub mod theme;
 pub mod ui;
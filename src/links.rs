@@ -0,0 +1,200 @@
+//! URLs in chat text: detection, OSC 8 hyperlinks and opening in a browser.
+//!
+//! ratatui measures every byte of a cell's symbol as a column, so escape
+//! sequences can't live in the buffer. Instead the chat pane underlines links and
+//! reports where they are ([`LinkSlot`]); after each frame [`paint`] re-prints
+//! those cells wrapped in OSC 8, which terminals without support simply ignore.
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+use ratatui::crossterm::queue;
+use ratatui::crossterm::style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use ratatui::style::Modifier;
+use unicode_width::UnicodeWidthStr;
+
+/// On-screen run of cells belonging to a link this frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkSlot {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub url: String,
+}
+
+/// Byte ranges of the http(s) URLs in `text`, without trailing punctuation.
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find("http") {
+        let start = pos + offset;
+        let tail = &text[start..];
+        let scheme = ["https://", "http://"].into_iter().find(|s| tail.starts_with(s));
+        let Some(scheme) = scheme else {
+            pos = start + 4;
+            continue;
+        };
+        let mut end = start + tail.find(|c: char| c.is_whitespace() || "<>\"'`".contains(c)).unwrap_or(tail.len());
+        // Drop trailing punctuation, keeping a closing paren that balances one in the URL
+        while let Some(c) = text[start..end].chars().last() {
+            let url = &text[start..end];
+            let unbalanced = c == ')' && url.matches('(').count() < url.matches(')').count();
+            if ".,;:!?]}".contains(c) || unbalanced {
+                end -= c.len_utf8();
+            } else {
+                break;
+            }
+        }
+        if end > start + scheme.len() {
+            found.push(start..end);
+        }
+        pos = end.max(start + scheme.len());
+    }
+    found
+}
+
+/// URLs in `text`, in order.
+pub fn urls(text: &str) -> Vec<&str> {
+    find_urls(text).into_iter().map(|range| &text[range]).collect()
+}
+
+/// Link ranges in the wrapped rows of one message: (row, byte range, url). A URL
+/// hard-split across rows links every piece to the whole URL from `urls`.
+pub fn row_links(rows: &[String], urls: &[&str]) -> Vec<(usize, Range<usize>, String)> {
+    let mut found = Vec::new();
+    // URL being continued from the previous row, and the part still to come
+    let mut rest: Option<(&str, &str)> = None;
+    for (r, row) in rows.iter().enumerate() {
+        let mut from = 0;
+        if let Some((url, remaining)) = rest.take() {
+            let n = common_prefix(row, remaining);
+            if n > 0 {
+                found.push((r, 0..n, url.to_string()));
+                if n == row.len() && n < remaining.len() {
+                    rest = Some((url, &remaining[n..]));
+                }
+                from = n;
+            }
+        }
+        for range in find_urls(&row[from..]) {
+            let range = range.start + from..range.end + from;
+            let fragment = &row[range.clone()];
+            let continued = (range.end == row.len())
+                .then(|| urls.iter().find(|u| u.len() > fragment.len() && u.starts_with(fragment)))
+                .flatten();
+            match continued {
+                Some(url) => {
+                    rest = Some((url, &url[fragment.len()..]));
+                    found.push((r, range, url.to_string()));
+                }
+                None => found.push((r, range, fragment.to_string())),
+            }
+        }
+    }
+    found
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map(|((i, _), _)| i)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+/// Display columns from the start of `row` to byte `offset`, and of `range`.
+pub fn columns(row: &str, range: &Range<usize>) -> (u16, u16) {
+    (row[..range.start].width() as u16, row[range.clone()].width() as u16)
+}
+
+/// Re-print the cells under `slots` from the drawn `buffer` as OSC 8 hyperlinks.
+pub fn paint(out: &mut impl Write, buffer: &Buffer, slots: &[LinkSlot]) -> io::Result<()> {
+    if slots.is_empty() {
+        return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for slot in slots {
+        queue!(out, MoveTo(slot.x, slot.y), Print(format!("\x1b]8;;{}\x1b\\", slot.url)))?;
+        let mut x = slot.x;
+        while x < slot.x + slot.width {
+            let Some(cell) = buffer.cell((x, slot.y)) else { break };
+            queue!(
+                out,
+                SetAttribute(Attribute::Reset),
+                SetForegroundColor(cell.fg.into()),
+                SetBackgroundColor(cell.bg.into()),
+            )?;
+            for (modifier, attribute) in [
+                (Modifier::BOLD, Attribute::Bold),
+                (Modifier::DIM, Attribute::Dim),
+                (Modifier::ITALIC, Attribute::Italic),
+                (Modifier::UNDERLINED, Attribute::Underlined),
+                (Modifier::REVERSED, Attribute::Reverse),
+            ] {
+                if cell.modifier.contains(modifier) {
+                    queue!(out, SetAttribute(attribute))?;
+                }
+            }
+            queue!(out, Print(cell.symbol()))?;
+            x += cell.symbol().width().max(1) as u16;
+        }
+        queue!(out, Print("\x1b]8;;\x1b\\"), SetAttribute(Attribute::Reset))?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+}
+
+/// Open `url` in the default browser without waiting for it.
+pub fn open(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(url).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_urls() {
+        let text = "See https://example.com/docs. Also (http://a.io/x_(y)) and https:// alone, httpx://no.";
+        assert_eq!(urls(text), vec!["https://example.com/docs", "http://a.io/x_(y)"]);
+        assert_eq!(urls("[docs](https://d.rs/a?b=1)"), vec!["https://d.rs/a?b=1"]);
+        assert!(urls("no links here").is_empty());
+    }
+
+    #[test]
+    fn test_row_links_follow_wrapped_url() {
+        let url = "https://example.com/a/very/long/path";
+        let rows = vec![
+            "  read https://example.com/a/".to_string(),
+            "very/long/pa".to_string(),
+            "th then stop".to_string(),
+        ];
+        let links = row_links(&rows, &[url]);
+        assert_eq!(
+            links,
+            vec![(0, 7..29, url.to_string()), (1, 0..12, url.to_string()), (2, 0..2, url.to_string())]
+        );
+        assert_eq!(columns(&rows[0], &links[0].1), (7, 22));
+    }
+
+    #[test]
+    fn test_row_links_unwrapped() {
+        let rows = vec!["a http://x.io b".to_string(), "http://y.io".to_string()];
+        let links = row_links(&rows, &["http://x.io", "http://y.io"]);
+        assert_eq!(links, vec![(0, 2..13, "http://x.io".to_string()), (1, 0..11, "http://y.io".to_string())]);
+    }
+}
@@ -3,23 +3,43 @@
 
 mod agent_thread;
 mod app;
+mod background;
+mod bench;
+mod clipboard;
 mod commands;
+mod config;
+mod diff;
+mod env_file;
+mod export;
+mod graphics;
+mod links;
 mod session;
+mod session_store;
+mod shell;
+mod tabs;
+mod tasks;
+mod theme;
 mod ui;
 
-use std::io;
+use std::io::{self, Write};
 use std::sync::mpsc;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle};
 use crossterm::execute;
 use ratatui::prelude::*;
 use ratatui::backend::CrosstermBackend;
 
-use agent_thread::AgentEvent;
+use agent_thread::{AgentEvent, EventSender, SessionId, UiCommand};
 use app::{App, ChatMessage};
+use commands::{ArtifactsAction, CommandResult, EditAction, ExportFormat, TabAction};
+use config::LayoutMode;
+use ui::json_tree::TreeAction;
+use ui::popup::{PopupAction, PopupState};
+use ui::palette::{PaletteAction, PaletteRun};
+use ui::keymap::KeyContext;
 use session::SessionConfig;
 
 fn get_arg(args: &[String], flag: &str) -> Option<String> {
@@ -46,14 +66,21 @@ fn main() -> Result<()> {
         println!("  --model <model>       LLM model (e.g. anthropic:claude-sonnet-4-20250514)");
         println!("  --provider <name>     LLM provider (anthropic, ollama, claude-cli)");
         println!("  --api-key <key>       API key for the provider");
+        println!("  --env-file <path>     Load environment variables from a dotenv file");
         println!("  --ollama-url <url>    Ollama base URL (default: http://localhost:11434)");
+        println!("  --failover <chain>    Fallback providers, e.g. claude-cli,ollama:llama3.2:3b");
         println!("  --workflow <path>     Custom workflow YAML file");
         println!("  --autonomy <level>    Autonomy level (manual, supervised, semi, full)");
         println!("  --mock                Use mock LLM for testing");
+        println!("  --safe-mode           Disable tools, shell and attachments; autonomy manual");
         println!("  --verbose             Enable verbose event logging");
         println!("  --checkpoint-dir <d>  Enable checkpointing");
         println!("  --event-log <path>    Write events to JSONL file");
         println!("  --trace <path>        Write trace to file");
+        println!("  --yes                 Skip confirmations (e.g. manifest workdir changes)");
+        println!("  --stream-scroll <m>   Chat scrolling while answers stream: follow, top, none");
+        println!("  --ascii               Plain ASCII icons instead of emoji");
+        println!("  --quiet               Don't print the session summary on exit");
         println!("  -h, --help            Show this help");
         return Ok(());
     }
@@ -72,194 +99,895 @@ fn main() -> Result<()> {
         checkpoint_dir: get_arg(&args, "--checkpoint-dir"),
         event_log_path: get_arg(&args, "--event-log"),
         trace_path: get_arg(&args, "--trace"),
+        assume_yes: has_flag(&args, "--yes"),
+        env_file: get_arg(&args, "--env-file"),
+        failover: get_arg(&args, "--failover"),
+        safe_mode: has_flag(&args, "--safe-mode"),
     };
 
-    // Create event channel
-    let (event_tx, event_rx) = mpsc::channel::<AgentEvent>();
+    let mut tui_config = config::load()?;
+    if let Some(mode) = get_arg(&args, "--stream-scroll") {
+        tui_config.chat.stream_scroll = mode.parse()?;
+    }
+    if has_flag(&args, "--ascii") {
+        tui_config.chat.ascii_icons = true;
+    }
+    ui::icons::set_ascii(tui_config.chat.ascii_icons);
+    let theme = config::load_theme()?;
+    ui::theme::set_color_support(ui::theme::ColorSupport::detect());
 
-    // Create session (before entering raw mode, so errors print normally)
-    let session = session::Session::from_config(config, event_tx.clone())?;
+    // Every session's events arrive on one channel, tagged with its id
+    let (events_tx, events_rx) = mpsc::channel::<(SessionId, AgentEvent)>();
+    let event_tx = EventSender::new(0, events_tx.clone());
 
-    let agent_name = session.agent_name.clone();
-    let model_name = session.model_name.clone();
-    let workflow_name = session.workflow_name.clone();
+    // Create session (before entering raw mode, so errors print normally)
+    let session = session::Session::from_config(config.clone(), event_tx.clone())?;
 
-    // Spawn agent thread
-    let input_tx = agent_thread::spawn(session, event_tx);
+    // Spawn agent thread; more sessions open as tabs with /tab new
+    let first = tabs::SessionTab::start(session, event_tx, &tui_config);
+    let mut tabs = tabs::Tabs::new(first, events_tx, events_rx);
+    // Exit summaries of tabs closed along the way
+    let mut summaries = Vec::new();
 
     // Setup terminal
     enable_raw_mode()?;
+    // Without a theme file, light terminals get the light palette
+    ui::theme::set(theme.unwrap_or_else(|| match background::detect() {
+        Some(background::Background::Light) => theme::Theme::LIGHT,
+        _ => theme::Theme::DEFAULT,
+    }));
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    // Save the window title (xterm title stack) so it can be put back on exit
+    execute!(stdout, crossterm::style::Print("\x1b[22;0t"), EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-    let mut app = App::new(&agent_name, &model_name, &workflow_name);
-    app.add_message(ChatMessage::System(format!(
-        "🧬 Neocognos TUI — Agent: {} | Model: {} | Workflow: {}",
-        agent_name, model_name, workflow_name
-    )));
-    app.add_message(ChatMessage::System(
-        "Type /help for commands, /quit to exit".into()
-    ));
+    let mut title = String::new();
+    // The tab on screen, whose images a switch has to erase
+    let mut shown_tab = tabs.active_mut().id;
 
     // Main event loop
     let tick_rate = Duration::from_millis(100);
 
     loop {
+        let labels = if tabs.len() > 1 { tabs.labels() } else { Vec::new() };
+        let active = tabs.active_index();
+        let tab = tabs.active_mut();
+        if tab.id != shown_tab {
+            shown_tab = tab.id;
+            tab.images.erase(terminal.backend_mut())?;
+            terminal.clear()?;
+        }
+        let (app, images) = (&mut tab.app, &mut tab.images);
+
+        // Window title follows the active tab's agent starting and finishing turns
+        let next_title = app.terminal_title();
+        if next_title != title {
+            execute!(terminal.backend_mut(), SetTitle(&next_title))?;
+            title = next_title;
+        }
+
         // Draw
-        terminal.draw(|frame| {
-            let layout = ui::layout::compute_layout(frame.area());
-            ui::chat::render(frame, layout.chat, &app);
-            ui::sidebar::render_status(frame, layout.sidebar_status, &app);
-            ui::sidebar::render_trace(frame, layout.sidebar_llm_log, &app);
-            ui::input::render(frame, layout.input, &app);
+        let completed = terminal.draw(|frame| {
+            let mut area = frame.area();
+            if !labels.is_empty() {
+                let [bar, rest] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+                ui::tab_bar::render(frame, bar, &labels, active);
+                area = rest;
+            }
+            let layout = if app.zen {
+                ui::layout::compute_zen_layout(area)
+            } else {
+                let (main, status_line) = ui::layout::split_status_line(area);
+                ui::status_line::render(frame, status_line, app);
+                ui::layout::compute_layout_for(app.layout_mode, main, app.show_sidebar, app.splits)
+            };
+            let notice_rows = ui::sidebar::notice_rows(app, layout.sidebar.width);
+            let layout = layout.with_notices(notice_rows);
+            ui::chat::render(frame, layout.chat, app);
+            if app.show_sidebar {
+                ui::sidebar::render(frame, &layout, app);
+            }
+            ui::input::render(frame, layout.input, app);
+            match app.overlay.as_mut() {
+                Some(app::Overlay::Pager(pager)) => ui::pager::render(frame, frame.area(), pager),
+                Some(app::Overlay::JsonTree(tree)) => ui::json_tree::render(frame, frame.area(), tree),
+                Some(app::Overlay::Popup(popup)) => ui::popup::render(frame, frame.area(), popup),
+                Some(app::Overlay::Palette(palette)) => ui::palette::render(frame, frame.area(), palette),
+                Some(app::Overlay::Trace(view)) => {
+                    ui::trace_view::render(frame, frame.area(), view, &app.trace_log, &app.trace_times, &app.trace_kinds)
+                }
+                Some(app::Overlay::Compare(comparison)) => ui::compare::render(frame, frame.area(), comparison),
+                None => {}
+            }
+            ui::theme::strip_colors(frame.buffer_mut());
         })?;
+        // Overlays cover the chat, previews and links included
+        if app.overlay.is_none() && !app.link_slots.is_empty() {
+            let buffer = completed.buffer.clone();
+            links::paint(terminal.backend_mut(), &buffer, &app.link_slots)?;
+        }
+        let slots = if app.overlay.is_none() { app.image_slots.as_slice() } else { &[] };
+        if images.sync(terminal.backend_mut(), slots)? {
+            terminal.clear()?;
+        }
 
-        // Process agent events (non-blocking)
-        while let Ok(evt) = event_rx.try_recv() {
-            match evt {
-                AgentEvent::Narration(text) => {
-                    app.add_message(ChatMessage::Narration(text.clone()));
-                    app.trace_log.push(app::TraceEntry::Narration(text));
-                }
-                AgentEvent::ToolCallStarted { name, args } => {
-                    app.trace_log.push(app::TraceEntry::ToolCall {
-                        name: name.clone(),
-                        args: args.clone(),
-                    });
-                    app.add_message(ChatMessage::ToolCall {
-                        name: name.clone(),
-                        args_short: args,
-                    });
-                    // Extract file path from tool args for sidebar
-                    if name == "read_file" || name == "write_file" {
-                        // Try to extract path from the args string
-                        if let Some(path) = extract_file_path(&app.messages.last()) {
-                            app.add_recent_file(path);
-                        }
-                    }
-                }
-                AgentEvent::LlmCall { model, prompt_tokens, completion_tokens, duration_ms } => {
-                    app.llm_calls.push(app::LlmCallEntry {
-                        model: model.clone(),
-                        prompt_tokens,
-                        completion_tokens,
-                        duration_ms,
-                    });
-                    app.trace_log.push(app::TraceEntry::LlmCall {
-                        model,
-                        ctx_tokens: prompt_tokens,
-                        out_tokens: completion_tokens,
-                        duration_ms,
-                    });
-                }
-                AgentEvent::StageStarted { stage_id, stage_kind } => {
-                    app.trace_log.push(app::TraceEntry::StageStart {
-                        id: stage_id,
-                        kind: stage_kind,
-                    });
-                }
-                AgentEvent::StageCompleted { stage_id, duration_ms, skipped } => {
-                    app.trace_log.push(app::TraceEntry::StageEnd {
-                        id: stage_id,
-                        duration_ms,
-                        skipped,
-                    });
-                }
-                AgentEvent::ToolCallCompleted { name, success, duration_ms } => {
-                    app.add_message(ChatMessage::ToolResult {
-                        name: name.clone(),
-                        success,
-                        duration_ms,
-                    });
-                    app.trace_log.push(app::TraceEntry::ToolResult {
-                        name: name.clone(),
-                        success,
-                        duration_ms,
-                    });
-                    app.add_recent_tool(name, success);
-                }
-                AgentEvent::Response(text) => {
-                    app.add_message(ChatMessage::Assistant(text));
-                }
-                AgentEvent::TokenUpdate { total, turns, cost } => {
-                    app.status.total_tokens = total;
-                    app.status.total_turns = turns;
-                    app.status.cost = cost;
-                }
-                AgentEvent::Error(text) => {
-                    app.add_message(ChatMessage::Error(text));
-                }
-                AgentEvent::SystemMessage(text) => {
-                    if text == "__clear__" {
-                        app.clear_messages();
-                    } else {
-                        app.add_message(ChatMessage::System(text));
-                    }
-                }
-                AgentEvent::Done => {
-                    app.agent_busy = false;
-                    app.thinking_since = None;
-                }
-                AgentEvent::Quit => {
-                    app.should_quit = true;
-                }
+        // Process agent events of every session, counting unread messages of tabs in the background
+        tabs.route_events(apply_event);
+
+        // A broadcast's answers open side by side once every tab has answered
+        if tabs.active_mut().app.overlay.is_none() {
+            if let Some(comparison) = tabs.finish_broadcast() {
+                tabs.active_mut().app.overlay = Some(app::Overlay::Compare(comparison));
             }
         }
 
-        if app.should_quit {
+        // A session that quits closes its tab; the last one ends the program
+        if quit_tabs(&mut tabs, &mut summaries) {
             break;
         }
 
         // Handle terminal input events
         if event::poll(tick_rate)? {
             if let Event::Key(key) = event::read()? {
-                handle_key_event(&mut app, key, &input_tx);
+                if let Some(index) = tab_shortcut(key) {
+                    if !tabs.select(index) {
+                        tabs.active_mut().app.show_toast(format!("No tab {}", index + 1));
+                    }
+                } else {
+                    let tab = tabs.active_mut();
+                    handle_key_event(&mut tab.app, key, &tab.command_tx);
+                }
             }
         }
 
-        if app.should_quit {
+        let tab = tabs.active_mut();
+        for (id, prompt) in std::mem::take(&mut tab.app.background_requests) {
+            tab.run_background(id, prompt);
+        }
+        for id in std::mem::take(&mut tab.app.background_cancels) {
+            tab.cancel_background(id);
+        }
+
+        if let Some(action) = tabs.active_mut().app.tab_request.take() {
+            handle_tab_action(&mut tabs, action, &config, &tui_config, &mut summaries);
+        }
+
+        let tab = tabs.active_mut();
+        let (app, images) = (&mut tab.app, &mut tab.images);
+        if std::mem::take(&mut app.external_pager) {
+            let transcript = export::to_markdown(app);
+            if let Err(e) = open_external_pager(&mut terminal, &transcript) {
+                app.add_message(ChatMessage::Error(format!("Pager failed: {e:#}")));
+            }
+            images.forget();
+        }
+        if std::mem::take(&mut app.external_editor) {
+            let prompt = app.last_prompt().unwrap_or_default().to_string();
+            match edit_in_external_editor(&mut terminal, &prompt) {
+                Ok(revised) => app.edit_prompt(&revised),
+                Err(e) => app.add_message(ChatMessage::Error(format!("Editor failed: {e:#}"))),
+            }
+            images.forget();
+        }
+
+        if quit_tabs(&mut tabs, &mut summaries) {
             break;
         }
     }
 
     // Restore terminal
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, crossterm::style::Print("\x1b[23;0t"))?;
     terminal.show_cursor()?;
 
-    println!("Goodbye! 👋");
+    if !has_flag(&args, "--quiet") {
+        summaries.extend(tabs.into_apps().map(|app| app.shutdown_summary()));
+        println!("{}", summaries.join("\n\n"));
+    }
     Ok(())
 }
 
-fn handle_key_event(app: &mut App, key: KeyEvent, input_tx: &mpsc::Sender<String>) {
+/// Update a tab's state from one of its agent's events.
+fn apply_event(app: &mut App, evt: AgentEvent) {
+    match evt {
+        AgentEvent::Narration(text) => {
+            app.add_message(ChatMessage::Narration(text.clone()));
+            app.push_trace(app::TraceEntry::Narration(text));
+        }
+        AgentEvent::ToolCallStarted { name, args } => {
+            app.push_trace(app::TraceEntry::ToolCall {
+                name: name.clone(),
+                args: args.clone(),
+            });
+            app.add_message(ChatMessage::ToolCall {
+                name: name.clone(),
+                args_short: app::shorten(&args, 60),
+                args,
+            });
+            // Extract file path from tool args for sidebar
+            if name == "read_file" || name == "write_file" {
+                // Try to extract path from the args string
+                if let Some(path) = extract_file_path(&app.messages.last()) {
+                    app.record_file_access(path, name == "write_file");
+                }
+            }
+        }
+        AgentEvent::LlmCall { model, prompt_tokens, completion_tokens, duration_ms } => {
+            app.rate_limited = None;
+            app.llm_calls.push(app::LlmCallEntry {
+                model: model.clone(),
+                prompt_tokens,
+                completion_tokens,
+                duration_ms,
+                timestamp: chrono::Local::now(),
+            });
+            app.push_trace(app::TraceEntry::LlmCall {
+                model,
+                ctx_tokens: prompt_tokens,
+                out_tokens: completion_tokens,
+                duration_ms,
+            });
+        }
+        AgentEvent::StageStarted { stage_id, stage_kind } => {
+            app.push_trace(app::TraceEntry::StageStart {
+                id: stage_id,
+                kind: stage_kind,
+            });
+        }
+        AgentEvent::StageCompleted { stage_id, duration_ms, skipped } => {
+            app.push_trace(app::TraceEntry::StageEnd {
+                id: stage_id,
+                duration_ms,
+                skipped,
+            });
+        }
+        AgentEvent::StageFailed { stage_id, error } => {
+            app.add_message(ChatMessage::Error(format!(
+                "Stage '{stage_id}' failed: {error} (select it in the trace and press r to retry)"
+            )));
+            app.push_trace(app::TraceEntry::StageFailed { id: stage_id, error });
+        }
+        AgentEvent::Artifact { stage_id, name, content } => {
+            app.push_trace(app::TraceEntry::Artifact {
+                stage_id: stage_id.clone(),
+                name: name.clone(),
+            });
+            let artifact = app::Artifact { stage_id, name, content };
+            if let Some(path) = artifact.image_path() {
+                let _ = app.show_image(&path);
+            }
+            app.add_artifact(artifact);
+        }
+        AgentEvent::ToolCallCompleted { name, success, duration_ms, output, error } => {
+            app.add_message(ChatMessage::ToolResult {
+                name: name.clone(),
+                success,
+                duration_ms,
+                output,
+                error: error.clone(),
+            });
+            app.push_trace(app::TraceEntry::ToolResult {
+                name: name.clone(),
+                success,
+                duration_ms,
+                error,
+            });
+            app.record_tool_call(&name, success, duration_ms);
+            app.add_recent_tool(name, success);
+        }
+        AgentEvent::ShellStarted { command, killer } => {
+            app.start_shell(command, killer);
+        }
+        AgentEvent::ShellOutput(lines) => {
+            app.append_shell(&lines);
+        }
+        AgentEvent::ShellFinished { code, killed } => {
+            app.finish_shell(code, killed);
+        }
+        AgentEvent::FileDiff { path, diff } => {
+            app.record_diff(path, diff);
+        }
+        AgentEvent::RateLimited { provider, retry_in_secs } => {
+            app.set_rate_limited(&provider, retry_in_secs);
+        }
+        AgentEvent::BackgroundStarted(id) => {
+            app.start_background(id);
+        }
+        AgentEvent::BackgroundFinished { id, outcome } => {
+            app.finish_background(id, outcome);
+        }
+        AgentEvent::TimeoutCountdown(secs) => {
+            app.timeout_left = Some(secs);
+        }
+        AgentEvent::StreamDelta(delta) => {
+            app.rate_limited = None;
+            app.append_stream(&delta);
+        }
+        AgentEvent::AttemptDiscarded => {
+            app.discard_stream();
+        }
+        AgentEvent::Response(text) => {
+            if !app.finish_stream(&text) && !text.is_empty() {
+                app.add_message(ChatMessage::Assistant(text));
+            }
+        }
+        AgentEvent::TokenUpdate { total, turns, cost, per_model } => {
+            app.status.total_tokens = total;
+            app.status.total_turns = turns;
+            app.status.cost = cost;
+            app.status.per_model = per_model;
+        }
+        AgentEvent::ModelChanged(model) => {
+            app.status.model = model;
+        }
+        AgentEvent::Pipeline(stages) => {
+            app.set_pipeline(stages);
+        }
+        AgentEvent::ConversationRestored(chat) => {
+            if let Err(e) = session_store::restore_chat(app, &chat) {
+                app.add_message(ChatMessage::Error(format!("The conversation was restored but its chat wasn't: {e:#}")));
+            }
+        }
+        AgentEvent::State(json) => {
+            app.open_state(&json);
+        }
+        AgentEvent::SessionSnapshot { name, state, checkpoint } => {
+            let dir = if checkpoint { app.checkpoint_dir.clone() } else { session_store::dir() };
+            match session_store::write(dir.as_deref(), &name, session_store::chat_state(app), state) {
+                Ok(path) => {
+                    let text = if checkpoint {
+                        format!(
+                            "{} Checkpoint '{name}' written to {} (/restore {name} rolls back to it)",
+                            ui::icons::current().checkpoint,
+                            path.display()
+                        )
+                    } else {
+                        format!(
+                            "{} Saved session '{name}' to {} (/load {name} restores it)",
+                            ui::icons::current().saved,
+                            path.display()
+                        )
+                    };
+                    app.add_message(ChatMessage::System(text));
+                    app.saves.push(path.display().to_string());
+                }
+                Err(e) => app.add_message(ChatMessage::Error(format!("Save failed: {e:#}"))),
+            }
+        }
+        AgentEvent::SafeMode(locked) => {
+            app.set_locked(locked);
+        }
+        AgentEvent::PinNotice { id, text } => {
+            app.pin_notice(&id, text);
+        }
+        AgentEvent::ClearNotice(id) => {
+            app.clear_notice(&id);
+        }
+        AgentEvent::Context(entries) => {
+            let report = app.context_report(&entries);
+            app.overlay = Some(app::Overlay::Popup(PopupState::text("Context window", report)));
+        }
+        AgentEvent::Stats(turns) => {
+            let report = app::stats_report(&turns);
+            app.overlay = Some(app::Overlay::Popup(PopupState::text("Session stats", report)));
+        }
+        AgentEvent::OllamaModels(models) => {
+            app.ollama_models = models;
+        }
+        AgentEvent::AutonomyChanged(level) => {
+            app.status.autonomy = level;
+        }
+        AgentEvent::WorkflowChanged { name, stages } => {
+            app.status.workflow = name;
+            app.set_pipeline(stages);
+        }
+        AgentEvent::TurnUndone(input) => {
+            app.remove_exchange(&input);
+            app.add_message(ChatMessage::System(format!("{} Undid: {}", ui::icons::current().undo, app::shorten(&input, 60))));
+        }
+        // Swap the old exchange for the revised prompt once the agent rolled it back
+        AgentEvent::TurnRevised { previous, prompt } => {
+            app.remove_exchange(&previous);
+            app.add_message(ChatMessage::User(prompt));
+        }
+        AgentEvent::GuidanceUndelivered(guidance) => {
+            app.return_guidance(guidance);
+        }
+        AgentEvent::AgentChanged { agent, model, workflow, autonomy, token_budget } => {
+            app.status.agent_name = agent;
+            app.status.model = model;
+            app.status.workflow = workflow;
+            app.status.autonomy = autonomy;
+            app.token_budget = token_budget;
+        }
+        AgentEvent::Error(text) => {
+            app.add_message(ChatMessage::Error(text));
+        }
+        AgentEvent::SystemMessage(text) => {
+            if text == "__clear__" {
+                app.clear_messages();
+            } else {
+                app.add_message(ChatMessage::System(text));
+            }
+        }
+        AgentEvent::Done => {
+            // A failed turn may leave a partial answer; keep it but stop appending to it
+            app.streaming = None;
+            app.agent_busy = false;
+            app.thinking_since = None;
+            app.running_stage = None;
+            app.running_tool = None;
+            app.rate_limited = None;
+            app.canceling = false;
+            app.timeout_left = None;
+            app.shell = None;
+            // A pause that never reached a tool call or stage
+            if app.steering.is_paused() {
+                app.steering.resume(None);
+            }
+        }
+        AgentEvent::Quit => {
+            app.should_quit = true;
+        }
+    }
+}
+
+/// Alt+1..9 switch to the first nine session tabs.
+fn tab_shortcut(key: KeyEvent) -> Option<usize> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::ALT, KeyCode::Char(c @ '1'..='9')) => Some(c as usize - '1' as usize),
+        _ => None,
+    }
+}
+
+/// Close the tabs whose session quit, keeping their exit summaries. True once
+/// the last one quit, which ends the program.
+fn quit_tabs(tabs: &mut tabs::Tabs, summaries: &mut Vec<String>) -> bool {
+    summaries.extend(tabs.close_quit().into_iter().map(|tab| tab.app.shutdown_summary()));
+    tabs.active_mut().app.should_quit
+}
+
+/// Close the active tab, keeping its exit summary. False if it is the last one.
+fn close_tab(tabs: &mut tabs::Tabs, summaries: &mut Vec<String>) -> bool {
+    let Some(tab) = tabs.close_active() else { return false };
+    summaries.push(tab.app.shutdown_summary());
+    true
+}
+
+/// Handle `/tab`: the main loop owns the tabs, so it runs these for the active one.
+fn handle_tab_action(
+    tabs: &mut tabs::Tabs,
+    action: TabAction,
+    base: &SessionConfig,
+    tui_config: &config::TuiConfig,
+    summaries: &mut Vec<String>,
+) {
+    match action {
+        TabAction::List => {
+            let listing = tabs.listing();
+            tabs.active_mut().app.add_message(ChatMessage::System(listing));
+        }
+        TabAction::Select(n) => {
+            if !tabs.select(n - 1) {
+                tabs.active_mut().app.show_toast(format!("No tab {n}"));
+            }
+        }
+        TabAction::Close => {
+            if !close_tab(tabs, summaries) {
+                tabs.active_mut().app.show_toast("Last tab; /quit exits");
+            }
+        }
+        TabAction::Broadcast { tabs: targets, prompt } => match tabs.broadcast(&targets, &prompt) {
+            Ok(sent) => {
+                let plural = if sent == 1 { "" } else { "s" };
+                tabs.active_mut().app.show_toast(format!("Broadcast to {sent} tab{plural}; the answers open side by side"));
+            }
+            Err(e) => tabs.active_mut().app.add_message(ChatMessage::Error(e)),
+        },
+        TabAction::New { manifest, model } => match tabs::SessionTab::open(base, manifest, model, tui_config, tabs.event_sender()) {
+            Ok(tab) => tabs.open(tab),
+            Err(e) => {
+                tabs.active_mut().app.add_message(ChatMessage::Error(format!("Failed to open tab: {e:#}")));
+            }
+        },
+    }
+}
+
+/// Handle a key press. Returns the keymap context whose binding took it;
+/// `None` when an overlay or the search query took it, or nothing did.
+fn handle_key_event(app: &mut App, key: KeyEvent, command_tx: &mpsc::Sender<UiCommand>) -> Option<KeyContext> {
+    // An open overlay takes every key
+    match app.overlay.as_mut() {
+        Some(app::Overlay::Pager(pager)) => {
+            if key.code == KeyCode::Char('y') && !pager.editing_query {
+                pager.notice = Some(copy_notice(&pager.text, "chars"));
+            } else if !ui::pager::handle_key(pager, key) {
+                app.overlay = None;
+            }
+            return None;
+        }
+        Some(app::Overlay::JsonTree(tree)) => {
+            match ui::json_tree::handle_key(tree, key) {
+                TreeAction::Close => app.overlay = None,
+                TreeAction::Copy { text, label } => tree.notice = Some(copy_notice(&text, label)),
+                TreeAction::None => {}
+            }
+            return None;
+        }
+        Some(app::Overlay::Popup(popup)) => {
+            let action = ui::popup::handle_key(popup, key);
+            // The theme picker previews the highlighted theme
+            if let Some(("/theme", name)) = popup.highlighted() {
+                if let Some(theme) = theme::preset(name) {
+                    ui::theme::set(theme);
+                }
+            }
+            match action {
+                PopupAction::Close => {
+                    app.overlay = None;
+                    if let Some(theme) = app.theme_before_preview.take() {
+                        ui::theme::set(theme);
+                    }
+                }
+                PopupAction::Submit(text) if waits_for_agent(app, &text) => app.show_toast("Agent is busy"),
+                PopupAction::Submit(text) => {
+                    app.overlay = None;
+                    submit(app, text, command_tx);
+                }
+                PopupAction::None => {}
+            }
+            return None;
+        }
+        Some(app::Overlay::Palette(palette)) => {
+            match ui::palette::handle_key(palette, key) {
+                PaletteAction::None => {}
+                PaletteAction::Close => app.overlay = None,
+                PaletteAction::Run(run) => {
+                    app.overlay = None;
+                    match run {
+                        PaletteRun::Submit(text) if waits_for_agent(app, &text) => app.show_toast("Agent is busy"),
+                        PaletteRun::Submit(text) => submit(app, text, command_tx),
+                        PaletteRun::Insert(text) => {
+                            app.focus = app::PanelFocus::Input;
+                            text.chars().for_each(|c| app.insert_char(c));
+                        }
+                        PaletteRun::Key(key) => return handle_key_event(app, key, command_tx),
+                    }
+                }
+            }
+            return None;
+        }
+        Some(app::Overlay::Trace(view)) => {
+            if !ui::trace_view::handle_key(view, key, &app.trace_log) {
+                app.overlay = None;
+            }
+            return None;
+        }
+        Some(app::Overlay::Compare(comparison)) => {
+            if !ui::compare::handle_key(comparison, key) {
+                app.overlay = None;
+            }
+            return None;
+        }
+        None => {}
+    }
+
+    // While a search query is being typed, keys edit the query
+    if app.search.as_ref().is_some_and(|s| s.editing) {
+        match key.code {
+            KeyCode::Esc => app.end_search(),
+            KeyCode::Enter => {
+                if let Some(search) = app.search.as_mut() {
+                    search.editing = false;
+                }
+            }
+            KeyCode::Backspace => app.search_pop(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => app.search_push(c),
+            _ => {}
+        }
+        return None;
+    }
+
+    if global_key(app, key) {
+        return Some(KeyContext::Global);
+    }
+    // Esc: close the search or selection mode, cancel a running turn from
+    // the input bar, otherwise return to the input bar
+    if key.code == KeyCode::Esc {
+        if app.search.is_some() {
+            app.end_search();
+        } else if app.selection_mode {
+            app.toggle_selection_mode();
+        } else if app.editing_prompt && app.focus == app::PanelFocus::Input {
+            app.cancel_edit();
+        } else if app.focus == app::PanelFocus::Input && app.agent_busy {
+            app.cancel_turn();
+            return Some(KeyContext::Input);
+        } else {
+            app.selected_trace = None;
+            app.focus = app::PanelFocus::Input;
+        }
+        return Some(KeyContext::Global);
+    }
+    let panel = match (app.focus, app.sidebar_tab) {
+        (app::PanelFocus::Chat, _) => chat_key(app, key).then_some(KeyContext::Chat),
+        (app::PanelFocus::Sidebar, app::SidebarTab::Trace) if trace_key(app, key, command_tx) => Some(KeyContext::Trace),
+        (app::PanelFocus::Sidebar, app::SidebarTab::Llm) if llm_key(app, key) => Some(KeyContext::Llm),
+        (app::PanelFocus::Sidebar, _) => sidebar_key(app, key).then_some(KeyContext::Sidebar),
+        (app::PanelFocus::Input, _) => None,
+    };
+    panel.or_else(|| input_key(app, key, command_tx))
+}
+
+/// Keys that work from any panel. False if `key` isn't one of them.
+fn global_key(app: &mut App, key: KeyEvent) -> bool {
     match (key.modifiers, key.code) {
-        // Ctrl+C: quit if idle, ignore if busy (agent thread handles cancellation)
+        // Ctrl+C: kill a running `!` command, quit if idle, ignore if busy
+        // (Esc cancels the turn)
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) if app.kill_shell() => {}
+        // Ctrl+G: pause the running turn to steer it, or resume it
+        (KeyModifiers::CONTROL, KeyCode::Char('g')) => app.toggle_pause(),
         (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
             if !app.agent_busy {
                 app.should_quit = true;
             }
         }
-        // Ctrl+D: quit
-        (KeyModifiers::CONTROL, KeyCode::Char('d')) => {
+        // Ctrl+D: quit, except in the chat pane where it scrolls
+        (KeyModifiers::CONTROL, KeyCode::Char('d')) if app.focus != app::PanelFocus::Chat => {
             app.should_quit = true;
         }
         // Ctrl+L: clear chat
         (KeyModifiers::CONTROL, KeyCode::Char('l')) => {
             app.clear_messages();
         }
-        // Enter: submit input
+        // Ctrl+B: show or hide the sidebar
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) => {
+            app.toggle_sidebar();
+        }
+        // Ctrl+P: command palette
+        (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            app.overlay = Some(app::Overlay::Palette(ui::palette::PaletteState::new(app)));
+        }
+        // F1: key and command reference
+        (_, KeyCode::F(1)) => {
+            let help = format!("{}\n\n{}", commands::help(&app.custom_commands), ui::keymap::reference());
+            app.overlay = Some(app::Overlay::Popup(PopupState::text("Help", help)));
+        }
+        // Ctrl+T: the trace in full screen
+        (KeyModifiers::CONTROL, KeyCode::Char('t')) => {
+            app.open_trace_view();
+        }
+        // F11: zen mode, chat only
+        (_, KeyCode::F(11)) => {
+            app.toggle_zen();
+        }
+        // Alt+Left/Right move the chat/sidebar divider, Alt+Up/Down the status/tools one;
+        // with the sidebar under the chat, Alt+Up/Down move the divider instead
+        (KeyModifiers::ALT, KeyCode::Up) if app.layout_mode == LayoutMode::Horizontal => app.splits.resize_sidebar(5),
+        (KeyModifiers::ALT, KeyCode::Down) if app.layout_mode == LayoutMode::Horizontal => {
+            app.splits.resize_sidebar(-5);
+        }
+        (KeyModifiers::ALT, KeyCode::Left) if app.layout_mode == LayoutMode::Horizontal => app.splits.resize_status(-5),
+        (KeyModifiers::ALT, KeyCode::Right) if app.layout_mode == LayoutMode::Horizontal => app.splits.resize_status(5),
+        (KeyModifiers::ALT, KeyCode::Left) => app.splits.resize_sidebar(5),
+        (KeyModifiers::ALT, KeyCode::Right) => app.splits.resize_sidebar(-5),
+        (KeyModifiers::ALT, KeyCode::Up) => app.splits.resize_status(-5),
+        (KeyModifiers::ALT, KeyCode::Down) => app.splits.resize_status(5),
+        // Ctrl+F: search the chat
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) => {
+            app.focus = app::PanelFocus::Chat;
+            app.start_search();
+        }
+        // Tab cycles focus: Input → Chat → each sidebar tab; Shift+Tab goes back
+        // (in the input bar, Tab completes a slash command instead)
+        (_, KeyCode::Tab) if app.focus == app::PanelFocus::Input && app.input.starts_with('/') => return false,
+        (_, KeyCode::Tab) => app.cycle_focus(true),
+        (_, KeyCode::BackTab) => app.cycle_focus(false),
+        // Page Up/Down for scrolling (routes to focused panel)
+        (_, KeyCode::PageUp) => {
+            match app.focus {
+                app::PanelFocus::Input | app::PanelFocus::Chat => {
+                    app.scroll_chat_by(-(app.chat_page() as isize));
+                }
+                app::PanelFocus::Sidebar => match app.sidebar_tab {
+                    app::SidebarTab::Status => app.scroll_status(-5),
+                    app::SidebarTab::Trace => {
+                        let total = app.trace_log.len();
+                        let pos = app.trace_scroll.unwrap_or(total);
+                        app.trace_scroll = Some(pos.saturating_sub(5));
+                    }
+                    app::SidebarTab::Llm => app.scroll_llm_log(-5),
+                    app::SidebarTab::Files => app.scroll_files(-5),
+                },
+            }
+        }
+        (_, KeyCode::PageDown) => {
+            match app.focus {
+                app::PanelFocus::Input | app::PanelFocus::Chat => {
+                    app.scroll_chat_by(app.chat_page() as isize);
+                }
+                app::PanelFocus::Sidebar => match app.sidebar_tab {
+                    app::SidebarTab::Status => app.scroll_status(5),
+                    app::SidebarTab::Trace => {
+                        if let Some(pos) = app.trace_scroll {
+                            let total = app.trace_log.len();
+                            let new_pos = pos + 5;
+                            if new_pos >= total {
+                                app.trace_scroll = None;
+                            } else {
+                                app.trace_scroll = Some(new_pos);
+                            }
+                        }
+                    }
+                    app::SidebarTab::Llm => app.scroll_llm_log(5),
+                    app::SidebarTab::Files => app.scroll_files(5),
+                },
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the focused chat pane. False if `key` isn't one of them.
+fn chat_key(app: &mut App, key: KeyEvent) -> bool {
+    match (key.modifiers, key.code) {
+        // '/' starts a search, n/N jump between hits
+        (KeyModifiers::NONE, KeyCode::Char('/')) => app.start_search(),
+        (KeyModifiers::NONE, KeyCode::Char('n')) if app.search.is_some() => app.search_next(),
+        (_, KeyCode::Char('N')) if app.search.is_some() => app.search_prev(),
+        // Enter expands/collapses the selected tool or thought block, or pages through a message
         (_, KeyCode::Enter) => {
-            if app.agent_busy {
-                return;
+            if !app.open_pager() {
+                app.toggle_selected_tool();
             }
-            if let Some(text) = app.submit_input() {
-                app.add_message(ChatMessage::User(text.clone()));
+        }
+        // Up/Down move the selection (tool and thought blocks, or any message in selection mode)
+        (_, KeyCode::Up) => {
+            if app.selection_mode {
+                app.select_prev_message();
+            } else {
+                app.select_prev_tool();
+            }
+        }
+        (_, KeyCode::Down) => {
+            if app.selection_mode {
+                app.select_next_message();
+            } else {
+                app.select_next_tool();
+            }
+        }
+        // 'v' toggles selection mode, y/Y copy the selected message or its code blocks
+        (KeyModifiers::NONE, KeyCode::Char('v')) => app.toggle_selection_mode(),
+        (_, KeyCode::Char(c @ ('y' | 'Y'))) if app.selected().is_some() => copy_selected(app, c == 'Y'),
+        // 't' browses the selected tool call's args as a JSON tree
+        (KeyModifiers::NONE, KeyCode::Char('t')) => {
+            app.open_json_tree();
+        }
+        // 'r' shows the selected message's raw text
+        (KeyModifiers::NONE, KeyCode::Char('r')) => {
+            app.open_raw_view();
+        }
+        // 'h' hides or shows every thought block
+        (KeyModifiers::NONE, KeyCode::Char('h')) => {
+            let hidden = app.toggle_thoughts();
+            let thought = ui::icons::current().thought;
+            app.show_toast(if hidden { format!("{thought} Thoughts hidden (h to show)") } else { format!("{thought} Thoughts shown") });
+        }
+        // Ctrl+U/Ctrl+D scroll half a page, as in the pager
+        (KeyModifiers::CONTROL, KeyCode::Char('u')) => app.scroll_chat_by(-(app.chat_half_page() as isize)),
+        (KeyModifiers::CONTROL, KeyCode::Char('d')) => app.scroll_chat_by(app.chat_half_page() as isize),
+        // 'a' jumps back to the start of the latest answer
+        (KeyModifiers::NONE, KeyCode::Char('a')) => {
+            if !app.jump_to_last_answer() {
+                app.show_toast("No answer yet");
+            }
+        }
+        // 'o' opens the selected message's links in the browser, one per press
+        (KeyModifiers::NONE, KeyCode::Char('o')) => match app.next_link() {
+            Some((url, n, count)) => match links::open(&url) {
+                Ok(()) if count > 1 => app.show_toast(format!("{} Opened {url} ({n}/{count}, o for next)", ui::icons::current().link)),
+                Ok(()) => app.show_toast(format!("{} Opened {url}", ui::icons::current().link)),
+                Err(e) => app.show_toast(format!("Couldn't open {url}: {e}")),
+            },
+            None => app.show_toast("No links in this message"),
+        },
+        // g/Home and G/End jump to the top or bottom of the transcript
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => app.scroll_chat_to_top(),
+        (_, KeyCode::Char('G') | KeyCode::End) => app.scroll_chat_to_bottom(),
+        // 'p' reads the whole transcript in $PAGER
+        (KeyModifiers::NONE, KeyCode::Char('p')) => app.external_pager = true,
+        // 'e' exports the transcript as Markdown
+        (KeyModifiers::NONE, KeyCode::Char('e')) => export_transcript(app, ExportFormat::Markdown, None),
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the focused Trace tab. False if `key` isn't one of them.
+fn trace_key(app: &mut App, key: KeyEvent, command_tx: &mpsc::Sender<UiCommand>) -> bool {
+    match (key.modifiers, key.code) {
+        // Up/Down select stages, Enter opens the full-screen view
+        (_, KeyCode::Up) => app.select_prev_stage(),
+        (_, KeyCode::Down) => app.select_next_stage(),
+        (_, KeyCode::Enter) => app.open_trace_view(),
+        // 'r' re-runs the turn from a failed stage
+        (KeyModifiers::NONE, KeyCode::Char('r')) => match app.selected_failed_stage() {
+            Some(_) if app.agent_busy => app.show_toast("Agent is busy"),
+            Some(stage_id) => {
+                app.add_message(ChatMessage::System(format!("{} Retrying from stage '{stage_id}'", ui::icons::current().retry)));
                 app.agent_busy = true;
                 app.thinking_since = Some(Instant::now());
-                let _ = input_tx.send(text);
+                let _ = command_tx.send(UiCommand::Command(CommandResult::RetryStage(stage_id)));
+            }
+            None => app.show_toast("Select a failed stage to retry"),
+        },
+        // 'f' follows new entries again, 'e' exports the trace as JSON
+        (KeyModifiers::NONE, KeyCode::Char('f')) => {
+            app.trace_scroll = None;
+            app.selected_trace = None;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('e')) => {
+            let path = export::default_path("trace.json");
+            let content = serde_json::to_string_pretty(&export::trace_to_json(app)).unwrap_or_default();
+            match std::fs::write(&path, content) {
+                Ok(()) => app.show_toast(format!("{} Trace exported to {path}", ui::icons::current().exported)),
+                Err(e) => app.show_toast(format!("Export failed: {e}")),
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the focused LLM tab. False if `key` isn't one of them.
+fn llm_key(app: &mut App, key: KeyEvent) -> bool {
+    match (key.modifiers, key.code) {
+        // Up/Down scroll by a call, 'f' follows the newest again
+        (_, KeyCode::Up) => app.scroll_llm_log(-1),
+        (_, KeyCode::Down) => app.scroll_llm_log(1),
+        (KeyModifiers::NONE, KeyCode::Char('f')) => app.llm_log_scroll = None,
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the focused sidebar, whichever tab it shows. False if `key` isn't one of them.
+fn sidebar_key(app: &mut App, key: KeyEvent) -> bool {
+    match (key.modifiers, key.code) {
+        // Number keys switch tabs
+        (KeyModifiers::NONE, KeyCode::Char(c @ '1'..='4')) => app.focus_tab(app::SidebarTab::ALL[c as usize - '1' as usize]),
+        // Status and Files tabs: Up/Down scroll a line
+        (_, KeyCode::Up) if app.sidebar_tab == app::SidebarTab::Status => app.scroll_status(-1),
+        (_, KeyCode::Down) if app.sidebar_tab == app::SidebarTab::Status => app.scroll_status(1),
+        (_, KeyCode::Up) if app.sidebar_tab == app::SidebarTab::Files => app.scroll_files(-1),
+        (_, KeyCode::Down) if app.sidebar_tab == app::SidebarTab::Files => app.scroll_files(1),
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the input bar, which also takes whatever the focused panel
+/// didn't: typing from a panel returns to the input bar. `None` if nothing
+/// took `key`.
+fn input_key(app: &mut App, key: KeyEvent, command_tx: &mpsc::Sender<UiCommand>) -> Option<KeyContext> {
+    match (key.modifiers, key.code) {
+        // Enter: submit input
+        (_, KeyCode::Enter) => {
+            if app.focus != app::PanelFocus::Input {
+                return None;
+            }
+            if app.steering.is_paused() {
+                // During a pause the input steers the running turn
+                if let Some(text) = app.submit_input() {
+                    app.steer(text);
+                }
+            } else if !waits_for_agent(app, &app.input) {
+                // Background tasks and UI commands don't wait for the agent
+                if let Some(text) = app.submit_input() {
+                    submit(app, text, command_tx);
+                }
             }
         }
         // Backspace
@@ -277,65 +1005,429 @@ fn handle_key_event(app: &mut App, key: KeyEvent, input_tx: &mpsc::Sender<String
         (_, KeyCode::Down) => app.history_down(),
         (_, KeyCode::Home) => app.move_cursor_home(),
         (_, KeyCode::End) => app.move_cursor_end(),
-        // Tab toggles focus between Chat and Trace panels
-        (_, KeyCode::Tab) => {
-            app.focus = match app.focus {
-                app::PanelFocus::Chat => app::PanelFocus::Trace,
-                app::PanelFocus::Trace => app::PanelFocus::Chat,
-            };
+        // Tab completes a slash command
+        (_, KeyCode::Tab) => app.complete_input(),
+        // Regular character input; typing from a focused panel returns to the input bar
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            app.focus = app::PanelFocus::Input;
+            app.insert_char(c);
         }
-        // Page Up/Down for scrolling (routes to focused panel)
-        (_, KeyCode::PageUp) => {
-            match app.focus {
-                app::PanelFocus::Chat => {
-                    if app.scroll_offset == usize::MAX {
-                        let total = app.messages.len();
-                        app.scroll_offset = total.saturating_sub(10);
-                    }
-                    app.scroll_offset = app.scroll_offset.saturating_sub(10);
-                }
-                app::PanelFocus::Trace => {
-                    let total = app.trace_log.len();
-                    let pos = app.trace_scroll.unwrap_or(total);
-                    app.trace_scroll = Some(pos.saturating_sub(5));
+        _ => return None,
+    }
+    Some(KeyContext::Input)
+}
+
+/// Whether submitting `text` now has to wait for the agent's turn to end:
+/// anything but a command the UI handles itself.
+fn waits_for_agent(app: &App, text: &str) -> bool {
+    let text = app.expand_alias(text).unwrap_or_else(|| text.to_string());
+    app.agent_busy && !commands::process_command_with(&text, &app.custom_commands).is_ui_only()
+}
+
+/// Run a line from the input bar (or a popup): UI-only commands are handled
+/// here, everything else goes to the agent thread.
+fn submit(app: &mut App, mut text: String, command_tx: &mpsc::Sender<UiCommand>) {
+    if let Some(expanded) = app.expand_alias(&text) {
+        text = expanded;
+    }
+    // A prompt revised after /edit replaces the exchange it came from
+    if std::mem::take(&mut app.editing_prompt) && !text.starts_with('/') {
+        text = format!("/edit {text}");
+    }
+    app.add_message(ChatMessage::User(text.clone()));
+    // Commands that only touch UI state never reach the agent thread
+    match commands::process_command_with(&text, &app.custom_commands) {
+        CommandResult::Artifacts(action) => {
+            handle_artifacts(app, action);
+            return;
+        }
+        CommandResult::Export { format, path } => {
+            export_transcript(app, format, path);
+            return;
+        }
+        CommandResult::Goto(id) => {
+            if !app.goto_message(id) {
+                app.add_message(ChatMessage::Error(format!("No message #{id}")));
+            }
+            return;
+        }
+        CommandResult::Filter(action) => {
+            let summary = app.apply_filter(action);
+            if app.hidden_kinds.contains(&app::MessageKind::System) {
+                app.show_toast(summary);
+            } else {
+                app.add_message(ChatMessage::System(summary));
+            }
+            return;
+        }
+        CommandResult::Copy { code_only } => {
+            match app.last_answer().map(str::to_string) {
+                Some(answer) => copy_text(app, answer, code_only),
+                None => app.show_toast("No response to copy yet"),
+            }
+            return;
+        }
+        CommandResult::Files(None) => {
+            let report = app.files_report();
+            app.add_message(ChatMessage::System(report));
+            return;
+        }
+        CommandResult::Files(Some(path)) => {
+            match std::fs::write(&path, app.files_report() + "\n") {
+                Ok(()) => app.add_message(ChatMessage::System(format!("{} File list written to {path}", ui::icons::current().exported))),
+                Err(e) => app.add_message(ChatMessage::Error(format!("Can't write {path}: {e}"))),
+            }
+            return;
+        }
+        CommandResult::Alias(action) => {
+            let summary = app.apply_alias(action);
+            app.add_message(ChatMessage::System(summary));
+            return;
+        }
+        CommandResult::Diff(path) => {
+            if !app.open_diff(path.as_deref()) {
+                match path {
+                    Some(path) => app.show_toast(format!("No writes to {path} yet")),
+                    None => app.show_toast("No file writes yet"),
                 }
             }
+            return;
         }
-        (_, KeyCode::PageDown) => {
-            match app.focus {
-                app::PanelFocus::Chat => {
-                    app.scroll_offset = if app.scroll_offset == usize::MAX {
-                        usize::MAX
-                    } else {
-                        app.scroll_offset + 10
-                    };
+        CommandResult::Keys => {
+            app.overlay = Some(app::Overlay::Popup(PopupState::text("Keys", ui::keymap::reference())));
+            return;
+        }
+        CommandResult::Trace(action) => {
+            let summary = app.apply_trace(action);
+            app.add_message(ChatMessage::System(summary));
+            return;
+        }
+        CommandResult::Dismiss(n) => {
+            if app.dismiss_notices(n) == 0 {
+                app.show_toast("No such notice");
+            }
+            return;
+        }
+        CommandResult::Set { key, value } => {
+            match app.set_option(&key, value.as_deref()) {
+                Ok(summary) => app.show_toast(summary),
+                Err(e) => app.add_message(ChatMessage::Error(e)),
+            }
+            return;
+        }
+        CommandResult::Sidebar => {
+            app.toggle_sidebar();
+            return;
+        }
+        CommandResult::Zen => {
+            app.toggle_zen();
+            return;
+        }
+        CommandResult::Layout(Some(mode)) => {
+            app.layout_mode = mode;
+            return;
+        }
+        CommandResult::Layout(None) => {
+            let modes = vec!["vertical".to_string(), "horizontal".to_string()];
+            app.overlay = Some(app::Overlay::Popup(PopupState::picker("Layout", modes, "/layout")));
+            return;
+        }
+        CommandResult::Pager => {
+            app.external_pager = true;
+            return;
+        }
+        CommandResult::Theme(Some(name)) => {
+            match theme::preset(&name) {
+                Some(theme) => {
+                    ui::theme::set(theme);
+                    app.theme_before_preview = None;
+                    app.show_toast(format!("{} Theme: {name}", ui::icons::current().theme));
                 }
-                app::PanelFocus::Trace => {
-                    if let Some(pos) = app.trace_scroll {
-                        let total = app.trace_log.len();
-                        let new_pos = pos + 5;
-                        if new_pos >= total {
-                            app.trace_scroll = None;
-                        } else {
-                            app.trace_scroll = Some(new_pos);
-                        }
-                    }
+                None => {
+                    let names: Vec<&str> = theme::PRESETS.iter().map(|(name, _)| *name).collect();
+                    app.add_message(ChatMessage::Error(format!(
+                        "Unknown theme '{name}' (available: {})",
+                        names.join(", ")
+                    )));
                 }
             }
+            return;
         }
-        // Regular character input
-        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
-            app.insert_char(c);
+        CommandResult::Theme(None) => {
+            let names = theme::PRESETS.iter().map(|(name, _)| name.to_string()).collect();
+            app.theme_before_preview = Some(ui::theme::current());
+            app.overlay = Some(app::Overlay::Popup(PopupState::picker("Theme", names, "/theme")));
+            return;
+        }
+        CommandResult::Tab(action) => {
+            app.tab_request = Some(action);
+            return;
+        }
+        CommandResult::Background(prompt) => {
+            app.queue_background(prompt);
+            return;
+        }
+        CommandResult::CancelBackground(id) => {
+            app.cancel_background(id);
+            return;
+        }
+        CommandResult::Load(None) => {
+            let names = session_store::list(session_store::dir().as_deref());
+            if names.is_empty() {
+                app.add_message(ChatMessage::System("No saved sessions yet (/save [name] keeps one)".into()));
+            } else {
+                app.overlay = Some(app::Overlay::Popup(PopupState::picker("Saved sessions", names, "/load")));
+            }
+            return;
+        }
+        CommandResult::Restore(None) => {
+            let labels = session_store::list(app.checkpoint_dir.as_deref());
+            if labels.is_empty() {
+                app.add_message(ChatMessage::System("No checkpoints yet (/checkpoint [label] takes one)".into()));
+            } else {
+                app.overlay = Some(app::Overlay::Popup(PopupState::picker("Checkpoints", labels, "/restore")));
+            }
+            return;
+        }
+        CommandResult::Edit(EditAction::Load | EditAction::Editor) if app.agent_busy => {
+            app.add_message(ChatMessage::Error("Wait for the turn to finish before editing it".into()));
+            return;
+        }
+        CommandResult::Edit(action @ (EditAction::Load | EditAction::Editor)) => {
+            match app.last_prompt().map(str::to_string) {
+                Some(prompt) if action == EditAction::Load => app.edit_prompt(&prompt),
+                Some(_) => app.external_editor = true,
+                None => app.add_message(ChatMessage::Error("No prompt to edit yet".into())),
+            }
+            return;
+        }
+        // Lifting a safe-mode restriction asks for confirmation first
+        CommandResult::Unsafe { capability: Some(capability), confirmed: false } if app.locked.contains(&capability) => {
+            let prompt = format!("Enabling {} means {}. Enable it?", capability.name(), capability.risk());
+            let command = format!("/unsafe {} confirm", capability.name());
+            app.overlay = Some(app::Overlay::Popup(PopupState::confirm(format!("{} Safe mode", ui::icons::current().warning), prompt, command)));
+            return;
+        }
+        CommandResult::Image(_) if app.locked.contains(&commands::Capability::Attachments) => {
+            app.add_message(ChatMessage::Error(
+                "File attachments are disabled in safe mode (/unsafe attachments to allow)".into(),
+            ));
+            return;
+        }
+        CommandResult::Image(path) => {
+            if let Err(e) = app.show_image(std::path::Path::new(&path)) {
+                app.add_message(ChatMessage::Error(e));
+            }
+            return;
         }
         _ => {}
     }
+    app.agent_busy = true;
+    app.thinking_since = Some(Instant::now());
+    let _ = command_tx.send(UiCommand::parse(&text, &app.custom_commands));
+}
+
+/// Copy the selected message (or only its fenced code blocks) to the clipboard.
+fn copy_selected(app: &mut App, code_only: bool) {
+    let Some(msg) = app.selected() else {
+        return;
+    };
+    copy_text(app, msg.text(), code_only);
+}
+
+/// Copy `text`, or only its code blocks, and say so in a toast.
+fn copy_text(app: &mut App, text: String, code_only: bool) {
+    let text = if code_only {
+        let blocks = app::code_blocks(&text);
+        if blocks.is_empty() {
+            app.show_toast("No code blocks in this message");
+            return;
+        }
+        blocks.join("\n\n")
+    } else {
+        text
+    };
+    match clipboard::copy(&text) {
+        Ok(via) => app.show_toast(format!("{} Copied {} chars ({via})", ui::icons::current().copied, text.chars().count())),
+        Err(e) => app.show_toast(format!("Copy failed: {e}")),
+    }
+}
+
+/// Copy `text` from an overlay; returns the footer notice describing the result.
+fn copy_notice(text: &str, what: &str) -> String {
+    let copied = ui::icons::current().copied;
+    match clipboard::copy(text) {
+        Ok(via) if what == "chars" => format!("{copied} Copied {} chars ({via})", text.chars().count()),
+        Ok(via) => format!("{copied} Copied {what} ({via})"),
+        Err(e) => format!("Copy failed: {e}"),
+    }
+}
+
+/// Suspend the TUI to run `program` on the terminal, restoring the screen after.
+fn suspended<T>(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    program: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, crossterm::cursor::Show)?;
+    let result = program();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    result
+}
+
+/// Open `text` in `$VISUAL` or `$EDITOR` (default `vi`) and return it as saved.
+fn edit_in_external_editor(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, text: &str) -> Result<String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| "vi".into());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    // A fresh file only we can have opened: create_new refuses existing paths and symlinks
+    let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let path = std::env::temp_dir().join(format!("neocognos-prompt-{}-{stamp:x}.md", std::process::id()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path).and_then(|mut file| file.write_all(text.as_bytes()))?;
+    let result = suspended(terminal, || {
+        let status = std::process::Command::new(program)
+            .args(words)
+            .arg(&path)
+            .status()
+            .with_context(|| format!("couldn't run {program}"))?;
+        anyhow::ensure!(status.success(), "{program} exited with {status}");
+        Ok(std::fs::read_to_string(&path)?.trim_end().to_string())
+    });
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Suspend the TUI, pipe `text` into `$PAGER` (default `less -R`) and restore the
+/// screen once the pager exits.
+fn open_external_pager(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "more".into() } else { "less -R".into() });
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or("less");
+
+    suspended(terminal, || {
+        let mut child = std::process::Command::new(program)
+            .args(words)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .with_context(|| format!("couldn't run {program}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Quitting the pager early closes the pipe; that's not an error
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        child.wait()?;
+        Ok(())
+    })
+}
+
+/// Write the transcript to `path` (or a timestamped file in the working directory).
+fn export_transcript(app: &mut App, format: ExportFormat, path: Option<String>) {
+    let (content, extension) = match format {
+        ExportFormat::Markdown => (export::to_markdown(app), "md"),
+        ExportFormat::Json => (
+            serde_json::to_string_pretty(&export::to_json(app)).unwrap_or_default(),
+            "json",
+        ),
+        ExportFormat::Html => (export::to_html(app), "html"),
+        ExportFormat::Bundle => return export_bundle(app, path.unwrap_or_default()),
+    };
+    let path = path.unwrap_or_else(|| export::default_path(extension));
+    match std::fs::write(&path, content) {
+        Ok(()) => {
+            app.add_message(ChatMessage::System(format!("{} Transcript exported to {path}", ui::icons::current().exported)));
+            app.exports.push(path);
+        }
+        Err(e) => app.add_message(ChatMessage::Error(format!("Export failed: {e}"))),
+    }
+}
+
+/// Write the `/export <dir>` bundle.
+fn export_bundle(app: &mut App, dir: String) {
+    match export::write_bundle(app, std::path::Path::new(&dir)) {
+        Ok(()) => {
+            app.add_message(ChatMessage::System(format!(
+                "{} Session exported to {dir}/ ({})",
+                ui::icons::current().exported,
+                export::BUNDLE_FILES.join(", ")
+            )));
+            app.exports.push(dir);
+        }
+        Err(e) => app.add_message(ChatMessage::Error(format!("Export failed: {e:#}"))),
+    }
+}
+
+/// Handle `/artifacts` against the artifacts collected from stage events.
+fn handle_artifacts(app: &mut App, action: ArtifactsAction) {
+    match action {
+        ArtifactsAction::List => {
+            if app.artifacts.is_empty() {
+                app.add_message(ChatMessage::System("No artifacts produced yet.".into()));
+                return;
+            }
+            let mut msg = String::from("Artifacts (/artifacts <n> to preview, /artifacts export <n> <path>):");
+            for (i, a) in app.artifacts.iter().enumerate() {
+                msg.push_str(&format!(
+                    "\n  {}. {} [{}] — {} lines",
+                    i + 1,
+                    a.name,
+                    a.stage_id,
+                    a.content.lines().count(),
+                ));
+            }
+            app.add_message(ChatMessage::System(msg));
+        }
+        ArtifactsAction::Show(target) => {
+            let Some(artifact) = app.find_artifact(&target) else {
+                app.add_message(ChatMessage::Error(format!("No artifact '{target}'")));
+                return;
+            };
+            if let Some(path) = artifact.image_path() {
+                if let Err(e) = app.show_image(&path) {
+                    app.add_message(ChatMessage::Error(e));
+                }
+                return;
+            }
+            const PREVIEW_LINES: usize = 40;
+            let total = artifact.content.lines().count();
+            let mut msg = format!("{} {} [{}]", ui::icons::current().artifact, artifact.name, artifact.stage_id);
+            for line in artifact.content.lines().take(PREVIEW_LINES) {
+                msg.push_str(&format!("\n  {line}"));
+            }
+            if total > PREVIEW_LINES {
+                msg.push_str(&format!("\n  … {} more lines (export to see all)", total - PREVIEW_LINES));
+            }
+            app.add_message(ChatMessage::System(msg));
+        }
+        ArtifactsAction::Export { target, path } => {
+            let Some(artifact) = app.find_artifact(&target) else {
+                app.add_message(ChatMessage::Error(format!("No artifact '{target}'")));
+                return;
+            };
+            let msg = match std::fs::write(&path, &artifact.content) {
+                Ok(()) => ChatMessage::System(format!("{} Exported '{}' to {path}", ui::icons::current().artifact, artifact.name)),
+                Err(e) => ChatMessage::Error(format!("Failed to export artifact: {e}")),
+            };
+            app.add_message(msg);
+        }
+    }
 }
 
 /// Try to extract a file path from a tool call message.
 fn extract_file_path(msg: &Option<&ChatMessage>) -> Option<String> {
-    if let Some(ChatMessage::ToolCall { args_short, .. }) = msg {
+    if let Some(ChatMessage::ToolCall { args, .. }) = msg {
         // Simple heuristic: look for path-like strings
-        let args = args_short.trim();
+        let args = args.trim();
         if args.contains('/') || args.contains('.') {
             // Take first token that looks like a path
             for token in args.split_whitespace() {
@@ -348,3 +1440,61 @@ fn extract_file_path(msg: &Option<&ChatMessage>) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ui::keymap::{self, BINDINGS};
+
+    /// An app focused on `context`'s panel, with what its keys act on: a busy
+    /// agent and a half-typed command for the input bar, a selected message and
+    /// a finished search for the chat pane.
+    fn app_in(context: KeyContext) -> App {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::User("hi".into()));
+        app.add_message(ChatMessage::Assistant("hello".into()));
+        match context {
+            KeyContext::Input => {
+                app.agent_busy = true;
+                "/he".chars().for_each(|c| app.insert_char(c));
+            }
+            KeyContext::Chat => {
+                app.focus = app::PanelFocus::Chat;
+                app.toggle_selection_mode();
+                app.start_search();
+                app.search.as_mut().unwrap().editing = false;
+            }
+            KeyContext::Trace => app.focus_tab(app::SidebarTab::Trace),
+            KeyContext::Llm => app.focus_tab(app::SidebarTab::Llm),
+            KeyContext::Sidebar => app.focus_tab(app::SidebarTab::Status),
+            KeyContext::Global | KeyContext::TraceView => {}
+        }
+        app
+    }
+
+    #[test]
+    fn test_bindings_reach_their_context() {
+        let (command_tx, _command_rx) = mpsc::channel();
+        // The full-screen trace handles its own keys; exporting and copying
+        // would write files and the clipboard
+        let dispatched = BINDINGS
+            .iter()
+            .filter(|b| b.context != KeyContext::TraceView && !b.action.starts_with("export") && !b.action.starts_with("copy"));
+        for binding in dispatched {
+            for key in keymap::key_events(binding.keys).unwrap_or_default() {
+                let mut app = app_in(binding.context);
+                let context = match tab_shortcut(key) {
+                    Some(_) => Some(KeyContext::Global),
+                    None => handle_key_event(&mut app, key, &command_tx),
+                };
+                assert_eq!(context, Some(binding.context), "{} {key:?}", binding.keys);
+            }
+        }
+
+        // A key the chat pane doesn't bind is typed into the input bar
+        let mut app = app_in(KeyContext::Chat);
+        let context = handle_key_event(&mut app, KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE), &command_tx);
+        assert_eq!(context, Some(KeyContext::Input));
+        assert_eq!(app.focus, app::PanelFocus::Input);
+    }
+}
@@ -1,14 +1,19 @@
 //! Agent session management — wraps kernel AgentLoop with TUI-specific callbacks.
 
-use std::collections::HashMap;
-use std::sync::{mpsc, Arc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use neocognos_kernel::events::{EventBus, EventListener, EventKind, KernelEvent};
 use neocognos_kernel::llm::{AnthropicClient, ClaudeCliClient, LlmClient, MockLlmClient, MockStrategy, OllamaClient};
-use neocognos_kernel::loop_runner::AgentLoop;
+use neocognos_kernel::loop_runner::{AgentLoop, RunResult};
 use neocognos_kernel::module_loader::ModuleRegistry;
-use neocognos_kernel::policy::PolicyEngine;
+use neocognos_kernel::policy::{PolicyDecision, PolicyEngine};
 use neocognos_kernel::workflow_router::CompiledRouter;
 use neocognos_modules::about_me::AboutMeModule;
 use neocognos_modules::exec_tool::ExecModule;
@@ -21,38 +26,293 @@ use neocognos_modules::semantic_memory::SemanticMemoryModule;
 use neocognos_modules::session_memory::SessionMemoryModule;
 use neocognos_protocol::*;
 
-use crate::agent_thread::AgentEvent;
+use crate::agent_thread::{AgentEvent, EventSender};
+use crate::app::{self, ContextEntry, Steering, TurnRecord};
+use crate::bench::BenchRun;
+use crate::commands::{Capability, CompactStrategy};
+use crate::diff;
+use crate::env_file;
+use crate::session_store;
+use crate::ui::icons;
+
+/// Longest tool output kept for the chat preview, in characters.
+const MAX_TOOL_OUTPUT: usize = 16_000;
+
+/// Tool outputs and errors by call id. Executors record them and the event
+/// listener attaches them to ToolCallCompleted, which the kernel emits without.
+type ToolOutputs = Arc<Mutex<HashMap<String, (String, Option<String>)>>>;
+
+/// One LLM call and the provider that served it.
+#[derive(Debug, Clone)]
+struct LlmCall {
+    provider: String,
+    model: String,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+}
+
+/// LLM calls since the last turn finished, recorded by the event listener so
+/// the session can cost each at its own provider's and model's price.
+type LlmCalls = Arc<Mutex<Vec<LlmCall>>>;
+
+/// Prompt and completion tokens of the LLM calls made on a throwaway agent,
+/// for `/bench` and `/model --check`.
+#[derive(Clone, Default)]
+struct CallUsage(Arc<Mutex<(usize, usize)>>);
+
+impl EventListener for CallUsage {
+    fn on_event(&self, event: &KernelEvent) {
+        if let EventKind::LlmCallCompleted { prompt_tokens, completion_tokens, .. } = &event.event {
+            if let Ok(mut tokens) = self.0.lock() {
+                tokens.0 += prompt_tokens;
+                tokens.1 += completion_tokens;
+            }
+        }
+    }
+}
+
+/// The provider the session runs on, for the event listener to tag calls with;
+/// a failover mid-turn changes it between calls.
+type ActiveProvider = Arc<Mutex<String>>;
+
+/// Name and outcome of each tool call since the last turn finished, for the
+/// turn's record in [`SessionStats`].
+type ToolCalls = Arc<Mutex<Vec<(String, bool)>>>;
+
+/// Capabilities `--safe-mode` still withholds, shared with the tool executors.
+type Locked = Arc<Mutex<BTreeSet<Capability>>>;
+
+/// The directory a session's tools and `!` commands run in: the manifest's
+/// `workdir`, or where the TUI started. `/workdir reset` changes it. A tab's
+/// background queue and the agent it hands off to share it.
+pub type Workdir = Arc<Mutex<PathBuf>>;
+
+/// Arguments of the file and search tools that name a path.
+const PATH_ARGUMENTS: &[&str] = &["path", "directory"];
+
+/// `call` as it runs in `dir`. The module executors only know the process's
+/// working directory, which every tab shares, so relative paths of the file
+/// and search tools are resolved against `dir` (a listing or search without a
+/// path starts there), and `exec` runs its command through `sh` after a `cd`.
+fn in_workdir(call: &ToolCall, dir: &Path) -> ToolCall {
+    let mut call = call.clone();
+    let module = BUILTIN_TOOLS.iter().find(|(tool, ..)| *tool == call.name).map(|(_, module, _)| *module);
+    let Some(arguments) = call.arguments.as_object_mut() else { return call };
+    match module {
+        Some("exec") => {
+            let command = arguments.remove("command").unwrap_or_else(|| "echo".into());
+            let args = arguments.remove("args").and_then(|args| args.as_array().cloned()).unwrap_or_default();
+            let mut wrapped = vec!["-c".into(), "cd -- \"$0\" && exec \"$@\"".into(), dir.display().to_string().into(), command];
+            wrapped.extend(args);
+            arguments.insert("command".into(), "sh".into());
+            arguments.insert("args".into(), wrapped.into());
+        }
+        Some("file_tools" | "search_tools") => {
+            let lists = matches!(call.name.as_str(), "list_directory" | "grep" | "find");
+            if lists && !PATH_ARGUMENTS.iter().any(|key| arguments.contains_key(*key)) {
+                arguments.insert("path".into(), dir.display().to_string().into());
+            }
+            for key in PATH_ARGUMENTS {
+                if let Some(path) = arguments.get_mut(*key) {
+                    if let Some(relative) = path.as_str().filter(|path| Path::new(path).is_relative()) {
+                        *path = dir.join(relative).display().to_string().into();
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    call
+}
+
+/// State every tool executor shares.
+#[derive(Clone, Default)]
+struct ToolHooks {
+    outputs: ToolOutputs,
+    locked: Locked,
+    steering: Steering,
+    workdir: Workdir,
+}
+
+/// Wrap a tool executor so safe mode can refuse the call, a paused turn waits
+/// before it, it runs in the session's workdir, its output (or error) is
+/// recorded for the chat preview, and guidance typed during the pause rides
+/// along with the result.
+fn recording<F>(hooks: &ToolHooks, execute: F) -> impl Fn(&ToolCall) -> Result<ToolResult> + Send + Sync + 'static
+where
+    F: Fn(&ToolCall) -> Result<ToolResult> + Send + Sync + 'static,
+{
+    let hooks = hooks.clone();
+    move |call| {
+        hooks.steering.wait_while_paused();
+        let locked = hooks.locked.lock().map(|l| l.contains(&Capability::Tools)).unwrap_or(true);
+        let mut result = if locked {
+            Err(anyhow::anyhow!("Tools are disabled in safe mode (/unsafe tools to allow)"))
+        } else {
+            let dir = hooks.workdir.lock().map(|dir| dir.clone()).unwrap_or_default();
+            execute(&in_workdir(call, &dir))
+        };
+        let recorded = match &result {
+            Ok(result) => (truncate_output(&result.output), None),
+            Err(e) => (String::new(), Some(format!("{e:#}"))),
+        };
+        if let Ok(mut outputs) = hooks.outputs.lock() {
+            outputs.insert(call.id.clone(), recorded);
+        }
+        // The chat already shows the guidance as the user's; only the model needs it here
+        if let Ok(result) = &mut result {
+            if let Some(guidance) = hooks.steering.take_guidance() {
+                result.output.push_str(&format!("\n\n[The user paused you to add: {guidance}]"));
+            }
+        }
+        result
+    }
+}
+
+/// Register `execute` as tool `name` of `module`, wrapped by [`recording`],
+/// and add it to `tools`, the list `/tools` shows.
+fn register_tool<F>(agent: &mut AgentLoop, tools: &mut Vec<ToolInfo>, hooks: &ToolHooks, name: &str, module: &str, execute: F)
+where
+    F: Fn(&ToolCall) -> Result<ToolResult> + Send + Sync + 'static,
+{
+    agent.register_tool_executor(name, Arc::new(recording(hooks, execute)));
+    let description = BUILTIN_TOOLS.iter().find(|(tool, ..)| *tool == name).map_or("", |(.., description)| description);
+    tools.push(ToolInfo { name: name.to_string(), module: module.to_string(), description: description.to_string() });
+}
+
+/// Why a tool that returned a failed result failed: the last non-empty line of
+/// its output, where errors usually end up.
+fn failure_reason(output: &str) -> String {
+    output.lines().rev().map(str::trim).find(|line| !line.is_empty()).unwrap_or("failed without output").to_string()
+}
+
+fn truncate_output(output: &str) -> String {
+    match output.char_indices().nth(MAX_TOOL_OUTPUT) {
+        Some((cut, _)) => format!("{}\n… truncated ({} more bytes)", &output[..cut], output.len() - cut),
+        None => output.to_string(),
+    }
+}
+
+/// A file a tool call names, as it was before the call.
+struct PendingWrite {
+    /// As the call gave it.
+    path: String,
+    /// In the workdir.
+    target: PathBuf,
+    /// `None` if it didn't exist.
+    before: Option<String>,
+}
 
 /// TUI event listener that sends events through an mpsc channel.
 struct ChannelEventListener {
-    tx: mpsc::Sender<AgentEvent>,
+    tx: EventSender,
+    /// The files running tool calls name, by call id.
+    pending_writes: Mutex<HashMap<String, PendingWrite>>,
+    tool_outputs: ToolOutputs,
+    llm_calls: LlmCalls,
+    provider: ActiveProvider,
+    tool_calls: ToolCalls,
+    /// A paused turn waits here before its next stage.
+    steering: Steering,
+    /// What relative paths in tool arguments are relative to.
+    workdir: Workdir,
+}
+
+impl ChannelEventListener {
+    fn new(
+        tx: EventSender,
+        tool_outputs: ToolOutputs,
+        llm_calls: LlmCalls,
+        provider: ActiveProvider,
+        tool_calls: ToolCalls,
+        steering: Steering,
+        workdir: Workdir,
+    ) -> Self {
+        Self { tx, pending_writes: Mutex::new(HashMap::new()), tool_outputs, llm_calls, provider, tool_calls, steering, workdir }
+    }
+
+    /// Snapshot the file a tool call names so a change to it, by whatever tool,
+    /// can be diffed afterwards. The built-in tools that only read are skipped.
+    fn before_write(&self, call_id: &str, tool: &str, arguments: &str) {
+        if READ_ONLY_TOOLS.contains(&tool) {
+            return;
+        }
+        let Some(path) = write_target(arguments) else { return };
+        let target = self.workdir.lock().map(|dir| dir.join(&path)).unwrap_or_else(|_| PathBuf::from(&path));
+        let before = std::fs::read_to_string(&target).ok();
+        if let Ok(mut pending) = self.pending_writes.lock() {
+            pending.insert(call_id.to_string(), PendingWrite { path, target, before });
+        }
+    }
+
+    /// Diff the file the call named against its snapshot; created and deleted
+    /// files diff against nothing.
+    fn after_write(&self, call_id: &str) {
+        let Some(PendingWrite { path, target, before }) = self.pending_writes.lock().ok().and_then(|mut p| p.remove(call_id)) else {
+            return;
+        };
+        let after = std::fs::read_to_string(&target).ok();
+        if let Some(diff) = diff::unified(&path, &before.unwrap_or_default(), &after.unwrap_or_default()) {
+            let _ = self.tx.send(AgentEvent::FileDiff { path, diff });
+        }
+    }
+}
+
+/// Built-in tools that never change the files they name.
+const READ_ONLY_TOOLS: &[&str] = &["read_file", "list_directory", "grep", "find"];
+
+/// The `path` (or `file_path`) argument of a file tool call.
+fn write_target(arguments: &str) -> Option<String> {
+    let args: serde_json::Value = serde_json::from_str(arguments).ok()?;
+    args.get("path").or_else(|| args.get("file_path"))?.as_str().map(str::to_string)
+}
+
+/// Stage ids of a workflow definition, in order, for the sidebar pipeline.
+fn stage_ids(workflow_yaml: &str) -> Vec<String> {
+    let Ok(workflow) = serde_yaml::from_str::<serde_yaml::Value>(workflow_yaml) else { return Vec::new() };
+    let Some(stages) = workflow.get("stages").and_then(|s| s.as_sequence()) else { return Vec::new() };
+    stages.iter().filter_map(|stage| stage.get("id")?.as_str().map(str::to_string)).collect()
 }
 
 impl EventListener for ChannelEventListener {
     fn on_event(&self, event: &KernelEvent) {
         match &event.event {
-            EventKind::ToolCallStarted { tool_name, arguments, .. } => {
-                let args_short = if arguments.len() > 60 {
-                    format!("{}...", &arguments[..57])
-                } else {
-                    arguments.clone()
-                };
+            EventKind::ToolCallStarted { tool_name, arguments, call_id } => {
+                self.before_write(call_id, tool_name, arguments);
                 let _ = self.tx.send(AgentEvent::ToolCallStarted {
                     name: tool_name.clone(),
-                    args: args_short,
+                    args: arguments.clone(),
                 });
             }
-            EventKind::ToolCallCompleted { tool_name, success, duration_ms, .. } => {
+            EventKind::ToolCallCompleted { tool_name, success, duration_ms, call_id } => {
+                let (output, error) = self.tool_outputs.lock().ok().and_then(|mut o| o.remove(call_id)).unwrap_or_default();
+                if let Ok(mut calls) = self.tool_calls.lock() {
+                    calls.push((tool_name.clone(), *success));
+                }
+                // A tool that ran but reported failure explains itself in its output
+                let error = error.or_else(|| (!success).then(|| failure_reason(&output)));
                 let _ = self.tx.send(AgentEvent::ToolCallCompleted {
                     name: tool_name.clone(),
                     success: *success,
                     duration_ms: *duration_ms,
+                    output,
+                    error,
                 });
+                self.after_write(call_id);
             }
             EventKind::LlmNarration { text, .. } => {
                 let _ = self.tx.send(AgentEvent::Narration(text.clone()));
             }
             EventKind::LlmCallCompleted { model, prompt_tokens, completion_tokens, duration_ms, .. } => {
+                let provider = self.provider.lock().map(|p| p.clone()).unwrap_or_default();
+                if let Ok(mut calls) = self.llm_calls.lock() {
+                    calls.push(LlmCall {
+                        provider,
+                        model: model.clone(),
+                        prompt_tokens: *prompt_tokens,
+                        completion_tokens: *completion_tokens,
+                    });
+                }
                 let _ = self.tx.send(AgentEvent::LlmCall {
                     model: model.clone(),
                     prompt_tokens: *prompt_tokens,
@@ -61,6 +321,7 @@ impl EventListener for ChannelEventListener {
                 });
             }
             EventKind::StageStarted { stage_id, stage_kind, .. } => {
+                self.steering.wait_while_paused();
                 let _ = self.tx.send(AgentEvent::StageStarted {
                     stage_id: stage_id.clone(),
                     stage_kind: stage_kind.clone(),
@@ -73,17 +334,71 @@ impl EventListener for ChannelEventListener {
                     skipped: *skipped,
                 });
             }
+            EventKind::StageFailed { stage_id, error, .. } => {
+                let _ = self.tx.send(AgentEvent::StageFailed {
+                    stage_id: stage_id.clone(),
+                    error: error.clone(),
+                });
+            }
+            EventKind::ArtifactProduced { stage_id, name, content, .. } => {
+                let _ = self.tx.send(AgentEvent::Artifact {
+                    stage_id: stage_id.clone(),
+                    name: name.clone(),
+                    content: content.clone(),
+                });
+            }
             _ => {}
         }
     }
 }
 
+/// Per-million-token prices for a provider/model.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelPricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl Default for ModelPricing {
+    /// Sonnet pricing.
+    fn default() -> Self {
+        Self { input_per_mtok: 3.0, output_per_mtok: 15.0 }
+    }
+}
+
+/// Pricing used for cost estimates. Local and subscription providers cost nothing per token.
+pub fn pricing_for(provider: &str, model: &str) -> ModelPricing {
+    match provider {
+        "anthropic" if model.contains("haiku") => ModelPricing { input_per_mtok: 0.8, output_per_mtok: 4.0 },
+        "anthropic" if model.contains("opus") => ModelPricing { input_per_mtok: 15.0, output_per_mtok: 75.0 },
+        "anthropic" => ModelPricing::default(),
+        _ => ModelPricing { input_per_mtok: 0.0, output_per_mtok: 0.0 },
+    }
+}
+
+/// Tokens and cost attributed to one model.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub cost: f64,
+}
+
 /// Session statistics displayed in the status bar.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SessionStats {
     pub total_prompt_tokens: usize,
     pub total_completion_tokens: usize,
     pub total_turns: usize,
+    /// Pricing of the active provider, for usage not attributed to a model.
+    #[serde(skip)]
+    pub pricing: ModelPricing,
+    /// Usage by model name, kept across compactions.
+    pub per_model: BTreeMap<String, ModelUsage>,
+    /// Every turn run this session, failed ones included, for `/stats`.
+    #[serde(default)]
+    pub turns: Vec<TurnRecord>,
+    cost: f64,
 }
 
 impl SessionStats {
@@ -91,14 +406,346 @@ impl SessionStats {
         self.total_prompt_tokens + self.total_completion_tokens
     }
 
+    /// Add token usage by `model`, costed at `pricing`. Returns the cost.
+    pub fn record_usage(&mut self, model: &str, pricing: ModelPricing, prompt_tokens: usize, completion_tokens: usize) -> f64 {
+        let cost = prompt_tokens as f64 * pricing.input_per_mtok / 1_000_000.0
+            + completion_tokens as f64 * pricing.output_per_mtok / 1_000_000.0;
+        self.total_prompt_tokens += prompt_tokens;
+        self.total_completion_tokens += completion_tokens;
+        self.cost += cost;
+        let usage = self.per_model.entry(model.to_string()).or_default();
+        usage.prompt_tokens += prompt_tokens;
+        usage.completion_tokens += completion_tokens;
+        usage.cost += cost;
+        cost
+    }
+
+    /// Add `calls` to `turn` and the totals, each costed at the price of the
+    /// provider and model that made it. Without calls, `fallback` (a model and
+    /// the turn's total tokens) stands in, at the active provider's price.
+    fn record_calls(&mut self, turn: &mut TurnRecord, calls: Vec<LlmCall>, fallback: Option<(&str, usize)>) {
+        if let (true, Some((model, tokens))) = (calls.is_empty(), fallback) {
+            turn.prompt_tokens = tokens;
+            turn.cost = self.record_usage(model, self.pricing, tokens, 0);
+        }
+        for call in calls {
+            let pricing = pricing_for(&call.provider, &call.model);
+            turn.prompt_tokens += call.prompt_tokens;
+            turn.completion_tokens += call.completion_tokens;
+            turn.cost += self.record_usage(&call.model, pricing, call.prompt_tokens, call.completion_tokens);
+        }
+    }
+
     pub fn estimated_cost(&self) -> f64 {
-        let input_cost = self.total_prompt_tokens as f64 * 3.0 / 1_000_000.0;
-        let output_cost = self.total_completion_tokens as f64 * 15.0 / 1_000_000.0;
-        input_cost + output_cost
+        self.cost
+    }
+
+    /// Hand over the usage recorded so far, starting again from nothing at the
+    /// same pricing.
+    pub fn take(&mut self) -> SessionStats {
+        let pricing = self.pricing;
+        std::mem::replace(self, SessionStats { pricing, ..Default::default() })
+    }
+
+    /// Add usage recorded by another session, such as the background queue's.
+    pub fn absorb(&mut self, other: SessionStats) {
+        self.total_prompt_tokens += other.total_prompt_tokens;
+        self.total_completion_tokens += other.total_completion_tokens;
+        self.total_turns += other.total_turns;
+        self.cost += other.cost;
+        for (model, usage) in other.per_model {
+            let total = self.per_model.entry(model).or_default();
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.cost += usage.cost;
+        }
+        self.turns.extend(other.turns);
+    }
+}
+
+/// Error a turn ends with when the user canceled it.
+#[derive(Debug)]
+pub struct TurnCanceled;
+
+impl std::fmt::Display for TurnCanceled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Turn canceled")
+    }
+}
+
+impl std::error::Error for TurnCanceled {}
+
+/// Error a turn ends with when it outlived the manifest's `turn_timeout_secs`.
+#[derive(Debug)]
+pub struct TurnTimedOut(u64);
+
+impl std::fmt::Display for TurnTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Turn timed out after {}s (turn_timeout_secs in the manifest) and was aborted", self.0)
+    }
+}
+
+impl std::error::Error for TurnTimedOut {}
+
+/// Counts a turn down against its timeout on a thread of its own, sending the
+/// seconds left once it is into the last quarter and setting the cancel flag
+/// when none are. Time spent paused for steering doesn't count. Dropping it
+/// stops the count.
+struct Watchdog {
+    timeout: Duration,
+    fired: Arc<AtomicBool>,
+    _stop: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    fn start(timeout: Duration, cancel: Arc<AtomicBool>, steering: Steering, event_tx: Option<EventSender>) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = fired.clone();
+        std::thread::spawn(move || {
+            let tick = Duration::from_secs(1).min(timeout);
+            let mut left = timeout;
+            // The sender is dropped when the turn ends, which ends the wait
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(tick) {
+                if steering.is_paused() {
+                    continue;
+                }
+                left = left.saturating_sub(tick);
+                if left.is_zero() {
+                    flag.store(true, Ordering::Relaxed);
+                    cancel.store(true, Ordering::Relaxed);
+                    break;
+                }
+                if left <= timeout / 4 {
+                    if let Some(ref tx) = event_tx {
+                        let _ = tx.send(AgentEvent::TimeoutCountdown(left.as_secs()));
+                    }
+                }
+            }
+        });
+        Self { timeout, fired, _stop: stop }
+    }
+
+    /// The error for a turn that stopped on the cancel flag: timed out if this
+    /// set it, else canceled by the user.
+    fn interrupted(watchdog: Option<&Watchdog>) -> anyhow::Error {
+        match watchdog.filter(|w| w.fired.load(Ordering::Relaxed)) {
+            Some(w) => TurnTimedOut(w.timeout.as_secs()).into(),
+            None => TurnCanceled.into(),
+        }
+    }
+}
+
+/// Sleep for `wait` in short slices, stopping early once `flag` is set. True
+/// if it was.
+fn sleep_unless_set(flag: &AtomicBool, wait: Duration) -> bool {
+    let until = Instant::now() + wait;
+    while !flag.load(Ordering::Relaxed) {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        std::thread::sleep(left.min(CANCEL_POLL));
+    }
+    true
+}
+
+/// Default model for a provider when none is given.
+fn default_model(provider: &str) -> String {
+    if provider == "anthropic" || provider == "claude-cli" {
+        "sonnet".to_string()
+    } else {
+        "llama3.2:3b".to_string()
+    }
+}
+
+/// Parse a failover chain like `anthropic:sonnet,claude-cli,ollama:llama3.2:3b`.
+pub fn parse_failover_chain(spec: &str) -> Vec<(String, String)> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((provider, model)) => (provider.to_string(), model.to_string()),
+            None => (entry.to_string(), default_model(entry)),
+        })
+        .collect()
+}
+
+/// Providers a model can be prefixed with, as in `anthropic:sonnet`.
+const PROVIDERS: &[&str] = &["anthropic", "ollama", "claude-cli"];
+
+/// Provider and model from `--provider` and a model that may carry a
+/// `provider:` prefix. Ollama is assumed when neither names a provider.
+fn resolve_provider_model(provider: Option<String>, model: Option<String>) -> (String, String) {
+    match (provider, model) {
+        (Some(p), Some(m)) => {
+            let model = match m.strip_prefix(&format!("{p}:")) {
+                Some(rest) => rest.to_string(),
+                None => m,
+            };
+            (p, model)
+        }
+        (None, Some(m)) => match m.split_once(':') {
+            Some((pfx, rest)) if PROVIDERS.contains(&pfx) => (pfx.to_string(), rest.to_string()),
+            _ => ("ollama".to_string(), m),
+        },
+        (Some(p), None) => {
+            let default = default_model(&p);
+            (p, default)
+        }
+        (None, None) => ("ollama".to_string(), "llama3.2:3b".to_string()),
+    }
+}
+
+/// Models installed in the local Ollama, from `ollama list`; empty when it isn't installed.
+pub fn ollama_models() -> Vec<String> {
+    if !on_path("ollama") {
+        return Vec::new();
+    }
+    let Ok(out) = std::process::Command::new("ollama").arg("list").output() else {
+        return Vec::new();
+    };
+    // A NAME/ID/SIZE/MODIFIED header, then a model per line
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether an executable called `name` is on `PATH`.
+fn on_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+}
+
+/// Build an LLM client for a provider/model pair.
+fn build_llm_client(
+    provider: &str,
+    model: &str,
+    api_key: Option<&str>,
+    ollama_url: &str,
+) -> Result<Arc<dyn LlmClient>> {
+    Ok(match provider {
+        "anthropic" => {
+            let api_key = api_key
+                .map(str::to_string)
+                .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Anthropic API key not found (use --api-key, ANTHROPIC_API_KEY or --env-file)"
+                ))?;
+            Arc::new(AnthropicClient::new(model, &api_key))
+        }
+        "claude-cli" => Arc::new(ClaudeCliClient::new(model)),
+        "mock" => Arc::new(MockLlmClient::new(MockStrategy::Echo)),
+        _ => Arc::new(OllamaClient::new(model, ollama_url)),
+    })
+}
+
+/// Why a provider call failed, as far as trying it again goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProviderFailure {
+    /// Rate limited or overloaded: the same provider is worth another try after
+    /// this many seconds.
+    RateLimited(u64),
+    /// Out of quota, credits or budget: retrying the same provider won't help.
+    Exhausted,
+    Other,
+}
+
+impl ProviderFailure {
+    /// Classify `error` from the messages in its chain; the kernel's clients
+    /// report provider errors as text. `attempt` counts the rate-limit retries
+    /// so far, for the backoff.
+    fn of(error: &anyhow::Error, attempt: u32) -> Self {
+        let message = error.chain().map(ToString::to_string).collect::<Vec<_>>().join(": ").to_lowercase();
+        if is_exhaustion_error(&message) {
+            ProviderFailure::Exhausted
+        } else if let Some(secs) = rate_limit_delay(&message, attempt) {
+            ProviderFailure::RateLimited(secs)
+        } else {
+            ProviderFailure::Other
+        }
+    }
+}
+
+/// HTTP status a provider error carries: a 4xx/5xx code that opens the message
+/// or follows "status", "http", "code" or "error", so ids and token counts
+/// that happen to contain one don't count.
+fn http_status(lower: &str) -> Option<u16> {
+    let words: Vec<&str> = lower.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    words.iter().enumerate().find_map(|(i, word)| {
+        let code = word.parse::<u16>().ok().filter(|code| (400..600).contains(code))?;
+        let labeled = i == 0 || ["status", "http", "code", "error"].contains(&words[i - 1]);
+        labeled.then_some(code)
+    })
+}
+
+/// Errors that won't go away by retrying the same provider: HTTP 402, or a
+/// message about quota, credits, billing or budget.
+fn is_exhaustion_error(lower: &str) -> bool {
+    http_status(lower) == Some(402)
+        || ["quota", "credit balance", "insufficient", "billing", "budget"].iter().any(|needle| lower.contains(needle))
+}
+
+/// Times a rate-limited turn is retried before it counts as a failure.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// How often a wait for a rate limit checks whether the turn was canceled.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Seconds to wait before retrying, if `lower` is a rate-limit error (HTTP 429
+/// or 529, Anthropic's rate_limit/overloaded errors, Ollama's "too many
+/// requests"). Uses the provider's retry-after hint when the message carries
+/// one, and otherwise backs off exponentially with `attempt`.
+fn rate_limit_delay(lower: &str, attempt: u32) -> Option<u64> {
+    let limited = matches!(http_status(lower), Some(429 | 529))
+        || ["rate limit", "rate_limit", "too many requests", "overloaded"].iter().any(|needle| lower.contains(needle));
+    if !limited {
+        return None;
+    }
+    let hint = ["retry-after", "retry after", "try again in"].iter().find_map(|key| {
+        let rest = &lower[lower.find(key)? + key.len()..];
+        let digits: String = rest
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse::<u64>().ok()
+    });
+    Some(hint.unwrap_or(5 << attempt.min(4)).min(120))
+}
+
+/// Consecutive failed turns before failing over to the next provider.
+const FAILOVER_THRESHOLD: u32 = 2;
+
+/// What a failed attempt at a turn leads to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recovery {
+    /// Roll the attempt back and run it again on the same provider after this
+    /// many seconds.
+    Wait(u64),
+    /// Move on to the next provider in the failover chain, running the attempt
+    /// again there if `rerun`.
+    FailOver { rerun: bool },
+    Fail,
+}
+
+/// `retries` counts the rate limits the turn waited out already, `failures`
+/// the consecutive failed turns including this one. An attempt that ran tools
+/// is never run again: they did their work, and would run twice.
+fn recovery(failure: ProviderFailure, retries: u32, failures: u32, tools_ran: bool) -> Recovery {
+    match failure {
+        ProviderFailure::RateLimited(secs) if retries < MAX_RATE_LIMIT_RETRIES && !tools_ran => Recovery::Wait(secs),
+        ProviderFailure::Exhausted => Recovery::FailOver { rerun: !tools_ran },
+        _ if failures >= FAILOVER_THRESHOLD => Recovery::FailOver { rerun: !tools_ran },
+        _ => Recovery::Fail,
     }
 }
 
 /// Configuration parsed from CLI args.
+#[derive(Clone, Default)]
 pub struct SessionConfig {
     pub manifest_path: Option<String>,
     pub model: Option<String>,
@@ -112,6 +759,14 @@ pub struct SessionConfig {
     pub checkpoint_dir: Option<String>,
     pub event_log_path: Option<String>,
     pub trace_path: Option<String>,
+    /// Skip interactive confirmations (e.g. manifest workdir changes).
+    pub assume_yes: bool,
+    /// Dotenv file to load into the environment; nothing is read implicitly.
+    pub env_file: Option<String>,
+    /// Ordered fallback providers, e.g. `claude-cli,ollama:llama3.2:3b`.
+    pub failover: Option<String>,
+    /// Start with tools, shell and attachments disabled and autonomy forced to manual.
+    pub safe_mode: bool,
 }
 
 /// A TUI session wrapping the agent kernel.
@@ -119,13 +774,211 @@ pub struct Session {
     pub agent: AgentLoop,
     pub stats: SessionStats,
     pub model_name: String,
+    pub provider: String,
     pub agent_name: String,
     pub agent_version: String,
     pub workflow_name: String,
+    /// Autonomy level the policy runs at, after `--autonomy` and safe mode.
+    pub autonomy: String,
+    /// Policy configuration the agent's `PolicyEngine` was built from.
+    behavior: BehaviorConfig,
+    /// Stage ids of the active workflow, sent to the UI at the start of each turn.
+    pipeline: Vec<String>,
+    /// What the agent was told about itself (the about_me module's configuration).
+    identity: serde_json::Value,
+    /// Module configuration from the manifest, by module name.
+    module_configs: HashMap<String, serde_json::Value>,
+    /// Tool executors registered on the agent, in registration order.
+    tools: Vec<ToolInfo>,
+    /// Turns run through this session as (input, answer): what `/undo` rolls
+    /// back, and the context without a history module.
+    turns: Vec<(String, String)>,
+    /// Input of the latest turn still in the history, answered or not.
+    last_input: Option<String>,
+    /// How often the history was compacted since the session started.
+    compactions: usize,
+    /// Set from the UI (Esc) to cancel the running turn.
+    cancel: Arc<AtomicBool>,
+    steering: Steering,
+    /// `!` commands run in this session, oldest first, for `!!` and `!?`.
+    pub shell_history: Vec<String>,
     pub compiled_router: Option<CompiledRouter>,
     pub verbose: bool,
+    /// Working directory at startup, before any manifest `workdir` was applied.
+    pub original_cwd: PathBuf,
+    workdir: Workdir,
+    /// Remaining fallback providers, tried in order.
+    pub failover_chain: Vec<(String, String)>,
+    /// Token budget set by the manifest's `kernel.token_budget`, if any.
+    pub token_budget: Option<usize>,
+    /// Longest a turn may run, from the manifest's `kernel.turn_timeout_secs`.
+    turn_timeout: Option<Duration>,
+    /// Configuration this session was built from, reused by `/handoff`.
+    config: SessionConfig,
+    locked: Locked,
+    llm_calls: LlmCalls,
+    /// `provider`, shared with the event listener.
+    active_provider: ActiveProvider,
+    tool_calls: ToolCalls,
+    consecutive_failures: u32,
+    api_key: Option<String>,
+    ollama_url: String,
     /// Channel sender for UI events — set after construction.
-    event_tx: Option<mpsc::Sender<AgentEvent>>,
+    event_tx: Option<EventSender>,
+}
+
+/// Tools of the built-in modules as (name, module, description): what gets
+/// registered on the agent loop and told to about_me.
+const BUILTIN_TOOLS: &[(&str, &str, &str)] = &[
+    ("exec", "exec", "Execute shell commands"),
+    ("read_file", "file_tools", "Read file contents (with offset/limit)"),
+    ("write_file", "file_tools", "Write content to a file (shows diff)"),
+    ("list_directory", "file_tools", "List files in a directory"),
+    ("grep", "search_tools", "Search for patterns in files"),
+    ("find", "search_tools", "Find files by name/pattern"),
+    ("memory_save", "session_memory", "Save a fact to session memory"),
+    ("memory_recall", "session_memory", "Recall facts from session memory"),
+    ("memory_clear", "session_memory", "Clear session memory"),
+    ("remember", "semantic_memory", "Store a fact in long-term memory"),
+    ("recall", "semantic_memory", "Search long-term memory by meaning"),
+    ("forget", "semantic_memory", "Remove a fact from long-term memory"),
+    ("memory_stats", "semantic_memory", "Show long-term memory statistics"),
+    ("about_me", "about_me", "Learn about yourself"),
+];
+
+/// Names of the built-in tools `module` provides.
+fn module_tools(module: &str) -> Vec<&'static str> {
+    BUILTIN_TOOLS.iter().filter(|(_, m, _)| *m == module).map(|(name, _, _)| *name).collect()
+}
+
+/// Rough token count of `text`: about four characters a token.
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Text of a history message's content: a string, or the text of its blocks.
+fn content_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .map(|block| block.get("text").and_then(|t| t.as_str()).map(str::to_string).unwrap_or_else(|| block.to_string()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Entries of the kernel's exported history: a list of messages, bare or under `messages`.
+fn history_entries(history: &serde_json::Value) -> Option<Vec<ContextEntry>> {
+    let messages = history.as_array().or_else(|| history.get("messages")?.as_array())?;
+    Some(
+        messages
+            .iter()
+            .map(|message| {
+                let text = content_text(message.get("content").unwrap_or(message));
+                ContextEntry {
+                    role: message_role(message).to_string(),
+                    tokens: estimate_tokens(&text),
+                    text,
+                    pinned: message.get("pinned").and_then(|p| p.as_bool()).unwrap_or(false),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The messages of the kernel's exported history, bare or under `messages`.
+fn history_messages(history: &serde_json::Value) -> Option<Vec<serde_json::Value>> {
+    history.as_array().or_else(|| history.get("messages")?.as_array()).cloned()
+}
+
+/// Put `messages` back where [`history_messages`] found them.
+fn set_history_messages(history: &mut serde_json::Value, messages: Vec<serde_json::Value>) {
+    match history.get_mut("messages") {
+        Some(slot) => *slot = serde_json::Value::Array(messages),
+        None => *history = serde_json::Value::Array(messages),
+    }
+}
+
+/// Mask the string values of keys naming a secret, such as `api_key` or
+/// `auth_token`, anywhere in `value`.
+fn redact_secrets(value: &mut serde_json::Value) {
+    const SECRETS: [&str; 5] = ["key", "token", "secret", "password", "credential"];
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                let name = name.to_lowercase();
+                match value.as_str() {
+                    Some(text) if SECRETS.iter().any(|secret| name.contains(secret)) => {
+                        *value = serde_json::Value::String(env_file::mask(text));
+                    }
+                    _ => redact_secrets(value),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+fn message_role(message: &serde_json::Value) -> &str {
+    message.get("role").or_else(|| message.get("type")).and_then(|r| r.as_str()).unwrap_or("message")
+}
+
+/// Messages compaction never drops: pinned ones, system prompts and summaries.
+fn always_kept(message: &serde_json::Value) -> bool {
+    message.get("pinned").and_then(|p| p.as_bool()).unwrap_or(false)
+        || matches!(message_role(message), "system" | "summary")
+}
+
+/// `messages` from index `start` on, plus the older ones that are always kept.
+fn keep_from(messages: &[serde_json::Value], start: usize) -> Vec<serde_json::Value> {
+    messages.iter().enumerate().filter(|(i, m)| *i >= start || always_kept(m)).map(|(_, m)| m.clone()).collect()
+}
+
+/// Whether `message` opens an exchange: a user message, unless it only carries
+/// tool results back, which belong with the tool calls before them.
+fn starts_exchange(message: &serde_json::Value) -> bool {
+    let tool_result = |block: &serde_json::Value| block.get("type").and_then(|t| t.as_str()) == Some("tool_result");
+    message_role(message) == "user"
+        && !message.get("content").and_then(|c| c.as_array()).is_some_and(|blocks| blocks.iter().any(tool_result))
+}
+
+/// Where each exchange starts, so cutting there never separates a tool result
+/// from its call.
+fn exchange_starts(messages: &[serde_json::Value]) -> Vec<usize> {
+    messages.iter().enumerate().filter(|(_, m)| starts_exchange(m)).map(|(i, _)| i).collect()
+}
+
+/// Start of the last `n` exchanges (0 when there are no more than that).
+fn last_exchanges_start(messages: &[serde_json::Value], n: usize) -> usize {
+    let starts = exchange_starts(messages);
+    starts.len().checked_sub(n).and_then(|i| starts.get(i).copied()).unwrap_or(0)
+}
+
+/// The first exchange start from which what's kept fits in `budget` tokens.
+/// Whole exchanges go, oldest first, and the latest one always stays.
+fn fitting_start(messages: &[serde_json::Value], budget: usize) -> usize {
+    let tokens = |kept: Vec<serde_json::Value>| -> usize {
+        kept.iter().map(|m| estimate_tokens(&content_text(m.get("content").unwrap_or(m)))).sum()
+    };
+    let starts = exchange_starts(messages);
+    std::iter::once(0)
+        .chain(starts.iter().copied().skip(1))
+        .find(|&start| tokens(keep_from(messages, start)) <= budget)
+        .or(starts.last().copied())
+        .unwrap_or(0)
+}
+
+/// A tool executor registered on the agent loop.
+#[derive(Debug, Clone)]
+pub struct ToolInfo {
+    pub name: String,
+    /// Module providing it; `grpc` for tools of external gRPC modules.
+    pub module: String,
+    pub description: String,
 }
 
 fn build_module_registry() -> ModuleRegistry {
@@ -142,9 +995,85 @@ fn build_module_registry() -> ModuleRegistry {
     registry
 }
 
+/// The directory a manifest `workdir` names, relative to `cwd`, asking before
+/// switching into (or creating) it. Declining, or running without a terminal
+/// (or without asking, once the TUI owns it) and without `--yes`, stays in
+/// `cwd`; the latter with a notice.
+fn manifest_workdir(workdir: &str, cwd: &Path, assume_yes: bool, interactive: bool, event_tx: &EventSender) -> Result<PathBuf> {
+    let resolved = if Path::new(workdir).is_absolute() {
+        PathBuf::from(workdir)
+    } else {
+        cwd.join(workdir)
+    };
+    let create = !resolved.exists();
+    let action = if create {
+        format!("create and switch to {}", resolved.display())
+    } else {
+        format!("switch to {}", resolved.display())
+    };
+
+    if !assume_yes {
+        if !interactive || !std::io::stdin().is_terminal() {
+            let _ = event_tx.send(AgentEvent::SystemMessage(format!("Manifest wants to {action}; skipped (pass --yes to allow).")));
+            return Ok(cwd.to_path_buf());
+        }
+        print!("Manifest wants to {action}. Proceed? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Staying in {}", cwd.display());
+            return Ok(cwd.to_path_buf());
+        }
+    }
+
+    if create {
+        std::fs::create_dir_all(&resolved)?;
+    }
+    Ok(resolved)
+}
+
+/// Asks the outgoing agent for the summary handed to the next one.
+const HANDOFF_PROMPT: &str = "Summarize this conversation for another agent who will take over the work. \
+Cover the goal, decisions made, work completed, open questions and the next steps. \
+Reply with the summary only.";
+
 impl Session {
     /// Create a new session from CLI configuration.
-    pub fn from_config(cfg: SessionConfig, event_tx: mpsc::Sender<AgentEvent>) -> Result<Self> {
+    pub fn from_config(cfg: SessionConfig, event_tx: EventSender) -> Result<Self> {
+        Self::build(cfg, event_tx, true, Steering::default(), None)
+    }
+
+    /// Create a session for another tab, once the TUI owns the terminal.
+    pub fn for_tab(cfg: SessionConfig, event_tx: EventSender) -> Result<Self> {
+        Self::build(cfg, event_tx, false, Steering::default(), None)
+    }
+
+    /// Create the session of a tab's background queue, working in the tab's
+    /// `workdir`.
+    pub fn for_background(cfg: SessionConfig, event_tx: EventSender, workdir: Workdir) -> Result<Self> {
+        Self::build(cfg, event_tx, false, Steering::default(), Some(workdir))
+    }
+
+    /// `interactive` is false once the TUI owns the terminal and stdin can't be
+    /// prompted. `steering` is shared with whoever pauses and steers the turns.
+    /// A `shared` workdir stands in for the manifest's.
+    fn build(cfg: SessionConfig, event_tx: EventSender, interactive: bool, steering: Steering, shared: Option<Workdir>) -> Result<Self> {
+        let original_cwd = std::env::current_dir()?;
+        let mut workdir = shared
+            .as_ref()
+            .and_then(|dir| dir.lock().ok().map(|dir| dir.clone()))
+            .unwrap_or_else(|| original_cwd.clone());
+        let session_config = cfg.clone();
+        if cfg.safe_mode && cfg.use_mock {
+            anyhow::bail!("--safe-mode runs real providers only; drop --mock");
+        }
+
+        if let Some(ref path) = cfg.env_file {
+            let pairs = env_file::load(path)?;
+            let _ = event_tx.send(AgentEvent::SystemMessage(env_file::summary(path, &pairs)));
+        }
+
         // Load manifest or defaults
         let (config, system_prompt, module_configs, manifest_model, behavior_config,
              workflow_path, workflow_router_config, manifest_name, manifest_version) =
@@ -158,17 +1087,9 @@ impl Session {
                 let manifest_dir = std::path::Path::new(path).parent()
                     .unwrap_or(std::path::Path::new(".")).to_path_buf();
 
-                if let Some(ref workdir) = manifest.workdir {
-                    if workdir != "." {
-                        let resolved = if std::path::Path::new(workdir).is_absolute() {
-                            std::path::PathBuf::from(workdir)
-                        } else {
-                            std::env::current_dir()?.join(workdir)
-                        };
-                        if !resolved.exists() {
-                            std::fs::create_dir_all(&resolved)?;
-                        }
-                        std::env::set_current_dir(&resolved)?;
+                if let (Some(ref dir), None) = (&manifest.workdir, &shared) {
+                    if dir != "." {
+                        workdir = manifest_workdir(dir, &original_cwd, cfg.assume_yes, interactive, &event_tx)?;
                     }
                 }
 
@@ -194,68 +1115,21 @@ impl Session {
         let workflow_path = cfg.workflow.or(workflow_path);
 
         // Resolve provider/model
-        let (resolved_provider, resolved_model) = {
-            let raw_model = cfg.model.or(manifest_model);
-            let provider_from_cli = cfg.provider;
-            match (provider_from_cli, raw_model) {
-                (Some(p), Some(m)) => {
-                    let model = if let Some((_pfx, rest)) = m.split_once(':') {
-                        if m.starts_with(&format!("{p}:")) { rest.to_string() } else { m }
-                    } else { m };
-                    (p, model)
-                }
-                (None, Some(m)) => {
-                    if let Some((pfx, rest)) = m.split_once(':') {
-                        if pfx == "anthropic" || pfx == "ollama" || pfx == "claude-cli" {
-                            (pfx.to_string(), rest.to_string())
-                        } else {
-                            ("ollama".to_string(), m)
-                        }
-                    } else {
-                        ("ollama".to_string(), m)
-                    }
-                }
-                (Some(p), None) => {
-                    let default = if p == "anthropic" || p == "claude-cli" {
-                        "sonnet".to_string()
-                    } else {
-                        "llama3.2:3b".to_string()
-                    };
-                    (p, default)
-                }
-                (None, None) => ("ollama".to_string(), "llama3.2:3b".to_string()),
-            }
-        };
+        let (resolved_provider, resolved_model) = resolve_provider_model(cfg.provider, cfg.model.or(manifest_model));
 
         // Build LLM client
-        let active_model;
-        let llm: Arc<dyn LlmClient> = if cfg.use_mock {
-            active_model = "mock".to_string();
-            Arc::new(MockLlmClient::new(MockStrategy::Echo))
-        } else if resolved_provider == "anthropic" {
-            active_model = resolved_model;
-            let api_key = cfg.api_key
-                .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
-                .or_else(|| {
-                    let env_path = std::path::Path::new(".env");
-                    if env_path.exists() {
-                        std::fs::read_to_string(env_path).ok().and_then(|content| {
-                            content.lines().find_map(|line| {
-                                let line = line.trim();
-                                line.strip_prefix("ANTHROPIC_API_KEY=")
-                                    .map(|val| val.trim_matches('"').trim_matches('\'').to_string())
-                            })
-                        })
-                    } else { None }
-                })
-                .ok_or_else(|| anyhow::anyhow!("Anthropic API key not found"))?;
-            Arc::new(AnthropicClient::new(&active_model, &api_key))
-        } else if resolved_provider == "claude-cli" {
-            active_model = resolved_model;
-            Arc::new(ClaudeCliClient::new(&active_model))
+        let (llm, active_model, active_provider): (Arc<dyn LlmClient>, String, String) = if cfg.use_mock {
+            (Arc::new(MockLlmClient::new(MockStrategy::Echo)), "mock".to_string(), "mock".to_string())
         } else {
-            active_model = resolved_model;
-            Arc::new(OllamaClient::new(&active_model, &cfg.ollama_url))
+            let client = build_llm_client(
+                &resolved_provider, &resolved_model, cfg.api_key.as_deref(), &cfg.ollama_url,
+            )?;
+            (client, resolved_model, resolved_provider)
+        };
+        let failover_chain = if cfg.use_mock {
+            Vec::new()
+        } else {
+            cfg.failover.as_deref().map(parse_failover_chain).unwrap_or_default()
         };
 
         // Create agent loop
@@ -294,7 +1168,11 @@ impl Session {
                 behavior.autonomy.level = level;
             }
         }
-        agent.set_policy(PolicyEngine::new(behavior));
+        if cfg.safe_mode {
+            behavior.autonomy.level = AutonomyLevel::Manual;
+        }
+        let autonomy = format!("{:?}", behavior.autonomy.level).to_lowercase();
+        agent.set_policy(PolicyEngine::new(behavior.clone()));
 
         // Modules
         let registry = build_module_registry();
@@ -311,7 +1189,16 @@ impl Session {
             agent.add_module(module);
         }
 
-        // Register tool executors
+        // Register tool executors, recording outputs for the chat preview
+        let shared = shared.unwrap_or_else(|| Arc::new(Mutex::new(workdir.clone())));
+        let tool_hooks = ToolHooks { steering, workdir: shared, ..Default::default() };
+        let mut tools = Vec::new();
+        if cfg.safe_mode {
+            if let Ok(mut locked) = tool_hooks.locked.lock() {
+                locked.extend(Capability::ALL);
+            }
+            let _ = event_tx.send(AgentEvent::SafeMode(Capability::ALL.to_vec()));
+        }
         {
             let mut exec_for_init = ExecModule::new();
             if let Some(cfg) = module_config_map.get("exec") {
@@ -319,7 +1206,7 @@ impl Session {
             }
             let exec_arc = Arc::new(exec_for_init);
             let exec_clone = exec_arc.clone();
-            agent.register_tool_executor("exec", Arc::new(move |call| {
+            register_tool(&mut agent, &mut tools, &tool_hooks, "exec", "exec", move |call| {
                 let command = call.arguments.get("command")
                     .and_then(|v| v.as_str()).unwrap_or("echo");
                 let args: Vec<String> = call.arguments.get("args")
@@ -328,7 +1215,7 @@ impl Session {
                     .unwrap_or_default();
                 let output = exec_clone.execute(command, &args)?;
                 Ok(ToolResult { call_id: call.id.clone(), success: true, output })
-            }));
+            });
         }
         {
             let mut ft = FileToolsModule::new();
@@ -336,17 +1223,17 @@ impl Session {
                 ft.init(cfg).ok();
             }
             let ft = Arc::new(ft);
-            for tool_name in &["read_file", "write_file", "list_directory"] {
+            for tool_name in module_tools("file_tools") {
                 let ft_clone = ft.clone();
-                agent.register_tool_executor(*tool_name, Arc::new(move |call| {
+                register_tool(&mut agent, &mut tools, &tool_hooks, tool_name, "file_tools", move |call| {
                     ft_clone.execute_tool(call)
-                }));
+                });
             }
         }
+        let identity;
         {
             let mut about_me = AboutMeModule::new();
-            let workdir = std::env::current_dir()
-                .map(|p| p.display().to_string()).unwrap_or_else(|_| ".".to_string());
+            let workdir = workdir.display().to_string();
             let about_me_config = serde_json::json!({
                 "agent_name": manifest_name,
                 "agent_version": manifest_version,
@@ -359,25 +1246,17 @@ impl Session {
                 "turn_timeout_secs": about_me_timeout,
                 "token_budget": about_me_budget,
                 "autonomy_level": about_me_autonomy,
-                "tools": serde_json::json!([
-                    {"name": "exec", "description": "Execute shell commands"},
-                    {"name": "read_file", "description": "Read file contents (with offset/limit)"},
-                    {"name": "write_file", "description": "Write content to a file (shows diff)"},
-                    {"name": "list_directory", "description": "List files in a directory"},
-                    {"name": "grep", "description": "Search for patterns in files"},
-                    {"name": "find", "description": "Find files by name/pattern"},
-                    {"name": "memory_save", "description": "Save a fact to session memory"},
-                    {"name": "memory_recall", "description": "Recall facts from session memory"},
-                    {"name": "memory_clear", "description": "Clear session memory"},
-                    {"name": "about_me", "description": "Learn about yourself"}
-                ]),
+                "tools": BUILTIN_TOOLS.iter()
+                    .map(|(name, _, description)| serde_json::json!({"name": name, "description": description}))
+                    .collect::<Vec<_>>(),
             });
             about_me.init(&about_me_config).ok();
+            identity = about_me_config;
             let about_me = Arc::new(about_me);
             let about_me_clone = about_me.clone();
-            agent.register_tool_executor("about_me", Arc::new(move |call| {
+            register_tool(&mut agent, &mut tools, &tool_hooks, "about_me", "about_me", move |call| {
                 about_me_clone.execute_tool(call)
-            }));
+            });
         }
 
         // Search tools
@@ -387,11 +1266,11 @@ impl Session {
                 st.init(cfg).ok();
             }
             let st = Arc::new(st);
-            for tool_name in &["grep", "find"] {
+            for tool_name in module_tools("search_tools") {
                 let st_clone = st.clone();
-                agent.register_tool_executor(*tool_name, Arc::new(move |call| {
+                register_tool(&mut agent, &mut tools, &tool_hooks, tool_name, "search_tools", move |call| {
                     st_clone.execute_tool(call)
-                }));
+                });
             }
         }
         // Session memory
@@ -401,11 +1280,11 @@ impl Session {
                 sm.init(cfg).ok();
             }
             let sm = Arc::new(sm);
-            for tool_name in &["memory_save", "memory_recall", "memory_clear"] {
+            for tool_name in module_tools("session_memory") {
                 let sm_clone = sm.clone();
-                agent.register_tool_executor(*tool_name, Arc::new(move |call| {
+                register_tool(&mut agent, &mut tools, &tool_hooks, tool_name, "session_memory", move |call| {
                     sm_clone.execute_tool(call)
-                }));
+                });
             }
         }
         // Semantic memory
@@ -415,82 +1294,1005 @@ impl Session {
                 sem.init(cfg).ok();
             }
             let sem = Arc::new(sem);
-            for tool_name in &["remember", "recall", "forget", "memory_stats"] {
+            for tool_name in module_tools("semantic_memory") {
                 let sem_clone = sem.clone();
-                agent.register_tool_executor(*tool_name, Arc::new(move |call| {
+                register_tool(&mut agent, &mut tools, &tool_hooks, tool_name, "semantic_memory", move |call| {
                     sem_clone.execute_tool(call)
-                }));
+                });
             }
         }
 
         // Register gRPC module tool executors
         for (tool_name, executor) in loaded.grpc_tool_executors {
-            agent.register_tool_executor(&tool_name, executor);
+            register_tool(&mut agent, &mut tools, &tool_hooks, &tool_name, "grpc", move |call| executor(call));
         }
 
         // Event bus with channel listener
+        let llm_calls = LlmCalls::default();
+        let shared_provider = Arc::new(Mutex::new(active_provider.clone()));
+        let tool_calls = ToolCalls::default();
         {
             let mut bus = EventBus::new(&format!("tui-{}", std::process::id()));
-            bus.add_listener(Box::new(ChannelEventListener { tx: event_tx.clone() }));
+            bus.add_listener(Box::new(ChannelEventListener::new(
+                event_tx.clone(),
+                tool_hooks.outputs.clone(),
+                llm_calls.clone(),
+                shared_provider.clone(),
+                tool_calls.clone(),
+                tool_hooks.steering.clone(),
+                tool_hooks.workdir.clone(),
+            )));
             agent.set_event_bus(bus);
         }
+        // The loop checks it between stages and aborts its LLM call when it is set
+        let cancel = Arc::new(AtomicBool::new(false));
+        agent.set_cancel_flag(cancel.clone());
 
         agent.init(&module_config_map)?;
 
         Ok(Session {
             agent,
-            stats: SessionStats::default(),
+            stats: SessionStats {
+                pricing: pricing_for(&active_provider, &active_model),
+                ..Default::default()
+            },
             model_name: active_model,
+            provider: active_provider,
             agent_name: manifest_name,
             agent_version: manifest_version,
             workflow_name: workflow_name_str,
+            autonomy,
+            behavior,
+            pipeline: stage_ids(&workflow_yaml_text),
+            identity,
+            module_configs: module_config_map,
+            tools,
+            turns: Vec::new(),
+            last_input: None,
+            compactions: 0,
+            cancel,
+            steering: tool_hooks.steering.clone(),
+            shell_history: Vec::new(),
             compiled_router,
             verbose: cfg.verbose,
+            original_cwd,
+            workdir: tool_hooks.workdir,
+            failover_chain,
+            token_budget: cfg.manifest_path.as_ref().map(|_| about_me_budget).filter(|&budget| budget > 0),
+            turn_timeout: cfg.manifest_path.as_ref().filter(|_| about_me_timeout > 0).map(|_| Duration::from_secs(about_me_timeout)),
+            config: session_config,
+            locked: tool_hooks.locked,
+            llm_calls,
+            active_provider: shared_provider,
+            tool_calls,
+            consecutive_failures: 0,
+            api_key: cfg.api_key,
+            ollama_url: cfg.ollama_url,
             event_tx: Some(event_tx),
         })
     }
 
     /// Run a single user turn, sending events through the channel.
-    pub fn run_turn_with_events(&mut self, input: &str, _event_tx: &mpsc::Sender<AgentEvent>) -> Result<String> {
+    pub fn run_turn_with_events(&mut self, input: &str, _event_tx: &EventSender) -> Result<String> {
         // Route workflow if needed
         if let Some(ref router) = self.compiled_router {
             let selected_path = router.select(input);
             if let Ok(wf_content) = std::fs::read_to_string(selected_path) {
                 if let Ok(wf) = neocognos_kernel::workflow::parse_workflow(&wf_content) {
                     self.agent.set_workflow(wf);
+                    self.pipeline = stage_ids(&wf_content);
+                }
+            }
+        }
+        self.notify(AgentEvent::Pipeline(self.pipeline.clone()));
+        self.last_input = Some(input.to_string());
+        let started = Instant::now();
+        self.cancel.store(false, Ordering::Relaxed);
+        let watchdog = self.watchdog();
+
+        let stream_tx = self.event_tx.clone();
+        let on_token = |token: &str| {
+            if let Some(ref tx) = stream_tx {
+                let _ = tx.send(AgentEvent::StreamDelta(token.to_string()));
+            }
+        };
+        let mut rate_limit_retries = 0;
+        let result = loop {
+            // What to go back to if this attempt fails and is run again
+            let history = self.agent.export_history();
+            let tools_before = self.tool_calls.lock().map(|tools| tools.len()).unwrap_or(0);
+            match self.agent.run_streaming(input, &on_token) {
+                Ok(result) => {
+                    self.consecutive_failures = 0;
+                    break result;
+                }
+                Err(_) if self.canceled() => {
+                    self.record_turn(started, None, true);
+                    return Err(Watchdog::interrupted(watchdog.as_ref()));
+                }
+                Err(e) => {
+                    let failure = ProviderFailure::of(&e, rate_limit_retries);
+                    // Tools the attempt ran did their work; running it again would run them twice
+                    let tools_ran = self.tool_calls.lock().map(|tools| tools.len() > tools_before).unwrap_or(true);
+                    let recovery = recovery(failure, rate_limit_retries, self.consecutive_failures + 1, tools_ran);
+                    if !matches!(recovery, Recovery::Wait(_)) {
+                        self.consecutive_failures += 1;
+                    }
+                    match recovery {
+                        // Wait out a rate limit rather than failing the turn
+                        Recovery::Wait(secs) if self.discard_attempt(history.clone()) => {
+                            rate_limit_retries += 1;
+                            self.notify(AgentEvent::RateLimited { provider: self.provider.clone(), retry_in_secs: secs });
+                            if self.wait_unless_canceled(Duration::from_secs(secs)) {
+                                self.record_turn(started, None, true);
+                                return Err(Watchdog::interrupted(watchdog.as_ref()));
+                            }
+                        }
+                        Recovery::FailOver { rerun } if self.fail_over(&e) => {
+                            if !(rerun && self.discard_attempt(history)) {
+                                self.record_turn(started, None, true);
+                                return Err(e.context("the next turn runs on the fallback; /retry runs this one again"));
+                            }
+                        }
+                        _ => {
+                            self.record_turn(started, None, true);
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        };
+
+        let answer = self.finish_turn(result, started);
+        self.turns.push((input.to_string(), answer.clone()));
+        Ok(answer)
+    }
+
+    /// Input of the latest turn in the conversation, if any.
+    pub fn last_input(&self) -> Option<&str> {
+        self.last_input.as_deref()
+    }
+
+    /// Take the latest turn back out of the conversation: the history module
+    /// drops everything from its user message on. Returns that turn's input.
+    pub fn rollback_turn(&mut self) -> Result<String> {
+        let input = self.last_input.take().context("No turn to roll back")?;
+        if !self.agent.drop_last_exchange() {
+            self.last_input = Some(input);
+            anyhow::bail!("No history module to roll back");
+        }
+        if self.turns.last().is_some_and(|(last, _)| *last == input) {
+            self.turns.pop();
+        }
+        // Rolling back again reaches the turn before
+        self.last_input = self.turns.last().map(|(input, _)| input.clone());
+        Ok(input)
+    }
+
+    /// `/context`: the system prompt and the conversation the next call will
+    /// send. Without a history module to read, the turns run here stand in.
+    pub fn context_entries(&self) -> Vec<ContextEntry> {
+        let system_prompt = self.identity["system_prompt"].as_str().unwrap_or_default();
+        let mut entries = vec![ContextEntry {
+            role: "system".into(),
+            text: system_prompt.to_string(),
+            tokens: estimate_tokens(system_prompt),
+            pinned: true,
+        }];
+        match self.memory_entries() {
+            Some(history) => entries.extend(history),
+            None => {
+                for (input, answer) in &self.turns {
+                    for (role, text) in [("user", input), ("assistant", answer)] {
+                        entries.push(ContextEntry {
+                            role: role.into(),
+                            text: text.clone(),
+                            tokens: estimate_tokens(text),
+                            pinned: false,
+                        });
+                    }
                 }
             }
         }
+        entries
+    }
+
+    /// Configuration this session was built from.
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Where `/checkpoint` keeps this session's checkpoints.
+    pub fn checkpoint_dir(&self) -> Option<PathBuf> {
+        session_store::checkpoint_dir(self.config.checkpoint_dir.as_deref())
+    }
+
+    /// `/memory list`: the messages the history module holds, if there is one.
+    pub fn memory_entries(&self) -> Option<Vec<ContextEntry>> {
+        self.agent.export_history().as_ref().and_then(history_entries)
+    }
 
-        let result = self.agent.run_streaming(input, &|_token| {})?;
+    /// `/memory clear`: forget the whole conversation; the chat pane stays.
+    pub fn clear_memory(&mut self) -> Result<()> {
+        anyhow::ensure!(self.agent.clear_history(), "no history module to clear");
+        self.turns.clear();
+        self.last_input = None;
+        Ok(())
+    }
 
+    /// `/memory pin <n>`: keep message `n` (1-based, as `/memory list` numbers
+    /// them) through compaction.
+    pub fn pin_memory(&mut self, n: usize) -> Result<()> {
+        let count = self.memory_entries().context("no history module to pin in")?.len();
+        anyhow::ensure!((1..=count).contains(&n), "no message {n} (memory holds {count})");
+        anyhow::ensure!(self.agent.pin_history_message(n - 1), "the history module can't pin message {n}");
+        Ok(())
+    }
+
+    /// The conversation for `/save`: the kernel's history, the turns run and
+    /// token usage so far.
+    pub fn conversation_snapshot(&self) -> Result<serde_json::Value> {
+        let history = self.agent.export_history().context("No history module to save the conversation from")?;
+        Ok(serde_json::json!({
+            "agent": self.agent_name,
+            "provider": self.provider,
+            "model": self.model_name,
+            "history": history,
+            "turns": self.turns,
+            "stats": self.stats,
+            "compactions": self.compactions,
+        }))
+    }
+
+    /// `/load`: continue the conversation in `saved` (from [`Self::conversation_snapshot`])
+    /// on this session's agent and model.
+    pub fn restore_conversation(&mut self, saved: &serde_json::Value) -> Result<()> {
+        anyhow::ensure!(!saved["history"].is_null(), "the saved file holds no conversation");
+        self.agent.import_history(saved["history"].clone())?;
+        self.turns = serde_json::from_value(saved["turns"].clone()).unwrap_or_default();
+        self.last_input = self.turns.last().map(|(input, _)| input.clone());
+        let pricing = self.stats.pricing;
+        self.stats = serde_json::from_value(saved["stats"].clone()).unwrap_or_default();
+        self.stats.pricing = pricing;
+        self.compactions = saved["compactions"].as_u64().unwrap_or(0) as usize;
+        Ok(())
+    }
+
+    /// Go back to the provider and model `saved` (from [`Self::conversation_snapshot`])
+    /// was saved on, if this session runs on others.
+    pub fn restore_llm(&mut self, saved: &serde_json::Value) -> Result<()> {
+        let (Some(provider), Some(model)) = (saved["provider"].as_str(), saved["model"].as_str()) else {
+            return Ok(());
+        };
+        if (provider, model) == (self.provider.as_str(), self.model_name.as_str()) {
+            return Ok(());
+        }
+        self.use_llm(provider.to_string(), model.to_string())
+            .with_context(|| format!("can't switch back to {provider}:{model}"))
+    }
+
+    /// Re-run the last turn from `stage_id`, reusing the kernel's checkpoint of the
+    /// stages before it. Fails if no checkpoint covers that stage.
+    pub fn retry_stage(&mut self, stage_id: &str) -> Result<String> {
+        let stream_tx = self.event_tx.clone();
+        let started = Instant::now();
+        self.cancel.store(false, Ordering::Relaxed);
+        let watchdog = self.watchdog();
+        let result = self.agent.resume_from_stage(stage_id, &|token: &str| {
+            if let Some(ref tx) = stream_tx {
+                let _ = tx.send(AgentEvent::StreamDelta(token.to_string()));
+            }
+        });
+        match result {
+            Ok(result) => Ok(self.finish_turn(result, started)),
+            Err(e) => {
+                self.record_turn(started, None, true);
+                Err(if self.canceled() { Watchdog::interrupted(watchdog.as_ref()) } else { e })
+            }
+        }
+    }
+
+    /// Pause-and-steer state of the turns, for the UI.
+    pub fn steering(&self) -> Steering {
+        self.steering.clone()
+    }
+
+    /// The flag that cancels the running turn, for the UI to set.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+
+    /// Cancel through `flag` instead, so a session that replaces this one
+    /// answers to the flag the UI already holds.
+    pub fn set_cancel_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.agent.set_cancel_flag(flag.clone());
+        self.cancel = flag;
+    }
+
+    /// Start counting the turn down, if the manifest sets a timeout.
+    fn watchdog(&self) -> Option<Watchdog> {
+        let timeout = self.turn_timeout?;
+        Some(Watchdog::start(timeout, self.cancel.clone(), self.steering.clone(), self.event_tx.clone()))
+    }
+
+    fn canceled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Sleep for `wait`, giving up as soon as the turn is canceled (by the
+    /// user or the watchdog). True if it was.
+    fn wait_unless_canceled(&self, wait: Duration) -> bool {
+        sleep_unless_set(&self.cancel, wait)
+    }
+
+    /// Put the conversation back as it was before a failed attempt at the turn
+    /// (`history`, taken before it) so the attempt can run again without a
+    /// second copy of the user message, and have the UI drop what it streamed.
+    /// False if the history couldn't be put back.
+    fn discard_attempt(&mut self, history: Option<serde_json::Value>) -> bool {
+        if let Some(history) = history {
+            if let Err(e) = self.agent.import_history(history) {
+                self.notify(AgentEvent::Error(format!("Couldn't roll back the failed attempt: {e:#}")));
+                return false;
+            }
+        }
+        self.notify(AgentEvent::AttemptDiscarded);
+        true
+    }
+
+    fn finish_turn(&mut self, result: RunResult, started: Instant) -> String {
         self.stats.total_turns += result.turns;
-        self.stats.total_prompt_tokens += result.total_tokens;
+        self.record_turn(started, Some(result.total_tokens), false);
+
+        // Sent even when empty, so the UI settles whatever streamed before the next turn
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(AgentEvent::Response(result.output.text.clone()));
+        }
 
-        if !result.output.text.is_empty() {
-            if let Some(ref tx) = self.event_tx {
-                let _ = tx.send(AgentEvent::Response(result.output.text.clone()));
+        result.output.text
+    }
+
+    /// Cost the LLM calls made since `started` and add the turn to the stats.
+    /// `fallback_tokens` (the turn's total) stands in when the kernel reported
+    /// no calls.
+    fn record_turn(&mut self, started: Instant, fallback_tokens: Option<usize>, failed: bool) {
+        let calls = self.llm_calls.lock().map(|mut calls| std::mem::take(&mut *calls)).unwrap_or_default();
+        let tools = self.tool_calls.lock().map(|mut tools| std::mem::take(&mut *tools)).unwrap_or_default();
+        let mut turn = TurnRecord {
+            finished: chrono::Local::now().timestamp(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            tools,
+            failed,
+            ..Default::default()
+        };
+        self.stats.record_calls(&mut turn, calls, fallback_tokens.map(|tokens| (self.model_name.as_str(), tokens)));
+        self.stats.turns.push(turn);
+    }
+
+    /// Switch to the next provider in the failover chain. Returns false when the chain is exhausted.
+    fn fail_over(&mut self, reason: &anyhow::Error) -> bool {
+        while !self.failover_chain.is_empty() {
+            let (provider, model) = self.failover_chain.remove(0);
+            let failed = format!("{}:{}", self.provider, self.model_name);
+            match self.use_llm(provider.clone(), model.clone()) {
+                Ok(()) => {
+                    self.notify(AgentEvent::SystemMessage(format!(
+                        "{} {failed} failed ({reason}) — failing over to {provider}:{model}",
+                        icons::current().warning
+                    )));
+                    self.notify(AgentEvent::PinNotice {
+                        id: "provider".into(),
+                        text: format!("Provider degraded: running on fallback {provider}:{model}"),
+                    });
+                    return true;
+                }
+                Err(e) => {
+                    self.notify(AgentEvent::Error(format!("Failover to {provider}:{model} unavailable: {e}")));
+                }
             }
         }
+        false
+    }
+
+    /// Run the rest of the session on `provider`/`model`. The agent loop keeps
+    /// its history; only the client behind it changes. Any failover notice goes
+    /// away; `fail_over` pins its own again afterwards.
+    fn use_llm(&mut self, provider: String, model: String) -> Result<()> {
+        let client = build_llm_client(&provider, &model, self.api_key.as_deref(), &self.ollama_url)?;
+        self.agent.set_llm(client);
+        self.agent.set_model_name(&model);
+        self.stats.pricing = pricing_for(&provider, &model);
+        if let Ok(mut active) = self.active_provider.lock() {
+            *active = provider.clone();
+        }
+        self.provider = provider;
+        self.model_name = model.clone();
+        self.consecutive_failures = 0;
+        self.notify(AgentEvent::ModelChanged(model));
+        self.notify(AgentEvent::ClearNotice("provider".into()));
+        Ok(())
+    }
+
+    /// `/provider`: move the conversation to `provider`, on `model` or the
+    /// provider's default, resolved as `--provider`/`--model` are at startup.
+    pub fn switch_provider(&mut self, provider: &str, model: Option<&str>) -> Result<()> {
+        if !PROVIDERS.contains(&provider) {
+            anyhow::bail!("unknown provider '{provider}' (expected {})", PROVIDERS.join(", "));
+        }
+        if provider == "claude-cli" && !on_path("claude") {
+            anyhow::bail!("the claude CLI is not on PATH");
+        }
+        let (provider, model) = resolve_provider_model(Some(provider.to_string()), model.map(str::to_string));
+        self.use_llm(provider, model)
+    }
+
+    /// Autonomy levels `/autonomy` accepts, least to most independent.
+    pub const AUTONOMY_LEVELS: &'static [&'static str] = &["manual", "supervised", "semi", "full"];
+
+    /// `/autonomy <level>`: rebuild the agent's policy at another autonomy level.
+    /// Safe mode keeps it manual until tools are unlocked.
+    pub fn set_autonomy(&mut self, level: &str) -> Result<()> {
+        let parsed = level.parse::<AutonomyLevel>().map_err(|_| {
+            anyhow::anyhow!("unknown autonomy level '{level}' (expected {})", Self::AUTONOMY_LEVELS.join(", "))
+        })?;
+        if level != "manual" && self.is_locked(Capability::Tools) {
+            anyhow::bail!("safe mode keeps autonomy manual (/unsafe tools first)");
+        }
+        self.behavior.autonomy.level = parsed;
+        self.agent.set_policy(PolicyEngine::new(self.behavior.clone()));
+        self.autonomy = format!("{:?}", self.behavior.autonomy.level).to_lowercase();
+        self.notify(AgentEvent::AutonomyChanged(self.autonomy.clone()));
+        Ok(())
+    }
+
+    /// Stage ids of the active workflow.
+    pub fn pipeline(&self) -> &[String] {
+        &self.pipeline
+    }
+
+    /// Whether a workflow router picks the workflow for each turn.
+    pub fn routed(&self) -> bool {
+        self.compiled_router.is_some()
+    }
 
-        Ok(result.output.text)
+    /// `/workflow <path>`: run later turns on the workflow at `path`. The
+    /// conversation carries over; an explicit workflow replaces any router.
+    pub fn switch_workflow(&mut self, path: &str) -> Result<()> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("can't read {path}"))?;
+        let workflow = neocognos_kernel::workflow::parse_workflow(&content)
+            .with_context(|| format!("invalid workflow {path}"))?;
+        self.workflow_name = workflow.name.clone();
+        self.agent.set_workflow(workflow);
+        self.pipeline = stage_ids(&content);
+        self.compiled_router = None;
+        self.notify(AgentEvent::WorkflowChanged { name: self.workflow_name.clone(), stages: self.pipeline.clone() });
+        Ok(())
+    }
+
+    /// `/model`: switch to `spec`, a model of the current provider or a
+    /// `provider:model` pair, mid-conversation. With `check`, a short call to
+    /// the model has to succeed first.
+    pub fn switch_model(&mut self, spec: &str, check: bool) -> Result<()> {
+        let provider = match spec.split_once(':') {
+            Some((pfx, _)) if PROVIDERS.contains(&pfx) => None,
+            _ => Some(self.provider.clone()),
+        };
+        let (provider, model) = resolve_provider_model(provider, Some(spec.to_string()));
+        if check {
+            self.check_model(&provider, &model).with_context(|| format!("{provider}:{model} didn't answer"))?;
+        }
+        self.use_llm(provider, model)
+    }
+
+    /// Make one short call to `model` on a throwaway agent, counted in the
+    /// stats like any other call.
+    fn check_model(&mut self, provider: &str, model: &str) -> Result<()> {
+        let llm = build_llm_client(provider, model, self.api_key.as_deref(), &self.ollama_url)?;
+        let mut agent = AgentLoop::new(llm, KernelConfig::default(), "You are a helpful assistant.".to_string());
+        agent.set_model_name(model);
+        let usage = CallUsage::default();
+        let mut bus = EventBus::new(&format!("tui-check-{}", std::process::id()));
+        bus.add_listener(Box::new(usage.clone()));
+        agent.set_event_bus(bus);
+        let result = agent.run_streaming("Reply with OK.", &|_: &str| {});
+        let (prompt_tokens, completion_tokens) = usage.0.lock().map(|tokens| *tokens).unwrap_or_default();
+        self.stats.record_usage(model, pricing_for(provider, model), prompt_tokens, completion_tokens);
+        result.map(|_| ())
+    }
+
+    fn notify(&self, event: AgentEvent) {
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Have this agent summarize the conversation, then start a session for the agent
+    /// in `manifest`. Returns the new session and the opening context to run as its
+    /// first turn; the caller shuts this one down.
+    pub fn handoff(&mut self, manifest: &str) -> Result<(Session, String)> {
+        let event_tx = self.event_tx.clone().context("Session has no event channel")?;
+        let summary = self.run_turn_with_events(HANDOFF_PROMPT, &event_tx)?;
+        anyhow::ensure!(!summary.trim().is_empty(), "{} produced no summary to hand over", self.agent_name);
+
+        // The new agent's manifest decides its model and workflow; the env file is already loaded
+        let mut cfg = self.config.clone();
+        cfg.manifest_path = Some(manifest.to_string());
+        cfg.model = None;
+        cfg.workflow = None;
+        cfg.env_file = None;
+        // The new agent carries on in this tab's workdir
+        let mut next = Session::build(cfg, event_tx, false, self.steering(), Some(self.workdir.clone()))?;
+        next.set_cancel_flag(self.cancel_flag());
+        // Capabilities already re-enabled stay enabled for the new agent
+        let locked = self.locked_capabilities();
+        if let Ok(mut next_locked) = next.locked.lock() {
+            *next_locked = locked.iter().copied().collect();
+        }
+        next.notify(AgentEvent::SafeMode(locked));
+        let context = format!(
+            "You are taking over from the {} agent. Summary of the conversation so far:\n\n{}\n\n\
+             Acknowledge the handoff briefly and continue with the next steps.",
+            self.agent_name,
+            summary.trim()
+        );
+        Ok((next, context))
     }
 
-    /// Compact conversation history.
-    pub fn compact_with_callback<F: Fn(String)>(&mut self, callback: F) {
-        match self.agent.compact_history(2) {
-            Some((old, new)) => {
-                callback(format!("✅ Compacted: {} messages → {} messages", old, new));
-                self.stats.total_prompt_tokens = 0;
-                self.stats.total_completion_tokens = 0;
+    /// Time one call of `prompt` on a fresh agent using the current provider and
+    /// model, leaving this conversation untouched.
+    pub fn bench_call(&self, prompt: &str) -> Result<BenchRun> {
+        let llm = build_llm_client(&self.provider, &self.model_name, self.api_key.as_deref(), &self.ollama_url)?;
+        let mut agent = AgentLoop::new(llm, KernelConfig::default(), "You are a helpful assistant.".to_string());
+        agent.set_model_name(&self.model_name);
+        let usage = CallUsage::default();
+        let mut bus = EventBus::new(&format!("tui-bench-{}", std::process::id()));
+        bus.add_listener(Box::new(usage.clone()));
+        agent.set_event_bus(bus);
+        let started = Instant::now();
+        let result = agent.run_streaming(prompt, &|_: &str| {})?;
+        let duration_ms = started.elapsed().as_millis() as u64;
+        // Without reported calls the total stands in, priced as prompt tokens
+        let (prompt_tokens, completion_tokens) = match usage.0.lock().map(|tokens| *tokens) {
+            Ok((0, 0)) | Err(_) => (result.total_tokens, 0),
+            Ok(tokens) => tokens,
+        };
+        Ok(BenchRun { duration_ms, prompt_tokens, completion_tokens })
+    }
+
+    /// Capabilities safe mode still withholds.
+    pub fn locked_capabilities(&self) -> Vec<Capability> {
+        self.locked.lock().map(|l| l.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Tool executors registered on the agent.
+    pub fn tools(&self) -> &[ToolInfo] {
+        &self.tools
+    }
+
+    /// What the agent's policy, at its autonomy level, lets the agent do with
+    /// `tool`. Safe mode withholds every tool alike.
+    pub fn tool_access(&self, tool: &str) -> String {
+        if self.is_locked(Capability::Tools) {
+            return "denied (safe mode)".into();
+        }
+        match PolicyEngine::new(self.behavior.clone()).check_tool(tool) {
+            PolicyDecision::Allow => "allowed".into(),
+            PolicyDecision::RequireApproval => "asks first".into(),
+            PolicyDecision::Deny(reason) if reason.is_empty() => "denied".into(),
+            PolicyDecision::Deny(reason) => format!("denied: {reason}"),
+        }
+    }
+
+    pub fn is_locked(&self, capability: Capability) -> bool {
+        self.locked.lock().map(|l| l.contains(&capability)).unwrap_or(true)
+    }
+
+    /// Re-enable a capability withheld by safe mode. Returns false if it wasn't withheld.
+    pub fn unlock(&self, capability: Capability) -> bool {
+        self.locked.lock().map(|mut l| l.remove(&capability)).unwrap_or(false)
+    }
+
+    /// What the agent knows and remembers, as a JSON tree for `/state`. The kernel
+    /// doesn't expose the history module's store, so turns are this session's own
+    /// record of what it sent; compactions since are counted alongside.
+    pub fn state_snapshot(&self) -> serde_json::Value {
+        // What the history module holds, not what the chat showed: compaction
+        // and pinning change it, and it is what the agent is prompted with
+        let messages = self
+            .agent
+            .export_history()
+            .map(|history| history_messages(&history).map(serde_json::Value::Array).unwrap_or(history));
+        let mut modules = serde_json::json!(self.module_configs);
+        redact_secrets(&mut modules);
+        let locked: Vec<&str> = self.locked_capabilities().iter().map(|c| c.name()).collect();
+        serde_json::json!({
+            "identity": self.identity,
+            "session": {
+                "agent": self.agent_name,
+                "version": self.agent_version,
+                "provider": self.provider,
+                "model": self.model_name,
+                "workflow": self.workflow_name,
+                "stages": self.pipeline,
+                "safe_mode_locked": locked,
+            },
+            "history": {
+                "messages": messages,
+                "compactions": self.compactions,
+            },
+            "modules": modules,
+        })
+    }
+
+    /// `/compact`: shrink the history with `strategy` and report what it freed.
+    pub fn compact(&mut self, strategy: CompactStrategy) -> Result<String> {
+        let before = self.memory_entries();
+        let (old, new) = match strategy {
+            CompactStrategy::Summarize => self.agent.compact_history(2).context("No history module found to compact")?,
+            CompactStrategy::Truncate | CompactStrategy::KeepLast(_) => {
+                let mut history = self.agent.export_history().context("No history module found to compact")?;
+                let messages = history_messages(&history).context("The history module's history can't be trimmed")?;
+                let start = match strategy {
+                    CompactStrategy::KeepLast(n) => last_exchanges_start(&messages, n),
+                    _ => fitting_start(&messages, app::context_window(&self.model_name) / 2),
+                };
+                let kept = keep_from(&messages, start);
+                let counts = (messages.len(), kept.len());
+                set_history_messages(&mut history, kept);
+                self.agent.import_history(history)?;
+                counts
             }
-            None => {
-                callback("⚠ No history module found to compact.".to_string());
+        };
+        self.compactions += 1;
+        self.stats.total_prompt_tokens = 0;
+        self.stats.total_completion_tokens = 0;
+
+        let tokens = |entries: Option<Vec<ContextEntry>>| entries.map(|e| e.iter().map(|entry| entry.tokens).sum::<usize>());
+        let reclaimed = match (tokens(before), tokens(self.memory_entries())) {
+            (Some(before), Some(after)) => {
+                format!(", ~{} tokens reclaimed", app::format_tokens(before.saturating_sub(after)))
             }
+            _ => String::new(),
+        };
+        Ok(format!("{} Compacted ({}): {old} messages → {new}{reclaimed}", icons::current().ok, strategy.name()))
+    }
+
+    /// The directory the session's tools and `!` commands run in.
+    pub fn workdir(&self) -> PathBuf {
+        self.workdir.lock().map(|dir| dir.clone()).unwrap_or_else(|_| self.original_cwd.clone())
+    }
+
+    /// The workdir itself, to share with the tab's background queue.
+    pub fn shared_workdir(&self) -> Workdir {
+        self.workdir.clone()
+    }
+
+    /// Return to the working directory the TUI was started from.
+    pub fn reset_workdir(&self) -> PathBuf {
+        if let Ok(mut dir) = self.workdir.lock() {
+            *dir = self.original_cwd.clone();
         }
+        self.original_cwd.clone()
     }
 
     pub fn shutdown(&mut self) -> Result<()> {
         self.agent.shutdown()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_delay() {
+        assert_eq!(rate_limit_delay("429 too many requests", 0), Some(5));
+        assert_eq!(rate_limit_delay("api error (status 429): slow down", 2), Some(20));
+        assert_eq!(rate_limit_delay("http 529: overloaded", 9), Some(80));
+        assert_eq!(rate_limit_delay("rate_limit_error: retry-after: 30", 0), Some(30));
+        assert_eq!(rate_limit_delay("rate limited, try again in 500 seconds", 0), Some(120));
+        // A 429 that isn't a status code
+        assert_eq!(rate_limit_delay("request req_4290 failed", 0), None);
+        assert_eq!(rate_limit_delay("context holds 429 tokens too many", 0), None);
+        assert_eq!(rate_limit_delay("connection refused", 0), None);
+    }
+
+    #[test]
+    fn test_provider_failure() {
+        let of = |message: &str| ProviderFailure::of(&anyhow::anyhow!(message.to_string()), 0);
+        assert_eq!(of("HTTP 429 Too Many Requests"), ProviderFailure::RateLimited(5));
+        assert_eq!(of("Your credit balance is too low"), ProviderFailure::Exhausted);
+        assert_eq!(of("status 402: payment required"), ProviderFailure::Exhausted);
+        assert_eq!(of("insufficient_quota (rate limit)"), ProviderFailure::Exhausted);
+        assert_eq!(of("error 500: internal"), ProviderFailure::Other);
+
+        // The cause counts too, wherever it sits in the chain
+        let wrapped = anyhow::anyhow!("status 429").context("LLM call failed");
+        assert_eq!(ProviderFailure::of(&wrapped, 1), ProviderFailure::RateLimited(10));
+    }
+
+    #[test]
+    fn test_watchdog_interrupts_wait() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let watchdog = Watchdog::start(Duration::from_millis(50), cancel.clone(), Steering::default(), None);
+        let started = Instant::now();
+        // A rate-limit wait far longer than the timeout ends when the watchdog fires
+        assert!(sleep_unless_set(&cancel, Duration::from_secs(30)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let error = Watchdog::interrupted(Some(&watchdog));
+        assert!(error.downcast_ref::<TurnTimedOut>().is_some());
+
+        // Without a watchdog that fired, the flag was the user's
+        let flag = AtomicBool::new(false);
+        assert!(!sleep_unless_set(&flag, Duration::from_millis(10)));
+        assert!(Watchdog::interrupted(None).downcast_ref::<TurnCanceled>().is_some());
+    }
+
+    #[test]
+    fn test_in_workdir() {
+        let call = |name: &str, arguments: serde_json::Value| ToolCall { id: "1".into(), name: name.into(), arguments };
+        let dir = Path::new("/work/tab");
+        let read = in_workdir(&call("read_file", serde_json::json!({"path": "src/main.rs"})), dir);
+        assert_eq!(read.arguments["path"], "/work/tab/src/main.rs");
+        let read = in_workdir(&call("read_file", serde_json::json!({"path": "/etc/hosts"})), dir);
+        assert_eq!(read.arguments["path"], "/etc/hosts");
+        // Listings and searches without a path start in the workdir
+        let grep = in_workdir(&call("grep", serde_json::json!({"pattern": "fn main"})), dir);
+        assert_eq!(grep.arguments, serde_json::json!({"pattern": "fn main", "path": "/work/tab"}));
+
+        let exec = in_workdir(&call("exec", serde_json::json!({"command": "ls", "args": ["-la", "a b"]})), dir);
+        assert_eq!(exec.arguments["command"], "sh");
+        assert_eq!(exec.arguments["args"], serde_json::json!(["-c", "cd -- \"$0\" && exec \"$@\"", "/work/tab", "ls", "-la", "a b"]));
+
+        let other = call("memory_save", serde_json::json!({"path": "notes"}));
+        assert_eq!(in_workdir(&other, dir).arguments, other.arguments);
+    }
+
+    #[test]
+    fn test_file_changes() {
+        let dir = std::env::temp_dir().join(format!("neocognos-changes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "one\n").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let listener = ChannelEventListener::new(
+            EventSender::new(1, tx),
+            ToolOutputs::default(),
+            LlmCalls::default(),
+            ActiveProvider::default(),
+            ToolCalls::default(),
+            Steering::default(),
+            Arc::new(Mutex::new(dir.clone())),
+        );
+        let diffs = || -> Vec<String> {
+            rx.try_iter()
+                .filter_map(|(_, event)| match event {
+                    AgentEvent::FileDiff { path, .. } => Some(path),
+                    _ => None,
+                })
+                .collect()
+        };
+        // Any tool naming a file counts, not only write_file
+        listener.before_write("1", "edit_file", r#"{"path":"notes.txt"}"#);
+        std::fs::write(dir.join("notes.txt"), "two\n").unwrap();
+        listener.after_write("1");
+        assert_eq!(diffs(), ["notes.txt"]);
+        // New files diff against nothing; unchanged ones and reads don't count
+        listener.before_write("2", "write_file", r#"{"file_path":"new.txt"}"#);
+        std::fs::write(dir.join("new.txt"), "fresh\n").unwrap();
+        listener.after_write("2");
+        listener.before_write("3", "write_file", r#"{"path":"notes.txt"}"#);
+        listener.after_write("3");
+        listener.before_write("4", "read_file", r#"{"path":"notes.txt"}"#);
+        listener.after_write("4");
+        assert_eq!(diffs(), ["new.txt"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancel_interrupts_wait() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        let esc = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            flag.store(true, Ordering::Relaxed);
+        });
+        let started = Instant::now();
+        assert!(sleep_unless_set(&cancel, Duration::from_secs(120)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        esc.join().unwrap();
+    }
+
+    #[test]
+    fn test_pricing_for() {
+        let price = |provider, model| {
+            let pricing = pricing_for(provider, model);
+            (pricing.input_per_mtok, pricing.output_per_mtok)
+        };
+        assert_eq!(price("anthropic", "claude-3-5-haiku-latest"), (0.8, 4.0));
+        assert_eq!(price("anthropic", "opus"), (15.0, 75.0));
+        assert_eq!(price("anthropic", "sonnet"), (3.0, 15.0));
+        // Subscriptions and local models cost nothing per token, whatever the model
+        assert_eq!(price("claude-cli", "opus"), (0.0, 0.0));
+        assert_eq!(price("ollama", "llama3.2:3b"), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_record_usage() {
+        let mut stats = SessionStats::default();
+        let cost = stats.record_usage("sonnet", pricing_for("anthropic", "sonnet"), 1_000_000, 100_000);
+        assert!((cost - 4.5).abs() < 1e-9);
+        stats.record_usage("sonnet", pricing_for("anthropic", "sonnet"), 1_000_000, 0);
+        stats.record_usage("llama3.2:3b", pricing_for("ollama", "llama3.2:3b"), 500, 500);
+        assert_eq!(stats.total_tokens(), 2_101_000);
+        assert!((stats.estimated_cost() - 7.5).abs() < 1e-9);
+        assert_eq!(stats.per_model["sonnet"].prompt_tokens, 2_000_000);
+        assert!((stats.per_model["sonnet"].cost - 7.5).abs() < 1e-9);
+        assert_eq!(stats.per_model["llama3.2:3b"].cost, 0.0);
+    }
+
+    #[test]
+    fn test_absorb_usage() {
+        let sonnet = pricing_for("anthropic", "sonnet");
+        let mut background = SessionStats { pricing: sonnet, ..Default::default() };
+        background.record_usage("sonnet", sonnet, 1_000_000, 0);
+        background.turns.push(TurnRecord::default());
+        let usage = background.take();
+        assert_eq!(background.total_tokens(), 0);
+        assert!(background.turns.is_empty());
+        assert_eq!(background.pricing.input_per_mtok, 3.0);
+
+        let mut stats = SessionStats::default();
+        stats.record_usage("sonnet", sonnet, 0, 100_000);
+        stats.absorb(usage);
+        assert_eq!(stats.total_tokens(), 1_100_000);
+        assert!((stats.estimated_cost() - 4.5).abs() < 1e-9);
+        assert_eq!(stats.per_model["sonnet"].prompt_tokens, 1_000_000);
+        assert_eq!(stats.turns.len(), 1);
+    }
+
+    #[test]
+    fn test_record_calls() {
+        let call = |provider: &str, model: &str, prompt_tokens| LlmCall {
+            provider: provider.into(),
+            model: model.into(),
+            prompt_tokens,
+            completion_tokens: 0,
+        };
+        // The session failed over to Ollama, but the first call was Anthropic's
+        let mut stats = SessionStats { pricing: pricing_for("ollama", "llama3.2:3b"), ..Default::default() };
+        let mut turn = TurnRecord::default();
+        let calls = vec![call("anthropic", "opus", 1_000_000), call("ollama", "llama3.2:3b", 1_000_000)];
+        stats.record_calls(&mut turn, calls, Some(("llama3.2:3b", 7)));
+        assert_eq!(turn.prompt_tokens, 2_000_000);
+        assert!((turn.cost - 15.0).abs() < 1e-9);
+        assert!((stats.per_model["opus"].cost - 15.0).abs() < 1e-9);
+
+        // No calls reported: the turn's total stands in, at the active price
+        let mut stats = SessionStats::default();
+        let mut turn = TurnRecord::default();
+        stats.record_calls(&mut turn, Vec::new(), Some(("sonnet", 1_000_000)));
+        assert_eq!(turn.prompt_tokens, 1_000_000);
+        assert!((turn.cost - 3.0).abs() < 1e-9);
+        let mut turn = TurnRecord::default();
+        stats.record_calls(&mut turn, Vec::new(), None);
+        assert_eq!(turn.prompt_tokens, 0);
+    }
+
+    /// A conversation of three exchanges, the second using a tool, behind a
+    /// system prompt and with a pinned message in the first.
+    fn tool_history() -> Vec<serde_json::Value> {
+        let text = |role: &str, text: &str| serde_json::json!({ "role": role, "content": text });
+        vec![
+            text("system", "You are terse."),
+            text("user", "Hello"),
+            serde_json::json!({ "role": "assistant", "content": "Hi, remember this", "pinned": true }),
+            text("user", "List the files"),
+            serde_json::json!({ "role": "assistant", "content": [
+                { "type": "text", "text": "Looking" },
+                { "type": "tool_use", "id": "t1", "name": "exec", "input": { "command": "ls" } },
+            ] }),
+            serde_json::json!({ "role": "user", "content": [
+                { "type": "tool_result", "tool_use_id": "t1", "content": "a.rs b.rs" },
+            ] }),
+            text("assistant", "a.rs and b.rs"),
+            text("user", "Thanks"),
+            text("assistant", "You're welcome"),
+        ]
+    }
+
+    #[test]
+    fn test_exchange_starts() {
+        let messages = tool_history();
+        // The tool result at 5 is no exchange of its own
+        assert_eq!(exchange_starts(&messages), vec![1, 3, 7]);
+        assert_eq!(last_exchanges_start(&messages, 1), 7);
+        assert_eq!(last_exchanges_start(&messages, 2), 3);
+        assert_eq!(last_exchanges_start(&messages, 5), 0);
+        assert_eq!(last_exchanges_start(&[], 1), 0);
+    }
+
+    #[test]
+    fn test_keep_from() {
+        let messages = tool_history();
+        let kept = keep_from(&messages, 7);
+        let roles: Vec<&str> = kept.iter().map(message_role).collect();
+        // The system prompt and the pinned answer survive
+        assert_eq!(roles, ["system", "assistant", "user", "assistant"]);
+        assert_eq!(kept[1]["content"], "Hi, remember this");
+        assert_eq!(keep_from(&messages, 0).len(), messages.len());
+    }
+
+    #[test]
+    fn test_redact_secrets() {
+        let mut modules = serde_json::json!({
+            "llm": { "api_key": "sk-ant-api03-abcdef", "max_tokens": 4096, "model": "sonnet" },
+            "search_tools": { "providers": [{ "name": "brave", "AuthToken": "brv-1234567890" }] },
+        });
+        redact_secrets(&mut modules);
+        assert_eq!(modules["llm"]["api_key"], "sk…ef");
+        assert_eq!(modules["llm"]["max_tokens"], 4096);
+        assert_eq!(modules["llm"]["model"], "sonnet");
+        assert_eq!(modules["search_tools"]["providers"][0]["AuthToken"], "br…90");
+        assert_eq!(modules["search_tools"]["providers"][0]["name"], "brave");
+    }
+
+    #[test]
+    fn test_fitting_start() {
+        let messages = tool_history();
+        assert_eq!(fitting_start(&messages, 10_000), 0);
+        // Too little room for the tool exchange: it goes whole
+        let last = estimate_tokens("You are terse.") + estimate_tokens("Hi, remember this") + 10;
+        assert_eq!(fitting_start(&messages, last), 7);
+        // Even with no room, the latest exchange stays
+        assert_eq!(fitting_start(&messages, 0), 7);
+        assert_eq!(fitting_start(&[], 0), 0);
+    }
+
+    #[test]
+    fn test_recovery() {
+        let limited = ProviderFailure::RateLimited(5);
+        assert_eq!(recovery(limited, 0, 1, false), Recovery::Wait(5));
+        // Out of retries, the rate limit is an ordinary failure
+        assert_eq!(recovery(limited, MAX_RATE_LIMIT_RETRIES, 1, false), Recovery::Fail);
+        assert_eq!(recovery(limited, MAX_RATE_LIMIT_RETRIES, 2, false), Recovery::FailOver { rerun: true });
+        // Tools ran: no waiting to run them again, and the fallback gets the next turn
+        assert_eq!(recovery(limited, 0, 1, true), Recovery::Fail);
+        assert_eq!(recovery(limited, 0, 2, true), Recovery::FailOver { rerun: false });
+
+        assert_eq!(recovery(ProviderFailure::Exhausted, 0, 1, false), Recovery::FailOver { rerun: true });
+        assert_eq!(recovery(ProviderFailure::Exhausted, 0, 1, true), Recovery::FailOver { rerun: false });
+        assert_eq!(recovery(ProviderFailure::Other, 0, 1, false), Recovery::Fail);
+        assert_eq!(recovery(ProviderFailure::Other, 0, FAILOVER_THRESHOLD, false), Recovery::FailOver { rerun: true });
+    }
+
+    #[test]
+    fn test_resolve_provider_model() {
+        let resolve = |provider: Option<&str>, model: Option<&str>| {
+            resolve_provider_model(provider.map(str::to_string), model.map(str::to_string))
+        };
+        let pair = |provider: &str, model: &str| (provider.to_string(), model.to_string());
+        assert_eq!(resolve(Some("anthropic"), Some("anthropic:opus")), pair("anthropic", "opus"));
+        assert_eq!(resolve(Some("anthropic"), Some("sonnet")), pair("anthropic", "sonnet"));
+        // An Ollama tag isn't a provider prefix
+        assert_eq!(resolve(Some("ollama"), Some("llama3.2:3b")), pair("ollama", "llama3.2:3b"));
+        assert_eq!(resolve(None, Some("llama3.2:3b")), pair("ollama", "llama3.2:3b"));
+        assert_eq!(resolve(None, Some("claude-cli:opus")), pair("claude-cli", "opus"));
+        assert_eq!(resolve(None, Some("qwen2.5")), pair("ollama", "qwen2.5"));
+        assert_eq!(resolve(Some("claude-cli"), None), pair("claude-cli", "sonnet"));
+        assert_eq!(resolve(None, None), pair("ollama", "llama3.2:3b"));
+    }
+
+    #[test]
+    fn test_parse_failover_chain() {
+        assert_eq!(
+            parse_failover_chain("anthropic:sonnet, claude-cli,,ollama:llama3.2:3b"),
+            vec![
+                ("anthropic".to_string(), "sonnet".to_string()),
+                ("claude-cli".to_string(), "sonnet".to_string()),
+                ("ollama".to_string(), "llama3.2:3b".to_string()),
+            ]
+        );
+        assert_eq!(parse_failover_chain("ollama"), vec![("ollama".to_string(), "llama3.2:3b".to_string())]);
+        assert!(parse_failover_chain(" ").is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! Saved sessions for `/save` and `/load`, kept as JSON in
+//! `~/.local/share/neocognos/sessions/`. `/checkpoint` and `/restore` keep the
+//! same files in the checkpoint directory.
+//!
+//! A file holds two halves: what the chat pane showed (messages with their
+//! times, recent and changed files) and what the agent thread needs to pick the
+//! conversation back up (the kernel's history, turns and usage).
+//!
+//! Every `*.json` in a store's directory is taken for one of its files, so each
+//! store has a directory of its own.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+use crate::app::{App, ChatMessage};
+
+/// `$XDG_DATA_HOME/neocognos`, falling back to `~/.local/share/neocognos`.
+fn data_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(base.join("neocognos"))
+}
+
+/// Where `/save` keeps sessions.
+pub fn dir() -> Option<PathBuf> {
+    Some(data_dir()?.join("sessions"))
+}
+
+/// Where `/checkpoint` keeps checkpoints: `neocognos-checkpoints` in
+/// `--checkpoint-dir`, apart from whatever else is kept there, or
+/// `checkpoints` beside the saved sessions.
+pub fn checkpoint_dir(configured: Option<&str>) -> Option<PathBuf> {
+    match configured {
+        Some(dir) => Some(Path::new(dir).join("neocognos-checkpoints")),
+        None => Some(data_dir()?.join("checkpoints")),
+    }
+}
+
+/// Name for `/save` or `/checkpoint` without one, e.g. `session-20260114-153012`.
+pub fn default_name(prefix: &str) -> String {
+    format!("{prefix}-{}", Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Names stay plain file names: no separators, nothing hidden.
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    anyhow::ensure!(valid, "invalid session name '{name}' (use letters, digits, - _ and .)");
+    Ok(())
+}
+
+pub fn path(dir: Option<&Path>, name: &str) -> Result<PathBuf> {
+    check_name(name)?;
+    let dir = dir.context("No home directory to keep sessions in")?;
+    Ok(dir.join(format!("{name}.json")))
+}
+
+/// Names saved in `dir`, newest first.
+pub fn list(dir: Option<&Path>) -> Vec<String> {
+    let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<(std::time::SystemTime, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_str()?.to_string();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            (path.extension()? == "json").then_some((modified, name))
+        })
+        .collect();
+    sessions.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    sessions.into_iter().map(|(_, name)| name).collect()
+}
+
+/// The chat half of a saved session.
+pub fn chat_state(app: &App) -> Value {
+    let messages: Vec<Value> = app
+        .messages
+        .iter()
+        .zip(&app.message_meta)
+        .map(|(msg, meta)| json!({ "time": meta.timestamp.to_rfc3339(), "message": msg }))
+        .collect();
+    json!({
+        "agent": app.status.agent_name,
+        "model": app.status.model,
+        "messages": messages,
+        "recent_files": app.recent_files,
+        "changed_files": app.changed_files,
+    })
+}
+
+/// Replace the chat with the one in `saved` (the output of [`chat_state`]).
+pub fn restore_chat(app: &mut App, saved: &Value) -> Result<()> {
+    let messages = saved["messages"].as_array().context("Saved session has no messages")?;
+    let mut restored = Vec::with_capacity(messages.len());
+    for entry in messages {
+        let message: ChatMessage = serde_json::from_value(entry["message"].clone())?;
+        let time = entry["time"].as_str().and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+        restored.push((message, time.map(|t| t.with_timezone(&Local))));
+    }
+    app.clear_messages();
+    for (message, time) in restored {
+        app.add_message(message);
+        if let (Some(meta), Some(time)) = (app.message_meta.last_mut(), time) {
+            meta.timestamp = time;
+        }
+    }
+    app.recent_files = serde_json::from_value(saved["recent_files"].clone()).unwrap_or_default();
+    app.changed_files = serde_json::from_value(saved["changed_files"].clone()).unwrap_or_default();
+    Ok(())
+}
+
+/// Write a session file into `dir` from both halves; returns where it went.
+pub fn write(dir: Option<&Path>, name: &str, chat: Value, agent: Value) -> Result<PathBuf> {
+    let path = path(dir, name)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Can't create {}", dir.display()))?;
+    }
+    let file = json!({ "version": 1, "saved": Local::now().to_rfc3339(), "chat": chat, "agent": agent });
+    std::fs::write(&path, serde_json::to_string_pretty(&file)?)
+        .with_context(|| format!("Can't write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Read the session saved in `dir` as `name`.
+pub fn read(dir: Option<&Path>, name: &str) -> Result<Value> {
+    let path = path(dir, name)?;
+    let text = std::fs::read_to_string(&path).with_context(|| format!("Nothing saved as '{name}'"))?;
+    serde_json::from_str(&text).with_context(|| format!("Corrupt session file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("bugfix-2").is_ok());
+        assert!(check_name("session-20260114-153012").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("../etc/passwd").is_err());
+        assert!(check_name(".hidden").is_err());
+        assert!(check_name("a/b").is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_dir() {
+        assert_eq!(checkpoint_dir(Some("/tmp/run")), Some(PathBuf::from("/tmp/run/neocognos-checkpoints")));
+        if let Some(default) = checkpoint_dir(None) {
+            assert!(default.ends_with("neocognos/checkpoints"));
+        }
+    }
+
+    #[test]
+    fn test_chat_round_trip() {
+        let mut app = App::new("coder", "sonnet", "default");
+        app.add_message(ChatMessage::User("fix it".into()));
+        app.add_message(ChatMessage::ToolResult {
+            name: "exec".into(),
+            success: true,
+            duration_ms: 12,
+            output: "ok".into(),
+            error: None,
+        });
+        app.add_recent_file("src/main.rs".into());
+        app.changed_files.insert("src/lib.rs".into());
+        let saved = chat_state(&app);
+
+        let mut restored = App::new("coder", "sonnet", "default");
+        restored.add_message(ChatMessage::System("welcome".into()));
+        restore_chat(&mut restored, &saved).unwrap();
+        assert_eq!(restored.messages.len(), 2);
+        assert_eq!(restored.messages[0].text(), "fix it");
+        assert!(matches!(restored.messages[1], ChatMessage::ToolResult { duration_ms: 12, .. }));
+        assert_eq!(restored.message_meta[0].timestamp, app.message_meta[0].timestamp);
+        assert_eq!(restored.recent_files, vec!["src/main.rs".to_string()]);
+        assert!(restored.changed_files.contains("src/lib.rs"));
+        assert!(restore_chat(&mut restored, &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_write_read_list() {
+        let dir = std::env::temp_dir().join(format!("neocognos-checkpoints-{}", std::process::id()));
+        let app = App::new("coder", "sonnet", "default");
+        let path = write(Some(&dir), "before-refactor", chat_state(&app), json!({ "turns": [] })).unwrap();
+        assert_eq!(path, dir.join("before-refactor.json"));
+        assert_eq!(list(Some(&dir)), vec!["before-refactor".to_string()]);
+        let saved = read(Some(&dir), "before-refactor").unwrap();
+        assert_eq!(saved["chat"]["agent"], "coder");
+        assert!(read(Some(&dir), "missing").is_err());
+        assert!(write(None, "x", json!({}), json!({})).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
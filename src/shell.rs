@@ -0,0 +1,119 @@
+//! `!` commands. They run in a pseudo-terminal, so programs that color their
+//! output when attached to a terminal still do, and their output reaches the
+//! chat line by line as it is printed rather than all at the end.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+
+use crate::app::ShellKiller;
+
+/// How a `!` command ended.
+pub struct Exit {
+    pub code: u32,
+    /// Ended by a signal, as when Ctrl+C kills it.
+    pub killed: bool,
+}
+
+/// Run `command` with `sh -c` in `dir`. `started` gets a handle to kill it;
+/// `output` gets each batch of complete lines as it arrives.
+///
+/// Nothing can be typed into it: stdin is /dev/null, so a prompt reads end of
+/// input instead of waiting, and pagers are `cat`.
+pub fn run(command: &str, dir: &Path, started: impl FnOnce(ShellKiller), mut output: impl FnMut(Vec<String>)) -> Result<Exit> {
+    // As wide as the terminal, so programs lay out their output for it
+    let cols = crossterm::terminal::size().map(|(cols, _)| cols).unwrap_or(80);
+    let pair = native_pty_system()
+        .openpty(PtySize { rows: 24, cols, pixel_width: 0, pixel_height: 0 })
+        .context("Can't open a pty")?;
+    let mut cmd = CommandBuilder::new("sh");
+    cmd.args(["-c", &format!("exec </dev/null\n{command}")]);
+    cmd.env("PAGER", "cat");
+    cmd.env("GIT_PAGER", "cat");
+    cmd.cwd(dir);
+    let mut child = pair.slave.spawn_command(cmd)?;
+    // Only the child holds the terminal end now, so reading ends when it exits
+    drop(pair.slave);
+    let mut killer = child.clone_killer();
+    started(ShellKiller::new(move || {
+        let _ = killer.kill();
+    }));
+
+    let mut reader = pair.master.try_clone_reader()?;
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 4096];
+    // Once the child is gone the read fails (EIO) rather than returning 0
+    while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let Some(end) = pending.iter().rposition(|&b| b == b'\n') else { continue };
+        let complete: Vec<u8> = pending.drain(..=end).collect();
+        output(String::from_utf8_lossy(&complete).lines().map(terminal_line).collect());
+    }
+    if !pending.is_empty() {
+        output(vec![terminal_line(&String::from_utf8_lossy(&pending))]);
+    }
+    let status = child.wait()?;
+    Ok(Exit { code: status.exit_code(), killed: status.signal().is_some() })
+}
+
+/// What a terminal ends up showing for `line`: a carriage return (as in
+/// progress bars) starts it over, so only the text after the last one stays.
+fn terminal_line(line: &str) -> String {
+    let line = line.trim_end_matches('\r');
+    line.rsplit('\r').next().unwrap_or(line).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `command`, returning its exit and everything it printed.
+    fn run_collecting(command: &str) -> (Exit, Vec<String>) {
+        let mut lines = Vec::new();
+        let exit = run(command, &std::env::temp_dir(), |_| {}, |batch| lines.extend(batch)).unwrap();
+        (exit, lines)
+    }
+
+    #[test]
+    fn test_terminal_line() {
+        assert_eq!(terminal_line("plain"), "plain");
+        assert_eq!(terminal_line("crlf\r"), "crlf");
+        assert_eq!(terminal_line(" 10%\r 50%\r100% done"), "100% done");
+        assert_eq!(terminal_line("\r"), "");
+    }
+
+    #[test]
+    fn test_run() {
+        let (exit, lines) = run_collecting("echo one; printf 'two\\n 50%%\\rdone'; exit 3");
+        assert_eq!(lines, ["one", "two", "done"]);
+        assert_eq!(exit.code, 3);
+        assert!(!exit.killed);
+    }
+
+    #[test]
+    fn test_run_in_dir() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let (_, lines) = run_collecting("pwd -P");
+        assert_eq!(lines, [dir.display().to_string()]);
+    }
+
+    #[test]
+    fn test_run_takes_no_input() {
+        // A prompt gets end of input rather than waiting for keys that never come
+        let (exit, lines) = run_collecting("read answer; echo \"got:$answer\"; echo $PAGER $GIT_PAGER");
+        assert_eq!(lines, ["got:", "cat cat"]);
+        assert_eq!(exit.code, 0);
+    }
+
+    #[test]
+    fn test_run_killed() {
+        let started = |killer: ShellKiller| killer.kill();
+        let exit = run("sleep 30", &std::env::temp_dir(), started, |_| {}).unwrap();
+        assert!(exit.killed);
+    }
+}
@@ -0,0 +1,454 @@
+//! Several agent sessions in one TUI instance, one per tab. Each tab has its own
+//! [`App`] state and agent thread; only the active tab is drawn and takes keys,
+//! but the events of every session are routed to its tab by session id, so
+//! background sessions stay current.
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use anyhow::Result;
+
+use crate::agent_thread::{self, AgentEvent, EventSender, SessionId, UiCommand};
+use crate::app::{App, ChatMessage, Control};
+use crate::config::TuiConfig;
+use crate::graphics;
+use crate::session::{Session, SessionConfig, Workdir};
+use crate::tasks;
+use crate::ui;
+use crate::ui::compare::{CompareState, Reply};
+use crate::ui::tab_bar::TabLabel;
+
+/// One session: its UI state and the channel to its agent thread.
+pub struct SessionTab {
+    /// The id the session's events carry.
+    pub id: SessionId,
+    pub app: App,
+    pub command_tx: mpsc::Sender<UiCommand>,
+    /// Chat messages that arrived while the tab was in the background.
+    pub unread: usize,
+    /// Paints the session's image previews while the tab is shown.
+    pub images: graphics::Renderer,
+    event_tx: EventSender,
+    /// What the session was built from, for the background queue's session.
+    config: SessionConfig,
+    /// The session's workdir, which the background queue's session shares.
+    workdir: Workdir,
+    /// The background queue, started with the first task.
+    background: Option<tasks::Queue>,
+}
+
+impl SessionTab {
+    /// Spawn the agent thread for `session` and set up its UI state from `tui_config`.
+    pub fn start(session: Session, event_tx: EventSender, tui_config: &TuiConfig) -> Self {
+        let mut app = App::new(&session.agent_name, &session.model_name, &session.workflow_name);
+        app.stream_scroll = tui_config.chat.stream_scroll;
+        app.token_budget = session.token_budget;
+        app.status.autonomy = session.autonomy.clone();
+        app.checkpoint_dir = session.checkpoint_dir();
+        app.steering = session.steering();
+        app.aliases = tui_config.aliases.clone();
+        app.custom_commands = tui_config.commands.clone();
+        app.budget_thresholds = tui_config.budget;
+        app.splits = ui::layout::Splits::from_config(&tui_config.layout);
+        app.layout_mode = tui_config.layout.mode;
+        app.image_protocol = tui_config.chat.images.unwrap_or_else(graphics::ImageProtocol::detect);
+        app.add_message(ChatMessage::System(format!(
+            "{} Neocognos TUI — Agent: {} | Model: {} | Workflow: {}",
+            ui::icons::current().logo,
+            session.agent_name, session.model_name, session.workflow_name
+        )));
+        app.add_message(ChatMessage::System(
+            "Type /help for commands, /quit to exit".into()
+        ));
+        let mut config = session.config().clone();
+        // The env file was loaded into the process with this session
+        config.env_file = None;
+        let workdir = session.shared_workdir();
+        let agent = agent_thread::spawn(session, event_tx.clone());
+        app.control = Some(agent.control_tx);
+        let images = graphics::Renderer::new(app.image_protocol);
+        Self {
+            id: agent.id,
+            app,
+            command_tx: agent.command_tx,
+            unread: 0,
+            images,
+            event_tx,
+            config,
+            workdir,
+            background: None,
+        }
+    }
+
+    /// Hand a task to the background queue, starting the queue with the first.
+    pub fn run_background(&mut self, id: usize, prompt: String) {
+        let queue = self.background.get_or_insert_with(|| {
+            tasks::spawn(self.config.clone(), self.workdir.clone(), self.command_tx.clone(), self.event_tx.clone())
+        });
+        if !queue.send(id, prompt) {
+            self.app.finish_background(id, Err("The background queue stopped".into()));
+        }
+    }
+
+    /// Send `prompt` to the agent as if it was typed in this tab. Returns the
+    /// stable id of its chat message.
+    pub fn send_prompt(&mut self, prompt: &str) -> usize {
+        self.app.add_message(ChatMessage::User(prompt.to_string()));
+        let id = self.app.message_id(self.app.messages.len() - 1).unwrap_or_default();
+        self.app.agent_busy = true;
+        self.app.thinking_since = Some(Instant::now());
+        let _ = self.command_tx.send(UiCommand::UserInput(prompt.to_string()));
+        id
+    }
+
+    /// Cancel background task `id`, which the queue already has.
+    pub fn cancel_background(&mut self, id: usize) {
+        if let Some(queue) = &self.background {
+            queue.cancel(id);
+        }
+    }
+
+    /// Start a session for a new tab from the first tab's configuration, with
+    /// another manifest and/or model. Its events go out through `event_tx`.
+    pub fn open(
+        base: &SessionConfig,
+        manifest: Option<String>,
+        model: Option<String>,
+        tui_config: &TuiConfig,
+        event_tx: EventSender,
+    ) -> Result<Self> {
+        let mut cfg = base.clone();
+        if manifest.is_some() {
+            // The manifest decides its model and workflow unless a model is given too
+            cfg.manifest_path = manifest;
+            cfg.model = None;
+            cfg.workflow = None;
+        }
+        if model.is_some() {
+            cfg.model = model;
+        }
+        // The env file was loaded into the process with the first session
+        cfg.env_file = None;
+        let session = Session::for_tab(cfg, event_tx.clone())?;
+        Ok(Self::start(session, event_tx, tui_config))
+    }
+
+    fn label(&self) -> TabLabel {
+        TabLabel {
+            title: format!("{} · {}", self.app.status.agent_name, self.app.status.model),
+            busy_since: self.app.agent_busy.then(|| self.app.thinking_since.unwrap_or_else(Instant::now)),
+            unread: self.unread,
+        }
+    }
+}
+
+/// A `/broadcast` waiting for the tabs it went to.
+struct Broadcast {
+    prompt: String,
+    replies: Vec<Awaited>,
+}
+
+/// A tab's part of a broadcast.
+struct Awaited {
+    session: SessionId,
+    title: String,
+    /// The id of the prompt's message in the tab's chat.
+    since: usize,
+    /// Set once the tab's turn is over, or if the prompt never went out.
+    answer: Option<Result<String, String>>,
+}
+
+/// The open tabs; there is always at least one.
+pub struct Tabs {
+    tabs: Vec<SessionTab>,
+    active: usize,
+    broadcast: Option<Broadcast>,
+    /// The channel every session's events arrive on, tagged with its id.
+    events_tx: mpsc::Sender<(SessionId, AgentEvent)>,
+    events_rx: mpsc::Receiver<(SessionId, AgentEvent)>,
+    next_id: SessionId,
+}
+
+impl Tabs {
+    /// Tabs starting with `first`, whose session sends on `events_tx`.
+    pub fn new(
+        first: SessionTab,
+        events_tx: mpsc::Sender<(SessionId, AgentEvent)>,
+        events_rx: mpsc::Receiver<(SessionId, AgentEvent)>,
+    ) -> Self {
+        let next_id = first.id + 1;
+        Self { tabs: vec![first], active: 0, broadcast: None, events_tx, events_rx, next_id }
+    }
+
+    /// A sender for the events of a session about to be opened, under a fresh id.
+    pub fn event_sender(&mut self) -> EventSender {
+        let id = self.next_id;
+        self.next_id += 1;
+        EventSender::new(id, self.events_tx.clone())
+    }
+
+    /// Apply the pending events (non-blocking) to the tabs of the sessions they
+    /// came from, counting the chat messages background tabs get. Closed tabs'
+    /// events are dropped.
+    pub fn route_events(&mut self, mut apply: impl FnMut(&mut App, AgentEvent)) {
+        while let Ok((id, event)) = self.events_rx.try_recv() {
+            let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else { continue };
+            let tab = &mut self.tabs[index];
+            let before = tab.app.messages.len();
+            apply(&mut tab.app, event);
+            if index != self.active {
+                tab.unread += tab.app.messages.len().saturating_sub(before);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active_mut(&mut self) -> &mut SessionTab {
+        &mut self.tabs[self.active]
+    }
+
+    /// Add a tab and switch to it.
+    pub fn open(&mut self, tab: SessionTab) {
+        self.tabs.push(tab);
+        self.active = self.tabs.len() - 1;
+    }
+
+    /// Switch to the tab at `index` (0-based). False if there is no such tab.
+    pub fn select(&mut self, index: usize) -> bool {
+        let Some(tab) = self.tabs.get_mut(index) else { return false };
+        tab.unread = 0;
+        self.active = index;
+        true
+    }
+
+    /// Remove the active tab, shutting its agent thread down, and return it.
+    /// The last tab is never removed.
+    pub fn close_active(&mut self) -> Option<SessionTab> {
+        (self.tabs.len() > 1).then(|| self.remove(self.active))
+    }
+
+    /// Remove the tabs whose session quit, in the background or not, shutting
+    /// their agent threads down. The last tab is never removed; if it quit too,
+    /// it is left for the caller to end on.
+    pub fn close_quit(&mut self) -> Vec<SessionTab> {
+        let mut closed = Vec::new();
+        let mut index = 0;
+        while index < self.tabs.len() {
+            if self.tabs[index].app.should_quit && self.tabs.len() > 1 {
+                closed.push(self.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        closed
+    }
+
+    /// Remove the tab at `index`, keeping the active one active if it stays.
+    fn remove(&mut self, index: usize) -> SessionTab {
+        let tab = self.tabs.remove(index);
+        if let Some(control) = &tab.app.control {
+            let _ = control.send(Control::Shutdown);
+        }
+        if index < self.active {
+            self.active -= 1;
+        } else if index == self.active {
+            self.active = self.active.min(self.tabs.len() - 1);
+            self.tabs[self.active].unread = 0;
+        }
+        tab
+    }
+
+    /// `/broadcast`: send `prompt` to the 1-based `targets`, or to every tab
+    /// when there are none. Busy tabs are skipped. Returns how many tabs got it.
+    pub fn broadcast(&mut self, targets: &[usize], prompt: &str) -> Result<usize, String> {
+        if self.broadcast.is_some() {
+            return Err("The last broadcast is still waiting for answers".into());
+        }
+        let indexes: Vec<usize> = match targets {
+            [] => (0..self.tabs.len()).collect(),
+            targets => targets.iter().map(|n| n - 1).collect(),
+        };
+        if let Some(index) = indexes.iter().find(|&&index| index >= self.tabs.len()) {
+            return Err(format!("No tab {}", index + 1));
+        }
+        let mut sent = 0;
+        let replies = indexes
+            .into_iter()
+            .map(|index| {
+                let tab = &mut self.tabs[index];
+                let title = format!("{} {}", index + 1, tab.label().title);
+                let (since, answer) = if tab.app.agent_busy {
+                    (0, Some(Err("Busy with another turn; not sent".to_string())))
+                } else {
+                    sent += 1;
+                    (tab.send_prompt(prompt), None)
+                };
+                Awaited { session: tab.id, title, since, answer }
+            })
+            .collect();
+        self.broadcast = Some(Broadcast { prompt: prompt.to_string(), replies });
+        Ok(sent)
+    }
+
+    /// The comparison of the broadcast's answers, once every tab it went to
+    /// has finished its turn.
+    pub fn finish_broadcast(&mut self) -> Option<CompareState> {
+        let broadcast = self.broadcast.as_mut()?;
+        for reply in broadcast.replies.iter_mut().filter(|reply| reply.answer.is_none()) {
+            match self.tabs.iter().find(|tab| tab.id == reply.session) {
+                None => reply.answer = Some(Err("The tab was closed".to_string())),
+                Some(tab) if !tab.app.agent_busy => reply.answer = Some(tab.app.answer_since(reply.since)),
+                Some(_) => {}
+            }
+        }
+        if broadcast.replies.iter().any(|reply| reply.answer.is_none()) {
+            return None;
+        }
+        let broadcast = self.broadcast.take()?;
+        let replies = broadcast
+            .replies
+            .into_iter()
+            .filter_map(|reply| Some(Reply { title: reply.title, answer: reply.answer? }))
+            .collect();
+        Some(CompareState::new(broadcast.prompt, replies))
+    }
+
+    /// Remove every tab, for the exit summaries.
+    pub fn into_apps(self) -> impl Iterator<Item = App> {
+        self.tabs.into_iter().map(|tab| tab.app)
+    }
+
+    pub fn labels(&self) -> Vec<TabLabel> {
+        self.tabs.iter().map(SessionTab::label).collect()
+    }
+
+    /// One line per tab for `/tab`, the active one marked.
+    pub fn listing(&self) -> String {
+        let mut out = String::from("Tabs:");
+        for (i, tab) in self.tabs.iter().enumerate() {
+            let marker = if i == self.active { "▸" } else { " " };
+            let label = tab.label();
+            let mut state = Vec::new();
+            if label.busy_since.is_some() {
+                state.push("working".to_string());
+            }
+            if label.unread > 0 {
+                state.push(format!("{} unread", label.unread));
+            }
+            let state = if state.is_empty() { String::new() } else { format!(" ({})", state.join(", ")) };
+            out.push_str(&format!("\n{marker} {}  {}{state}", i + 1, label.title));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tab for session `id` with no agent thread behind it.
+    fn tab(id: SessionId, events_tx: &mpsc::Sender<(SessionId, AgentEvent)>) -> SessionTab {
+        let app = App::new("a", "m", "w");
+        let images = graphics::Renderer::new(app.image_protocol);
+        SessionTab {
+            id,
+            app,
+            command_tx: mpsc::channel().0,
+            unread: 0,
+            images,
+            event_tx: EventSender::new(id, events_tx.clone()),
+            config: SessionConfig::default(),
+            workdir: Workdir::default(),
+            background: None,
+        }
+    }
+
+    /// `count` tabs with the first active, and the channel their events arrive on.
+    fn tabs(count: SessionId) -> (Tabs, mpsc::Sender<(SessionId, AgentEvent)>) {
+        let (events_tx, events_rx) = mpsc::channel();
+        let mut tabs = Tabs::new(tab(0, &events_tx), events_tx.clone(), events_rx);
+        for id in 1..count {
+            tabs.open(tab(id, &events_tx));
+        }
+        tabs.select(0);
+        (tabs, events_tx)
+    }
+
+    fn apply(app: &mut App, event: AgentEvent) {
+        if let AgentEvent::SystemMessage(text) = event {
+            app.add_message(ChatMessage::System(text));
+        }
+    }
+
+    #[test]
+    fn test_route_events() {
+        let (mut tabs, events_tx) = tabs(2);
+        let before = tabs.tabs[0].app.messages.len();
+        for (id, text) in [(0, "shown"), (1, "one"), (1, "two"), (7, "closed tab")] {
+            events_tx.send((id, AgentEvent::SystemMessage(text.into()))).unwrap();
+        }
+        events_tx.send((1, AgentEvent::Done)).unwrap();
+        tabs.route_events(apply);
+        assert_eq!(tabs.tabs[0].app.messages.len(), before + 1);
+        assert_eq!(tabs.tabs[0].unread, 0);
+        // Events that add no message aren't unread
+        assert_eq!(tabs.labels()[1].unread, 2);
+        assert!(tabs.listing().contains("2 unread"));
+        assert!(tabs.select(1));
+        assert_eq!(tabs.tabs[1].unread, 0);
+        assert!(!tabs.select(5));
+        assert_eq!(tabs.active_index(), 1);
+    }
+
+    #[test]
+    fn test_close() {
+        let (mut tabs, _events_tx) = tabs(4);
+        tabs.select(2);
+        // A background tab that quits closes without taking the active one along
+        tabs.tabs[1].app.should_quit = true;
+        let closed: Vec<SessionId> = tabs.close_quit().into_iter().map(|tab| tab.id).collect();
+        assert_eq!(closed, [1]);
+        assert_eq!(tabs.active_mut().id, 2);
+        // Closing the active tab moves to the next one, or the last
+        tabs.close_active().unwrap();
+        assert_eq!(tabs.active_mut().id, 3);
+        tabs.close_active().unwrap();
+        assert_eq!(tabs.active_mut().id, 0);
+        assert!(tabs.close_active().is_none());
+        tabs.active_mut().app.should_quit = true;
+        assert!(tabs.close_quit().is_empty());
+        assert_eq!(tabs.len(), 1);
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let (mut tabs, _events_tx) = tabs(3);
+        assert_eq!(tabs.broadcast(&[1, 4], "why"), Err("No tab 4".into()));
+        tabs.tabs[1].app.agent_busy = true;
+        assert_eq!(tabs.broadcast(&[], "why"), Ok(2));
+        assert!(tabs.broadcast(&[1], "again").is_err());
+        assert!(tabs.tabs[0].app.agent_busy);
+        assert!(matches!(tabs.tabs[2].app.messages.last(), Some(ChatMessage::User(prompt)) if prompt == "why"));
+
+        // The comparison waits for every tab the prompt went to
+        tabs.tabs[0].app.add_message(ChatMessage::Assistant("because".into()));
+        tabs.tabs[0].app.agent_busy = false;
+        assert!(tabs.finish_broadcast().is_none());
+        tabs.close_active().unwrap();
+        tabs.select(1);
+        tabs.active_mut().app.add_message(ChatMessage::Error("rate limited".into()));
+        tabs.active_mut().app.agent_busy = false;
+        let comparison = tabs.finish_broadcast().unwrap();
+        assert_eq!(comparison.prompt, "why");
+        let answers: Vec<_> = comparison.replies.iter().map(|reply| reply.answer.clone()).collect();
+        assert_eq!(answers, [Ok("because".into()), Err("Busy with another turn; not sent".into()), Err("rate limited".into())]);
+        assert!(tabs.finish_broadcast().is_none());
+    }
+}
@@ -0,0 +1,134 @@
+//! Background tasks: prompts submitted with a trailing `&` or `/bg` run one at
+//! a time on a session of their own, so the tab's agent stays free for the
+//! conversation. That session starts with the first task, works in the tab's
+//! workdir and keeps its history from task to task; of its events only the
+//! start and finish of each task reach the tab, and its usage is added to the
+//! tab session's stats after each task.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::agent_thread::{AgentEvent, EventSender, UiCommand};
+use crate::session::{Session, SessionConfig, TurnCanceled, Workdir};
+
+/// A tab's background queue: where its tasks go, and what cancels them.
+pub struct Queue {
+    tasks: mpsc::Sender<(usize, String)>,
+    state: Arc<Mutex<QueueState>>,
+    /// The background session's cancel flag.
+    cancel: Arc<AtomicBool>,
+}
+
+/// The task running now and the ones canceled, which the queue skips.
+#[derive(Default)]
+struct QueueState {
+    running: Option<usize>,
+    canceled: BTreeSet<usize>,
+}
+
+impl Queue {
+    /// Queue task `id`. Returns false once the queue has stopped.
+    pub fn send(&self, id: usize, prompt: String) -> bool {
+        self.tasks.send((id, prompt)).is_ok()
+    }
+
+    /// Cancel task `id`: its turn if it is running, or else its turn never starts.
+    pub fn cancel(&self, id: usize) {
+        let Ok(mut state) = self.state.lock() else { return };
+        state.canceled.insert(id);
+        if state.running == Some(id) {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl QueueState {
+    /// Mark task `id` running, unless it was canceled while queued.
+    fn start(&mut self, id: usize) -> bool {
+        self.running = (!self.canceled.contains(&id)).then_some(id);
+        self.running.is_some()
+    }
+}
+
+/// Spawn the background queue for sessions built from `cfg`, working in
+/// `workdir`. Their usage goes to the tab's agent thread through `command_tx`.
+pub fn spawn(cfg: SessionConfig, workdir: Workdir, command_tx: mpsc::Sender<UiCommand>, event_tx: EventSender) -> Queue {
+    let (task_tx, task_rx) = mpsc::channel::<(usize, String)>();
+    let queue = Queue { tasks: task_tx, state: Arc::default(), cancel: Arc::default() };
+    let worker = Worker { cfg, workdir, state: queue.state.clone(), cancel: queue.cancel.clone(), command_tx, event_tx };
+    std::thread::Builder::new()
+        .name("background".into())
+        .spawn(move || worker.run(task_rx))
+        .expect("Failed to spawn background thread");
+    queue
+}
+
+/// The thread that runs the tasks.
+struct Worker {
+    cfg: SessionConfig,
+    workdir: Workdir,
+    state: Arc<Mutex<QueueState>>,
+    cancel: Arc<AtomicBool>,
+    command_tx: mpsc::Sender<UiCommand>,
+    event_tx: EventSender,
+}
+
+impl Worker {
+    fn run(self, task_rx: mpsc::Receiver<(usize, String)>) {
+        // Tool calls, stages and streamed tokens of background turns would
+        // interleave with the tab's own, so nothing listens to them
+        let session_tx = self.event_tx.discarding();
+        let mut session = None;
+        while let Ok((id, prompt)) = task_rx.recv() {
+            if !self.state.lock().is_ok_and(|mut state| state.start(id)) {
+                let outcome = Err(TurnCanceled.to_string());
+                let _ = self.event_tx.send(AgentEvent::BackgroundFinished { id, outcome });
+                continue;
+            }
+            let _ = self.event_tx.send(AgentEvent::BackgroundStarted(id));
+            if session.is_none() {
+                match Session::for_background(self.cfg.clone(), session_tx.clone(), self.workdir.clone()) {
+                    Ok(mut started) => {
+                        started.set_cancel_flag(self.cancel.clone());
+                        session = Some(started);
+                    }
+                    Err(e) => {
+                        let outcome = Err(format!("Can't start the background session: {e:#}"));
+                        let _ = self.event_tx.send(AgentEvent::BackgroundFinished { id, outcome });
+                        continue;
+                    }
+                }
+            }
+            let Some(session) = session.as_mut() else { continue };
+            let outcome = session.run_turn_with_events(&prompt, &session_tx).map_err(|e| format!("{e:#}"));
+            if let Ok(mut state) = self.state.lock() {
+                state.running = None;
+            }
+            let _ = self.command_tx.send(UiCommand::BackgroundUsage(session.stats.take()));
+            let _ = self.event_tx.send(AgentEvent::BackgroundFinished { id, outcome });
+        }
+        if let Some(mut session) = session {
+            let _ = session.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel() {
+        let queue = Queue { tasks: mpsc::channel().0, state: Arc::default(), cancel: Arc::default() };
+        assert!(queue.state.lock().unwrap().start(1));
+        // A queued task is skipped when its turn comes, without stopping the running one
+        queue.cancel(2);
+        assert!(!queue.cancel.load(Ordering::Relaxed));
+        assert!(!queue.state.lock().unwrap().start(2));
+        // The running task's turn is stopped
+        assert!(queue.state.lock().unwrap().start(3));
+        queue.cancel(3);
+        assert!(queue.cancel.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,256 @@
+//! Color themes: the built-in presets and `~/.config/neocognos/theme.toml`.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+
+/// Every color the UI styles are built from. The defaults suit dark
+/// terminals; `theme.toml` can override any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub user: Color,
+    pub assistant: Color,
+    pub tool: Color,
+    pub error: Color,
+    pub dim: Color,
+    pub accent: Color,
+    pub narration: Color,
+    pub system: Color,
+    pub success: Color,
+    pub border: Color,
+    /// Background of the selected row.
+    pub selection: Color,
+    /// Background of search hits, and of the current one.
+    pub search: Color,
+    pub search_active: Color,
+    pub link: Color,
+    /// Pipeline stages in the trace.
+    pub stage: Color,
+    /// Borders of focused panels and overlays, and the active sidebar tab.
+    pub focus: Color,
+    /// Text drawn on the `focus` color.
+    pub on_focus: Color,
+    /// Labels drawn over the budget and context gauges.
+    pub gauge_label: Color,
+}
+
+impl Theme {
+    pub const DEFAULT: Theme = Theme {
+        user: Color::Rgb(100, 149, 237),          // Cornflower blue
+        assistant: Color::Rgb(120, 200, 120),     // Green
+        tool: Color::Rgb(230, 190, 60),           // Yellow
+        error: Color::Rgb(220, 80, 80),           // Red
+        dim: Color::DarkGray,
+        accent: Color::Rgb(160, 120, 230),        // Purple
+        narration: Color::Rgb(180, 180, 180),     // Light grey
+        system: Color::Rgb(100, 100, 100),        // Dark grey
+        success: Color::Rgb(80, 200, 80),         // Green
+        border: Color::Rgb(60, 60, 80),           // Dim border
+        selection: Color::Rgb(45, 45, 70),        // Selected row
+        search: Color::Rgb(90, 80, 30),           // Search hit
+        search_active: Color::Rgb(200, 160, 40),  // Active search hit
+        link: Color::Rgb(90, 170, 230),           // Sky blue
+        stage: Color::Cyan,
+        focus: Color::Cyan,
+        on_focus: Color::Black,
+        gauge_label: Color::White,
+    };
+
+    /// For light terminal backgrounds: darker foregrounds, pale highlights.
+    pub const LIGHT: Theme = Theme {
+        user: Color::Rgb(30, 90, 190),
+        assistant: Color::Rgb(30, 120, 40),
+        tool: Color::Rgb(160, 110, 0),
+        error: Color::Rgb(190, 30, 30),
+        dim: Color::Rgb(110, 110, 110),
+        accent: Color::Rgb(110, 60, 180),
+        narration: Color::Rgb(80, 80, 80),
+        system: Color::Rgb(120, 120, 120),
+        success: Color::Rgb(30, 140, 30),
+        border: Color::Rgb(190, 190, 200),
+        selection: Color::Rgb(220, 225, 240),
+        search: Color::Rgb(250, 235, 150),
+        search_active: Color::Rgb(240, 190, 40),
+        link: Color::Rgb(20, 100, 180),
+        stage: Color::Rgb(0, 120, 140),
+        focus: Color::Rgb(0, 120, 140),
+        on_focus: Color::White,
+        gauge_label: Color::Rgb(30, 30, 30),
+    };
+
+    /// Solarized dark.
+    pub const SOLARIZED: Theme = Theme {
+        user: Color::Rgb(38, 139, 210),
+        assistant: Color::Rgb(133, 153, 0),
+        tool: Color::Rgb(181, 137, 0),
+        error: Color::Rgb(220, 50, 47),
+        dim: Color::Rgb(88, 110, 117),
+        accent: Color::Rgb(108, 113, 196),
+        narration: Color::Rgb(131, 148, 150),
+        system: Color::Rgb(88, 110, 117),
+        success: Color::Rgb(133, 153, 0),
+        border: Color::Rgb(7, 54, 66),
+        selection: Color::Rgb(7, 54, 66),
+        search: Color::Rgb(88, 74, 30),
+        search_active: Color::Rgb(203, 75, 22),
+        link: Color::Rgb(42, 161, 152),
+        stage: Color::Rgb(42, 161, 152),
+        focus: Color::Rgb(42, 161, 152),
+        on_focus: Color::Rgb(0, 43, 54),
+        gauge_label: Color::Rgb(238, 232, 213),
+    };
+
+    /// Gruvbox dark.
+    pub const GRUVBOX: Theme = Theme {
+        user: Color::Rgb(131, 165, 152),
+        assistant: Color::Rgb(184, 187, 38),
+        tool: Color::Rgb(250, 189, 47),
+        error: Color::Rgb(251, 73, 52),
+        dim: Color::Rgb(146, 131, 116),
+        accent: Color::Rgb(211, 134, 155),
+        narration: Color::Rgb(213, 196, 161),
+        system: Color::Rgb(124, 111, 100),
+        success: Color::Rgb(142, 192, 124),
+        border: Color::Rgb(80, 73, 69),
+        selection: Color::Rgb(60, 56, 54),
+        search: Color::Rgb(102, 92, 84),
+        search_active: Color::Rgb(215, 153, 33),
+        link: Color::Rgb(142, 192, 124),
+        stage: Color::Rgb(142, 192, 124),
+        focus: Color::Rgb(142, 192, 124),
+        on_focus: Color::Rgb(40, 40, 40),
+        gauge_label: Color::Rgb(235, 219, 178),
+    };
+
+    /// The terminal's own foreground with bold, italics and grays only.
+    pub const MONOCHROME: Theme = Theme {
+        user: Color::White,
+        assistant: Color::Reset,
+        tool: Color::Reset,
+        error: Color::White,
+        dim: Color::DarkGray,
+        accent: Color::White,
+        narration: Color::Gray,
+        system: Color::DarkGray,
+        success: Color::Reset,
+        border: Color::DarkGray,
+        selection: Color::DarkGray,
+        search: Color::DarkGray,
+        search_active: Color::Gray,
+        link: Color::Reset,
+        stage: Color::White,
+        focus: Color::White,
+        on_focus: Color::Black,
+        gauge_label: Color::White,
+    };
+
+    /// Every color, for adapting the whole theme to the terminal.
+    pub(crate) fn colors_mut(&mut self) -> [&mut Color; 18] {
+        [
+            &mut self.user,
+            &mut self.assistant,
+            &mut self.tool,
+            &mut self.error,
+            &mut self.dim,
+            &mut self.accent,
+            &mut self.narration,
+            &mut self.system,
+            &mut self.success,
+            &mut self.border,
+            &mut self.selection,
+            &mut self.search,
+            &mut self.search_active,
+            &mut self.link,
+            &mut self.stage,
+            &mut self.focus,
+            &mut self.on_focus,
+            &mut self.gauge_label,
+        ]
+    }
+
+    /// The color called `name` in `theme.toml`.
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "user" => &mut self.user,
+            "assistant" => &mut self.assistant,
+            "tool" => &mut self.tool,
+            "error" => &mut self.error,
+            "dim" => &mut self.dim,
+            "accent" => &mut self.accent,
+            "narration" => &mut self.narration,
+            "system" => &mut self.system,
+            "success" => &mut self.success,
+            "border" => &mut self.border,
+            "selection" => &mut self.selection,
+            "search" => &mut self.search,
+            "search_active" => &mut self.search_active,
+            "link" => &mut self.link,
+            "stage" => &mut self.stage,
+            "focus" => &mut self.focus,
+            "on_focus" => &mut self.on_focus,
+            "gauge_label" => &mut self.gauge_label,
+            _ => return None,
+        })
+    }
+
+    /// Parse `theme.toml`: `name = "color"` lines over the defaults (or over the
+    /// built-in theme named by `preset`), where a color is `#rrggbb`, an ANSI
+    /// name such as `lightblue`, or a 0–255 index.
+    pub fn parse(text: &str) -> Result<Theme> {
+        let mut colors: BTreeMap<String, String> = toml::from_str(text)?;
+        let mut theme = match colors.remove("preset") {
+            Some(name) => preset(&name).with_context(|| format!("unknown preset '{name}'"))?,
+            None => Theme::DEFAULT,
+        };
+        for (name, value) in colors {
+            let slot = theme.color_mut(&name).with_context(|| format!("unknown color '{name}'"))?;
+            *slot = Color::from_str(&value).map_err(|_| anyhow::anyhow!("invalid color '{value}' for {name}"))?;
+        }
+        Ok(theme)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Built-in themes, for `/theme` and `preset` in `theme.toml`.
+pub const PRESETS: &[(&str, Theme)] = &[
+    ("default", Theme::DEFAULT),
+    ("light", Theme::LIGHT),
+    ("solarized", Theme::SOLARIZED),
+    ("gruvbox", Theme::GRUVBOX),
+    ("monochrome", Theme::MONOCHROME),
+];
+
+pub fn preset(name: &str) -> Option<Theme> {
+    PRESETS.iter().find(|(preset, _)| *preset == name).map(|(_, theme)| *theme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        assert_eq!(Theme::parse("").unwrap(), Theme::DEFAULT);
+        let theme = Theme::parse("user = \"#112233\"\nborder = \"darkgray\"\nlink = \"33\"\n").unwrap();
+        assert_eq!(theme.user, Color::Rgb(0x11, 0x22, 0x33));
+        assert_eq!(theme.border, Color::DarkGray);
+        assert_eq!(theme.link, Color::Indexed(33));
+        assert_eq!(theme.tool, Theme::DEFAULT.tool);
+        let theme = Theme::parse("focus = \"magenta\"\ngauge_label = \"black\"\n").unwrap();
+        assert_eq!((theme.focus, theme.gauge_label), (Color::Magenta, Color::Black));
+        assert!(Theme::parse("usr = \"red\"\n").is_err());
+        assert!(Theme::parse("user = \"not a color\"\n").is_err());
+
+        let theme = Theme::parse("preset = \"gruvbox\"\nlink = \"blue\"\n").unwrap();
+        assert_eq!(theme.tool, Theme::GRUVBOX.tool);
+        assert_eq!(theme.link, Color::Blue);
+        assert!(Theme::parse("preset = \"neon\"\n").is_err());
+    }
+}
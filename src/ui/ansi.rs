@@ -0,0 +1,133 @@
+//! ANSI styling in `!` command output, which runs in a pty and so keeps its
+//! colors. SGR sequences (colors, bold, dim, italic, underline, reverse) become
+//! ratatui styles; other escapes (cursor movement, erasing, window titles)
+//! mean nothing in the chat and are dropped.
+
+use ratatui::prelude::*;
+use ratatui::text::{Line, Span};
+
+/// `text` without its escape sequences.
+pub fn strip(text: &str) -> String {
+    parse(text, Style::default()).into_iter().map(|(text, _)| text).collect()
+}
+
+/// One line of output as spans styled on top of `base`.
+pub fn line(text: &str, base: Style) -> Line<'static> {
+    let spans: Vec<Span<'static>> = parse(text, base).into_iter().map(|(text, style)| Span::styled(text, style)).collect();
+    Line::from(spans)
+}
+
+/// Split `text` into runs of one style each.
+fn parse(text: &str, base: Style) -> Vec<(String, Style)> {
+    let mut runs = Vec::new();
+    let mut style = base;
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            current.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                let mut params = String::new();
+                let mut last = None;
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        last = Some(c);
+                        break;
+                    }
+                    params.push(c);
+                }
+                if last == Some('m') {
+                    if !current.is_empty() {
+                        runs.push((std::mem::take(&mut current), style));
+                    }
+                    style = apply_sgr(style, base, &params);
+                }
+            }
+            // OSC (titles, hyperlinks): up to BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Character set selection, e.g. ESC ( B
+            Some('(' | ')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    if !current.is_empty() {
+        runs.push((current, style));
+    }
+    runs
+}
+
+/// `style` after the SGR parameters `params` (`1;31`, `38;5;208`, …); a reset goes back to `base`.
+fn apply_sgr(mut style: Style, base: Style, params: &str) -> Style {
+    let mut codes = params.split(';').map(|code| code.parse::<u16>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => base,
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            7 => style.add_modifier(Modifier::REVERSED),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            30..=37 => style.fg(Color::Indexed((code - 30) as u8)),
+            90..=97 => style.fg(Color::Indexed((code - 90 + 8) as u8)),
+            38 => extended(&mut codes).map_or(style, |color| style.fg(color)),
+            39 => style.fg(base.fg.unwrap_or(Color::Reset)),
+            40..=47 => style.bg(Color::Indexed((code - 40) as u8)),
+            100..=107 => style.bg(Color::Indexed((code - 100 + 8) as u8)),
+            48 => extended(&mut codes).map_or(style, |color| style.bg(color)),
+            49 => style.bg(base.bg.unwrap_or(Color::Reset)),
+            _ => style,
+        };
+    }
+    style
+}
+
+/// The color after a 38 or 48: `5;n` from the 256-color palette, or `2;r;g;b`.
+fn extended(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()? as u8)),
+        2 => Some(Color::Rgb(codes.next()? as u8, codes.next()? as u8, codes.next()? as u8)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip("plain"), "plain");
+        assert_eq!(strip("\x1b[1;32mok\x1b[0m done"), "ok done");
+        assert_eq!(strip("\x1b]0;title\x07a\x1b[2Kb\x1b(B"), "ab");
+        assert_eq!(strip("\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"), "link");
+    }
+
+    #[test]
+    fn test_line_styles() {
+        let base = Style::default().fg(Color::Gray);
+        let styled = line("\x1b[1;31merror\x1b[0m: \x1b[38;5;208mwarn\x1b[38;2;1;2;3mrgb\x1b[39mx", base);
+        let spans: Vec<(&str, Style)> = styled.spans.iter().map(|s| (s.content.as_ref(), s.style)).collect();
+        assert_eq!(spans[0], ("error", base.fg(Color::Indexed(1)).add_modifier(Modifier::BOLD)));
+        assert_eq!(spans[1], (": ", base));
+        assert_eq!(spans[2], ("warn", base.fg(Color::Indexed(208))));
+        assert_eq!(spans[3], ("rgb", base.fg(Color::Rgb(1, 2, 3))));
+        assert_eq!(spans[4], ("x", base));
+        assert_eq!(line("\x1b[94mhi", base).spans[0].style.fg, Some(Color::Indexed(12)));
+    }
+}
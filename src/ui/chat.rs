@@ -1,91 +1,210 @@
 //! Chat area widget — renders scrollable message list.
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use std::collections::HashMap;
+
+use ratatui::widgets::{Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState};
 use ratatui::text::{Line, Span};
 
-use crate::app::{App, ChatMessage};
-use super::theme;
+use crate::app::{self, App, ChatMessage, PanelFocus};
+use crate::graphics::{ImageProtocol, ImageSlot};
+use crate::links::{self, LinkSlot};
+use super::line_cache::RenderKey;
+use super::{ansi, icons, theme};
 
 /// Render the chat area.
-pub fn render(frame: &mut Frame, area: Rect, app: &App) {
-    let block = Block::default()
+pub fn render(frame: &mut Frame, area: Rect, app: &mut App) {
+    let is_focused = app.focus == PanelFocus::Chat;
+    let agent = theme::agent_style(&app.status.agent_name);
+    let (border_style, mut title) = if is_focused && app.selection_mode {
+        (agent, " ● Chat [select] ".to_string())
+    } else if is_focused {
+        (agent, " ● Chat ".to_string())
+    } else {
+        (theme::border_style(), " Chat ".to_string())
+    };
+    if let Some(id) = app.selected_message.and_then(|i| app.message_id(i)) {
+        title.push_str(&format!("#{id} "));
+    }
+    if !app.hidden_kinds.is_empty() {
+        let hidden: Vec<&str> = app.hidden_kinds.iter().map(|k| k.name()).collect();
+        title.push_str(&format!("[hiding {}] ", hidden.join(", ")));
+    }
+    let mut block = Block::default()
         .borders(Borders::ALL)
-        .border_style(theme::border_style())
-        .title(Span::styled(" Chat ", theme::accent_style()));
+        .border_style(border_style)
+        .title(Span::styled(title, theme::accent_style()));
+    if let Some(search) = &app.search {
+        let position = if search.matches.is_empty() {
+            "no matches".to_string()
+        } else {
+            format!("{}/{}", search.current + 1, search.matches.len())
+        };
+        let cursor = if search.editing { "▏" } else { "" };
+        let hint = if search.editing { "Enter done, Esc cancel" } else { "n/N next/prev, Esc close" };
+        block = block.title_bottom(Span::styled(
+            format!(" /{}{cursor} ({position}) {hint} ", search.query),
+            theme::accent_style(),
+        ));
+    } else if let Some((text, _)) = app.toast.as_ref().filter(|(_, since)| since.elapsed().as_secs() < 3) {
+        block = block.title_bottom(Span::styled(format!(" {text} "), theme::accent_style()));
+    }
 
     let inner = block.inner(area);
+    let visible_height = inner.height as usize;
 
-    // Build lines from messages
-    let mut lines: Vec<Line> = Vec::new();
+    let tool_pairs = app.tool_pairs();
+    let paired_results: std::collections::HashSet<usize> = tool_pairs.values().copied().collect();
+
+    // Lay out every message from the cache: (message index, first row, wrapped rows)
+    let mut cache = std::mem::take(&mut app.line_cache);
+    cache.prepare(inner.width as usize, app.messages.len());
+    let mut placed: Vec<(usize, usize, &[Line<'static>])> = Vec::new();
+    let mut total_lines = 0;
+    // Collect keys first so the cache can be borrowed for all rows at once
+    let theme = theme::generation();
+    let keys: Vec<(usize, RenderKey)> = (0..app.messages.len())
+        .filter(|&i| !paired_results.contains(&i) && app.is_visible(i))
+        .map(|i| {
+            let key = RenderKey {
+                expanded: app.expanded_tools.contains(&i),
+                settled: tool_pairs.contains_key(&i),
+                theme,
+            };
+            (i, key)
+        })
+        .collect();
+    if keys.is_empty() {
+        total_lines = 1;
+    }
+    for &(i, key) in &keys {
+        cache.rows(i, key, || message_lines(app, i, &tool_pairs));
+    }
+    for &(i, _) in &keys {
+        let rows = cache.get(i).unwrap_or_default();
+        placed.push((i, total_lines, rows));
+        // Blank line between messages for readability
+        total_lines += rows.len() + 1;
+    }
+    if app.agent_busy {
+        total_lines += 1;
+    }
+
+    // Calculate scroll: jump to the active search hit or /goto target, otherwise auto-scroll if at bottom
+    let active_match = app.active_match();
+    let first_row_of = |index: usize| placed.iter().find(|(i, _, _)| *i == index).map(|&(_, row, _)| row);
+    let search_jump = app.search.as_ref().filter(|s| s.jump_pending).and(active_match).and_then(first_row_of);
+    let jump_to = search_jump.or_else(|| app.jump_to_message.and_then(first_row_of));
+    app.chat_lines = total_lines;
+    app.chat_view_height = visible_height;
+    let scroll = if let Some(line) = jump_to {
+        line.saturating_sub(visible_height / 3)
+            .min(total_lines.saturating_sub(visible_height))
+    } else {
+        app.chat_scroll_position()
+    };
+    let window = scroll..scroll + visible_height;
 
+    // Reading position inside an answer taller than the view that starts above it
+    let answer_progress = placed
+        .iter()
+        .find(|&&(i, first_row, rows)| {
+            matches!(app.messages[i], ChatMessage::Assistant(_))
+                && rows.len() > visible_height
+                && (first_row..first_row + rows.len()).contains(&window.start)
+        })
+        .map(|&(_, first_row, rows)| (window.end - first_row).min(rows.len()) * 100 / rows.len());
+    if let Some(percent) = answer_progress {
+        block = block.title_top(
+            Line::from(Span::styled(format!(" answer {percent}% "), theme::accent_style())).right_aligned(),
+        );
+    }
+
+    // Previews are painted over their reserved rows after the frame, if fully in view
+    let previews = app.image_protocol != ImageProtocol::None;
+    app.image_slots = placed
+        .iter()
+        .filter(|_| previews)
+        .filter_map(|&(i, first_row, rows)| {
+            let path = app.images.get(&i)?;
+            let top = first_row + rows.len() - IMAGE_ROWS;
+            (top >= window.start && top + IMAGE_ROWS <= window.end).then(|| ImageSlot {
+                path: path.clone(),
+                x: inner.x + 4,
+                y: inner.y + (top - scroll) as u16,
+                cols: inner.width.saturating_sub(6).min(IMAGE_COLS),
+                rows: IMAGE_ROWS as u16,
+            })
+        })
+        .collect();
+
+    // Scrolled away from the tail: say where we are
+    let scrollable = total_lines > visible_height;
+    if scrollable && scroll + visible_height < total_lines {
+        let bottom = scroll + visible_height;
+        block = block.title_top(
+            Line::from(Span::styled(
+                format!(" {bottom}/{total_lines} lines, {}% ", bottom * 100 / total_lines),
+                theme::accent_style(),
+            ))
+            .right_aligned(),
+        );
+    }
+
+    // Only the rows in view are cloned and styled
+    let mut lines: Vec<Line> = Vec::new();
     if app.messages.is_empty() {
         lines.push(Line::from(Span::styled(
             "  Type a message to begin...",
             theme::dim_style(),
         )));
+    } else if keys.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  Every message is hidden (/filter reset to show them)",
+            theme::dim_style(),
+        )));
     }
-
-    for msg in &app.messages {
-        match msg {
-            ChatMessage::User(text) => {
-                lines.push(Line::from(vec![
-                    Span::styled("> ", theme::user_style()),
-                    Span::styled(text.as_str(), theme::user_style()),
-                ]));
-            }
-            ChatMessage::Assistant(text) => {
-                // Split into lines for multi-line responses
-                for line in text.lines() {
-                    lines.push(Line::from(Span::styled(
-                        format!("  {line}"),
-                        theme::assistant_style(),
-                    )));
-                }
-            }
-            ChatMessage::Narration(text) => {
-                lines.push(Line::from(vec![
-                    Span::styled("  💬 ", Style::default()),
-                    Span::styled(text.as_str(), theme::narration_style()),
-                ]));
-            }
-            ChatMessage::ToolCall { name, args_short } => {
-                lines.push(Line::from(vec![
-                    Span::styled("  ⚡ ", Style::default()),
-                    Span::styled(name.as_str(), theme::tool_style()),
-                    Span::raw(" "),
-                    Span::styled(args_short.as_str(), theme::dim_style()),
-                ]));
+    let needle = app.search.as_ref().map(|s| s.query.to_ascii_lowercase()).unwrap_or_default();
+    let matches: &[usize] = app.search.as_ref().map(|s| s.matches.as_slice()).unwrap_or(&[]);
+    for &(i, first_row, rows) in &placed {
+        let end_row = first_row + rows.len() + 1;
+        if end_row <= window.start || first_row >= window.end {
+            continue;
+        }
+        let highlight = (!needle.is_empty() && matches.contains(&i)).then(|| {
+            if active_match == Some(i) {
+                theme::search_active_style()
+            } else {
+                theme::search_match_style()
             }
-            ChatMessage::ToolResult { name, success, duration_ms } => {
-                let icon = if *success { "  ✓ " } else { "  ✗ " };
-                let style = if *success { theme::success_style() } else { theme::error_style() };
-                lines.push(Line::from(vec![
-                    Span::styled(icon, style),
-                    Span::styled(name.as_str(), theme::dim_style()),
-                    Span::raw(" "),
-                    Span::styled(format!("{duration_ms}ms"), theme::dim_style()),
-                ]));
+        });
+        // Restyling the reserved rows would paint over the image
+        let preview_start = if previews && app.images.contains_key(&i) { rows.len() - IMAGE_ROWS } else { rows.len() };
+        for (r, row) in rows.iter().enumerate() {
+            if !window.contains(&(first_row + r)) {
+                continue;
             }
-            ChatMessage::Error(text) => {
-                lines.push(Line::from(vec![
-                    Span::styled("  ✗ ", theme::error_style()),
-                    Span::styled(text.as_str(), theme::error_style()),
-                ]));
+            let mut line = row.clone();
+            if app.selected_message == Some(i) && r < preview_start {
+                line.style = line.style.patch(theme::selection_style());
             }
-            ChatMessage::System(text) => {
-                lines.push(Line::from(Span::styled(
-                    format!("  {text}"),
-                    theme::system_style(),
-                )));
+            if let Some(style) = highlight {
+                line = highlight_matches(line, &needle, style);
             }
+            lines.push(line);
+        }
+        if window.contains(&(end_row - 1)) {
+            lines.push(Line::from(""));
         }
-        // Add blank line between messages for readability
-        lines.push(Line::from(""));
     }
 
-    // Show thinking indicator
-    if app.agent_busy {
+    // Show thinking indicator, or why the turn is waiting
+    if let (true, Some(wait), Some((provider, _))) = (app.agent_busy, app.rate_limit_wait(), &app.rate_limited) {
+        lines.push(Line::from(Span::styled(
+            format!("  {} Rate limited by {provider}, retrying in {wait}s", icons::current().waiting),
+            theme::tool_style(),
+        )));
+    } else if app.agent_busy {
         let dots = if let Some(since) = app.thinking_since {
             let elapsed = since.elapsed().as_secs();
             let dot_count = (elapsed % 4) as usize;
@@ -94,25 +213,308 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
             "...".to_string()
         };
         lines.push(Line::from(Span::styled(
-            format!("  🧠 Thinking{dots}"),
+            format!("  {} Thinking{dots}", icons::current().model),
             theme::dim_style(),
         )));
     }
 
-    let total_lines = lines.len();
-    let visible_height = inner.height as usize;
+    // Links in view: underlined here, turned into OSC 8 hyperlinks after the frame
+    let mut link_slots = Vec::new();
+    for &(i, first_row, rows) in &placed {
+        if first_row + rows.len() <= window.start || first_row >= window.end {
+            continue;
+        }
+        let text = app.messages[i].text();
+        let urls = links::urls(&text);
+        if urls.is_empty() {
+            continue;
+        }
+        let row_text: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+        for (r, range, url) in links::row_links(&row_text, &urls) {
+            let row = first_row + r;
+            if !window.contains(&row) {
+                continue;
+            }
+            let (x, width) = links::columns(&row_text[r], &range);
+            let width = width.min(inner.width.saturating_sub(x));
+            if width > 0 {
+                link_slots.push(LinkSlot { x: inner.x + x, y: inner.y + (row - scroll) as u16, width, url });
+            }
+        }
+    }
 
-    // Calculate scroll: auto-scroll if at bottom
-    let scroll = if app.scroll_offset == usize::MAX || app.scroll_offset + visible_height >= total_lines {
-        total_lines.saturating_sub(visible_height)
-    } else {
-        app.scroll_offset
-    };
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    for slot in &link_slots {
+        frame.buffer_mut().set_style(Rect::new(slot.x, slot.y, slot.width, 1), theme::link_style());
+    }
+    app.link_slots = link_slots;
+    if scrollable {
+        let mut scrollbar_state = ScrollbarState::new(total_lines.saturating_sub(visible_height))
+            .position(scroll)
+            .viewport_content_length(visible_height);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .track_symbol(None)
+                .thumb_style(theme::dim_style()),
+            area.inner(Margin { vertical: 1, horizontal: 0 }),
+            &mut scrollbar_state,
+        );
+    }
+
+    drop(placed);
+    app.line_cache = cache;
+    if jump_to.is_some() {
+        app.scroll_offset = scroll;
+        app.jump_to_message = None;
+        if let Some(search) = app.search.as_mut() {
+            search.jump_pending = false;
+        }
+    }
+}
+
+/// Diff lines shown inline before the rest is left to the pager.
+const MAX_DIFF_LINES: usize = 40;
+
+/// Color for a unified diff line: additions green, removals red, hunk headers accented.
+pub fn diff_line_style(line: &str) -> Style {
+    match line.chars().next() {
+        Some('+') => theme::success_style(),
+        Some('-') => theme::error_style(),
+        Some('@') => theme::accent_style(),
+        _ => theme::dim_style(),
+    }
+}
+
+/// Tool output lines shown under a collapsed tool block.
+const OUTPUT_PREVIEW_LINES: usize = 3;
+
+/// Tool output under its call: a few lines when collapsed, all of it when
+/// expanded. Why a failed call failed comes first.
+fn output_lines(output: &str, error: Option<&str>, expanded: bool) -> Vec<Line<'static>> {
+    let rows: Vec<(&str, Style)> = error
+        .unwrap_or_default()
+        .lines()
+        .map(|line| (line, theme::error_style()))
+        .chain(output.lines().map(|line| (line, theme::dim_style())))
+        .collect();
+    let total = rows.len();
+    let shown = if expanded { total } else { total.min(OUTPUT_PREVIEW_LINES) };
+    let mut lines = Vec::new();
+    if expanded && total > 0 {
+        lines.push(Line::from(Span::styled("    ├─ output", theme::dim_style())));
+    }
+    for (line, style) in rows.into_iter().take(shown) {
+        lines.push(Line::from(vec![
+            Span::styled(if expanded { "    │ " } else { "    ┊ " }, theme::dim_style()),
+            Span::styled(line.to_string(), style),
+        ]));
+    }
+    if shown < total {
+        lines.push(Line::from(Span::styled(
+            format!("    ┊ … {} more lines (Enter to expand)", total - shown),
+            theme::dim_style(),
+        )));
+    }
+    lines
+}
 
-    let paragraph = Paragraph::new(lines)
-        .block(block)
-        .wrap(Wrap { trim: false })
-        .scroll((scroll as u16, 0));
+/// Size of the box reserved for an inline image preview, in cells.
+const IMAGE_ROWS: usize = 10;
+const IMAGE_COLS: u16 = 40;
+
+/// Unwrapped lines for message `i` (tool calls include their paired result).
+fn message_lines(app: &App, i: usize, tool_pairs: &HashMap<usize, usize>) -> Vec<Line<'static>> {
+    let icons = icons::current();
+    let mut lines = Vec::new();
+    match &app.messages[i] {
+        ChatMessage::User(text) => {
+            lines.push(Line::from(vec![
+                Span::styled("> ", theme::user_style()),
+                Span::styled(text.clone(), theme::user_style()),
+            ]));
+        }
+        ChatMessage::Assistant(text) => {
+            // Split into lines for multi-line responses; the gutter marks which agent answered
+            for line in text.lines() {
+                lines.push(Line::from(vec![
+                    Span::styled("▎ ", theme::agent_style(&app.status.agent_name)),
+                    Span::styled(line.to_string(), theme::assistant_style()),
+                ]));
+            }
+        }
+        ChatMessage::Narration(text) => {
+            // Reasoning before the answer: a collapsed, dimmed block unless opened
+            let expanded = app.expanded_tools.contains(&i);
+            let mut header = vec![
+                Span::styled(format!("  {} ", if expanded { icons.expanded } else { icons.collapsed }), theme::dim_style()),
+                Span::styled(format!("{} ", icons.thought), Style::default()),
+                Span::styled("thought", theme::narration_style()),
+            ];
+            if !expanded {
+                let first = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+                header.push(Span::styled(format!(" · {}", crate::app::shorten(first.trim(), 80)), theme::thought_style()));
+                let more = text.lines().count().saturating_sub(1);
+                if more > 0 {
+                    header.push(Span::styled(format!(" (+{more} lines)"), theme::dim_style()));
+                }
+            }
+            lines.push(Line::from(header));
+            if expanded {
+                for line in text.lines() {
+                    lines.push(Line::from(vec![
+                        Span::styled("    │ ", theme::dim_style()),
+                        Span::styled(line.to_string(), theme::thought_style()),
+                    ]));
+                }
+            }
+        }
+        ChatMessage::ToolCall { name, args_short, args } => {
+            let expanded = app.expanded_tools.contains(&i);
+            let result = tool_pairs.get(&i).and_then(|&r| app.messages.get(r));
+            let mut header = vec![
+                Span::styled(format!("  {} ", if expanded { icons.expanded } else { icons.collapsed }), theme::dim_style()),
+                Span::styled(format!("{} ", icons.tool), Style::default()),
+                Span::styled(name.clone(), theme::tool_style()),
+                Span::raw(" "),
+            ];
+            if !expanded {
+                header.push(Span::styled(args_short.clone(), theme::dim_style()));
+                header.push(Span::raw(" "));
+            }
+            match result {
+                Some(ChatMessage::ToolResult { success, duration_ms, .. }) => {
+                    let (icon, style) = if *success {
+                        (icons.ok, theme::success_style())
+                    } else {
+                        (icons.failed, theme::error_style())
+                    };
+                    header.push(Span::styled(icon, style));
+                    header.push(Span::styled(format!(" {duration_ms}ms"), theme::dim_style()));
+                }
+                _ => header.push(Span::styled("… running", theme::dim_style())),
+            }
+            lines.push(Line::from(header));
+            if expanded {
+                let pretty = serde_json::from_str::<serde_json::Value>(args)
+                    .ok()
+                    .and_then(|v| serde_json::to_string_pretty(&v).ok())
+                    .unwrap_or_else(|| args.clone());
+                for line in pretty.lines() {
+                    lines.push(Line::from(vec![
+                        Span::styled("    │ ", theme::dim_style()),
+                        Span::styled(line.to_string(), theme::dim_style()),
+                    ]));
+                }
+            }
+            if let Some(ChatMessage::ToolResult { output, error, .. }) = result {
+                lines.extend(output_lines(output, error.as_deref(), expanded));
+            }
+        }
+        ChatMessage::ToolResult { name, success, duration_ms, output, error } => {
+            let icon = format!("  {} ", if *success { icons.ok } else { icons.failed });
+            let style = if *success { theme::success_style() } else { theme::error_style() };
+            lines.push(Line::from(vec![
+                Span::styled(icon, style),
+                Span::styled(name.clone(), theme::dim_style()),
+                Span::raw(" "),
+                Span::styled(format!("{duration_ms}ms"), theme::dim_style()),
+            ]));
+            lines.extend(output_lines(output, error.as_deref(), false));
+        }
+        ChatMessage::Diff { path, diff } => {
+            let (added, removed) = crate::diff::stats(diff);
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {} ", icons.edit), theme::tool_style()),
+                Span::styled(path.clone(), theme::tool_style()),
+                Span::styled(format!(" +{added}"), theme::success_style()),
+                Span::styled(format!(" −{removed}"), theme::error_style()),
+            ]));
+            // File headers repeat the path; long diffs are read in the pager
+            let body: Vec<&str> = crate::diff::hunks(diff).collect();
+            for line in body.iter().take(MAX_DIFF_LINES) {
+                lines.push(Line::from(vec![
+                    Span::styled("    │ ", theme::dim_style()),
+                    Span::styled(line.to_string(), diff_line_style(line)),
+                ]));
+            }
+            if body.len() > MAX_DIFF_LINES {
+                lines.push(Line::from(Span::styled(
+                    format!("    … {} more lines (Enter to read)", body.len() - MAX_DIFF_LINES),
+                    theme::dim_style(),
+                )));
+            }
+        }
+        ChatMessage::Shell { command, output, exit, killed } => {
+            let status = app::shell_status(*exit, *killed);
+            let (icon, style) = match exit {
+                None => (icons.working, theme::dim_style()),
+                Some(0) => (icons.ok, theme::success_style()),
+                Some(_) => (icons.failed, theme::error_style()),
+            };
+            lines.push(Line::from(vec![
+                Span::styled("  $ ", theme::tool_style()),
+                Span::styled(command.clone(), theme::tool_style()),
+                Span::styled(format!(" {icon} {status}"), style),
+            ]));
+            // Output keeps the command's own colors
+            for line in output.lines() {
+                let mut row = ansi::line(line, Style::default());
+                row.spans.insert(0, Span::styled("    │ ", theme::dim_style()));
+                lines.push(row);
+            }
+        }
+        ChatMessage::Error(text) => {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {} ", icons.failed), theme::error_style()),
+                Span::styled(text.clone(), theme::error_style()),
+            ]));
+        }
+        ChatMessage::System(text) => {
+            lines.push(Line::from(Span::styled(
+                format!("  {text}"),
+                theme::system_style(),
+            )));
+        }
+    }
+    if let Some(path) = app.images.get(&i) {
+        let path = path.display().to_string();
+        if app.image_protocol == ImageProtocol::None {
+            lines.push(Line::from(Span::styled(format!("    {} [image: {path}]", icons.image), theme::dim_style())));
+        } else {
+            lines.push(Line::from(Span::styled(format!("    {} {path}", icons.image), theme::dim_style())));
+            lines.extend((0..IMAGE_ROWS).map(|_| Line::from("")));
+        }
+    }
+    lines
+}
 
-    frame.render_widget(paragraph, area);
+/// Split spans so every case-insensitive occurrence of `needle` (already lowercased) gets `style`.
+pub(super) fn highlight_matches<'a>(line: Line<'a>, needle: &str, style: Style) -> Line<'a> {
+    let line_style = line.style;
+    let mut spans: Vec<Span<'a>> = Vec::new();
+    for span in line.spans {
+        // ASCII lowercasing keeps byte offsets identical to the original text
+        let lower = span.content.to_ascii_lowercase();
+        if !lower.contains(needle) {
+            spans.push(span);
+            continue;
+        }
+        let text = span.content.as_ref();
+        let mut pos = 0;
+        while let Some(found) = lower[pos..].find(needle) {
+            let start = pos + found;
+            let end = start + needle.len();
+            if start > pos {
+                spans.push(Span::styled(text[pos..start].to_string(), span.style));
+            }
+            spans.push(Span::styled(text[start..end].to_string(), span.style.patch(style)));
+            pos = end;
+        }
+        if pos < text.len() {
+            spans.push(Span::styled(text[pos..].to_string(), span.style));
+        }
+    }
+    Line::from(spans).style(line_style)
 }
@@ -0,0 +1,127 @@
+//! Side-by-side comparison of the answers a `/broadcast` got from each tab.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+
+use super::{theme, wrap};
+
+/// One tab's answer: its reply, or why there is none.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    /// The tab number and its agent and model.
+    pub title: String,
+    pub answer: Result<String, String>,
+}
+
+/// The comparison: the broadcast prompt and a column per tab, scrolled together.
+#[derive(Debug, Clone)]
+pub struct CompareState {
+    pub prompt: String,
+    pub replies: Vec<Reply>,
+    /// First visible row of every column.
+    pub scroll: usize,
+    pub page_height: usize,
+    /// Rows of the longest column as last drawn.
+    pub rows: usize,
+}
+
+impl CompareState {
+    pub fn new(prompt: String, replies: Vec<Reply>) -> Self {
+        Self { prompt, replies, scroll: 0, page_height: 0, rows: 0 }
+    }
+
+    pub fn max_scroll(&self) -> usize {
+        self.rows.saturating_sub(self.page_height)
+    }
+
+    pub fn scroll_by(&mut self, delta: isize) {
+        self.scroll = self.scroll.saturating_add_signed(delta).min(self.max_scroll());
+    }
+}
+
+/// Handle a key. Returns false when the comparison should close.
+pub fn handle_key(state: &mut CompareState, key: KeyEvent) -> bool {
+    let page = state.page_height.max(1) as isize;
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => return false,
+        KeyCode::Down | KeyCode::Char('j') => state.scroll_by(1),
+        KeyCode::Up | KeyCode::Char('k') => state.scroll_by(-1),
+        KeyCode::PageDown | KeyCode::Char(' ') => state.scroll_by(page),
+        KeyCode::PageUp | KeyCode::Char('b') => state.scroll_by(-page),
+        KeyCode::Home | KeyCode::Char('g') => state.scroll = 0,
+        KeyCode::End | KeyCode::Char('G') => state.scroll = state.max_scroll(),
+        _ => {}
+    }
+    true
+}
+
+/// Render the comparison over `area`, one column per reply.
+pub fn render(frame: &mut Frame, area: Rect, state: &mut CompareState) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(format!(" Broadcast: {} ", state.prompt), theme::accent_style()))
+        .title_bottom(Span::styled(" ↑↓ scroll · q close ", theme::accent_style()));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let count = state.replies.len().max(1) as u32;
+    let columns = Layout::horizontal(vec![Constraint::Ratio(1, count); count as usize]).split(inner);
+    // Each column has a border of its own
+    state.page_height = inner.height.saturating_sub(2) as usize;
+    let wrapped: Vec<Vec<String>> = state
+        .replies
+        .iter()
+        .zip(columns.iter())
+        .map(|(reply, column)| {
+            let width = column.width.saturating_sub(2) as usize;
+            let text = match &reply.answer {
+                Ok(text) | Err(text) => text,
+            };
+            text.lines().flat_map(|line| wrap::wrap_str(line, width)).collect()
+        })
+        .collect();
+    state.rows = wrapped.iter().map(Vec::len).max().unwrap_or(0);
+    state.scroll = state.scroll.min(state.max_scroll());
+
+    for ((reply, rows), column) in state.replies.iter().zip(wrapped).zip(columns.iter()) {
+        let style = if reply.answer.is_ok() { theme::assistant_style() } else { theme::error_style() };
+        let lines: Vec<Line> = rows
+            .into_iter()
+            .skip(state.scroll)
+            .take(state.page_height)
+            .map(|row| Line::from(Span::styled(row, style)))
+            .collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme::border_style())
+            .title(Span::styled(format!(" {} ", reply.title), theme::accent_style()));
+        frame.render_widget(Paragraph::new(lines).block(block), *column);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll() {
+        let reply = |answer: Result<String, String>| Reply { title: "1 a · m".into(), answer };
+        let mut state = CompareState::new("q".into(), vec![reply(Ok("a\nb".into())), reply(Err("failed".into()))]);
+        state.rows = 10;
+        state.page_height = 4;
+        let key = |code| KeyEvent::from(code);
+        assert!(handle_key(&mut state, key(KeyCode::PageDown)));
+        assert_eq!(state.scroll, 4);
+        assert!(handle_key(&mut state, key(KeyCode::End)));
+        assert_eq!(state.scroll, 6);
+        state.scroll_by(5);
+        assert_eq!(state.scroll, 6);
+        assert!(handle_key(&mut state, key(KeyCode::Up)));
+        assert_eq!(state.scroll, 5);
+        assert!(!handle_key(&mut state, key(KeyCode::Esc)));
+    }
+}
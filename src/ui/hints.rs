@@ -0,0 +1,131 @@
+//! Context-sensitive key hints for the focused panel, shown in the status line.
+
+use ratatui::text::{Line, Span};
+
+use crate::app::{App, ChatMessage, PanelFocus, SidebarTab};
+use super::theme;
+
+/// (key, action) pairs for the given panel in the app's current mode.
+pub fn key_hints(app: &App, panel: PanelFocus) -> Vec<(&'static str, &'static str)> {
+    match panel {
+        PanelFocus::Input => vec![("Enter", "send"), ("↑↓", "history"), ("Tab", "panels"), ("/help", "commands")],
+        PanelFocus::Chat if app.selection_mode => vec![
+            ("↑↓", "move"),
+            ("y", "copy"),
+            ("Y", "code"),
+            ("Enter", "read"),
+            ("r", "raw"),
+            ("o", "open link"),
+            ("v", "exit"),
+        ],
+        PanelFocus::Chat if matches!(app.selected(), Some(ChatMessage::ToolCall { .. })) => vec![
+            ("Enter", "expand"),
+            ("t", "args tree"),
+            ("r", "raw"),
+            ("↑↓", "blocks"),
+            ("Esc", "back"),
+        ],
+        PanelFocus::Chat if matches!(app.selected(), Some(ChatMessage::Narration(_))) => vec![
+            ("Enter", "expand"),
+            ("r", "raw"),
+            ("h", "hide thoughts"),
+            ("↑↓", "blocks"),
+            ("Esc", "back"),
+        ],
+        PanelFocus::Chat => vec![
+            ("/", "search"),
+            ("v", "select"),
+            ("↑↓", "blocks"),
+            ("g/G", "top/end"),
+            ("a", "answer start"),
+            ("e", "export"),
+            ("Esc", "back"),
+        ],
+        PanelFocus::Sidebar => match app.sidebar_tab {
+            SidebarTab::Trace if app.selected_failed_stage().is_some() => vec![
+                ("r", "retry stage"),
+                ("↑↓", "stages"),
+                ("Enter", "full screen"),
+                ("f", "follow"),
+                ("e", "export"),
+            ],
+            SidebarTab::Trace => vec![
+                ("↑↓", "stages"),
+                ("Enter", "full screen"),
+                ("PgUp/Dn", "scroll"),
+                ("f", "follow"),
+                ("e", "export"),
+            ],
+            SidebarTab::Llm => vec![("↑↓", "scroll"), ("PgUp/Dn", "page"), ("f", "follow")],
+            SidebarTab::Status | SidebarTab::Files => vec![("↑↓", "scroll"), ("1-4", "tabs"), ("Esc", "back")],
+        },
+    }
+}
+
+/// The hints for `panel` that fit in `width` columns, or `None` if it isn't focused.
+pub fn hint_line(app: &App, panel: PanelFocus, width: usize) -> Option<Line<'static>> {
+    if app.focus != panel {
+        return None;
+    }
+    let mut spans = vec![Span::raw(" ")];
+    let mut used = 2;
+    for (i, (key, action)) in key_hints(app, panel).into_iter().enumerate() {
+        let separator = if i > 0 { 2 } else { 0 };
+        let hint_width = separator + key.chars().count() + 2 + action.chars().count();
+        if used + hint_width > width {
+            break;
+        }
+        used += hint_width;
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled(key, theme::accent_style()));
+        spans.push(Span::styled(format!(": {action}"), theme::dim_style()));
+    }
+    spans.push(Span::raw(" "));
+    (spans.len() > 2).then(|| Line::from(spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::TraceEntry;
+
+    fn keys(app: &App, panel: PanelFocus) -> Vec<&'static str> {
+        key_hints(app, panel).into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn test_hints_follow_mode() {
+        let mut app = App::new("a", "m", "w");
+        app.add_message(ChatMessage::ToolCall { name: "exec".into(), args_short: "{}".into(), args: "{}".into() });
+        assert!(keys(&app, PanelFocus::Chat).contains(&"/"));
+
+        app.selected_message = Some(0);
+        assert!(keys(&app, PanelFocus::Chat).contains(&"t"));
+
+        app.toggle_selection_mode();
+        assert!(keys(&app, PanelFocus::Chat).contains(&"Y"));
+
+        app.push_trace(TraceEntry::StageStart { id: "s".into(), kind: "llm".into() });
+        app.push_trace(TraceEntry::StageFailed { id: "s".into(), error: "boom".into() });
+        app.sidebar_tab = SidebarTab::Trace;
+        assert!(!keys(&app, PanelFocus::Sidebar).contains(&"r"));
+        app.select_prev_stage();
+        assert_eq!(keys(&app, PanelFocus::Sidebar)[0], "r");
+    }
+
+    #[test]
+    fn test_hint_line_only_when_focused() {
+        let mut app = App::new("a", "m", "w");
+        assert!(hint_line(&app, PanelFocus::Chat, usize::MAX).is_none());
+        app.focus = PanelFocus::Chat;
+        let line = hint_line(&app, PanelFocus::Chat, usize::MAX).unwrap();
+        assert!(line.to_string().contains("/: search  v: select"));
+
+        // Only whole hints that fit are kept
+        let line = hint_line(&app, PanelFocus::Chat, 26).unwrap();
+        assert_eq!(line.to_string(), " /: search  v: select ");
+        assert!(hint_line(&app, PanelFocus::Chat, 5).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! Glyphs used across chat, sidebar and trace rendering. Fonts without the
+//! emoji show tofu and throw the columns off, so `chat.ascii_icons` in `tui.toml`
+//! swaps in plain ASCII.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icons {
+    pub tool: &'static str,
+    pub ok: &'static str,
+    pub failed: &'static str,
+    pub model: &'static str,
+    pub thought: &'static str,
+    pub file: &'static str,
+    pub edit: &'static str,
+    pub image: &'static str,
+    pub waiting: &'static str,
+    pub working: &'static str,
+    pub idle: &'static str,
+    pub stage: &'static str,
+    pub pending: &'static str,
+    pub skipped: &'static str,
+    /// A pipeline stage that was skipped.
+    pub not_run: &'static str,
+    pub timer: &'static str,
+    pub narration: &'static str,
+    pub artifact: &'static str,
+    pub warning: &'static str,
+    pub collapsed: &'static str,
+    pub expanded: &'static str,
+    /// Glyphs leading system messages and toasts.
+    pub compact: &'static str,
+    pub handoff: &'static str,
+    pub loaded: &'static str,
+    pub rewind: &'static str,
+    pub pin: &'static str,
+    pub cleared: &'static str,
+    pub unlocked: &'static str,
+    pub saved: &'static str,
+    pub checkpoint: &'static str,
+    pub link: &'static str,
+    pub theme: &'static str,
+    pub copied: &'static str,
+    pub exported: &'static str,
+    pub key: &'static str,
+    pub logo: &'static str,
+    pub retry: &'static str,
+    pub undo: &'static str,
+    pub resume: &'static str,
+    /// Frames of the busy spinner, one per tick.
+    pub spinner: &'static [&'static str],
+}
+
+impl Icons {
+    pub const UNICODE: Icons = Icons {
+        tool: "⚡",
+        ok: "✓",
+        failed: "✗",
+        model: "🧠",
+        thought: "💭",
+        file: "📄",
+        edit: "✎",
+        image: "🖼",
+        waiting: "⏳",
+        working: "◐",
+        idle: "●",
+        stage: "▶",
+        pending: "○",
+        skipped: "⏭",
+        not_run: "⊘",
+        timer: "⏱",
+        narration: "💬",
+        artifact: "📦",
+        warning: "⚠",
+        collapsed: "▸",
+        expanded: "▾",
+        compact: "⚡",
+        handoff: "🤝",
+        loaded: "📂",
+        rewind: "⏪",
+        pin: "📌",
+        cleared: "🧹",
+        unlocked: "🔓",
+        saved: "💾",
+        checkpoint: "📍",
+        link: "🔗",
+        theme: "🎨",
+        copied: "📋",
+        exported: "📝",
+        key: "🔑",
+        logo: "🧬",
+        retry: "↻",
+        undo: "↶",
+        resume: "↪",
+        spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+    };
+
+    pub const ASCII: Icons = Icons {
+        tool: "*",
+        ok: "+",
+        failed: "x",
+        model: "@",
+        thought: "?",
+        file: "-",
+        edit: "~",
+        image: "#",
+        waiting: "~",
+        working: "~",
+        idle: "o",
+        stage: ">",
+        pending: ".",
+        skipped: ">>",
+        not_run: "-",
+        timer: "t",
+        narration: "\"",
+        artifact: "=",
+        warning: "!",
+        collapsed: ">",
+        expanded: "v",
+        compact: "z",
+        handoff: "<>",
+        loaded: "[]",
+        rewind: "<<",
+        pin: "^",
+        cleared: "_",
+        unlocked: "!",
+        saved: "[=]",
+        checkpoint: "|",
+        link: "&",
+        theme: "%",
+        copied: "[c]",
+        exported: "=>",
+        key: "$",
+        logo: "::",
+        retry: "@>",
+        undo: "<-",
+        resume: "->",
+        spinner: &["|", "/", "-", "\\"],
+    };
+}
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// The glyphs to draw with.
+pub fn current() -> &'static Icons {
+    if ASCII.load(Ordering::Relaxed) {
+        &Icons::ASCII
+    } else {
+        &Icons::UNICODE
+    }
+}
+
+/// Draw with plain ASCII instead of emoji and symbols.
+pub fn set_ascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
@@ -2,19 +2,31 @@
 
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph};
-use ratatui::text::Span;
+use ratatui::text::{Line, Span};
 
 use crate::app::App;
 use super::theme;
 
 pub fn render(frame: &mut Frame, area: Rect, app: &App) {
+    if app.zen {
+        return render_bare(frame, area, app);
+    }
     let prompt_prefix = format!("{} ({}) > ", app.status.agent_name, app.status.model);
-    let display_text = format!("{}{}", prompt_prefix, app.input);
 
-    let paragraph = Paragraph::new(Span::raw(&display_text))
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .border_style(theme::border_style()));
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::border_style());
+    if app.steering.is_paused() {
+        block = block.title(Span::styled(" paused · Enter sends guidance and resumes · Ctrl+G resumes ", theme::tool_style()));
+    } else if app.editing_prompt {
+        block = block.title(Span::styled(" editing last prompt · Enter replaces it · Esc cancels ", theme::tool_style()));
+    }
+    let prompt = Line::from(vec![
+        Span::styled(&app.status.agent_name, theme::agent_style(&app.status.agent_name).add_modifier(Modifier::BOLD)),
+        Span::styled(format!(" ({}) > ", app.status.model), theme::dim_style()),
+        Span::raw(&app.input),
+    ]);
+    let paragraph = Paragraph::new(prompt).block(block);
 
     frame.render_widget(paragraph, area);
 
@@ -25,3 +37,14 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
         frame.set_cursor_position((cursor_x, cursor_y));
     }
 }
+
+/// Zen mode's input: a single borderless line with a short prompt.
+fn render_bare(frame: &mut Frame, area: Rect, app: &App) {
+    let prompt = Line::from(vec![Span::styled("› ", theme::dim_style()), Span::raw(&app.input)]);
+    frame.render_widget(Paragraph::new(prompt), area);
+
+    let cursor_x = area.x + 2 + app.cursor_pos as u16;
+    if cursor_x < area.right() {
+        frame.set_cursor_position((cursor_x, area.y));
+    }
+}
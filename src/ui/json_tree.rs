@@ -0,0 +1,333 @@
+//! Collapsible JSON tree viewer for tool call arguments.
+
+use std::collections::HashSet;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+use serde_json::Value;
+
+use super::theme;
+
+/// What the caller should do after a key press.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeAction {
+    None,
+    Close,
+    /// Copy this text to the clipboard; the label describes it ("path", "value").
+    Copy { text: String, label: &'static str },
+}
+
+/// A visible row of the flattened tree.
+#[derive(Debug, Clone)]
+pub struct TreeRow<'a> {
+    /// JSONPath-style location, e.g. `$.files[0].path`.
+    pub path: String,
+    pub depth: usize,
+    /// Object key or array index; empty for the root.
+    pub label: String,
+    pub value: &'a Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonTreeState {
+    pub title: String,
+    pub root: Value,
+    /// Paths of expanded containers.
+    pub expanded: HashSet<String>,
+    pub cursor: usize,
+    pub scroll: usize,
+    pub page_height: usize,
+    pub notice: Option<String>,
+}
+
+impl JsonTreeState {
+    /// Parse `args` as JSON; non-JSON args are shown as a single string node.
+    pub fn new(title: impl Into<String>, args: &str) -> Self {
+        let root = serde_json::from_str(args).unwrap_or_else(|_| Value::String(args.to_string()));
+        let mut expanded = HashSet::new();
+        expanded.insert("$".to_string());
+        Self {
+            title: title.into(),
+            root,
+            expanded,
+            cursor: 0,
+            scroll: 0,
+            page_height: 0,
+            notice: None,
+        }
+    }
+
+    /// Flatten the tree into the rows currently visible (children of expanded containers).
+    pub fn rows(&self) -> Vec<TreeRow<'_>> {
+        let mut rows = Vec::new();
+        self.collect("$".to_string(), 0, String::new(), &self.root, &mut rows);
+        rows
+    }
+
+    fn collect<'a>(
+        &self,
+        path: String,
+        depth: usize,
+        label: String,
+        value: &'a Value,
+        rows: &mut Vec<TreeRow<'a>>,
+    ) {
+        let open = self.expanded.contains(&path);
+        rows.push(TreeRow { path: path.clone(), depth, label, value });
+        if !open {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    self.collect(child_path(&path, key), depth + 1, key.clone(), child, rows);
+                }
+            }
+            Value::Array(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    self.collect(format!("{path}[{i}]"), depth + 1, format!("[{i}]"), child, rows);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn current(&self) -> Option<TreeRow<'_>> {
+        self.rows().into_iter().nth(self.cursor)
+    }
+
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.rows().len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Expand the container under the cursor.
+    pub fn expand(&mut self) {
+        if let Some(path) = self.current().filter(|r| is_container(r.value)).map(|r| r.path) {
+            self.expanded.insert(path);
+        }
+    }
+
+    /// Collapse the container under the cursor, or move to its parent if already collapsed.
+    pub fn collapse(&mut self) {
+        let Some((path, depth)) = self.current().map(|r| (r.path, r.depth)) else { return };
+        if self.expanded.remove(&path) {
+            return;
+        }
+        let parent = self.rows()[..self.cursor].iter().rposition(|r| r.depth < depth);
+        if let Some(parent) = parent {
+            self.cursor = parent;
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        let open = self.current().is_some_and(|r| self.expanded.contains(&r.path));
+        if open {
+            self.collapse();
+        } else {
+            self.expand();
+        }
+    }
+
+    /// Expand every container in the document.
+    pub fn expand_all(&mut self) {
+        let mut stack = vec![("$".to_string(), self.root.clone())];
+        while let Some((path, value)) = stack.pop() {
+            match &value {
+                Value::Object(map) => {
+                    stack.extend(map.iter().map(|(k, v)| (child_path(&path, k), v.clone())));
+                }
+                Value::Array(items) => {
+                    stack.extend(items.iter().enumerate().map(|(i, v)| (format!("{path}[{i}]"), v.clone())));
+                }
+                _ => continue,
+            }
+            self.expanded.insert(path);
+        }
+    }
+
+    /// Keep the cursor inside the visible window.
+    fn follow_cursor(&mut self) {
+        let height = self.page_height.max(1);
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(value, Value::Object(_) | Value::Array(_))
+}
+
+/// Path of `key` under `parent`, bracket-quoting keys that aren't plain identifiers.
+fn child_path(parent: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!("{parent}.{key}")
+    } else {
+        format!("{parent}[{}]", Value::String(key.to_string()))
+    }
+}
+
+/// One-line summary of a value: containers show their size, scalars their JSON text.
+fn summary(value: &Value, open: bool) -> String {
+    match value {
+        Value::Object(_) if open => "{".to_string(),
+        Value::Object(map) => format!("{{…}} {} keys", map.len()),
+        Value::Array(_) if open => "[".to_string(),
+        Value::Array(items) => format!("[…] {} items", items.len()),
+        other => other.to_string(),
+    }
+}
+
+/// Handle a key for the tree viewer.
+pub fn handle_key(state: &mut JsonTreeState, key: KeyEvent) -> TreeAction {
+    state.notice = None;
+    let page = state.page_height.max(1) as isize;
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => return TreeAction::Close,
+        KeyCode::Down | KeyCode::Char('j') => state.move_by(1),
+        KeyCode::Up | KeyCode::Char('k') => state.move_by(-1),
+        KeyCode::PageDown => state.move_by(page),
+        KeyCode::PageUp => state.move_by(-page),
+        KeyCode::Home | KeyCode::Char('g') => state.cursor = 0,
+        KeyCode::End | KeyCode::Char('G') => state.move_by(isize::MAX),
+        KeyCode::Right | KeyCode::Char('l') => state.expand(),
+        KeyCode::Left | KeyCode::Char('h') => state.collapse(),
+        KeyCode::Enter | KeyCode::Char(' ') => state.toggle(),
+        KeyCode::Char('*') => state.expand_all(),
+        KeyCode::Char('p') => {
+            if let Some(row) = state.current() {
+                return TreeAction::Copy { text: row.path, label: "path" };
+            }
+        }
+        KeyCode::Char('y') => {
+            if let Some(row) = state.current() {
+                let text = match row.value {
+                    Value::String(s) => s.clone(),
+                    other => serde_json::to_string_pretty(other).unwrap_or_default(),
+                };
+                return TreeAction::Copy { text, label: "value" };
+            }
+        }
+        _ => {}
+    }
+    state.follow_cursor();
+    TreeAction::None
+}
+
+/// Render the tree viewer over `area`.
+pub fn render(frame: &mut Frame, area: Rect, state: &mut JsonTreeState) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(format!(" {} ", state.title), theme::accent_style()));
+    let inner = block.inner(area);
+    state.page_height = inner.height as usize;
+    state.follow_cursor();
+
+    let rows = state.rows();
+    let path = rows.get(state.cursor).map(|r| r.path.as_str()).unwrap_or("$");
+    let footer = match &state.notice {
+        Some(notice) => format!(" {notice} "),
+        None => format!(" {path} · ←→ fold · * expand all · p copy path · y copy value · q close "),
+    };
+    let block = block.title_bottom(Span::styled(footer, theme::accent_style()));
+
+    let lines: Vec<Line> = rows
+        .iter()
+        .enumerate()
+        .skip(state.scroll)
+        .take(state.page_height)
+        .map(|(i, row)| {
+            let open = state.expanded.contains(&row.path);
+            let marker = match (is_container(row.value), open) {
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+                _ => "  ",
+            };
+            let value_style = match row.value {
+                Value::String(_) => theme::success_style(),
+                Value::Number(_) | Value::Bool(_) | Value::Null => theme::tool_style(),
+                _ => theme::dim_style(),
+            };
+            let mut spans = vec![
+                Span::raw("  ".repeat(row.depth)),
+                Span::styled(marker, theme::dim_style()),
+            ];
+            if !row.label.is_empty() {
+                spans.push(Span::styled(row.label.clone(), theme::accent_style()));
+                spans.push(Span::styled(": ", theme::dim_style()));
+            }
+            spans.push(Span::styled(summary(row.value, open), value_style));
+            let line = Line::from(spans);
+            if i == state.cursor {
+                line.style(theme::selection_style())
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARGS: &str = r#"{"path":"a.txt","edits":[{"line":3,"text":"x"}],"my key":true}"#;
+
+    #[test]
+    fn test_rows_and_folding() {
+        let mut tree = JsonTreeState::new("t", ARGS);
+        let labels: Vec<String> = tree.rows().iter().map(|r| r.label.clone()).collect();
+        assert_eq!(labels, vec!["", "edits", "my key", "path"]);
+
+        tree.cursor = 1;
+        tree.expand();
+        assert_eq!(tree.rows().len(), 5);
+        tree.cursor = 2;
+        tree.expand();
+        let paths: Vec<String> = tree.rows().iter().map(|r| r.path.clone()).collect();
+        assert!(paths.contains(&"$.edits[0].line".to_string()));
+        assert!(paths.contains(&r#"$["my key"]"#.to_string()));
+
+        // Collapsing a leaf moves to its parent
+        tree.cursor = 3;
+        tree.collapse();
+        assert_eq!(tree.cursor, 2);
+        tree.collapse();
+        assert_eq!(tree.rows().len(), 5);
+    }
+
+    #[test]
+    fn test_copy_actions() {
+        let mut tree = JsonTreeState::new("t", ARGS);
+        tree.expand_all();
+        tree.cursor = tree.rows().iter().position(|r| r.path == "$.edits[0].text").unwrap();
+        assert_eq!(
+            handle_key(&mut tree, KeyEvent::from(KeyCode::Char('p'))),
+            TreeAction::Copy { text: "$.edits[0].text".into(), label: "path" }
+        );
+        assert_eq!(
+            handle_key(&mut tree, KeyEvent::from(KeyCode::Char('y'))),
+            TreeAction::Copy { text: "x".into(), label: "value" }
+        );
+        assert_eq!(handle_key(&mut tree, KeyEvent::from(KeyCode::Esc)), TreeAction::Close);
+    }
+
+    #[test]
+    fn test_non_json_args() {
+        let tree = JsonTreeState::new("t", "not json");
+        assert_eq!(tree.rows().len(), 1);
+        assert_eq!(tree.rows()[0].value, &Value::String("not json".into()));
+    }
+}
@@ -0,0 +1,241 @@
+//! Every key binding, grouped by where it applies, for `/keys` and the F1
+//! popup. Add a binding here when adding it to the key handler; the key
+//! handler's tests press every key listed here and check it lands in its
+//! context.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Where a binding applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyContext {
+    Global,
+    Input,
+    Chat,
+    Trace,
+    TraceView,
+    Llm,
+    Sidebar,
+}
+
+impl KeyContext {
+    pub const ALL: [KeyContext; 7] = [
+        KeyContext::Global,
+        KeyContext::Input,
+        KeyContext::Chat,
+        KeyContext::Trace,
+        KeyContext::TraceView,
+        KeyContext::Llm,
+        KeyContext::Sidebar,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            KeyContext::Global => "Anywhere",
+            KeyContext::Input => "Input bar",
+            KeyContext::Chat => "Chat pane",
+            KeyContext::Trace => "Trace tab",
+            KeyContext::TraceView => "Full-screen trace",
+            KeyContext::Llm => "LLM tab",
+            KeyContext::Sidebar => "Sidebar",
+        }
+    }
+}
+
+pub struct Binding {
+    pub context: KeyContext,
+    pub keys: &'static str,
+    pub action: &'static str,
+}
+
+const fn bind(context: KeyContext, keys: &'static str, action: &'static str) -> Binding {
+    Binding { context, keys, action }
+}
+
+use KeyContext::*;
+
+pub const BINDINGS: &[Binding] = &[
+    bind(Global, "Ctrl+C", "kill a running ! command, or quit (when idle)"),
+    bind(Global, "Ctrl+D", "quit (outside the chat pane)"),
+    bind(Global, "Ctrl+L", "clear the chat"),
+    bind(Global, "Ctrl+B", "show or hide the sidebar"),
+    bind(Global, "Ctrl+F", "search the chat"),
+    bind(Global, "Ctrl+T", "trace in full screen"),
+    bind(Global, "Ctrl+P", "command palette"),
+    bind(Global, "Ctrl+G", "pause the running turn to steer it, or resume it"),
+    bind(Global, "F1", "commands and keys"),
+    bind(Global, "F11", "zen mode"),
+    bind(Global, "Alt+1..9", "switch session tab"),
+    bind(Global, "Alt+←→↑↓", "resize panes"),
+    bind(Global, "Tab/Shift+Tab", "focus panels and sidebar tabs"),
+    bind(Global, "PgUp/PgDn", "scroll the focused panel"),
+    bind(Global, "Esc", "close search or selection, cancel an edit, back to input"),
+    bind(Input, "Esc", "cancel the running turn"),
+    bind(Input, "Enter", "send"),
+    bind(Input, "Tab", "complete a /command or its argument (again to cycle)"),
+    bind(Input, "↑↓", "input history"),
+    bind(Input, "←→ Home End", "move the cursor"),
+    bind(Input, "Backspace/Delete", "delete a character"),
+    bind(Input, "!<command>", "run a shell command"),
+    bind(Input, "!! !?<keyword>", "run the last shell command, or the last containing keyword"),
+    bind(Input, "<prompt> &", "run the prompt in the background, even while the agent works"),
+    bind(Chat, "↑↓", "select tool and thought blocks (any message in selection mode)"),
+    bind(Chat, "Enter", "expand the block, or read the message in a pager"),
+    bind(Chat, "/", "search; n/N next and previous hit"),
+    bind(Chat, "v", "selection mode"),
+    bind(Chat, "y/Y", "copy the selected message or its code blocks"),
+    bind(Chat, "t", "browse tool args as a tree"),
+    bind(Chat, "r", "raw text"),
+    bind(Chat, "h", "hide or show thoughts"),
+    bind(Chat, "a", "start of the latest answer"),
+    bind(Chat, "o", "open the message's links"),
+    bind(Chat, "g/G Home/End", "top or bottom"),
+    bind(Chat, "Ctrl+U/Ctrl+D", "half a page up or down"),
+    bind(Chat, "p", "transcript in $PAGER"),
+    bind(Chat, "e", "export the transcript as Markdown"),
+    bind(Trace, "↑↓", "select stages"),
+    bind(Trace, "Enter", "full screen"),
+    bind(Trace, "r", "retry the selected failed stage"),
+    bind(Trace, "f", "follow new entries"),
+    bind(Trace, "e", "export the trace as JSON"),
+    bind(TraceView, "↑↓ j/k", "scroll"),
+    bind(TraceView, "PgUp/PgDn Space/b", "page"),
+    bind(TraceView, "g/G", "top or end"),
+    bind(TraceView, "s/S", "next or previous stage"),
+    bind(TraceView, "a", "all stages"),
+    bind(TraceView, "q/Esc", "close"),
+    bind(Llm, "↑↓", "scroll by a call"),
+    bind(Llm, "f", "follow the newest call"),
+    bind(Sidebar, "1-4", "pick a tab"),
+    bind(Sidebar, "↑↓", "scroll the Status and Files tabs"),
+];
+
+/// The key presses a binding's `keys` stand for: alternatives are separated by
+/// spaces or `/`, `←→↑↓` are one arrow each and `1..9` a range of digits.
+/// `None` for text typed into the input bar, like `!<command>`.
+pub fn key_events(keys: &str) -> Option<Vec<KeyEvent>> {
+    if keys.contains(['<', '!']) {
+        return None;
+    }
+    let mut events = Vec::new();
+    for group in keys.split_whitespace() {
+        let alternatives = if group == "/" { vec![group] } else { group.split('/').collect() };
+        for key in alternatives {
+            events.extend(parse_key(key)?);
+        }
+    }
+    Some(events)
+}
+
+/// One key with an optional `Ctrl+`/`Alt+` prefix, expanding arrow runs and digit ranges.
+fn parse_key(key: &str) -> Option<Vec<KeyEvent>> {
+    let (modifiers, key) = match key.split_once('+') {
+        Some(("Shift", "Tab")) => return Some(vec![KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT)]),
+        Some(("Ctrl", key)) => (KeyModifiers::CONTROL, key),
+        Some(("Alt", key)) => (KeyModifiers::ALT, key),
+        Some(_) => return None,
+        None => (KeyModifiers::NONE, key),
+    };
+    let codes = match key {
+        "Esc" => vec![KeyCode::Esc],
+        "Enter" => vec![KeyCode::Enter],
+        "Tab" => vec![KeyCode::Tab],
+        "Home" => vec![KeyCode::Home],
+        "End" => vec![KeyCode::End],
+        "PgUp" => vec![KeyCode::PageUp],
+        "PgDn" => vec![KeyCode::PageDown],
+        "Backspace" => vec![KeyCode::Backspace],
+        "Delete" => vec![KeyCode::Delete],
+        "Space" => vec![KeyCode::Char(' ')],
+        _ if key.len() > 1 && key.starts_with('F') => vec![KeyCode::F(key[1..].parse().ok()?)],
+        _ if key.chars().all(|c| "←→↑↓".contains(c)) => key
+            .chars()
+            .map(|c| match c {
+                '←' => KeyCode::Left,
+                '→' => KeyCode::Right,
+                '↑' => KeyCode::Up,
+                _ => KeyCode::Down,
+            })
+            .collect(),
+        _ => {
+            let (first, last) = key.split_once("..").or_else(|| key.split_once('-')).unwrap_or((key, key));
+            let (mut first, mut last) = (first.chars(), last.chars());
+            let (first, last) = match (first.next(), first.next(), last.next(), last.next()) {
+                (Some(first), None, Some(last), None) => (first, last),
+                _ => return None,
+            };
+            (first..=last).map(KeyCode::Char).collect()
+        }
+    };
+    Some(
+        codes
+            .into_iter()
+            .map(|code| match code {
+                KeyCode::Char(c) if modifiers == KeyModifiers::CONTROL => KeyEvent::new(KeyCode::Char(c.to_ascii_lowercase()), modifiers),
+                KeyCode::Char(c) if c.is_ascii_uppercase() => KeyEvent::new(code, modifiers | KeyModifiers::SHIFT),
+                _ => KeyEvent::new(code, modifiers),
+            })
+            .collect(),
+    )
+}
+
+/// The bindings as text, a section per context.
+pub fn reference() -> String {
+    let width = BINDINGS.iter().map(|b| b.keys.chars().count()).max().unwrap_or(0);
+    let mut sections = Vec::new();
+    for context in KeyContext::ALL {
+        let mut section = context.title().to_string();
+        for binding in BINDINGS.iter().filter(|b| b.context == context) {
+            let pad = width - binding.keys.chars().count();
+            section.push_str(&format!("\n  {}{}  {}", binding.keys, " ".repeat(pad), binding.action));
+        }
+        sections.push(section);
+    }
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_groups_by_context() {
+        let text = reference();
+        for context in KeyContext::ALL {
+            assert!(BINDINGS.iter().any(|b| b.context == context), "{context:?} has no bindings");
+            assert!(text.contains(&format!("{}\n  ", context.title())));
+        }
+        let chat = text.split("\n\n").find(|s| s.starts_with("Chat pane")).unwrap();
+        assert!(chat.contains("y/Y"));
+        assert!(!chat.contains("Ctrl+B"));
+    }
+
+    #[test]
+    fn test_key_events() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert_eq!(key_events("Ctrl+B"), Some(vec![key(KeyCode::Char('b'), KeyModifiers::CONTROL)]));
+        assert_eq!(key_events("F11"), Some(vec![key(KeyCode::F(11), KeyModifiers::NONE)]));
+        assert_eq!(key_events("/"), Some(vec![key(KeyCode::Char('/'), KeyModifiers::NONE)]));
+        assert_eq!(
+            key_events("Tab/Shift+Tab"),
+            Some(vec![key(KeyCode::Tab, KeyModifiers::NONE), key(KeyCode::BackTab, KeyModifiers::SHIFT)])
+        );
+        assert_eq!(
+            key_events("g/G Home/End"),
+            Some(vec![
+                key(KeyCode::Char('g'), KeyModifiers::NONE),
+                key(KeyCode::Char('G'), KeyModifiers::SHIFT),
+                key(KeyCode::Home, KeyModifiers::NONE),
+                key(KeyCode::End, KeyModifiers::NONE),
+            ])
+        );
+        assert_eq!(key_events("Alt+1..9").unwrap().len(), 9);
+        assert_eq!(key_events("Alt+←→↑↓").unwrap()[3], key(KeyCode::Down, KeyModifiers::ALT));
+        assert_eq!(key_events("1-4").unwrap()[3], key(KeyCode::Char('4'), KeyModifiers::NONE));
+        assert_eq!(key_events("!<command>"), None);
+        assert_eq!(key_events("<prompt> &"), None);
+        // Everything but typed text names keys
+        for binding in BINDINGS.iter().filter(|b| !b.keys.contains(['<', '!'])) {
+            assert!(key_events(binding.keys).is_some_and(|keys| !keys.is_empty()), "{}", binding.keys);
+        }
+    }
+}
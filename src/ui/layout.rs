@@ -1,16 +1,93 @@
-//! Split-pane layout: chat + sidebar (status + llm log) on top, input bar on bottom.
+//! Split-pane layout: chat + tabbed sidebar on top, input bar on bottom. The
+//! sidebar sits right of the chat, or under it in horizontal mode.
 
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 
+use crate::config::{LayoutConfig, LayoutMode};
+
 /// The main areas of the UI.
 pub struct AppLayout {
     pub chat: Rect,
-    pub sidebar_status: Rect,
-    pub sidebar_llm_log: Rect,
+    /// One-row tab bar at the top of the sidebar.
+    pub sidebar_tabs: Rect,
+    /// Pinned notices under the tab bar; zero height when there are none.
+    pub sidebar_notices: Rect,
+    /// The active sidebar tab's panel.
+    pub sidebar: Rect,
     pub input: Rect,
 }
 
-pub fn compute_layout(area: Rect) -> AppLayout {
+/// Narrowest and widest the sidebar can be, in percent of the width (or of
+/// the height in horizontal mode).
+const MIN_SIDEBAR_PERCENT: u16 = 15;
+const MAX_SIDEBAR_PERCENT: u16 = 50;
+/// Smallest and largest share of the Status tab the status box can take from
+/// the tool statistics.
+const MIN_STATUS_PERCENT: u16 = 15;
+const MAX_STATUS_PERCENT: u16 = 60;
+
+/// Split ratios, set from the `[layout]` config and adjustable with Alt+Arrow
+/// keys or `/set layout.*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Splits {
+    /// Sidebar share of the width (of the height in horizontal mode), in percent.
+    pub sidebar: u16,
+    /// Status box share of the Status tab, in percent, when tool statistics
+    /// share it.
+    pub status: u16,
+}
+
+impl Default for Splits {
+    fn default() -> Self {
+        Self { sidebar: 25, status: 35 }
+    }
+}
+
+impl Splits {
+    /// Splits for the configured proportions, clamped so every pane stays usable.
+    pub fn from_config(config: &LayoutConfig) -> Self {
+        let mut splits = Self::default();
+        splits.set_chat(config.chat);
+        splits.set_status(config.status);
+        splits
+    }
+
+    /// Chat share of the width, in percent.
+    pub fn chat(&self) -> u16 {
+        100 - self.sidebar
+    }
+
+    /// Give the chat `percent` of the width and the sidebar the rest.
+    pub fn set_chat(&mut self, percent: u16) {
+        self.sidebar = 100u16.saturating_sub(percent).clamp(MIN_SIDEBAR_PERCENT, MAX_SIDEBAR_PERCENT);
+    }
+
+    /// Give the status box `percent` of the Status tab.
+    pub fn set_status(&mut self, percent: u16) {
+        self.status = percent.clamp(MIN_STATUS_PERCENT, MAX_STATUS_PERCENT);
+    }
+
+    /// Grow (positive) or shrink the sidebar, keeping both panes usable.
+    pub fn resize_sidebar(&mut self, delta: i16) {
+        self.sidebar = self.sidebar.saturating_add_signed(delta).clamp(MIN_SIDEBAR_PERCENT, MAX_SIDEBAR_PERCENT);
+    }
+
+    /// Grow (positive) or shrink the status box at the tool statistics' expense.
+    pub fn resize_status(&mut self, delta: i16) {
+        self.set_status(self.status.saturating_add_signed(delta));
+    }
+}
+
+/// Lay out the panes for `mode`.
+pub fn compute_layout_for(mode: LayoutMode, area: Rect, show_sidebar: bool, splits: Splits) -> AppLayout {
+    match mode {
+        LayoutMode::Vertical => compute_layout(area, show_sidebar, splits),
+        LayoutMode::Horizontal => compute_horizontal_layout(area, show_sidebar, splits),
+    }
+}
+
+/// Without the sidebar the chat takes the full width and the sidebar areas are empty.
+pub fn compute_layout(area: Rect, show_sidebar: bool, splits: Splits) -> AppLayout {
     // Vertical: main area + input bar (3 lines)
     let vertical = Layout::default()
         .direction(Direction::Vertical)
@@ -20,32 +97,115 @@ pub fn compute_layout(area: Rect) -> AppLayout {
         ])
         .split(area);
 
-    // Horizontal: chat (75%) + sidebar (25%)
+    // Horizontal: chat + sidebar (75% / 25% by default)
+    let sidebar_width = if show_sidebar { splits.sidebar } else { 0 };
     let horizontal = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(75),
-            Constraint::Percentage(25),
+            Constraint::Percentage(100 - sidebar_width),
+            Constraint::Percentage(sidebar_width),
         ])
         .split(vertical[0]);
 
-    // Sidebar vertical split: status (top 40%) + LLM log (bottom 60%)
+    // Sidebar: tab bar over the active tab's panel
     let sidebar = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage(40),
-            Constraint::Percentage(60),
-        ])
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
         .split(horizontal[1]);
 
     AppLayout {
         chat: horizontal[0],
-        sidebar_status: sidebar[0],
-        sidebar_llm_log: sidebar[1],
+        sidebar_tabs: sidebar[0],
+        sidebar_notices: Rect { height: 0, ..sidebar[1] },
+        sidebar: sidebar[1],
         input: vertical[1],
     }
 }
 
+/// Horizontal mode: the sidebar is a full-width strip between the chat and the
+/// input bar. Without the sidebar the chat takes the full height.
+pub fn compute_horizontal_layout(area: Rect, show_sidebar: bool, splits: Splits) -> AppLayout {
+    let strip_height = if show_sidebar { splits.sidebar } else { 0 };
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(5),
+            Constraint::Percentage(strip_height),
+            Constraint::Length(3),
+        ])
+        .split(area);
+
+    // Strip: tab bar over the active tab's panel
+    let strip = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(vertical[1]);
+
+    AppLayout {
+        chat: vertical[0],
+        sidebar_tabs: strip[0],
+        sidebar_notices: Rect { height: 0, ..strip[1] },
+        sidebar: strip[1],
+        input: vertical[2],
+    }
+}
+
+/// Zen mode: the chat takes everything above a one-row, borderless input line
+/// and the sidebar areas are empty.
+pub fn compute_zen_layout(area: Rect) -> AppLayout {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(area);
+    let hidden = Rect { x: area.right(), width: 0, ..vertical[0] };
+    AppLayout {
+        chat: vertical[0],
+        sidebar_tabs: Rect { height: 0, ..hidden },
+        sidebar_notices: Rect { height: 0, ..hidden },
+        sidebar: hidden,
+        input: vertical[1],
+    }
+}
+
+impl AppLayout {
+    /// Carve a notices box (`rows` lines plus borders) off the top of the sidebar
+    /// panel, leaving the panel at least half its height.
+    pub fn with_notices(mut self, rows: u16) -> Self {
+        if rows == 0 {
+            return self;
+        }
+        let height = (rows + 2).min(self.sidebar.height / 2);
+        self.sidebar_notices = Rect { height, ..self.sidebar };
+        self.sidebar.y += height;
+        self.sidebar.height -= height;
+        self
+    }
+}
+
+/// Take the bottom row of `area` for the status line, returning the rest and that row.
+pub fn split_status_line(area: Rect) -> (Rect, Rect) {
+    let height = area.height.saturating_sub(1);
+    (Rect { height, ..area }, Rect { y: area.y + height, height: area.height - height, ..area })
+}
+
+/// Status tab: the status box over a tool statistics box of `tool_rows` lines
+/// (plus borders). Without tool statistics the status box takes the whole tab.
+pub fn split_status_tab(area: Rect, splits: Splits, tool_rows: u16) -> (Rect, Rect) {
+    if tool_rows == 0 {
+        return (area, Rect { y: area.bottom(), height: 0, ..area });
+    }
+    let status = (area.height * splits.status / 100).max(area.height.saturating_sub(tool_rows + 2));
+    let status = status.min(area.height);
+    (Rect { height: status, ..area }, Rect { y: area.y + status, height: area.height - status, ..area })
+}
+
+/// Trace tab: a workflow pipeline box of `pipeline_rows` lines (plus borders)
+/// over the trace, which keeps at least half the tab.
+pub fn split_trace_tab(area: Rect, pipeline_rows: u16) -> (Rect, Rect) {
+    let pipeline = if pipeline_rows == 0 { 0 } else { (pipeline_rows + 2).min(area.height / 2) };
+    (Rect { height: pipeline, ..area }, Rect { y: area.y + pipeline, height: area.height - pipeline, ..area })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,7 +213,7 @@ mod tests {
     #[test]
     fn test_layout_dimensions() {
         let area = Rect::new(0, 0, 120, 40);
-        let layout = compute_layout(area);
+        let layout = compute_layout(area, true, Splits::default());
 
         // Input bar should be 3 lines tall at the bottom
         assert_eq!(layout.input.height, 3);
@@ -66,21 +226,148 @@ mod tests {
         assert_eq!(layout.chat.height, 37);
 
         // Sidebar should take ~25%
-        let sidebar_w = layout.sidebar_status.width;
+        let sidebar_w = layout.sidebar.width;
         assert!(sidebar_w >= 25 && sidebar_w <= 35);
     }
 
     #[test]
-    fn test_sidebar_split() {
+    fn test_status_line() {
+        let (main, status) = split_status_line(Rect::new(0, 1, 120, 40));
+        assert_eq!(main, Rect::new(0, 1, 120, 39));
+        assert_eq!(status, Rect::new(0, 40, 120, 1));
+    }
+
+    #[test]
+    fn test_sidebar_tabs() {
+        let area = Rect::new(0, 0, 120, 40);
+        let layout = compute_layout(area, true, Splits::default());
+
+        // Tab bar on top, the active panel below it, together the main area height (37)
+        assert_eq!(layout.sidebar_tabs.height, 1);
+        assert_eq!(layout.sidebar.y, layout.sidebar_tabs.bottom());
+        assert_eq!(layout.sidebar_tabs.height + layout.sidebar.height, 37);
+        assert_eq!(layout.sidebar.width, layout.sidebar_tabs.width);
+    }
+
+    #[test]
+    fn test_resized_splits() {
+        let area = Rect::new(0, 0, 120, 40);
+        let mut splits = Splits::default();
+        splits.resize_sidebar(10);
+        splits.resize_status(-10);
+        let layout = compute_layout(area, true, splits);
+        assert_eq!(layout.sidebar.width, 42);
+        assert_eq!(layout.chat.width, 78);
+        let tab = Rect::new(0, 0, 30, 30);
+        assert!(split_status_tab(tab, splits, 20).0.height < split_status_tab(tab, Splits::default(), 20).0.height);
+
+        // Neither split can squeeze a pane away
+        splits.resize_sidebar(100);
+        splits.resize_status(-100);
+        assert_eq!(splits, Splits { sidebar: 50, status: 15 });
+        splits.resize_sidebar(-100);
+        splits.resize_status(100);
+        assert_eq!(splits, Splits { sidebar: 15, status: 60 });
+    }
+
+    #[test]
+    fn test_splits_from_config() {
+        let splits = Splits::from_config(&LayoutConfig::default());
+        assert_eq!(splits, Splits::default());
+        assert_eq!(splits.chat(), 75);
+
+        let splits = Splits::from_config(&LayoutConfig { chat: 60, status: 50, ..LayoutConfig::default() });
+        assert_eq!(splits, Splits { sidebar: 40, status: 50 });
+        // Out-of-range settings are clamped rather than hiding a pane
+        let splits = Splits::from_config(&LayoutConfig { chat: 100, status: 0, ..LayoutConfig::default() });
+        assert_eq!(splits, Splits { sidebar: 15, status: 15 });
+    }
+
+    #[test]
+    fn test_hidden_sidebar() {
+        let area = Rect::new(0, 0, 120, 40);
+        let layout = compute_layout(area, false, Splits::default());
+        assert_eq!(layout.chat.width, 120);
+        assert_eq!(layout.chat.height, 37);
+        assert_eq!(layout.sidebar_tabs.width, 0);
+        assert_eq!(layout.sidebar.width, 0);
+    }
+
+    #[test]
+    fn test_horizontal_layout() {
+        let area = Rect::new(0, 0, 200, 40);
+        let layout = compute_layout_for(LayoutMode::Horizontal, area, true, Splits::default());
+        assert_eq!(layout.chat.width, 200);
+        assert_eq!(layout.sidebar.width, 200);
+        assert_eq!(layout.sidebar_tabs.y, layout.chat.bottom());
+        assert_eq!(layout.sidebar.bottom(), layout.input.y);
+        assert_eq!(layout.input, Rect::new(0, 37, 200, 3));
+        // The strip takes its share of the height above the input bar
+        assert!((8..=10).contains(&(layout.sidebar_tabs.height + layout.sidebar.height)));
+
+        let hidden = compute_layout_for(LayoutMode::Horizontal, area, false, Splits::default());
+        assert_eq!(hidden.chat.height, 37);
+        assert_eq!(hidden.sidebar.height, 0);
+        assert_eq!("horizontal".parse::<LayoutMode>().unwrap(), LayoutMode::Horizontal);
+        assert!("diagonal".parse::<LayoutMode>().is_err());
+    }
+
+    #[test]
+    fn test_zen_layout() {
+        let area = Rect::new(0, 0, 120, 40);
+        let layout = compute_zen_layout(area);
+        assert_eq!(layout.chat, Rect::new(0, 0, 120, 39));
+        assert_eq!(layout.input, Rect::new(0, 39, 120, 1));
+        assert_eq!(layout.sidebar.width, 0);
+    }
+
+    #[test]
+    fn test_notices_take_from_panel() {
         let area = Rect::new(0, 0, 120, 40);
-        let layout = compute_layout(area);
-
-        // Status is top part, llm_log is bottom part of sidebar
-        assert!(layout.sidebar_status.y < layout.sidebar_llm_log.y);
-        // Combined heights should equal the main area height (37)
-        let combined = layout.sidebar_status.height + layout.sidebar_llm_log.height;
-        assert_eq!(combined, 37);
-        // Status ~40%, log ~60%
-        assert!(layout.sidebar_status.height < layout.sidebar_llm_log.height);
+        let plain = compute_layout(area, true, Splits::default());
+        assert_eq!(plain.sidebar_notices.height, 0);
+
+        let layout = compute_layout(area, true, Splits::default()).with_notices(2);
+        assert_eq!(layout.sidebar_notices.y, plain.sidebar.y);
+        assert_eq!(layout.sidebar_notices.height, 4);
+        assert_eq!(layout.sidebar.y, plain.sidebar.y + 4);
+        assert_eq!(layout.sidebar.height, plain.sidebar.height - 4);
+        assert_eq!(layout.sidebar_tabs, plain.sidebar_tabs);
+
+        // Many notices never squeeze the panel below half its height
+        let crowded = compute_layout(area, true, Splits::default()).with_notices(50);
+        assert_eq!(crowded.sidebar_notices.height, plain.sidebar.height / 2);
+    }
+
+    #[test]
+    fn test_status_tab_split() {
+        let tab = Rect::new(90, 1, 30, 36);
+        let (status, tools) = split_status_tab(tab, Splits::default(), 0);
+        assert_eq!(status, tab);
+        assert_eq!(tools.height, 0);
+
+        // Tools get the room they need, as long as the status box keeps its share
+        let (status, tools) = split_status_tab(tab, Splits::default(), 3);
+        assert_eq!(tools.height, 5);
+        assert_eq!(status.height, 31);
+        assert_eq!(tools.y, status.bottom());
+        let (status, tools) = split_status_tab(tab, Splits::default(), 40);
+        assert_eq!(status.height, 36 * 35 / 100);
+        assert_eq!(tools.bottom(), tab.bottom());
+    }
+
+    #[test]
+    fn test_trace_tab_split() {
+        let tab = Rect::new(90, 1, 30, 36);
+        let (pipeline, trace) = split_trace_tab(tab, 0);
+        assert_eq!(pipeline.height, 0);
+        assert_eq!(trace, tab);
+
+        let (pipeline, trace) = split_trace_tab(tab, 4);
+        assert_eq!(pipeline.height, 6);
+        assert_eq!(trace.y, pipeline.bottom());
+        assert_eq!(trace.bottom(), tab.bottom());
+        // The trace keeps at least half the tab
+        assert_eq!(split_trace_tab(tab, 30).0.height, 18);
     }
 }
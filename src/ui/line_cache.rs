@@ -0,0 +1,121 @@
+//! Per-message cache of wrapped chat lines.
+//!
+//! Wrapping the whole transcript every 100ms tick is the chat pane's main CPU
+//! cost, so each message's wrapped rows are kept until the width changes or the
+//! message's own rendering inputs (see [`RenderKey`]) do.
+
+use ratatui::text::Line;
+
+use super::wrap;
+
+/// Everything besides the message text that changes how a message renders.
+/// Selection and search highlighting are applied on top of cached rows instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderKey {
+    /// Tool block is expanded.
+    pub expanded: bool,
+    /// Tool call has its result (header shows ✓/✗ instead of "running").
+    pub settled: bool,
+    /// [`theme::generation`](super::theme::generation) when the rows were
+    /// built, since they hold the styles of the theme at the time.
+    pub theme: usize,
+}
+
+/// Wrapped rows per message index, for a single pane width.
+#[derive(Debug, Default)]
+pub struct LineCache {
+    width: usize,
+    entries: Vec<Option<(RenderKey, Vec<Line<'static>>)>>,
+}
+
+impl LineCache {
+    /// Start a frame: drop everything if the width changed and keep one slot per message.
+    pub fn prepare(&mut self, width: usize, message_count: usize) {
+        if width != self.width {
+            self.entries.clear();
+            self.width = width;
+        }
+        self.entries.resize(message_count, None);
+    }
+
+    /// Wrapped rows for message `index`, built with `build` on a miss.
+    pub fn rows(
+        &mut self,
+        index: usize,
+        key: RenderKey,
+        build: impl FnOnce() -> Vec<Line<'static>>,
+    ) -> &[Line<'static>] {
+        let width = self.width;
+        let slot = &mut self.entries[index];
+        if matches!(slot, Some((cached, _)) if *cached != key) {
+            *slot = None;
+        }
+        &slot
+            .get_or_insert_with(|| {
+                let rows = build().iter().flat_map(|line| wrap::wrap_line(line, width)).collect();
+                (key, rows)
+            })
+            .1
+    }
+
+    /// Rows cached for message `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&[Line<'static>]> {
+        self.entries.get(index)?.as_ref().map(|(_, rows)| rows.as_slice())
+    }
+
+    /// Forget message `index` and everything after it (e.g. a message changed in place).
+    pub fn invalidate_from(&mut self, index: usize) {
+        self.entries.truncate(index);
+    }
+
+    /// Forget everything (e.g. the transcript was cleared).
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_cache_hits_and_invalidation() {
+        let builds = Cell::new(0);
+        let build = || {
+            builds.set(builds.get() + 1);
+            vec![Line::from("one two three four")]
+        };
+        let mut cache = LineCache::default();
+
+        cache.prepare(10, 1);
+        assert_eq!(cache.rows(0, RenderKey::default(), build).len(), 2);
+        assert_eq!(cache.rows(0, RenderKey::default(), build).len(), 2);
+        assert_eq!(builds.get(), 1);
+
+        // A new message doesn't touch existing entries
+        cache.prepare(10, 2);
+        cache.rows(0, RenderKey::default(), build);
+        assert_eq!(builds.get(), 1);
+
+        // A changed key rebuilds just that entry
+        cache.rows(0, RenderKey { expanded: true, ..Default::default() }, build);
+        assert_eq!(builds.get(), 2);
+
+        // So does a theme change, whose colors the cached rows hold
+        cache.rows(0, RenderKey { expanded: true, theme: 1, ..Default::default() }, build);
+        assert_eq!(builds.get(), 3);
+        cache.rows(0, RenderKey { expanded: true, theme: 1, ..Default::default() }, build);
+        assert_eq!(builds.get(), 3);
+
+        // A width change rebuilds with the new wrapping
+        cache.prepare(40, 2);
+        assert_eq!(cache.rows(0, RenderKey::default(), build).len(), 1);
+        assert_eq!(builds.get(), 4);
+
+        cache.invalidate();
+        cache.prepare(40, 2);
+        cache.rows(0, RenderKey::default(), build);
+        assert_eq!(builds.get(), 5);
+    }
+}
@@ -1,5 +1,19 @@
+pub mod ansi;
 pub mod chat;
+pub mod compare;
+pub mod hints;
+pub mod icons;
 pub mod input;
+pub mod json_tree;
+pub mod keymap;
 pub mod layout;
+pub mod line_cache;
+pub mod pager;
+pub mod palette;
+pub mod popup;
 pub mod sidebar;
+pub mod status_line;
+pub mod tab_bar;
 pub mod theme;
+pub mod trace_view;
+pub mod wrap;
@@ -0,0 +1,361 @@
+//! Full-screen pager for reading a single message, either wrapped or raw.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+
+use super::{chat, theme, wrap};
+
+/// Pager state: the source text plus its wrapped rows for the current width.
+#[derive(Debug, Clone, Default)]
+pub struct PagerState {
+    pub title: String,
+    pub text: String,
+    /// Wrapped display rows, rebuilt whenever the width changes.
+    pub rows: Vec<String>,
+    pub width: usize,
+    /// First visible row.
+    pub scroll: usize,
+    pub page_height: usize,
+    pub query: String,
+    pub editing_query: bool,
+    /// Raw view: exact text with line numbers and visible whitespace, no word wrap.
+    pub raw: bool,
+    /// Diff view: color lines by their `+`/`-`/`@@` prefix.
+    pub diff: bool,
+    /// One-off feedback shown in the footer (e.g. after copying).
+    pub notice: Option<String>,
+}
+
+impl PagerState {
+    pub fn new(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { title: title.into(), text: text.into(), ..Default::default() }
+    }
+
+    pub fn raw(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { raw: true, ..Self::new(title, text) }
+    }
+
+    pub fn diff(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { diff: true, ..Self::new(title, text) }
+    }
+
+    /// Re-wrap the text for a new width, keeping roughly the same reading position.
+    pub fn rewrap(&mut self, width: usize) {
+        if width == self.width && !self.rows.is_empty() {
+            return;
+        }
+        let fraction = if self.rows.is_empty() { 0.0 } else { self.scroll as f64 / self.rows.len() as f64 };
+        self.width = width;
+        self.rows = if self.raw {
+            raw_rows(&self.text, width)
+        } else {
+            self.text.lines().flat_map(|line| wrap::wrap_str(line, width)).collect()
+        };
+        self.scroll = (fraction * self.rows.len() as f64) as usize;
+        self.clamp();
+    }
+
+    pub fn max_scroll(&self) -> usize {
+        self.rows.len().saturating_sub(self.page_height)
+    }
+
+    pub fn scroll_by(&mut self, delta: isize) {
+        self.scroll = self.scroll.saturating_add_signed(delta);
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// Position through the text, as the percentage of rows read (0–100).
+    pub fn percent(&self) -> usize {
+        if self.rows.len() <= self.page_height {
+            return 100;
+        }
+        ((self.scroll + self.page_height) * 100 / self.rows.len()).min(100)
+    }
+
+    /// Jump to the next row after the top one matching `predicate`, if any.
+    fn jump_forward(&mut self, predicate: impl Fn(&str) -> bool) -> bool {
+        if let Some(i) = (self.scroll + 1..self.rows.len()).find(|&i| predicate(&self.rows[i])) {
+            self.scroll = i;
+            self.clamp();
+            return true;
+        }
+        false
+    }
+
+    fn jump_backward(&mut self, predicate: impl Fn(&str) -> bool) -> bool {
+        if let Some(i) = (0..self.scroll).rev().find(|&i| predicate(&self.rows[i])) {
+            self.scroll = i;
+            return true;
+        }
+        false
+    }
+
+    pub fn next_match(&mut self) -> bool {
+        let needle = self.query.to_ascii_lowercase();
+        !needle.is_empty() && self.jump_forward(|row| row.to_ascii_lowercase().contains(&needle))
+    }
+
+    pub fn prev_match(&mut self) -> bool {
+        let needle = self.query.to_ascii_lowercase();
+        !needle.is_empty() && self.jump_backward(|row| row.to_ascii_lowercase().contains(&needle))
+    }
+
+    /// Jump to the next opening code fence.
+    pub fn next_code_block(&mut self) -> bool {
+        let openings = self.fence_openings();
+        match openings.into_iter().find(|&i| i > self.scroll) {
+            Some(i) => {
+                self.scroll = i.min(self.max_scroll());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn prev_code_block(&mut self) -> bool {
+        let openings = self.fence_openings();
+        match openings.into_iter().rev().find(|&i| i < self.scroll) {
+            Some(i) => {
+                self.scroll = i;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Row indices of opening ``` fences (closing fences are skipped).
+    fn fence_openings(&self) -> Vec<usize> {
+        let mut inside = false;
+        let mut openings = Vec::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.trim_start().starts_with("```") {
+                if !inside {
+                    openings.push(i);
+                }
+                inside = !inside;
+            }
+        }
+        openings
+    }
+}
+
+/// Number each source line and make tabs, carriage returns and trailing spaces visible.
+fn raw_rows(text: &str, width: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let gutter = lines.len().to_string().len();
+    let body_width = width.saturating_sub(gutter + 3).max(1);
+    let mut rows = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let content = line.trim_end_matches(' ');
+        let mut visible: String = content
+            .chars()
+            .map(|c| match c {
+                '\t' => '→',
+                '\r' => '␍',
+                c => c,
+            })
+            .collect();
+        visible.push_str(&"·".repeat(line.len() - content.len()));
+        for (j, chunk) in wrap::split_str(&visible, body_width).into_iter().enumerate() {
+            if j == 0 {
+                rows.push(format!("{:>gutter$} │ {chunk}", i + 1));
+            } else {
+                rows.push(format!("{:>gutter$} │ {chunk}", ""));
+            }
+        }
+    }
+    rows
+}
+
+/// Handle a key for the pager. Returns false when the pager should close.
+pub fn handle_key(state: &mut PagerState, key: KeyEvent) -> bool {
+    if state.editing_query {
+        match key.code {
+            KeyCode::Esc => {
+                state.editing_query = false;
+                state.query.clear();
+            }
+            KeyCode::Enter => {
+                state.editing_query = false;
+                state.next_match();
+            }
+            KeyCode::Backspace => {
+                state.query.pop();
+            }
+            KeyCode::Char(c) => state.query.push(c),
+            _ => {}
+        }
+        return true;
+    }
+
+    state.notice = None;
+    let page = state.page_height.max(1) as isize;
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc | KeyCode::Char('q')) => return false,
+        (_, KeyCode::Down | KeyCode::Char('j')) => state.scroll_by(1),
+        (_, KeyCode::Up | KeyCode::Char('k')) => state.scroll_by(-1),
+        (_, KeyCode::PageDown | KeyCode::Char(' ')) => state.scroll_by(page),
+        (_, KeyCode::PageUp | KeyCode::Char('b')) => state.scroll_by(-page),
+        (KeyModifiers::CONTROL, KeyCode::Char('d')) => state.scroll_by(page / 2),
+        (KeyModifiers::CONTROL, KeyCode::Char('u')) => state.scroll_by(-page / 2),
+        (_, KeyCode::Home | KeyCode::Char('g')) => state.scroll = 0,
+        (_, KeyCode::End | KeyCode::Char('G')) => state.scroll = state.max_scroll(),
+        (_, KeyCode::Char('/')) => {
+            state.query.clear();
+            state.editing_query = true;
+        }
+        (_, KeyCode::Char('n')) => {
+            state.next_match();
+        }
+        (_, KeyCode::Char('N')) => {
+            state.prev_match();
+        }
+        (_, KeyCode::Char(']')) => {
+            state.next_code_block();
+        }
+        (_, KeyCode::Char('[')) => {
+            state.prev_code_block();
+        }
+        _ => {}
+    }
+    true
+}
+
+/// Render the pager over `area`.
+pub fn render(frame: &mut Frame, area: Rect, state: &mut PagerState) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(format!(" {} ", state.title), theme::accent_style()));
+    let inner = block.inner(area);
+    state.page_height = inner.height as usize;
+    state.rewrap(inner.width as usize);
+    state.clamp();
+
+    let footer = if state.editing_query {
+        format!(" /{}▏ Enter search, Esc cancel ", state.query)
+    } else if let Some(notice) = &state.notice {
+        format!(" {notice} · q close ")
+    } else if state.raw {
+        format!(
+            " raw · {} chars · {}% · / search · n/N · y copy · q close ",
+            state.text.chars().count(),
+            state.percent(),
+        )
+    } else {
+        format!(
+            " {}% · {}/{} · / search · n/N · [ ] code · q close ",
+            state.percent(),
+            (state.scroll + state.page_height).min(state.rows.len()),
+            state.rows.len(),
+        )
+    };
+    let block = block.title_bottom(Span::styled(footer, theme::accent_style()));
+
+    // Code fences before the visible window decide whether we start inside a block
+    let mut in_code = state.rows[..state.scroll]
+        .iter()
+        .filter(|row| row.trim_start().starts_with("```"))
+        .count()
+        % 2
+        == 1;
+    // A diff's leading file header is not colored like added/removed lines
+    let header_rows = if state.diff { state.rows.iter().position(|row| row.starts_with("@@")).unwrap_or(0) } else { 0 };
+    let needle = state.query.to_ascii_lowercase();
+    let mut lines: Vec<Line> = Vec::new();
+    for (index, row) in state.rows.iter().enumerate().skip(state.scroll).take(state.page_height) {
+        let is_fence = !state.diff && row.trim_start().starts_with("```");
+        let style = if index < header_rows {
+            theme::dim_style()
+        } else if state.diff {
+            chat::diff_line_style(row)
+        } else if state.raw {
+            theme::assistant_style()
+        } else if is_fence {
+            theme::dim_style()
+        } else if in_code {
+            theme::tool_style()
+        } else {
+            theme::assistant_style()
+        };
+        if is_fence {
+            in_code = !in_code;
+        }
+        let mut line = Line::from(Span::styled(row.as_str(), style));
+        if !needle.is_empty() {
+            line = chat::highlight_matches(line, &needle, theme::search_match_style());
+        }
+        lines.push(line);
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager(text: &str) -> PagerState {
+        let mut state = PagerState::new("t", text);
+        state.page_height = 3;
+        state.rewrap(40);
+        state
+    }
+
+    #[test]
+    fn test_scroll_and_percent() {
+        let text: Vec<String> = (0..10).map(|i| format!("line {i}")).collect();
+        let mut state = pager(&text.join("\n"));
+        assert_eq!(state.rows.len(), 10);
+        assert_eq!(state.percent(), 30);
+        state.scroll_by(100);
+        assert_eq!(state.scroll, 7);
+        assert_eq!(state.percent(), 100);
+        state.scroll_by(-2);
+        assert_eq!(state.scroll, 5);
+    }
+
+    #[test]
+    fn test_search_and_code_blocks() {
+        let text = "intro\n```\ncode a\n```\nmiddle needle\nmore\n```rust\ncode b\n```\nend\nfinal needle\nx\ny";
+        let mut state = pager(text);
+
+        assert!(state.next_code_block());
+        assert_eq!(state.scroll, 1);
+        assert!(state.next_code_block());
+        assert_eq!(state.scroll, 6);
+        assert!(!state.next_code_block()); // closing fences don't count
+        assert!(state.prev_code_block());
+        assert_eq!(state.scroll, 1);
+
+        state.query = "NEEDLE".into();
+        assert!(state.next_match());
+        assert_eq!(state.scroll, 4);
+        assert!(state.next_match());
+        assert_eq!(state.scroll, 10.min(state.max_scroll()));
+        assert!(state.prev_match());
+        assert_eq!(state.scroll, 4);
+    }
+
+    #[test]
+    fn test_raw_rows() {
+        let mut state = PagerState::raw("raw", "a\tb  \n\nlong line here");
+        state.page_height = 10;
+        state.rewrap(12);
+        assert_eq!(state.rows, vec!["1 │ a→b··", "2 │ ", "3 │ long lin", "  │ e here"]);
+    }
+
+    #[test]
+    fn test_handle_key_closes() {
+        let mut state = pager("text");
+        assert!(handle_key(&mut state, KeyEvent::from(KeyCode::Char('j'))));
+        assert!(!handle_key(&mut state, KeyEvent::from(KeyCode::Char('q'))));
+    }
+}
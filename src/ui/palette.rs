@@ -0,0 +1,250 @@
+//! Command palette (Ctrl+P): every slash command, alias and global key action
+//! plus the recent files in one list, narrowed by fuzzy matching. Commands that
+//! work bare run on Enter; ones that need an argument go to the input bar.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+
+use crate::app::{self, App};
+use crate::commands::{self, CommandResult};
+use super::keymap::{self, KeyContext};
+use super::{popup, theme};
+
+/// What picking an entry does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteRun {
+    /// Run this command line, as if typed.
+    Submit(String),
+    /// Put this text into the input bar to be finished.
+    Insert(String),
+    /// Act as if this key was pressed.
+    Key(KeyEvent),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteEntry {
+    pub label: String,
+    /// Dim text beside the label: the keys, the alias text, `file`, …
+    pub detail: String,
+    pub run: PaletteRun,
+}
+
+/// Outcome of a key press in the palette.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteAction {
+    None,
+    Close,
+    Run(PaletteRun),
+}
+
+#[derive(Debug, Clone)]
+pub struct PaletteState {
+    pub entries: Vec<PaletteEntry>,
+    pub query: String,
+    pub selected: usize,
+}
+
+impl PaletteState {
+    pub fn new(app: &App) -> Self {
+        Self { entries: entries(app), query: String::new(), selected: 0 }
+    }
+
+    /// Entries matching the query, best first.
+    pub fn matches(&self) -> Vec<&PaletteEntry> {
+        let mut scored: Vec<(i64, &PaletteEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| Some((fuzzy_score(&format!("{} {}", entry.label, entry.detail), &self.query)?, entry)))
+            .collect();
+        // Stable, so ties keep the listing order
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// Score `text` against `query` as a case-insensitive subsequence, or `None` if
+/// some query character is missing. Consecutive characters and word starts
+/// score higher; skipping ahead costs a little.
+pub fn fuzzy_score(text: &str, query: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let i = (next..text.len()).find(|&i| text[i] == wanted)?;
+        score += 1;
+        if i > 0 && previous == Some(i - 1) {
+            score += 5;
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= ((i - next) as i64).min(3);
+        previous = Some(i);
+        next = i + 1;
+    }
+    Some(score)
+}
+
+/// A single chord from a binding like `Ctrl+B` or `F11`; ranges, lists and
+/// plain keys like Esc give `None`.
+fn parse_key(keys: &str) -> Option<KeyEvent> {
+    match keymap::key_events(keys)?.as_slice() {
+        [key] if key.modifiers == KeyModifiers::CONTROL || matches!(key.code, KeyCode::F(_)) => Some(*key),
+        _ => None,
+    }
+}
+
+/// Everything the palette lists: commands, aliases, global key actions, recent files.
+fn entries(app: &App) -> Vec<PaletteEntry> {
+    let mut entries = Vec::new();
+    for &name in commands::COMMANDS.iter().filter(|name| !matches!(**name, "/exit" | "/q" | "/?")) {
+        // Commands that need an argument are finished in the input bar
+        let complete = !matches!(commands::process_command(name), CommandResult::Usage(_));
+        let run = if complete { PaletteRun::Submit(name.to_string()) } else { PaletteRun::Insert(format!("{name} ")) };
+        let detail = if complete { "command" } else { "command …" };
+        entries.push(PaletteEntry { label: name.to_string(), detail: detail.into(), run });
+    }
+    for name in app.custom_commands.keys() {
+        let label = format!("/{name}");
+        entries.push(PaletteEntry { label: label.clone(), detail: "custom command".into(), run: PaletteRun::Submit(label) });
+    }
+    for (name, text) in &app.aliases {
+        let label = format!("/{name}");
+        entries.push(PaletteEntry { label: label.clone(), detail: app::shorten(text, 40), run: PaletteRun::Insert(format!("{label} ")) });
+    }
+    for binding in keymap::BINDINGS.iter().filter(|b| b.context == KeyContext::Global && b.keys != "Ctrl+P") {
+        if let Some(key) = parse_key(binding.keys) {
+            entries.push(PaletteEntry { label: binding.action.to_string(), detail: binding.keys.to_string(), run: PaletteRun::Key(key) });
+        }
+    }
+    for path in &app.recent_files {
+        entries.push(PaletteEntry { label: path.clone(), detail: "file".into(), run: PaletteRun::Insert(path.clone()) });
+    }
+    entries
+}
+
+/// Handle a key for the palette.
+pub fn handle_key(state: &mut PaletteState, key: KeyEvent) -> PaletteAction {
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => return PaletteAction::Close,
+        (_, KeyCode::Up) => state.selected = state.selected.saturating_sub(1),
+        (_, KeyCode::Down) => state.selected += 1,
+        (_, KeyCode::Backspace) => {
+            state.query.pop();
+            state.selected = 0;
+        }
+        (_, KeyCode::Enter) => {
+            let matches = state.matches();
+            return match matches.get(state.selected.min(matches.len().saturating_sub(1))) {
+                Some(entry) => PaletteAction::Run(entry.run.clone()),
+                None => PaletteAction::None,
+            };
+        }
+        (modifiers, KeyCode::Char(c)) if !modifiers.contains(KeyModifiers::CONTROL) => {
+            state.query.push(c);
+            state.selected = 0;
+        }
+        _ => {}
+    }
+    PaletteAction::None
+}
+
+/// Render the palette near the top of `area`, dimming the rest.
+pub fn render(frame: &mut Frame, area: Rect, state: &mut PaletteState) {
+    popup::dim(frame, area);
+    let width = popup::popup_width(area.width);
+    let visible = (area.height * 3 / 5).saturating_sub(3).max(1) as usize;
+    let matches = state.matches();
+    let selected = state.selected.min(matches.len().saturating_sub(1));
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled(" › ", theme::accent_style()),
+        Span::raw(state.query.clone()),
+        Span::styled("▏", theme::accent_style()),
+    ])];
+    let label_width = (width as usize).saturating_sub(4) * 3 / 5;
+    let start = (selected + 1).saturating_sub(visible);
+    for (i, entry) in matches.iter().enumerate().skip(start).take(visible) {
+        let label = app::shorten(&entry.label, label_width);
+        let line = Line::from(vec![
+            Span::raw(format!("  {label:<label_width$} ")),
+            Span::styled(entry.detail.clone(), theme::dim_style()),
+        ]);
+        lines.push(if i == selected { line.style(theme::selection_style()) } else { line });
+    }
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled("  no matches", theme::dim_style())));
+    }
+
+    let count = matches.len();
+    state.selected = selected;
+
+    let height = lines.len() as u16 + 2;
+    let centered = popup::centered(area, width, height);
+    // Near the top, so the list grows down from a fixed query line
+    let area = Rect { y: area.y + area.height / 8, ..centered };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(format!(" Commands ({count}) "), theme::accent_style()))
+        .title_bottom(Span::styled(" type to search · ↑↓ · Enter run · Esc close ", theme::dim_style()));
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: &mut PaletteState, code: KeyCode) -> PaletteAction {
+        handle_key(state, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("/checkpoint", "ckpt").is_some());
+        assert!(fuzzy_score("/checkpoint", "ptk").is_none());
+        assert_eq!(fuzzy_score("anything", ""), Some(0));
+        // A run of characters at a word start beats the same characters scattered
+        assert!(fuzzy_score("/trace", "tr") > fuzzy_score("/theme reset", "tr"));
+        assert!(fuzzy_score("show or hide the sidebar", "side") > fuzzy_score("/set", "se"));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("Ctrl+B"), Some(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("F11"), Some(KeyEvent::new(KeyCode::F(11), KeyModifiers::NONE)));
+        assert_eq!(parse_key("Alt+1..9"), None);
+        assert_eq!(parse_key("PgUp/PgDn"), None);
+        assert_eq!(parse_key("Esc"), None);
+    }
+
+    #[test]
+    fn test_entries_and_run() {
+        let mut app = App::new("a", "m", "w");
+        app.add_recent_file("src/main.rs".into());
+        let mut palette = PaletteState::new(&app);
+        let find = |label: &str| palette.entries.iter().find(|e| e.label == label).map(|e| e.run.clone());
+        assert_eq!(find("/clear"), Some(PaletteRun::Submit("/clear".into())));
+        assert_eq!(find("/model"), Some(PaletteRun::Insert("/model ".into())));
+        assert_eq!(find("/q"), None);
+        assert_eq!(
+            find("show or hide the sidebar"),
+            Some(PaletteRun::Key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)))
+        );
+        assert_eq!(find("src/main.rs"), Some(PaletteRun::Insert("src/main.rs".into())));
+
+        for c in "sidebar".chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        assert_eq!(palette.matches()[0].label, "/sidebar");
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteAction::Run(PaletteRun::Submit("/sidebar".into())));
+        press(&mut palette, KeyCode::Char('#'));
+        assert!(palette.matches().is_empty());
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteAction::None);
+        assert_eq!(press(&mut palette, KeyCode::Esc), PaletteAction::Close);
+    }
+}
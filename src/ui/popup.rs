@@ -0,0 +1,248 @@
+//! Centered popups over a dimmed screen: read-only text such as help, yes/no
+//! confirmations and pick-one lists. Each kind handles its own keys and reports
+//! back a [`PopupAction`]; a popup that runs something does so by submitting a
+//! command line, exactly as if it had been typed.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+
+use super::{theme, wrap};
+
+/// What a popup shows and how it reacts to keys.
+#[derive(Debug, Clone)]
+pub enum PopupKind {
+    /// Read-only text, scrolled with ↑↓.
+    Text { text: String, scroll: usize },
+    /// A yes/no question; `command` is submitted when confirmed.
+    Confirm { prompt: String, command: String },
+    /// Pick one of `items`, narrowed by typing; submits `command` followed by the item.
+    Picker { items: Vec<String>, command: String, filter: String, selected: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct PopupState {
+    pub title: String,
+    pub kind: PopupKind,
+}
+
+/// Outcome of a key press in a popup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PopupAction {
+    None,
+    Close,
+    /// Close and run this command line.
+    Submit(String),
+}
+
+impl PopupState {
+    pub fn text(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { title: title.into(), kind: PopupKind::Text { text: text.into(), scroll: 0 } }
+    }
+
+    pub fn confirm(title: impl Into<String>, prompt: impl Into<String>, command: impl Into<String>) -> Self {
+        Self { title: title.into(), kind: PopupKind::Confirm { prompt: prompt.into(), command: command.into() } }
+    }
+
+    pub fn picker(title: impl Into<String>, items: Vec<String>, command: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            kind: PopupKind::Picker { items, command: command.into(), filter: String::new(), selected: 0 },
+        }
+    }
+
+    /// A picker's command and the item Enter would pick.
+    pub fn highlighted(&self) -> Option<(&str, &str)> {
+        let PopupKind::Picker { items, command, filter, selected } = &self.kind else { return None };
+        let matches = filtered(items, filter);
+        let item = matches.get((*selected).min(matches.len().saturating_sub(1)))?;
+        Some((command.as_str(), item.as_str()))
+    }
+}
+
+/// Picker items containing `filter` (case-insensitive), in order.
+fn filtered<'a>(items: &'a [String], filter: &str) -> Vec<&'a String> {
+    let needle = filter.to_lowercase();
+    items.iter().filter(|item| item.to_lowercase().contains(&needle)).collect()
+}
+
+/// Handle a key for the popup.
+pub fn handle_key(state: &mut PopupState, key: KeyEvent) -> PopupAction {
+    if key.code == KeyCode::Esc {
+        return PopupAction::Close;
+    }
+    match &mut state.kind {
+        PopupKind::Text { scroll, .. } => match key.code {
+            KeyCode::Char('q') | KeyCode::Enter => return PopupAction::Close,
+            KeyCode::Down | KeyCode::Char('j') => *scroll += 1,
+            KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => *scroll += 10,
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+            _ => {}
+        },
+        PopupKind::Confirm { command, .. } => match key.code {
+            KeyCode::Char('y' | 'Y') | KeyCode::Enter => return PopupAction::Submit(command.clone()),
+            KeyCode::Char('n' | 'N' | 'q') => return PopupAction::Close,
+            _ => {}
+        },
+        PopupKind::Picker { items, command, filter, selected } => match key.code {
+            KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::Down => *selected += 1,
+            KeyCode::Backspace => {
+                filter.pop();
+                *selected = 0;
+            }
+            KeyCode::Char(c) => {
+                filter.push(c);
+                *selected = 0;
+            }
+            KeyCode::Enter => {
+                let matches = filtered(items, filter);
+                return match matches.get((*selected).min(matches.len().saturating_sub(1))) {
+                    Some(item) => PopupAction::Submit(format!("{command} {item}").trim().to_string()),
+                    None => PopupAction::None,
+                };
+            }
+            _ => {}
+        },
+    }
+    PopupAction::None
+}
+
+/// A `width` × `height` box centered in `area`, shrunk to fit it.
+pub fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+/// Three fifths of the frame, at least 40 columns, but never wider than the
+/// frame: text is wrapped to this width.
+pub fn popup_width(frame_width: u16) -> u16 {
+    (frame_width * 3 / 5).max(40).min(frame_width)
+}
+
+/// Dim everything already drawn in `area` so the popup stands out.
+pub fn dim(frame: &mut Frame, area: Rect) {
+    let buffer = frame.buffer_mut();
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            if let Some(cell) = buffer.cell_mut((x, y)) {
+                cell.set_style(theme::dim_style().add_modifier(Modifier::DIM));
+            }
+        }
+    }
+}
+
+/// Render the popup centered over `area`, dimming the rest.
+pub fn render(frame: &mut Frame, area: Rect, state: &mut PopupState) {
+    dim(frame, area);
+    let width = popup_width(area.width);
+    let text_width = width.saturating_sub(4) as usize;
+
+    let (lines, footer): (Vec<Line>, &str) = match &mut state.kind {
+        PopupKind::Text { text, scroll } => {
+            let rows: Vec<String> = text.lines().flat_map(|line| wrap::wrap_str(line, text_width)).collect();
+            let visible = (area.height * 4 / 5).saturating_sub(2) as usize;
+            *scroll = (*scroll).min(rows.len().saturating_sub(visible));
+            let lines = rows.into_iter().skip(*scroll).map(|row| Line::from(format!(" {row}"))).collect();
+            (lines, " ↑↓ scroll · Esc close ")
+        }
+        PopupKind::Confirm { prompt, .. } => {
+            let mut lines: Vec<Line> =
+                wrap::wrap_str(prompt, text_width).into_iter().map(|row| Line::from(format!(" {row}"))).collect();
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::styled(" y", theme::accent_style()),
+                Span::styled(" yes   ", theme::dim_style()),
+                Span::styled("n", theme::accent_style()),
+                Span::styled(" no", theme::dim_style()),
+            ]));
+            (lines, " Enter confirm · Esc cancel ")
+        }
+        PopupKind::Picker { items, filter, selected, .. } => {
+            let matches = filtered(items, filter);
+            *selected = (*selected).min(matches.len().saturating_sub(1));
+            let mut lines = vec![Line::from(vec![
+                Span::styled(" › ", theme::accent_style()),
+                Span::raw(filter.clone()),
+                Span::styled("▏", theme::accent_style()),
+            ])];
+            let visible = (area.height * 4 / 5).saturating_sub(3) as usize;
+            let start = (*selected + 1).saturating_sub(visible);
+            for (i, item) in matches.iter().enumerate().skip(start).take(visible) {
+                let line = Line::from(format!("  {item}"));
+                lines.push(if i == *selected { line.style(theme::selection_style()) } else { line });
+            }
+            if matches.is_empty() {
+                lines.push(Line::from(Span::styled("  no matches", theme::dim_style())));
+            }
+            (lines, " type to filter · ↑↓ · Enter pick · Esc close ")
+        }
+    };
+
+    let height = (lines.len() as u16 + 2).min(area.height * 4 / 5).max(3);
+    let popup = centered(area, width, height);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(format!(" {} ", state.title), theme::accent_style()))
+        .title_bottom(Span::styled(footer, theme::dim_style()));
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(state: &mut PopupState, code: KeyCode) -> PopupAction {
+        handle_key(state, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut popup = PopupState::confirm("Safe mode", "Enable tools?", "/unsafe tools confirm");
+        assert_eq!(press(&mut popup, KeyCode::Char('x')), PopupAction::None);
+        assert_eq!(press(&mut popup, KeyCode::Char('y')), PopupAction::Submit("/unsafe tools confirm".into()));
+        assert_eq!(press(&mut popup, KeyCode::Char('n')), PopupAction::Close);
+        assert_eq!(press(&mut popup, KeyCode::Esc), PopupAction::Close);
+    }
+
+    #[test]
+    fn test_picker_filters_and_submits() {
+        let items = vec!["vertical".to_string(), "horizontal".to_string()];
+        let mut popup = PopupState::picker("Layout", items, "/layout");
+        assert_eq!(press(&mut popup, KeyCode::Down), PopupAction::None);
+        assert_eq!(press(&mut popup, KeyCode::Enter), PopupAction::Submit("/layout horizontal".into()));
+
+        press(&mut popup, KeyCode::Char('V'));
+        press(&mut popup, KeyCode::Char('e'));
+        assert_eq!(press(&mut popup, KeyCode::Enter), PopupAction::Submit("/layout vertical".into()));
+        assert_eq!(popup.highlighted(), Some(("/layout", "vertical")));
+        press(&mut popup, KeyCode::Char('z'));
+        assert_eq!(popup.highlighted(), None);
+        assert_eq!(press(&mut popup, KeyCode::Enter), PopupAction::None);
+    }
+
+    #[test]
+    fn test_text_scroll() {
+        let mut popup = PopupState::text("Help", "a\nb\nc");
+        press(&mut popup, KeyCode::Down);
+        press(&mut popup, KeyCode::Up);
+        press(&mut popup, KeyCode::Up);
+        assert!(matches!(popup.kind, PopupKind::Text { scroll: 0, .. }));
+        assert_eq!(press(&mut popup, KeyCode::Char('q')), PopupAction::Close);
+    }
+
+    #[test]
+    fn test_centered() {
+        assert_eq!(centered(Rect::new(0, 0, 100, 40), 60, 10), Rect::new(20, 15, 60, 10));
+        assert_eq!(centered(Rect::new(0, 0, 30, 5), 60, 10), Rect::new(0, 0, 30, 5));
+        assert_eq!(popup_width(100), 60);
+        assert_eq!(popup_width(50), 40);
+        assert_eq!(popup_width(30), 30);
+    }
+}
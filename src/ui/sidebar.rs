@@ -1,21 +1,76 @@
-//! Right sidebar — status panel + LLM call log.
+//! Right sidebar — a tab bar over one of the Status, Trace, LLM and Files
+//! panels, with pinned notices in between.
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline, Tabs};
 use ratatui::text::{Line, Span};
 
-use crate::app::App;
-use super::theme;
+use crate::app::{self, App, PanelFocus, SidebarTab, TaskState};
+use super::layout::{self, AppLayout};
+use super::{icons, theme, wrap};
 
-/// Render the status panel (upper sidebar).
+/// Render the tab bar, the pinned notices and the active tab.
+pub fn render(frame: &mut Frame, layout: &AppLayout, app: &App) {
+    render_tabs(frame, layout.sidebar_tabs, app);
+    render_notices(frame, layout.sidebar_notices, app);
+    match app.sidebar_tab {
+        SidebarTab::Status => {
+            let (status, tools) = layout::split_status_tab(layout.sidebar, app.splits, tool_stats_rows(app));
+            render_status(frame, status, app);
+            render_tool_stats(frame, tools, app);
+        }
+        SidebarTab::Trace => {
+            let (pipeline, trace) = layout::split_trace_tab(layout.sidebar, app.pipeline.len() as u16);
+            render_pipeline(frame, pipeline, app);
+            render_trace(frame, trace, app);
+        }
+        SidebarTab::Llm => render_llm_log(frame, layout.sidebar, app),
+        SidebarTab::Files => render_files(frame, layout.sidebar, app),
+    }
+}
+
+/// One-row tab bar; the active tab is highlighted, brighter while focused.
+/// Number keys 1-4 pick tabs in this order.
+fn render_tabs(frame: &mut Frame, area: Rect, app: &App) {
+    let titles: Vec<Line> = SidebarTab::ALL.iter().map(|tab| Line::from(tab.title())).collect();
+    let highlight = if app.focus == PanelFocus::Sidebar {
+        Style::default().fg(theme::current().on_focus).bg(theme::current().focus).add_modifier(Modifier::BOLD)
+    } else {
+        theme::accent_style().add_modifier(Modifier::REVERSED)
+    };
+    let selected = SidebarTab::ALL.iter().position(|&tab| tab == app.sidebar_tab).unwrap_or(0);
+    let tabs = Tabs::new(titles)
+        .select(selected)
+        .style(theme::dim_style())
+        .highlight_style(highlight)
+        .divider("")
+        .padding(" ", "");
+    frame.render_widget(tabs, area);
+}
+
+/// Border style of a tab's panel, highlighted while the sidebar has focus.
+fn tab_border(app: &App, tab: SidebarTab) -> Style {
+    if app.tab_focused(tab) {
+        theme::focus_border_style()
+    } else {
+        theme::border_style()
+    }
+}
+
+/// Render the status panel (Status tab).
 pub fn render_status(frame: &mut Frame, area: Rect, app: &App) {
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(theme::border_style())
+        .border_style(tab_border(app, SidebarTab::Status))
         .title(Span::styled(" Status ", theme::accent_style()));
 
     let mut lines: Vec<Line> = Vec::new();
 
+    let agent = theme::agent_style(&app.status.agent_name);
+    lines.push(Line::from(vec![
+        Span::styled(" ● ", agent),
+        Span::styled(&app.status.agent_name, agent.add_modifier(Modifier::BOLD)),
+    ]));
     lines.push(Line::from(vec![
         Span::styled(" Model: ", theme::dim_style()),
         Span::styled(&app.status.model, theme::user_style()),
@@ -32,22 +87,172 @@ pub fn render_status(frame: &mut Frame, area: Rect, app: &App) {
         Span::styled(" Cost: ", theme::dim_style()),
         Span::raw(app.status.cost_display()),
     ]));
-
-    // Recent files
-    if !app.recent_files.is_empty() {
+    if app.rate_limit_hits > 0 {
+        lines.push(Line::from(vec![
+            Span::styled(" Rate limits: ", theme::dim_style()),
+            Span::styled(app.rate_limit_hits.to_string(), theme::tool_style()),
+        ]));
+    }
+    // Rows left blank for the gauges, drawn over them below
+    let mut gauges = vec![(lines.len() as u16, context_gauge(app))];
+    lines.push(Line::from(""));
+    if let Some(gauge) = budget_gauge(app) {
+        gauges.push((lines.len() as u16, gauge));
         lines.push(Line::from(""));
-        for f in app.recent_files.iter().rev().take(4) {
-            let display = f.rsplit('/').next().unwrap_or(f);
-            lines.push(Line::from(Span::styled(format!(" 📄 {display}"), theme::dim_style())));
+    }
+
+    // Cost by model, once more than one has been used
+    if app.status.per_model.len() > 1 {
+        let width = (area.width as usize).saturating_sub(14).max(4);
+        for entry in &app.status.per_model {
+            lines.push(Line::from(vec![
+                Span::styled(format!("   {:<width$}", app::shorten(&entry.model, width)), theme::dim_style()),
+                Span::raw(format!(" ${:.4}", entry.cost)),
+            ]));
         }
     }
 
     // Busy indicator
     if app.agent_busy {
+        let icons = icons::current();
         lines.push(Line::from(""));
-        lines.push(Line::from(Span::styled(" ⏳ Working...", theme::tool_style())));
+        let working = match app.rate_limit_wait() {
+            Some(wait) => format!(" {} Rate limited, retry {wait}s", icons.waiting),
+            None => format!(" {} Working...", icons.waiting),
+        };
+        lines.push(Line::from(Span::styled(working, theme::tool_style())));
+        // Ticking timers tell a slow LLM call from a hung tool
+        for (marker, running) in [(icons.stage, &app.running_stage), (icons.tool, &app.running_tool)] {
+            if let Some((name, since)) = running {
+                lines.push(Line::from(vec![
+                    Span::styled(format!(" {marker} "), theme::tool_style()),
+                    Span::raw(name.as_str()),
+                    Span::styled(format!(" {} {:.1}s", icons.timer, since.elapsed().as_secs_f64()), theme::dim_style()),
+                ]));
+            }
+        }
+    }
+
+    if !app.background_tasks.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(" Background", theme::accent_style())));
+        lines.extend(app.background_tasks.iter().map(|task| background_line(task, area.width)));
+    }
+
+    let inner = block.inner(area);
+    let scroll = app.status_scroll.min(lines.len().saturating_sub(1)) as u16;
+    let paragraph = Paragraph::new(lines).block(block).scroll((scroll, 0));
+    frame.render_widget(paragraph, area);
+
+    for (row, gauge) in gauges {
+        let Some(row) = row.checked_sub(scroll).filter(|&row| row < inner.height) else { continue };
+        let gauge_area = Rect::new(inner.x + 1, inner.y + row, inner.width.saturating_sub(2), 1);
+        frame.render_widget(gauge, gauge_area);
     }
+}
+
+/// A background task with its state: waiting, running for how long, or how it ended.
+fn background_line(task: &app::BackgroundTask, width: u16) -> Line<'static> {
+    let icons = icons::current();
+    let (marker, style, time) = match task.state {
+        TaskState::Queued => (icons.pending, theme::dim_style(), String::new()),
+        TaskState::Running(since) => (icons.working, theme::tool_style(), format!(" {}", app::format_elapsed(since.elapsed().as_secs()))),
+        TaskState::Done { duration_ms } => (icons.ok, theme::success_style(), format!(" {}", app::format_duration(duration_ms))),
+        TaskState::Failed { .. } => (icons.failed, theme::error_style(), String::new()),
+        TaskState::Canceled => (icons.skipped, theme::dim_style(), String::new()),
+    };
+    let room = (width as usize).saturating_sub(8 + time.chars().count()).max(4);
+    Line::from(vec![
+        Span::styled(format!(" {marker} #{} ", task.id), style),
+        Span::raw(app::shorten(&task.prompt, room)),
+        Span::styled(time, theme::dim_style()),
+    ])
+}
+
+/// Session tokens against the manifest's `token_budget`, colored at the
+/// configured thresholds and flagged once the budget is blown.
+fn budget_gauge(app: &App) -> Option<Gauge<'static>> {
+    let (used, budget, level) = app.budget_usage()?;
+    let ratio = (used as f64 / budget.max(1) as f64).min(1.0);
+    let color = match level {
+        app::BudgetLevel::Ok => theme::current().success,
+        app::BudgetLevel::Warn => theme::current().tool,
+        app::BudgetLevel::Alert | app::BudgetLevel::Exceeded => theme::current().error,
+    };
+    let label = if level == app::BudgetLevel::Exceeded {
+        Span::styled(
+            format!("OVER {}/{}", app::format_tokens(used), app::format_tokens(budget)),
+            theme::gauge_label_style().add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::styled(
+            format!("budget {}/{}", app::format_tokens(used), app::format_tokens(budget)),
+            theme::gauge_label_style(),
+        )
+    };
+    Some(
+        Gauge::default()
+            .ratio(ratio)
+            .gauge_style(Style::default().fg(color).bg(theme::current().border))
+            .label(label),
+    )
+}
+
+/// Prompt tokens against the model's context window, green → yellow → red as it fills.
+fn context_gauge(app: &App) -> Gauge<'static> {
+    let (used, window) = app.context_usage();
+    let ratio = (used as f64 / window.max(1) as f64).min(1.0);
+    let color = if ratio < 0.6 {
+        theme::current().success
+    } else if ratio < 0.85 {
+        theme::current().tool
+    } else {
+        theme::current().error
+    };
+    Gauge::default()
+        .ratio(ratio)
+        .gauge_style(Style::default().fg(color).bg(theme::current().border))
+        .label(Span::styled(
+            format!(
+                "ctx {}/{} {:.0}%",
+                app::format_tokens(used),
+                app::format_tokens(window),
+                ratio * 100.0
+            ),
+            theme::gauge_label_style(),
+        ))
+}
 
+/// Rows the pinned notices need at a sidebar `width`, once wrapped.
+pub fn notice_rows(app: &App, width: u16) -> u16 {
+    app.notices.iter().map(|notice| wrap_notice(&notice.text, width).len() as u16).sum()
+}
+
+/// Word-wrap a notice to the inside of a `width`-wide box, after its "⚠" marker.
+fn wrap_notice(text: &str, width: u16) -> Vec<String> {
+    wrap::wrap_str(text, (width as usize).saturating_sub(2 + NOTICE_INDENT).max(1))
+}
+
+/// Columns taken by the " ⚠ " marker; continuation rows are indented to match.
+const NOTICE_INDENT: usize = 3;
+
+/// Render pinned notices between the status panel and the trace.
+pub fn render_notices(frame: &mut Frame, area: Rect, app: &App) {
+    if area.height == 0 || app.notices.is_empty() {
+        return;
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::tool_style())
+        .title(Span::styled(" Notices ", theme::tool_style()))
+        .title_bottom(Line::from(Span::styled(" /dismiss [n] ", theme::dim_style())).right_aligned());
+    let mut lines: Vec<Line> = Vec::new();
+    for notice in &app.notices {
+        for (i, row) in wrap_notice(&notice.text, area.width).into_iter().enumerate() {
+            let marker = if i == 0 { format!(" {} ", icons::current().warning) } else { "   ".to_string() };
+            lines.push(Line::from(vec![Span::styled(marker, theme::tool_style()), Span::raw(row)]));
+        }
+    }
     let paragraph = Paragraph::new(lines).block(block);
     frame.render_widget(paragraph, area);
 }
@@ -56,41 +261,51 @@ pub fn render_status(frame: &mut Frame, area: Rect, app: &App) {
 pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
     use crate::app::TraceEntry;
 
-    let is_focused = app.focus == crate::app::PanelFocus::Trace;
-    let border_style = if is_focused {
-        Style::default().fg(Color::Cyan)
-    } else {
-        theme::border_style()
-    };
+    let is_focused = app.tab_focused(SidebarTab::Trace);
     let mut block = Block::default()
         .borders(Borders::ALL)
-        .border_style(border_style);
+        .border_style(tab_border(app, SidebarTab::Trace));
 
+    let icons = icons::current();
     let mut lines: Vec<Line> = Vec::new();
+    let mut selected_line = None;
 
     if app.trace_log.is_empty() {
-        lines.push(Line::from(Span::styled(" Waiting...", theme::dim_style())));
+        let text = if app.trace_enabled { " Waiting..." } else { " Trace off (/trace on)" };
+        lines.push(Line::from(Span::styled(text, theme::dim_style())));
     } else {
-        for entry in &app.trace_log {
+        for (index, entry) in app.trace_log.iter().enumerate() {
+            if !app.shows_trace(entry) {
+                continue;
+            }
+            let first_line = lines.len();
             match entry {
                 TraceEntry::StageStart { id, kind } => {
                     lines.push(Line::from(vec![
-                        Span::styled(" ▶ ", Style::default().fg(Color::Cyan)),
+                        Span::styled(format!(" {} ", icons.stage), theme::stage_style()),
                         Span::styled(id, theme::dim_style()),
-                        Span::styled(format!(" ({})", kind), Style::default().fg(Color::DarkGray)),
+                        Span::styled(format!(" ({})", kind), theme::dim_style()),
                     ]));
                 }
                 TraceEntry::StageEnd { id: _, duration_ms, skipped } => {
                     if *skipped {
-                        lines.push(Line::from(Span::styled("   ⏭ skipped", Style::default().fg(Color::Yellow))));
+                        lines.push(Line::from(Span::styled(format!("   {} skipped", icons.skipped), theme::tool_style())));
                     } else if *duration_ms > 100 {
                         lines.push(Line::from(Span::styled(
-                            format!("   ✓ {}ms", duration_ms),
-                            Style::default().fg(Color::DarkGray),
+                            format!("   {} {}ms", icons.ok, duration_ms),
+                            theme::dim_style(),
                         )));
                     }
                     // Don't show completion for fast stages (< 100ms) to reduce noise
                 }
+                TraceEntry::StageFailed { id: _, error } => {
+                    let short = if error.chars().count() > 22 {
+                        format!("{}...", error.chars().take(19).collect::<String>())
+                    } else {
+                        error.clone()
+                    };
+                    lines.push(Line::from(Span::styled(format!("   {} {short}", icons.failed), theme::error_style())));
+                }
                 TraceEntry::LlmCall { model, ctx_tokens, out_tokens, duration_ms } => {
                     let ctx_k = (*ctx_tokens as f64 / 1000.0).round() as usize;
                     let dur = if *duration_ms >= 1000 {
@@ -100,7 +315,7 @@ pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
                     };
                     let model_short = if model.len() > 10 { &model[..10] } else { model.as_str() };
                     lines.push(Line::from(vec![
-                        Span::styled("   🧠 ", Style::default()),
+                        Span::styled(format!("   {} ", icons.model), Style::default()),
                         Span::styled(model_short, theme::user_style()),
                         Span::styled(format!(" {}k→{} {}", ctx_k, out_tokens, dur), theme::dim_style()),
                     ]));
@@ -112,21 +327,18 @@ pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
                         args.clone()
                     };
                     lines.push(Line::from(vec![
-                        Span::styled("   ⚡ ", Style::default().fg(Color::Yellow)),
-                        Span::styled(name, Style::default().fg(Color::Yellow)),
+                        Span::styled(format!("   {} ", icons.tool), theme::tool_style()),
+                        Span::styled(name, theme::tool_style()),
                         Span::styled(format!(" {}", args_short), theme::dim_style()),
                     ]));
                 }
-                TraceEntry::ToolResult { name: _, success, duration_ms } => {
-                    let (icon, color) = if *success {
-                        ("✓", Color::Green)
+                TraceEntry::ToolResult { success, duration_ms, .. } => {
+                    let (icon, style) = if *success {
+                        (icons.ok, theme::success_style())
                     } else {
-                        ("✗", Color::Red)
+                        (icons.failed, theme::error_style())
                     };
-                    lines.push(Line::from(Span::styled(
-                        format!("   {} {}ms", icon, duration_ms),
-                        Style::default().fg(color),
-                    )));
+                    lines.push(Line::from(Span::styled(format!("   {} {}ms", icon, duration_ms), style)));
                 }
                 TraceEntry::Narration(text) => {
                     let short = if text.len() > 25 {
@@ -135,10 +347,23 @@ pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
                         text.clone()
                     };
                     lines.push(Line::from(Span::styled(
-                        format!("   💬 {}", short),
+                        format!("   {} {}", icons.narration, short),
                         theme::dim_style(),
                     )));
                 }
+                TraceEntry::Artifact { stage_id, name } => {
+                    lines.push(Line::from(vec![
+                        Span::styled(format!("   {} ", icons.artifact), Style::default()),
+                        Span::styled(name, theme::accent_style()),
+                        Span::styled(format!(" ({})", stage_id), theme::dim_style()),
+                    ]));
+                }
+            }
+            if app.selected_trace == Some(index) {
+                for line in &mut lines[first_line..] {
+                    line.style = line.style.patch(theme::selection_style());
+                }
+                selected_line = Some(first_line);
             }
         }
     }
@@ -147,7 +372,7 @@ pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
     let total = lines.len();
 
     // Scroll handling
-    let start = match app.trace_scroll {
+    let mut start = match app.trace_scroll {
         None => {
             // Auto-scroll: show last N lines
             total.saturating_sub(max_visible)
@@ -156,21 +381,217 @@ pub fn render_trace(frame: &mut Frame, area: Rect, app: &App) {
             offset.min(total.saturating_sub(max_visible))
         }
     };
+    // Keep the selected stage in view
+    if let Some(line) = selected_line {
+        if line < start {
+            start = line;
+        } else if line >= start + max_visible {
+            start = line + 1 - max_visible;
+        }
+    }
 
     let visible: Vec<Line> = lines.into_iter().skip(start).take(max_visible).collect();
 
     let title = if is_focused {
         if app.trace_scroll.is_some() {
-            format!(" ● Trace [{}/{}] PgUp/Dn ", start + 1, total)
+            format!(" ● Trace [{}/{}] ", start + 1, total)
         } else {
-            " ● Trace [Tab→focus] ".to_string()
+            " ● Trace ".to_string()
         }
     } else {
-        " Trace [Tab→focus] ".to_string()
+        " Trace ".to_string()
     };
 
-    let block = block.title(Span::styled(title, theme::accent_style()));
+    block = block.title(Span::styled(title, theme::accent_style()));
 
     let paragraph = Paragraph::new(visible).block(block);
     frame.render_widget(paragraph, area);
 }
+
+/// Render the workflow's stages top to bottom: the running stage highlighted,
+/// finished ones checked, skipped ones dimmed.
+pub fn render_pipeline(frame: &mut Frame, area: Rect, app: &App) {
+    use crate::app::StageState;
+
+    if area.height == 0 {
+        return;
+    }
+    let icons = icons::current();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::border_style())
+        .title(Span::styled(" Pipeline ", theme::accent_style()));
+    let visible = area.height.saturating_sub(2) as usize;
+    // Keep the furthest stage that has started in view
+    let current = app.pipeline.iter().rposition(|stage| stage.state != StageState::Pending).unwrap_or(0);
+    let offset = (current + 1).saturating_sub(visible);
+    let lines: Vec<Line> = app
+        .pipeline
+        .iter()
+        .skip(offset)
+        .map(|stage| {
+            let (marker, style) = match stage.state {
+                StageState::Pending => (icons.pending, theme::dim_style()),
+                StageState::Running => (icons.stage, theme::stage_style().add_modifier(Modifier::BOLD)),
+                StageState::Done => (icons.ok, theme::success_style()),
+                StageState::Skipped => (icons.not_run, theme::dim_style()),
+                StageState::Failed => (icons.failed, theme::error_style()),
+            };
+            let name_style = match stage.state {
+                StageState::Running => style,
+                StageState::Skipped => style.add_modifier(Modifier::CROSSED_OUT),
+                StageState::Failed => theme::error_style(),
+                StageState::Pending | StageState::Done => theme::dim_style(),
+            };
+            Line::from(vec![Span::styled(format!(" {marker} "), style), Span::styled(stage.id.as_str(), name_style)])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Rows the tool statistics box needs: one per tool that has run.
+fn tool_stats_rows(app: &App) -> u16 {
+    app.tool_stats.len() as u16
+}
+
+/// Render per-tool call counts, failure rates and cumulative time under the status box.
+pub fn render_tool_stats(frame: &mut Frame, area: Rect, app: &App) {
+    if area.height == 0 {
+        return;
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::border_style())
+        .title(Span::styled(" Tools ", theme::accent_style()));
+    let inner_width = (area.width as usize).saturating_sub(2);
+    let lines: Vec<Line> = app
+        .tool_stats_by_calls()
+        .into_iter()
+        .map(|(name, stats)| {
+            // Count, failures and time are right-aligned; the name gets what's left
+            let calls = format!(" {}×", stats.calls);
+            let failures = if stats.failures > 0 {
+                format!(" {}{} {}%", stats.failures, icons::current().failed, stats.failure_percent())
+            } else {
+                String::new()
+            };
+            let time = format!(" {}", app::format_duration(stats.total_ms));
+            let used = 1 + calls.chars().count() + failures.chars().count() + time.chars().count();
+            let name_width = inner_width.saturating_sub(used).max(4);
+            Line::from(vec![
+                Span::styled(format!(" {:<name_width$}", app::shorten(name, name_width)), theme::dim_style()),
+                Span::raw(calls),
+                Span::styled(failures, theme::error_style()),
+                Span::styled(time, theme::dim_style()),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Rows the latency sparkline takes at the top of the LLM log.
+const SPARKLINE_ROWS: u16 = 3;
+
+/// Render the LLM call log (LLM tab): one row per call, oldest first, under a
+/// line of cumulative totals and a sparkline of recent call latencies.
+pub fn render_llm_log(frame: &mut Frame, area: Rect, app: &App) {
+    let is_focused = app.tab_focused(SidebarTab::Llm);
+    let mut block = Block::default().borders(Borders::ALL).border_style(tab_border(app, SidebarTab::Llm));
+
+    let (ctx_total, out_total, ms_total) = app.llm_totals();
+    let header = Line::from(vec![
+        Span::styled(" Σ ", theme::accent_style()),
+        Span::styled(
+            format!(
+                "{}→{} {}",
+                app::format_tokens(ctx_total),
+                app::format_tokens(out_total),
+                app::format_duration(ms_total)
+            ),
+            theme::dim_style(),
+        ),
+    ]);
+
+    let mut rows: Vec<Line> = app
+        .llm_calls
+        .iter()
+        .map(|call| {
+            Line::from(vec![
+                Span::raw(format!(
+                    " {}→{} ",
+                    app::format_tokens(call.prompt_tokens),
+                    app::format_tokens(call.completion_tokens)
+                )),
+                Span::styled(format!("{} ", app::format_duration(call.duration_ms)), theme::tool_style()),
+                Span::styled(call.model.clone(), theme::dim_style()),
+            ])
+        })
+        .collect();
+    // The call being made right now is the newest row, highlighted until it lands
+    if app.agent_busy {
+        rows.push(Line::from(Span::styled(" ⋯ in flight", theme::accent_style())).style(theme::selection_style()));
+    }
+
+    // A trend needs a few calls and room left over for the log itself
+    let inner = block.inner(area);
+    let show_trend = app.llm_calls.len() >= 2 && inner.height >= SPARKLINE_ROWS + 4;
+    let trend_rows = if show_trend { SPARKLINE_ROWS } else { 0 };
+
+    let max_visible = (area.height as usize).saturating_sub(3 + trend_rows as usize);
+    let total = rows.len();
+    let start = app.llm_log_scroll.unwrap_or(total).min(total.saturating_sub(max_visible));
+    let mut lines = vec![header];
+    lines.extend((0..trend_rows).map(|_| Line::from("")));
+    lines.extend(rows.into_iter().skip(start).take(max_visible));
+
+    let count = app.llm_calls.len();
+    let title = match (is_focused, app.llm_log_scroll) {
+        (true, Some(_)) => format!(" ● LLM Calls [{}/{}] ", start + 1, total),
+        (true, None) => format!(" ● LLM Calls ({count}) "),
+        (false, _) => format!(" LLM Calls ({count}) "),
+    };
+    block = block.title(Span::styled(title, theme::accent_style()));
+
+    let paragraph = Paragraph::new(lines).block(block);
+    frame.render_widget(paragraph, area);
+
+    if show_trend {
+        let trend_area = Rect::new(inner.x + 1, inner.y + 1, inner.width.saturating_sub(2), trend_rows);
+        let latencies = app.recent_latencies(trend_area.width as usize);
+        let sparkline = Sparkline::default().data(&latencies).style(theme::tool_style());
+        frame.render_widget(sparkline, trend_area);
+    }
+}
+
+/// Render the files the agent touched (Files tab): everything it wrote this
+/// session, then the most recently used files.
+pub fn render_files(frame: &mut Frame, area: Rect, app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(tab_border(app, SidebarTab::Files))
+        .title(Span::styled(" Files ", theme::accent_style()));
+
+    let icons = icons::current();
+    let mut lines: Vec<Line> = Vec::new();
+    if app.changed_files.is_empty() && app.recent_files.is_empty() {
+        lines.push(Line::from(Span::styled(" No files touched yet", theme::dim_style())));
+    }
+    if !app.changed_files.is_empty() {
+        lines.push(Line::from(Span::styled(format!(" Changed ({})", app.changed_files.len()), theme::accent_style())));
+        for path in &app.changed_files {
+            lines.push(Line::from(vec![Span::styled(format!(" {} ", icons.edit), theme::tool_style()), Span::raw(path.as_str())]));
+        }
+    }
+    if !app.recent_files.is_empty() {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(" Recent", theme::accent_style())));
+        for path in &app.recent_files {
+            lines.push(Line::from(Span::styled(format!(" {} {path}", icons.file), theme::dim_style())));
+        }
+    }
+
+    let scroll = app.files_scroll.min(lines.len().saturating_sub(1)) as u16;
+    frame.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
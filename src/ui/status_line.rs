@@ -0,0 +1,69 @@
+//! One-row status line under the input bar: what the agent is doing, its
+//! autonomy level, the running stage and context use on the left, the focused
+//! panel's key hints on the right.
+
+use ratatui::prelude::*;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+
+use crate::app::{format_elapsed, App, RunMode};
+use super::{hints, icons, theme};
+
+/// Left-hand state segments, separated by dim dots.
+fn state_spans(app: &App) -> Vec<Span<'static>> {
+    let icons = icons::current();
+    let mode = match app.run_mode() {
+        RunMode::Idle => Span::styled(format!("{} idle", icons.idle), theme::success_style()),
+        RunMode::Working => {
+            let elapsed = app.thinking_since.map(|since| since.elapsed().as_secs()).unwrap_or(0);
+            Span::styled(format!("{} working {}", icons.working, format_elapsed(elapsed)), theme::tool_style())
+        }
+        RunMode::RateLimited => Span::styled(
+            format!("{} rate limited, retry {}s", icons.waiting, app.rate_limit_wait().unwrap_or(0)),
+            theme::tool_style(),
+        ),
+        RunMode::Canceling => Span::styled(format!("{} canceling…", icons.waiting), theme::error_style()),
+    };
+    let mut segments = vec![mode];
+    if let Some(left) = app.timeout_left.filter(|_| app.agent_busy) {
+        segments.push(Span::styled(format!("{} times out in {}", icons.timer, format_elapsed(left)), theme::error_style()));
+    }
+    if !app.status.autonomy.is_empty() {
+        segments.push(Span::styled(format!("autonomy {}", app.status.autonomy), theme::dim_style()));
+    }
+    if let Some((stage, _)) = &app.running_stage {
+        segments.push(Span::styled(format!("{} {stage}", icons.stage), theme::accent_style()));
+    }
+    let (used, window) = app.context_usage();
+    if used > 0 {
+        let percent = used * 100 / window.max(1);
+        let style = if percent >= 85 {
+            theme::error_style()
+        } else if percent >= 60 {
+            theme::tool_style()
+        } else {
+            theme::dim_style()
+        };
+        segments.push(Span::styled(format!("ctx {percent}%"), style));
+    }
+
+    let mut spans = vec![Span::raw(" ")];
+    for (i, segment) in segments.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(" · ", theme::dim_style()));
+        }
+        spans.push(segment);
+    }
+    spans
+}
+
+/// Render the status line, with as many hints as fit beside the state.
+pub fn render(frame: &mut Frame, area: Rect, app: &App) {
+    let state = Line::from(state_spans(app));
+    let room = (area.width as usize).saturating_sub(state.width() + 1);
+    let hints = hints::hint_line(app, app.focus, room);
+    frame.render_widget(Paragraph::new(state), area);
+    if let Some(hints) = hints {
+        frame.render_widget(Paragraph::new(hints.right_aligned()), area);
+    }
+}
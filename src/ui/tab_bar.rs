@@ -0,0 +1,72 @@
+//! One-row bar of session tabs, shown above everything else while more than
+//! one session is open.
+
+use std::time::Instant;
+
+use ratatui::prelude::*;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+
+use super::{icons, theme};
+
+/// What the bar shows for one session tab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabLabel {
+    pub title: String,
+    /// When the session's agent started the turn it is working on.
+    pub busy_since: Option<Instant>,
+    /// Chat messages that arrived while the tab was in the background.
+    pub unread: usize,
+}
+
+/// The spinner frame for a turn running since `since`, advancing every 100ms tick.
+fn spinner(since: Instant) -> &'static str {
+    let frames = icons::current().spinner;
+    frames[(since.elapsed().as_millis() / 100) as usize % frames.len()]
+}
+
+/// A tab's text: its Alt+ number, title, spinner while busy and unread count.
+fn text(index: usize, label: &TabLabel) -> String {
+    let mut text = format!(" {} {}", index + 1, label.title);
+    if let Some(since) = label.busy_since {
+        text.push_str(&format!(" {}", spinner(since)));
+    }
+    if label.unread > 0 {
+        text.push_str(&format!(" ({})", label.unread));
+    }
+    text.push(' ');
+    text
+}
+
+/// Render the tabs numbered for Alt+1..9, highlighting `active`.
+pub fn render(frame: &mut Frame, area: Rect, labels: &[TabLabel], active: usize) {
+    let mut spans = Vec::new();
+    for (i, label) in labels.iter().enumerate() {
+        let text = text(i, label);
+        spans.push(if i == active {
+            Span::styled(text, theme::accent_style().add_modifier(Modifier::REVERSED))
+        } else if label.unread > 0 {
+            Span::styled(text, theme::accent_style())
+        } else {
+            Span::styled(text, theme::dim_style())
+        });
+        spans.push(Span::raw(" "));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let mut label = TabLabel { title: "coder · sonnet".into(), busy_since: None, unread: 0 };
+        assert_eq!(text(0, &label), " 1 coder · sonnet ");
+        label.unread = 3;
+        assert_eq!(text(1, &label), " 2 coder · sonnet (3) ");
+        label.busy_since = Some(Instant::now());
+        let busy = text(1, &label);
+        assert!(icons::current().spinner.iter().any(|frame| busy == format!(" 2 coder · sonnet {frame} (3) ")), "{busy}");
+    }
+}
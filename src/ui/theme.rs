@@ -1,54 +1,287 @@
-//! Color theme and styling constants.
+//! Styles built from the active color theme, adapted to what the terminal can show.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use ratatui::buffer::Buffer;
 use ratatui::style::{Color, Modifier, Style};
 
-pub const USER_COLOR: Color = Color::Rgb(100, 149, 237);       // Cornflower blue
-pub const ASSISTANT_COLOR: Color = Color::Rgb(120, 200, 120);  // Green
-pub const TOOL_COLOR: Color = Color::Rgb(230, 190, 60);        // Yellow
-pub const ERROR_COLOR: Color = Color::Rgb(220, 80, 80);        // Red
-pub const DIM_COLOR: Color = Color::DarkGray;
-pub const ACCENT_COLOR: Color = Color::Rgb(160, 120, 230);     // Purple
-pub const NARRATION_COLOR: Color = Color::Rgb(180, 180, 180);  // Light grey
-pub const SYSTEM_COLOR: Color = Color::Rgb(100, 100, 100);     // Dark grey
-pub const SUCCESS_COLOR: Color = Color::Rgb(80, 200, 80);      // Green
-pub const BORDER_COLOR: Color = Color::Rgb(60, 60, 80);        // Dim border
+use crate::theme::Theme;
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    /// `NO_COLOR` is set or the terminal is dumb: attributes only.
+    None,
+    /// The 16 basic ANSI colors.
+    Ansi16,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Read `NO_COLOR`, `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).ok();
+        Self::from_env(var("NO_COLOR").as_deref(), var("COLORTERM").as_deref(), var("TERM").as_deref())
+    }
+
+    /// Truecolor only when `COLORTERM` says so: a 256-color `TERM` (tmux's
+    /// default among them) turns RGB into poor approximations.
+    fn from_env(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if no_color.is_some_and(|v| !v.is_empty()) || term == Some("dumb") {
+            ColorSupport::None
+        } else if matches!(colorterm, Some("truecolor" | "24bit")) {
+            ColorSupport::TrueColor
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+
+    /// `color` as this terminal can show it.
+    pub fn adapt(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::TrueColor, _) | (_, Color::Reset) => color,
+            (ColorSupport::None, _) => Color::Reset,
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi((r, g, b)),
+            (ColorSupport::Ansi16, Color::Indexed(i)) => match ANSI.get(i as usize) {
+                Some((color, _)) => *color,
+                None => nearest_ansi(indexed_rgb(i)),
+            },
+            (ColorSupport::Ansi16, _) => color,
+        }
+    }
+}
+
+/// The ANSI colors with xterm's default values, in index order.
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Closest ANSI color. Black is left out: it is usually the background, and
+/// dim borders and highlights would vanish into it.
+fn nearest_ansi((r, g, b): (u8, u8, u8)) -> Color {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+    ANSI[1..].iter().min_by_key(|(_, rgb)| distance(*rgb)).map(|(color, _)| *color).unwrap_or(Color::Reset)
+}
+
+/// RGB of a 256-color index past the first 16: the 6×6×6 cube, then grays.
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    if i >= 232 {
+        let level = 8 + (i - 232) * 10;
+        return (level, level, level);
+    }
+    let i = i - 16;
+    let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+    (level(i / 36), level(i / 6 % 6), level(i % 6))
+}
+
+static THEME: RwLock<Theme> = RwLock::new(Theme::DEFAULT);
+static SUPPORT: RwLock<ColorSupport> = RwLock::new(ColorSupport::TrueColor);
+/// Bumped whenever the styles change, for what caches them.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+fn support() -> ColorSupport {
+    *SUPPORT.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Set what the terminal can show; every style is adapted to it from then on.
+pub fn set_color_support(level: ColorSupport) {
+    *SUPPORT.write().unwrap_or_else(|e| e.into_inner()) = level;
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The theme every style is drawn with, adapted to the terminal's colors.
+pub fn current() -> Theme {
+    let mut theme = *THEME.read().unwrap_or_else(|e| e.into_inner());
+    let level = support();
+    if level != ColorSupport::TrueColor {
+        for color in theme.colors_mut() {
+            *color = level.adapt(*color);
+        }
+    }
+    theme
+}
+
+/// Without color, drop the colors the rest of the UI sets directly. Run over
+/// each finished frame.
+pub fn strip_colors(buffer: &mut Buffer) {
+    if support() != ColorSupport::None {
+        return;
+    }
+    for cell in buffer.content.iter_mut() {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
+}
+
+/// Switch the theme; the next frame is drawn with it.
+pub fn set(theme: Theme) {
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = theme;
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// How many times the theme or color support has changed; styles built
+/// before a change are stale once this moves on.
+pub fn generation() -> usize {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Stable accent color for an agent, derived from its manifest name so the same
+/// agent always looks the same across sessions and tabs.
+pub fn agent_color(name: &str) -> Color {
+    // FNV-1a: tiny, stable across platforms and Rust versions (unlike DefaultHasher)
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    let hue = (hash % 360) as f32;
+    // Fixed saturation and lightness keep every hue readable on dark backgrounds
+    let (s, l) = (0.6f32, 0.62f32);
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+    support().adapt(Color::Rgb(channel(r), channel(g), channel(b)))
+}
+
+pub fn agent_style(name: &str) -> Style {
+    Style::default().fg(agent_color(name))
+}
 
 pub fn user_style() -> Style {
-    Style::default().fg(USER_COLOR)
+    Style::default().fg(current().user)
 }
 
 pub fn assistant_style() -> Style {
-    Style::default().fg(ASSISTANT_COLOR)
+    Style::default().fg(current().assistant)
 }
 
 pub fn tool_style() -> Style {
-    Style::default().fg(TOOL_COLOR)
+    Style::default().fg(current().tool)
 }
 
 pub fn error_style() -> Style {
-    Style::default().fg(ERROR_COLOR).add_modifier(Modifier::BOLD)
+    Style::default().fg(current().error).add_modifier(Modifier::BOLD)
 }
 
 pub fn dim_style() -> Style {
-    Style::default().fg(DIM_COLOR)
+    Style::default().fg(current().dim)
 }
 
 pub fn accent_style() -> Style {
-    Style::default().fg(ACCENT_COLOR).add_modifier(Modifier::BOLD)
+    Style::default().fg(current().accent).add_modifier(Modifier::BOLD)
 }
 
 pub fn narration_style() -> Style {
-    Style::default().fg(NARRATION_COLOR)
+    Style::default().fg(current().narration)
+}
+
+/// Model reasoning shown in collapsible thought blocks: quieter than the answer.
+pub fn thought_style() -> Style {
+    Style::default().fg(current().dim).add_modifier(Modifier::ITALIC)
 }
 
 pub fn system_style() -> Style {
-    Style::default().fg(SYSTEM_COLOR).add_modifier(Modifier::ITALIC)
+    Style::default().fg(current().system).add_modifier(Modifier::ITALIC)
 }
 
 pub fn success_style() -> Style {
-    Style::default().fg(SUCCESS_COLOR)
+    Style::default().fg(current().success)
 }
 
 pub fn border_style() -> Style {
-    Style::default().fg(BORDER_COLOR)
+    Style::default().fg(current().border)
+}
+
+/// Highlight with `background`; without colors, reverse video stands in.
+fn highlight(background: Color) -> Style {
+    if support() == ColorSupport::None {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default().bg(background)
+    }
+}
+
+pub fn selection_style() -> Style {
+    highlight(current().selection)
+}
+
+pub fn search_match_style() -> Style {
+    highlight(current().search)
+}
+
+pub fn link_style() -> Style {
+    Style::default().fg(current().link).add_modifier(Modifier::UNDERLINED)
+}
+
+pub fn stage_style() -> Style {
+    Style::default().fg(current().stage)
+}
+
+/// Border of the focused panel or an open overlay.
+pub fn focus_border_style() -> Style {
+    Style::default().fg(current().focus)
+}
+
+pub fn gauge_label_style() -> Style {
+    Style::default().fg(current().gauge_label)
+}
+
+pub fn search_active_style() -> Style {
+    highlight(current().search_active).fg(Color::Black)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_color_is_stable() {
+        assert_eq!(agent_color("coder"), agent_color("coder"));
+        assert_ne!(agent_color("coder"), agent_color("reviewer"));
+        let Color::Rgb(r, g, b) = agent_color("researcher") else { panic!("expected an RGB color") };
+        // Light enough to read on a dark terminal
+        assert!(r.max(g).max(b) >= 180);
+    }
+
+    #[test]
+    fn test_color_support() {
+        assert_eq!(ColorSupport::from_env(Some("1"), Some("truecolor"), None), ColorSupport::None);
+        assert_eq!(ColorSupport::from_env(Some(""), Some("truecolor"), None), ColorSupport::TrueColor);
+        assert_eq!(ColorSupport::from_env(None, None, Some("dumb")), ColorSupport::None);
+        assert_eq!(ColorSupport::from_env(None, None, Some("tmux-256color")), ColorSupport::Ansi16);
+
+        let ansi = ColorSupport::Ansi16;
+        assert_eq!(ansi.adapt(Theme::DEFAULT.user), Color::LightBlue);
+        assert_eq!(ansi.adapt(Theme::DEFAULT.border), Color::DarkGray);
+        assert_eq!(ansi.adapt(Theme::DEFAULT.search_active), Color::Yellow);
+        assert_eq!(ansi.adapt(Color::Indexed(9)), Color::LightRed);
+        assert_eq!(ansi.adapt(Color::Indexed(196)), Color::LightRed);
+        assert_eq!(ansi.adapt(Color::Indexed(255)), Color::Gray);
+        assert_eq!(ansi.adapt(Color::Cyan), Color::Cyan);
+        assert_eq!(ColorSupport::None.adapt(Color::Cyan), Color::Reset);
+        assert_eq!(ColorSupport::TrueColor.adapt(Theme::DEFAULT.user), Theme::DEFAULT.user);
+    }
 }
@@ -0,0 +1,310 @@
+//! Full-screen trace viewer. The sidebar's Trace tab only has room for the tail
+//! of the trace with everything cut short; here every entry gets its time, long
+//! tool args, errors and narration wrap in full, and the view can be narrowed
+//! to the entries of one stage. The `/trace filter` applies here too.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Local};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::text::{Line, Span};
+
+use crate::app::{TraceEntry, TraceKind};
+use super::{icons, theme, wrap};
+
+/// Width of the `HH:MM:SS ` column in front of every entry.
+const TIME_WIDTH: usize = 9;
+
+#[derive(Debug, Clone, Default)]
+pub struct TraceViewState {
+    /// Only show entries recorded while this stage ran.
+    pub stage: Option<String>,
+    /// First visible row; `None` follows new entries at the bottom.
+    pub scroll: Option<usize>,
+    pub page_height: usize,
+    /// Rows in the last render, to clamp scrolling against.
+    rows: usize,
+}
+
+impl TraceViewState {
+    pub fn new(stage: Option<String>) -> Self {
+        Self { stage, ..Default::default() }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.rows.saturating_sub(self.page_height)
+    }
+
+    /// Scroll by `delta` rows; reaching the bottom follows new entries again.
+    pub fn scroll_by(&mut self, delta: isize) {
+        let position = self.scroll.unwrap_or(self.max_scroll()).saturating_add_signed(delta);
+        self.scroll = (position < self.max_scroll()).then_some(position);
+    }
+
+    /// Narrow to the next (or previous) stage in `stages`, wrapping through "all stages".
+    pub fn cycle_stage(&mut self, stages: &[String], forward: bool) {
+        let current = self.stage.as_ref().and_then(|stage| stages.iter().position(|s| s == stage));
+        let next = match (current, forward) {
+            (None, true) => stages.first(),
+            (None, false) => stages.last(),
+            (Some(i), true) => stages.get(i + 1),
+            (Some(i), false) => i.checked_sub(1).and_then(|i| stages.get(i)),
+        };
+        self.stage = next.cloned();
+        self.scroll = Some(0);
+    }
+}
+
+/// The stage each entry belongs to: the one started most recently before it.
+pub fn entry_stages(trace: &[TraceEntry]) -> Vec<Option<&str>> {
+    let mut current = None;
+    trace
+        .iter()
+        .map(|entry| {
+            if let TraceEntry::StageStart { id, .. } = entry {
+                current = Some(id.as_str());
+            }
+            current
+        })
+        .collect()
+}
+
+/// Stage ids in the order they first started.
+pub fn stages(trace: &[TraceEntry]) -> Vec<String> {
+    let mut stages: Vec<String> = Vec::new();
+    for entry in trace {
+        if let TraceEntry::StageStart { id, .. } = entry {
+            if !stages.contains(id) {
+                stages.push(id.clone());
+            }
+        }
+    }
+    stages
+}
+
+/// Handle a key for the viewer. Returns false when it should close.
+pub fn handle_key(state: &mut TraceViewState, key: KeyEvent, trace: &[TraceEntry]) -> bool {
+    let page = state.page_height.max(1) as isize;
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc | KeyCode::Char('q')) => return false,
+        (KeyModifiers::CONTROL, KeyCode::Char('t')) => return false,
+        (_, KeyCode::Down | KeyCode::Char('j')) => state.scroll_by(1),
+        (_, KeyCode::Up | KeyCode::Char('k')) => state.scroll_by(-1),
+        (_, KeyCode::PageDown | KeyCode::Char(' ')) => state.scroll_by(page),
+        (_, KeyCode::PageUp | KeyCode::Char('b')) => state.scroll_by(-page),
+        (_, KeyCode::Home | KeyCode::Char('g')) => state.scroll = Some(0),
+        (_, KeyCode::End | KeyCode::Char('G' | 'f')) => state.scroll = None,
+        (_, KeyCode::Char('s')) => state.cycle_stage(&stages(trace), true),
+        (_, KeyCode::Char('S')) => state.cycle_stage(&stages(trace), false),
+        (_, KeyCode::Char('a')) => {
+            state.stage = None;
+            state.scroll = None;
+        }
+        _ => {}
+    }
+    true
+}
+
+/// Wrap `text` into rows under the entry's first line, indented past the time column.
+fn push_wrapped(lines: &mut Vec<Line<'static>>, text: &str, width: usize, style: Style) {
+    let indent = " ".repeat(TIME_WIDTH + 3);
+    for line in text.lines() {
+        for row in wrap::wrap_str(line, width.saturating_sub(indent.len()).max(1)) {
+            lines.push(Line::from(Span::styled(format!("{indent}{row}"), style)));
+        }
+    }
+}
+
+/// Display rows for the entries of `kinds` (all without any) passing the
+/// stage filter, wrapped to `width`.
+pub fn rows(
+    trace: &[TraceEntry],
+    times: &[DateTime<Local>],
+    stage: Option<&str>,
+    kinds: &BTreeSet<TraceKind>,
+    width: usize,
+) -> Vec<Line<'static>> {
+    let icons = icons::current();
+    let mut lines = Vec::new();
+    for ((index, entry), entry_stage) in trace.iter().enumerate().zip(entry_stages(trace)) {
+        if (stage.is_some() && entry_stage != stage) || !entry.passes(kinds) {
+            continue;
+        }
+        let time = times.get(index).map(|t| t.format("%H:%M:%S").to_string()).unwrap_or_default();
+        let time = Span::styled(format!("{time:<TIME_WIDTH$}"), theme::dim_style());
+        match entry {
+            TraceEntry::StageStart { id, kind } => lines.push(Line::from(vec![
+                time,
+                Span::styled(format!(" {} ", icons.stage), theme::stage_style()),
+                Span::styled(id.clone(), theme::accent_style()),
+                Span::styled(format!(" ({kind})"), theme::dim_style()),
+            ])),
+            TraceEntry::StageEnd { id, duration_ms, skipped } => {
+                let (text, style) = if *skipped {
+                    (format!(" {} {id} skipped", icons.skipped), theme::tool_style())
+                } else {
+                    (format!(" {} {id} done in {duration_ms}ms", icons.ok), theme::success_style())
+                };
+                lines.push(Line::from(vec![time, Span::styled(text, style)]));
+            }
+            TraceEntry::StageFailed { id, error } => {
+                lines.push(Line::from(vec![time, Span::styled(format!(" {} {id} failed", icons.failed), theme::error_style())]));
+                push_wrapped(&mut lines, error, width, theme::error_style());
+            }
+            TraceEntry::LlmCall { model, ctx_tokens, out_tokens, duration_ms } => lines.push(Line::from(vec![
+                time,
+                Span::raw(format!(" {} ", icons.model)),
+                Span::styled(model.clone(), theme::user_style()),
+                Span::styled(format!(" {ctx_tokens} → {out_tokens} tokens, {duration_ms}ms"), theme::dim_style()),
+            ])),
+            TraceEntry::ToolCall { name, args } => {
+                lines.push(Line::from(vec![
+                    time,
+                    Span::styled(format!(" {} ", icons.tool), theme::tool_style()),
+                    Span::styled(name.clone(), theme::tool_style()),
+                ]));
+                push_wrapped(&mut lines, args, width, theme::dim_style());
+            }
+            TraceEntry::ToolResult { name, success, duration_ms, error } => {
+                let (icon, style) = if *success { (icons.ok, theme::success_style()) } else { (icons.failed, theme::error_style()) };
+                lines.push(Line::from(vec![time, Span::styled(format!(" {icon} {name} {duration_ms}ms"), style)]));
+                if let Some(error) = error {
+                    push_wrapped(&mut lines, error, width, theme::error_style());
+                }
+            }
+            TraceEntry::Narration(text) => {
+                lines.push(Line::from(vec![time, Span::styled(format!(" {}", icons.narration), theme::dim_style())]));
+                push_wrapped(&mut lines, text, width, theme::narration_style());
+            }
+            TraceEntry::Artifact { stage_id, name } => lines.push(Line::from(vec![
+                time,
+                Span::raw(format!(" {} ", icons.artifact)),
+                Span::styled(name.clone(), theme::accent_style()),
+                Span::styled(format!(" ({stage_id})"), theme::dim_style()),
+            ])),
+        }
+    }
+    lines
+}
+
+/// Render the trace over `area`.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    state: &mut TraceViewState,
+    trace: &[TraceEntry],
+    times: &[DateTime<Local>],
+    kinds: &BTreeSet<TraceKind>,
+) {
+    let mut title = " Trace ".to_string();
+    if let Some(stage) = &state.stage {
+        title.push_str(&format!("· stage {stage} "));
+    }
+    if !kinds.is_empty() {
+        let names: Vec<&str> = kinds.iter().map(|kind| kind.name()).collect();
+        title.push_str(&format!("· {} ", names.join(", ")));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::focus_border_style())
+        .title(Span::styled(title, theme::accent_style()));
+    let inner = block.inner(area);
+    state.page_height = inner.height as usize;
+
+    let lines = rows(trace, times, state.stage.as_deref(), kinds, inner.width as usize);
+    state.rows = lines.len();
+    let start = state.scroll.unwrap_or(usize::MAX).min(state.max_scroll());
+    let position = if state.scroll.is_none() {
+        "following".to_string()
+    } else {
+        format!("{}/{}", (start + state.page_height).min(lines.len()), lines.len())
+    };
+    let footer = format!(" {position} · s/S stage · a all · g/G top/end · q close ");
+    let block = block.title_bottom(Span::styled(footer, theme::accent_style()));
+
+    let visible: Vec<Line> = lines.into_iter().skip(start).take(state.page_height).collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(visible).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::keymap::{self, KeyContext};
+
+    fn trace() -> Vec<TraceEntry> {
+        vec![
+            TraceEntry::Narration("before any stage".into()),
+            TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() },
+            TraceEntry::ToolCall { name: "exec".into(), args: "x".repeat(50) },
+            TraceEntry::StageEnd { id: "plan".into(), duration_ms: 5, skipped: false },
+            TraceEntry::StageStart { id: "act".into(), kind: "tool".into() },
+            TraceEntry::StageFailed { id: "act".into(), error: "boom".into() },
+            TraceEntry::StageStart { id: "plan".into(), kind: "llm".into() },
+        ]
+    }
+
+    #[test]
+    fn test_entry_stages() {
+        let trace = trace();
+        let stages = entry_stages(&trace);
+        assert_eq!(stages[0], None);
+        assert_eq!(stages[2], Some("plan"));
+        assert_eq!(stages[5], Some("act"));
+        assert_eq!(super::stages(&trace), vec!["plan".to_string(), "act".to_string()]);
+    }
+
+    #[test]
+    fn test_bindings_act() {
+        let trace = trace();
+        for binding in keymap::BINDINGS.iter().filter(|b| b.context == KeyContext::TraceView) {
+            for key in keymap::key_events(binding.keys).unwrap() {
+                let mut state = TraceViewState { stage: Some("plan".into()), scroll: Some(5), page_height: 3, rows: 20 };
+                let before = (state.stage.clone(), state.scroll);
+                let open = handle_key(&mut state, key, &trace);
+                assert!(!open || (state.stage.clone(), state.scroll) != before, "{} {key:?}", binding.keys);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle_stage() {
+        let stages = vec!["plan".to_string(), "act".to_string()];
+        let mut state = TraceViewState::new(None);
+        state.cycle_stage(&stages, true);
+        assert_eq!(state.stage.as_deref(), Some("plan"));
+        state.cycle_stage(&stages, true);
+        assert_eq!(state.stage.as_deref(), Some("act"));
+        state.cycle_stage(&stages, true);
+        assert_eq!(state.stage, None);
+        state.cycle_stage(&stages, false);
+        assert_eq!(state.stage.as_deref(), Some("act"));
+    }
+
+    #[test]
+    fn test_rows_filter_and_wrap() {
+        let trace = trace();
+        let all = rows(&trace, &[], None, &BTreeSet::new(), 40);
+        // Args are 50 chars in a 28-column wrap, so the tool call takes three rows
+        assert_eq!(all.len(), 11);
+        let plan = rows(&trace, &[], Some("plan"), &BTreeSet::new(), 40);
+        assert_eq!(plan.len(), 6);
+        assert!(plan.iter().all(|line| !line.to_string().contains("act")));
+        // The `/trace filter` leaves only the tool call of the plan stage
+        let tools = BTreeSet::from([TraceKind::Tools]);
+        let plan_tools = rows(&trace, &[], Some("plan"), &tools, 40);
+        assert_eq!(plan_tools.len(), 3);
+        assert!(plan_tools[0].to_string().contains("exec"));
+    }
+
+    #[test]
+    fn test_scroll_follows_at_bottom() {
+        let mut state = TraceViewState { rows: 20, page_height: 5, ..Default::default() };
+        state.scroll_by(-3);
+        assert_eq!(state.scroll, Some(12));
+        state.scroll_by(10);
+        assert_eq!(state.scroll, None);
+    }
+}
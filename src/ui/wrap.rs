@@ -0,0 +1,145 @@
+//! Width-aware word wrapping.
+
+use std::ops::Range;
+
+use ratatui::text::{Line, Span};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Word-wrap a single line to `width` display columns. Words longer than the
+/// width are split; leading indentation is kept. Always returns at least one row.
+pub fn wrap_str(text: &str, width: usize) -> Vec<String> {
+    wrap_ranges(text, width).into_iter().map(|range| text[range].to_string()).collect()
+}
+
+/// Word-wrap a styled line like [`wrap_str`], keeping each span's style.
+pub fn wrap_line(line: &Line<'_>, width: usize) -> Vec<Line<'static>> {
+    let mut text = String::new();
+    let mut spans = Vec::new();
+    for span in &line.spans {
+        let start = text.len();
+        text.push_str(&span.content);
+        spans.push((start..text.len(), span.style));
+    }
+    wrap_ranges(&text, width)
+        .into_iter()
+        .map(|row| {
+            let row_spans: Vec<Span<'static>> = spans
+                .iter()
+                .filter_map(|(range, style)| {
+                    let start = range.start.max(row.start);
+                    let end = range.end.min(row.end);
+                    (start < end).then(|| Span::styled(text[start..end].to_string(), *style))
+                })
+                .collect();
+            Line::from(row_spans).style(line.style)
+        })
+        .collect()
+}
+
+/// Byte ranges of the rows `text` wraps into.
+fn wrap_ranges(text: &str, width: usize) -> Vec<Range<usize>> {
+    if width == 0 || text.width() <= width {
+        return std::iter::once(0..text.len()).collect();
+    }
+    let trimmed = |start: usize, end: usize| start..start + text[start..end].trim_end().len();
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut current_width = 0;
+    let mut pos = 0;
+
+    for word in text.split_inclusive(' ') {
+        let word_start = pos;
+        pos += word.len();
+        let word_width = word.trim_end().width();
+        if current_width + word_width > width && end > start {
+            rows.push(trimmed(start, end));
+            start = word_start;
+            end = word_start;
+            current_width = 0;
+        }
+        if word_width > width {
+            for (offset, ch) in word.char_indices() {
+                let ch_width = ch.width().unwrap_or(0);
+                if current_width + ch_width > width && end > start {
+                    rows.push(start..end);
+                    start = word_start + offset;
+                    current_width = 0;
+                }
+                end = word_start + offset + ch.len_utf8();
+                current_width += ch_width;
+            }
+        } else {
+            end = pos;
+            current_width += word.width();
+        }
+    }
+    if end > start || rows.is_empty() {
+        rows.push(trimmed(start, end));
+    }
+    rows
+}
+
+/// Hard-split a line every `width` display columns, preserving all whitespace.
+pub fn split_str(text: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if width > 0 && current_width + ch_width > width && !current.is_empty() {
+            rows.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+        current.push(ch);
+        current_width += ch_width;
+    }
+    rows.push(current);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Style, Stylize};
+
+    #[test]
+    fn test_wrap_short_line() {
+        assert_eq!(wrap_str("hello", 10), vec!["hello"]);
+        assert_eq!(wrap_str("", 10), vec![""]);
+    }
+
+    #[test]
+    fn test_wrap_words() {
+        assert_eq!(
+            wrap_str("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(wrap_str("  indented text here", 10), vec!["  indented", "text here"]);
+    }
+
+    #[test]
+    fn test_wrap_long_word_and_wide_chars() {
+        assert_eq!(wrap_str("abcdefghij klm", 4), vec!["abcd", "efgh", "ij", "klm"]);
+        // CJK characters are two columns wide
+        assert_eq!(wrap_str("日本語テキスト", 6), vec!["日本語", "テキス", "ト"]);
+    }
+
+    #[test]
+    fn test_wrap_line_keeps_styles() {
+        let bold = Style::default().bold();
+        let line = Line::from(vec![Span::raw("> "), Span::styled("hello brave world", bold)]);
+        let rows = wrap_line(&line, 9);
+        let text: Vec<String> = rows.iter().map(|r| r.to_string()).collect();
+        assert_eq!(text, vec!["> hello", "brave", "world"]);
+        assert_eq!(rows[0].spans[0].style, Style::default());
+        assert_eq!(rows[0].spans[1].style, bold);
+        assert_eq!(rows[2].spans[0].style, bold);
+    }
+
+    #[test]
+    fn test_split_keeps_whitespace() {
+        assert_eq!(split_str("ab  cd  ", 3), vec!["ab ", " cd", "  "]);
+        assert_eq!(split_str("", 3), vec![""]);
+    }
+}
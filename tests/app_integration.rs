@@ -1,6 +1,6 @@
 //! Integration tests for App + command flow.
 
-use neocognos_tui::app::{App, ChatMessage, PanelFocus, TraceEntry};
+use neocognos_tui::app::{App, ChatMessage, PanelFocus, SidebarTab, TraceEntry};
 use neocognos_tui::commands::{process_command, CommandResult};
 
 #[test]
@@ -47,12 +47,13 @@ fn test_submit_and_command_flow() {
 fn test_scroll_with_focus() {
     let mut app = App::new("agent", "model", "workflow");
     // Chat focus - scroll_offset controls chat
-    assert_eq!(app.focus, PanelFocus::Chat);
+    assert_eq!(app.focus, PanelFocus::Input);
+    app.focus = PanelFocus::Chat;
     app.scroll_offset = 5;
     assert_eq!(app.scroll_offset, 5);
 
     // Trace focus - trace_scroll controls trace
-    app.focus = PanelFocus::Trace;
+    app.focus_tab(SidebarTab::Trace);
     assert!(app.trace_scroll.is_none()); // auto-follow
     app.trace_scroll = Some(3);
     assert_eq!(app.trace_scroll, Some(3));